@@ -1,28 +1,143 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use nu_protocol::Span;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use uuid::Uuid;
-use nu_protocol::Span;
-use chrono::{DateTime, Utc};
+
+#[cfg(test)]
+mod tests;
 
 /// Thread-safe cache for storing Nickel plugin objects
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct NickelCache {
     inner: Arc<Mutex<HashMap<Uuid, CachedNickelValue>>>,
+    counters: Arc<CacheCounters>,
+    policy: Arc<CachePolicy>,
+    clock: Arc<dyn Clock>,
+    ids: Arc<dyn IdGenerator>,
 }
 
-/// A cached Nickel value with metadata
+/// Limits applied to the entries of a cache
+///
+/// Pinned entries are never evicted. Without a spill directory, evicted entries are dropped and
+/// the values referring to them can no longer be used.
+#[derive(Debug, Clone, Default)]
+pub struct CachePolicy {
+    /// Evict entries that were not used for this long
+    pub ttl: Option<TimeDelta>,
+    /// Evict the least recently used entries beyond this number
+    pub max_entries: Option<usize>,
+    /// Write evicted entries to this directory, to load them back when they are used again
+    pub spill_dir: Option<PathBuf>,
+}
+
+/// Source of the current time of a cache
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when told to, so that tests can simulate long sessions
+///
+/// Clones share the same time, so a test can keep one to advance the clock of a cache.
 #[derive(Debug, Clone)]
+pub struct ManualClock(Arc<Mutex<DateTime<Utc>>>);
+
+impl ManualClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self(Arc::new(Mutex::new(now)))
+    }
+
+    pub fn advance(&self, by: TimeDelta) {
+        *self.0.lock().unwrap() += by;
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.0.lock().unwrap() = now;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.0.lock().unwrap()
+    }
+}
+
+/// Source of the IDs of cache entries
+pub trait IdGenerator: fmt::Debug + Send + Sync {
+    fn next_id(&self) -> Uuid;
+}
+
+/// Random version 4 UUIDs
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomIds;
+
+impl IdGenerator for RandomIds {
+    fn next_id(&self) -> Uuid {
+        Uuid::new_v4()
+    }
+}
+
+/// UUIDs counting up from 1, so that tests and snapshots get the same IDs on every run
+#[derive(Debug, Default)]
+pub struct SequentialIds(AtomicU64);
+
+impl IdGenerator for SequentialIds {
+    fn next_id(&self) -> Uuid {
+        Uuid::from_u128(self.0.fetch_add(1, Ordering::Relaxed) as u128 + 1)
+    }
+}
+
+/// Counters of cache operations since the plugin started
+#[derive(Debug, Default)]
+struct CacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    inserts: AtomicU64,
+    evictions: AtomicU64,
+}
+
+/// A snapshot of the cache size and operation counters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    pub entries: usize,
+    /// Lookups that found their entry
+    pub hits: u64,
+    /// Lookups of an entry that was already removed
+    pub misses: u64,
+    pub inserts: u64,
+    /// Entries removed because they were dropped, cleared, expired or spilled
+    pub evictions: u64,
+}
+
+/// A cached Nickel value with metadata
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CachedNickelValue {
     pub uuid: Uuid,
     pub value: NickelPluginObject,
     pub created: DateTime<Utc>,
+    pub last_used: DateTime<Utc>,
     pub span: Span,
     pub reference_count: i16,
+    pub pinned: bool,
 }
 
 /// Polymorphic storage for different types of Nickel objects
 /// Uses serialization to ensure thread safety
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum NickelPluginObject {
     /// Serializable JSON value
     JsonValue(serde_json::Value),
@@ -34,85 +149,258 @@ pub enum NickelPluginObject {
         source_code: String,
         /// Type information as string
         type_info: String,
+        /// File the source was read from, which its relative imports are resolved against
+        #[serde(default)]
+        source_path: Option<PathBuf>,
     },
     /// Evaluated result
     EvaluatedValue {
         json: serde_json::Value,
         source_code: Option<String>,
     },
+    /// A function, which has no data, kept as the source of the program it is the value of
+    NickelFunction {
+        source_code: String,
+        /// File the source was read from, which its relative imports are resolved against
+        #[serde(default)]
+        source_path: Option<PathBuf>,
+    },
+}
+
+impl NickelPluginObject {
+    /// Get the object type as a string for display
+    pub fn object_type(&self) -> &'static str {
+        match self {
+            NickelPluginObject::JsonValue(_) => "JsonValue",
+            NickelPluginObject::SerializedNickelTerm { .. } => "NickelTerm",
+            NickelPluginObject::EvaluatedValue { .. } => "EvaluatedValue",
+            NickelPluginObject::NickelFunction { .. } => "NickelFunction",
+        }
+    }
+}
+
+impl Default for NickelCache {
+    fn default() -> Self {
+        Self::new(
+            CachePolicy::default(),
+            Arc::new(SystemClock),
+            Arc::new(RandomIds),
+        )
+    }
 }
 
 impl NickelCache {
+    /// Create a cache applying `policy`, with `clock` telling its time and `ids` naming its entries
+    pub fn new(policy: CachePolicy, clock: Arc<dyn Clock>, ids: Arc<dyn IdGenerator>) -> Self {
+        Self {
+            inner: Arc::default(),
+            counters: Arc::default(),
+            policy: Arc::new(policy),
+            clock,
+            ids,
+        }
+    }
+
+    /// The time on the clock of this cache
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
     /// Insert a JSON value into the cache and return its UUID
     pub fn insert_json(&self, value: serde_json::Value, span: Span) -> Uuid {
-        let id = Uuid::new_v4();
-        let cached_value = CachedNickelValue {
-            uuid: id,
-            value: NickelPluginObject::JsonValue(value),
-            created: Utc::now(),
-            span,
-            reference_count: 1,
-        };
-        let mut cache = self.inner.lock().unwrap();
-        cache.insert(id, cached_value);
-        id
+        self.insert(NickelPluginObject::JsonValue(value), span)
     }
 
     /// Insert a Nickel term representation into the cache and return its UUID
     pub fn insert_nickel_term(
-        &self, 
+        &self,
         source_code: String,
         json_representation: Option<serde_json::Value>,
         type_info: String,
-        span: Span
+        source_path: Option<PathBuf>,
+        span: Span,
     ) -> Uuid {
-        let id = Uuid::new_v4();
-        let cached_value = CachedNickelValue {
-            uuid: id,
-            value: NickelPluginObject::SerializedNickelTerm {
-                json_representation,
-                source_code,
-                type_info,
-            },
-            created: Utc::now(),
-            span,
-            reference_count: 1,
+        let value = NickelPluginObject::SerializedNickelTerm {
+            json_representation,
+            source_code,
+            type_info,
+            source_path,
         };
-        let mut cache = self.inner.lock().unwrap();
-        cache.insert(id, cached_value);
-        id
+        self.insert(value, span)
     }
 
     /// Insert an evaluated value into the cache
     pub fn insert_evaluated(
-        &self, 
-        json: serde_json::Value, 
+        &self,
+        json: serde_json::Value,
         source_code: Option<String>,
-        span: Span
+        span: Span,
     ) -> Uuid {
-        let id = Uuid::new_v4();
-        let cached_value = CachedNickelValue {
-            uuid: id,
-            value: NickelPluginObject::EvaluatedValue {
-                json,
+        self.insert(
+            NickelPluginObject::EvaluatedValue { json, source_code },
+            span,
+        )
+    }
+
+    /// Insert a function, as the source of a program evaluating to it, into the cache
+    pub fn insert_function(
+        &self,
+        source_code: String,
+        source_path: Option<PathBuf>,
+        span: Span,
+    ) -> Uuid {
+        self.insert(
+            NickelPluginObject::NickelFunction {
                 source_code,
+                source_path,
             },
-            created: Utc::now(),
             span,
-            reference_count: 1,
+        )
+    }
+
+    fn insert(&self, value: NickelPluginObject, span: Span) -> Uuid {
+        let id = self.ids.next_id();
+        let now = self.clock.now();
+        let cached_value = CachedNickelValue {
+            uuid: id,
+            value,
+            created: now,
+            last_used: now,
+            span,
+            reference_count: 0,
+            pinned: false,
         };
         let mut cache = self.inner.lock().unwrap();
         cache.insert(id, cached_value);
+        self.counters.inserts.fetch_add(1, Ordering::Relaxed);
+        self.enforce_policy(&mut cache, now);
         id
     }
 
-    /// Get a cached value by UUID
+    /// Get a cached value by UUID, loading it back if it was spilled
     pub fn get(&self, id: &Uuid) -> Option<CachedNickelValue> {
-        let cache = self.inner.lock().unwrap();
-        cache.get(id).cloned()
+        let now = self.clock.now();
+        let mut cache = self.inner.lock().unwrap();
+        self.enforce_policy(&mut cache, now);
+        if !cache.contains_key(id)
+            && let Some(cached_value) = self.unspill(id)
+        {
+            cache.insert(*id, cached_value);
+        }
+        let cached_value = cache.get_mut(id).map(|cached_value| {
+            cached_value.last_used = now;
+            cached_value.clone()
+        });
+        let counter = match cached_value {
+            Some(_) => &self.counters.hits,
+            None => &self.counters.misses,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        if cached_value.is_some() {
+            self.enforce_policy(&mut cache, now);
+        }
+        cached_value
+    }
+
+    /// Keep an entry from being evicted by the policy, returning whether it was found
+    pub fn pin(&self, id: &Uuid) -> bool {
+        self.set_pinned(id, true)
+    }
+
+    /// Let the policy evict an entry again, returning whether it was found
+    pub fn unpin(&self, id: &Uuid) -> bool {
+        self.set_pinned(id, false)
+    }
+
+    fn set_pinned(&self, id: &Uuid, pinned: bool) -> bool {
+        let mut cache = self.inner.lock().unwrap();
+        if !cache.contains_key(id)
+            && let Some(cached_value) = self.unspill(id)
+        {
+            cache.insert(*id, cached_value);
+        }
+        match cache.get_mut(id) {
+            Some(cached_value) => {
+                cached_value.pinned = pinned;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Evict the entries that expired or exceed the capacity, returning how many were evicted
+    pub fn enforce(&self) -> usize {
+        let now = self.clock.now();
+        let mut cache = self.inner.lock().unwrap();
+        self.enforce_policy(&mut cache, now)
+    }
+
+    fn enforce_policy(
+        &self,
+        cache: &mut HashMap<Uuid, CachedNickelValue>,
+        now: DateTime<Utc>,
+    ) -> usize {
+        let mut evicted: Vec<Uuid> = match self.policy.ttl {
+            Some(ttl) => cache
+                .values()
+                .filter(|cached_value| !cached_value.pinned && now - cached_value.last_used > ttl)
+                .map(|cached_value| cached_value.uuid)
+                .collect(),
+            None => Vec::new(),
+        };
+        if let Some(max_entries) = self.policy.max_entries {
+            let mut candidates: Vec<_> = cache
+                .values()
+                .filter(|cached_value| {
+                    !cached_value.pinned && !evicted.contains(&cached_value.uuid)
+                })
+                .map(|cached_value| (cached_value.last_used, cached_value.uuid))
+                .collect();
+            let excess = (cache.len() - evicted.len()).saturating_sub(max_entries);
+            candidates.sort();
+            evicted.extend(candidates.into_iter().take(excess).map(|(_, id)| id));
+        }
+
+        for id in &evicted {
+            if let Some(cached_value) = cache.remove(id) {
+                self.spill(&cached_value);
+            }
+        }
+        self.counters
+            .evictions
+            .fetch_add(evicted.len() as u64, Ordering::Relaxed);
+        evicted.len()
+    }
+
+    fn spill_path(&self, id: &Uuid) -> Option<PathBuf> {
+        let dir = self.policy.spill_dir.as_ref()?;
+        Some(dir.join(format!("{id}.json")))
+    }
+
+    /// Write an evicted entry to the spill directory, dropping it when that fails
+    fn spill(&self, cached_value: &CachedNickelValue) {
+        let Some(path) = self.spill_path(&cached_value.uuid) else {
+            return;
+        };
+        if let Ok(json) = serde_json::to_vec(cached_value) {
+            let _ = std::fs::create_dir_all(path.parent().unwrap_or(&path))
+                .and_then(|_| std::fs::write(&path, json));
+        }
+    }
+
+    /// Take an entry back from the spill directory
+    fn unspill(&self, id: &Uuid) -> Option<CachedNickelValue> {
+        let path = self.spill_path(id)?;
+        let json = std::fs::read(&path).ok()?;
+        let _ = std::fs::remove_file(&path);
+        serde_json::from_slice(&json).ok()
     }
 
-    /// Increment reference count for a cached value
+    /// Check whether an entry is in the spill directory
+    pub fn is_spilled(&self, id: &Uuid) -> bool {
+        self.spill_path(id).is_some_and(|path| path.is_file())
+    }
+    /// Count one more Nushell value holding an entry
     pub fn increment_ref(&self, id: &Uuid) {
         let mut cache = self.inner.lock().unwrap();
         if let Some(cached_value) = cache.get_mut(id) {
@@ -120,26 +408,54 @@ impl NickelCache {
         }
     }
 
-    /// Decrement reference count for a cached value, removing if it reaches 0
+    /// Count one less Nushell value holding an entry, returning whether none holds it anymore
+    ///
+    /// An unreferenced entry is kept until it is cleared, cleaned up or evicted by the policy. A
+    /// spilled entry is loaded back, so that it can be cleared like the others.
     pub fn decrement_ref(&self, id: &Uuid) -> bool {
         let mut cache = self.inner.lock().unwrap();
-        if let Some(cached_value) = cache.get_mut(id) {
-            cached_value.reference_count -= 1;
-            if cached_value.reference_count <= 0 {
-                cache.remove(id);
-                return true; // Value was removed
+        if !cache.contains_key(id)
+            && let Some(cached_value) = self.unspill(id)
+        {
+            cache.insert(*id, cached_value);
+        }
+        match cache.get_mut(id) {
+            Some(cached_value) => {
+                cached_value.reference_count = (cached_value.reference_count - 1).max(0);
+                cached_value.reference_count == 0
             }
+            None => false,
         }
-        false // Value still exists
     }
 
-    /// Remove a cached item by UUID
+    /// Remove a cached item by UUID, along with its spilled copy
     pub fn remove(&self, id: &Uuid) -> Option<CachedNickelValue> {
         let mut cache = self.inner.lock().unwrap();
-        cache.remove(id)
+        let removed = cache.remove(id).or_else(|| self.unspill(id));
+        if removed.is_some() {
+            self.counters.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+        removed
     }
 
-    /// Get the number of cached items
+    /// Remove the given entries, or all of them when `ids` is `None`, returning how many were removed
+    ///
+    /// Entries still held by a Nushell value are only removed with `force`.
+    pub fn clear(&self, ids: Option<&[Uuid]>, force: bool) -> usize {
+        let mut cache = self.inner.lock().unwrap();
+        let before = cache.len();
+        cache.retain(|id, cached_value| {
+            let selected = ids.is_none_or(|ids| ids.contains(id));
+            !selected || (cached_value.reference_count > 0 && !force)
+        });
+        let removed = before - cache.len();
+        self.counters
+            .evictions
+            .fetch_add(removed as u64, Ordering::Relaxed);
+        removed
+    }
+
+    /// Get the number of cached items, not counting spilled ones
     pub fn len(&self) -> usize {
         let cache = self.inner.lock().unwrap();
         cache.len()
@@ -151,23 +467,55 @@ impl NickelCache {
         cache.is_empty()
     }
 
+    /// Get the number of entries and the operation counters
+    pub fn stats(&self) -> CacheStats {
+        let entries = self.len();
+        CacheStats {
+            entries,
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            inserts: self.counters.inserts.load(Ordering::Relaxed),
+            evictions: self.counters.evictions.load(Ordering::Relaxed),
+        }
+    }
+
     /// Clean up old unused cache entries
     pub fn cleanup_old_entries(&self, max_age_hours: i64) {
         let mut cache = self.inner.lock().unwrap();
-        let cutoff = Utc::now() - chrono::Duration::hours(max_age_hours);
+        let cutoff = self.clock.now() - TimeDelta::hours(max_age_hours);
+        let before = cache.len();
         cache.retain(|_, cached_value| {
             cached_value.reference_count > 0 || cached_value.created > cutoff
         });
+        self.counters
+            .evictions
+            .fetch_add((before - cache.len()) as u64, Ordering::Relaxed);
     }
 }
 
 impl CachedNickelValue {
+    /// An entry for data carried by a value itself rather than stored in a cache, read at `now`
+    pub fn detached(uuid: Uuid, value: NickelPluginObject, now: DateTime<Utc>, span: Span) -> Self {
+        Self {
+            uuid,
+            value,
+            created: now,
+            last_used: now,
+            span,
+            reference_count: 1,
+            pinned: true,
+        }
+    }
+
     /// Get the JSON value if this is a JSON type
     pub fn as_json(&self) -> Option<&serde_json::Value> {
         match &self.value {
             NickelPluginObject::JsonValue(json) => Some(json),
             NickelPluginObject::EvaluatedValue { json, .. } => Some(json),
-            NickelPluginObject::SerializedNickelTerm { json_representation: Some(json), .. } => Some(json),
+            NickelPluginObject::SerializedNickelTerm {
+                json_representation: Some(json),
+                ..
+            } => Some(json),
             _ => None,
         }
     }
@@ -175,23 +523,37 @@ impl CachedNickelValue {
     /// Get the source code if available
     pub fn as_source_code(&self) -> Option<&String> {
         match &self.value {
-            NickelPluginObject::SerializedNickelTerm { source_code, .. } => Some(source_code),
-            NickelPluginObject::EvaluatedValue { source_code: Some(code), .. } => Some(code),
+            NickelPluginObject::SerializedNickelTerm { source_code, .. }
+            | NickelPluginObject::NickelFunction { source_code, .. } => Some(source_code),
+            NickelPluginObject::EvaluatedValue {
+                source_code: Some(code),
+                ..
+            } => Some(code),
             _ => None,
         }
     }
 
-    /// Get the object type as a string for display
-    pub fn object_type(&self) -> &'static str {
+    /// Get the file the source code was read from, if any
+    pub fn source_path(&self) -> Option<&Path> {
         match &self.value {
-            NickelPluginObject::JsonValue(_) => "JsonValue",
-            NickelPluginObject::SerializedNickelTerm { .. } => "NickelTerm", 
-            NickelPluginObject::EvaluatedValue { .. } => "EvaluatedValue",
+            NickelPluginObject::SerializedNickelTerm { source_path, .. }
+            | NickelPluginObject::NickelFunction { source_path, .. } => source_path.as_deref(),
+            _ => None,
         }
     }
 
+    /// Get the object type as a string for display
+    pub fn object_type(&self) -> &'static str {
+        self.value.object_type()
+    }
+
+    /// Check if this value is a function, which `nickel call` applies
+    pub fn is_function(&self) -> bool {
+        matches!(self.value, NickelPluginObject::NickelFunction { .. })
+    }
+
     /// Check if this value can be evaluated to JSON
     pub fn has_json_representation(&self) -> bool {
         self.as_json().is_some()
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,132 @@
+use super::*;
+use crate::nickel::command::test_support::temp_dir;
+use serde_json::json;
+
+fn cache_with(policy: CachePolicy) -> (NickelCache, ManualClock) {
+    let clock = ManualClock::new(DateTime::UNIX_EPOCH);
+    let cache = NickelCache::new(
+        policy,
+        Arc::new(clock.clone()),
+        Arc::new(SequentialIds::default()),
+    );
+    (cache, clock)
+}
+
+#[test]
+fn test_cache_ttl() {
+    let (cache, clock) = cache_with(CachePolicy {
+        ttl: Some(TimeDelta::minutes(10)),
+        ..Default::default()
+    });
+    let used = cache.insert_json(json!(1), Span::test_data());
+    let idle = cache.insert_json(json!(2), Span::test_data());
+
+    clock.advance(TimeDelta::minutes(6));
+    assert!(cache.get(&used).is_some());
+    clock.advance(TimeDelta::minutes(6));
+    assert_eq!(cache.enforce(), 1);
+    assert!(cache.get(&idle).is_none());
+    assert!(cache.get(&used).is_some());
+    assert_eq!(cache.stats().evictions, 1);
+}
+
+#[test]
+fn test_cache_lru_and_pinning() {
+    let (cache, clock) = cache_with(CachePolicy {
+        max_entries: Some(2),
+        ..Default::default()
+    });
+    let pinned = cache.insert_json(json!("pinned"), Span::test_data());
+    assert!(cache.pin(&pinned));
+    clock.advance(TimeDelta::seconds(1));
+    let first = cache.insert_json(json!(1), Span::test_data());
+    clock.advance(TimeDelta::seconds(1));
+    let second = cache.insert_json(json!(2), Span::test_data());
+
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get(&pinned).is_some());
+    assert!(cache.get(&first).is_none());
+    assert!(cache.get(&second).is_some());
+
+    assert!(cache.unpin(&pinned));
+    clock.advance(TimeDelta::seconds(1));
+    cache.get(&second);
+    let third = cache.insert_json(json!(3), Span::test_data());
+    assert!(cache.get(&pinned).is_none());
+    assert!(cache.get(&third).is_some());
+}
+
+#[test]
+fn test_cache_spill() {
+    let dir = temp_dir();
+    let (cache, clock) = cache_with(CachePolicy {
+        max_entries: Some(1),
+        spill_dir: Some(dir.clone()),
+        ..Default::default()
+    });
+    let first = cache.insert_json(json!({ "a": 1 }), Span::test_data());
+    clock.advance(TimeDelta::seconds(1));
+    let second = cache.insert_json(json!({ "b": 2 }), Span::test_data());
+    assert!(cache.is_spilled(&first));
+    assert_eq!(cache.len(), 1);
+
+    clock.advance(TimeDelta::seconds(1));
+    let restored = cache.get(&first).unwrap();
+    assert_eq!(restored.as_json(), Some(&json!({ "a": 1 })));
+    assert_eq!(restored.created, DateTime::UNIX_EPOCH);
+    assert!(!cache.is_spilled(&first));
+    assert!(cache.is_spilled(&second));
+
+    assert!(cache.remove(&second).is_some());
+    assert!(!cache.is_spilled(&second));
+}
+
+#[test]
+fn test_cache_references() {
+    let (cache, _) = cache_with(CachePolicy::default());
+    let held = cache.insert_json(json!(1), Span::test_data());
+    let dropped = cache.insert_json(json!(2), Span::test_data());
+    cache.increment_ref(&held);
+    cache.increment_ref(&held);
+    cache.increment_ref(&dropped);
+
+    assert!(!cache.decrement_ref(&held));
+    assert!(cache.decrement_ref(&dropped));
+    assert_eq!(cache.len(), 2);
+
+    assert_eq!(cache.clear(None, false), 1);
+    assert!(cache.get(&dropped).is_none());
+    assert!(cache.get(&held).is_some());
+    assert_eq!(cache.clear(None, true), 1);
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn test_cache_sequential_ids() {
+    let (cache, clock) = cache_with(CachePolicy::default());
+    let first = cache.insert_json(json!(1), Span::test_data());
+    clock.advance(TimeDelta::hours(1));
+    let second = cache.insert_json(json!(2), Span::test_data());
+
+    assert_eq!(first.to_string(), "00000000-0000-0000-0000-000000000001");
+    assert_eq!(second, Uuid::from_u128(2));
+    assert_eq!(
+        cache.get(&second).unwrap().created.to_rfc3339(),
+        "1970-01-01T01:00:00+00:00"
+    );
+}
+
+#[test]
+fn test_cache_detached_clock() {
+    let (cache, clock) = cache_with(CachePolicy::default());
+    clock.advance(TimeDelta::hours(2));
+    let detached = CachedNickelValue::detached(
+        Uuid::from_u128(7),
+        NickelPluginObject::JsonValue(json!(1)),
+        cache.now(),
+        Span::test_data(),
+    );
+
+    assert_eq!(detached.created.to_rfc3339(), "1970-01-01T02:00:00+00:00");
+    assert_eq!(detached.last_used, detached.created);
+}
@@ -1,22 +1,33 @@
-use nu_plugin::{serve_plugin, MsgPackSerializer, Plugin, PluginCommand};
-use nu_protocol::{CustomValue, LabeledError};
+use nu_plugin::{EngineInterface, MsgPackSerializer, Plugin, PluginCommand, serve_plugin};
+use nu_protocol::{
+    CustomValue, LabeledError, Spanned, Value,
+    ast::{Operator, PathMember},
+    casing::Casing,
+};
+use std::cmp::Ordering;
+use std::path::Path;
 
-mod cache;
-mod nickel;
+pub mod cache;
+pub mod measure;
+pub mod nickel;
 
 use cache::NickelCache;
 use nickel::command;
+use nickel::index::IndexCache;
+use nickel::values::NuNickelValue;
+use nickel::worker::WorkerCommand;
 
+/// The plugin executable sets its allocator in `main.rs`, tests that measure memory need it too
+#[cfg(test)]
+#[global_allocator]
+static ALLOCATOR: measure::PeakAllocator = measure::PeakAllocator;
+
+#[derive(Default)]
 pub struct NickelPlugin {
     pub cache: NickelCache,
-}
-
-impl Default for NickelPlugin {
-    fn default() -> Self {
-        Self {
-            cache: NickelCache::default(),
-        }
-    }
+    pub index: IndexCache,
+    /// How worker processes running limited evaluations are started
+    pub worker: WorkerCommand,
 }
 
 impl Plugin for NickelPlugin {
@@ -26,11 +37,20 @@ impl Plugin for NickelPlugin {
 
     fn commands(&self) -> Vec<Box<dyn PluginCommand<Plugin = Self>>> {
         command::core_commands()
+            .into_iter()
+            .chain(command::cache_commands())
+            .chain(command::codegen_commands())
+            .chain(command::convert_commands())
+            .chain(command::outputs_commands())
+            .chain(command::package_commands())
+            .chain(command::project_commands())
+            .chain(command::stdlib_commands())
+            .collect()
     }
 
     fn custom_value_dropped(
         &self,
-        _engine: &nu_plugin::EngineInterface,
+        _engine: &EngineInterface,
         custom_value: Box<dyn CustomValue>,
     ) -> Result<(), LabeledError> {
         let custom_value = custom_value
@@ -38,13 +58,71 @@ impl Plugin for NickelPlugin {
             .downcast_ref::<nickel::values::NuNickelValueCustomValue>();
 
         if let Some(custom_value) = custom_value {
-            self.cache.remove(&custom_value.id);
+            self.cache.decrement_ref(&custom_value.id);
         }
 
         Ok(())
     }
+
+    fn custom_value_operation(
+        &self,
+        engine: &EngineInterface,
+        left: Spanned<Box<dyn CustomValue>>,
+        operator: Spanned<Operator>,
+        right: Value,
+    ) -> Result<Value, LabeledError> {
+        let left = Value::custom(left.item, left.span);
+        NuNickelValue::operation(self, engine, &left, operator, &right)
+    }
+
+    fn custom_value_partial_cmp(
+        &self,
+        engine: &EngineInterface,
+        custom_value: Box<dyn CustomValue>,
+        other_value: Value,
+    ) -> Result<Option<Ordering>, LabeledError> {
+        let value = Value::custom(custom_value, other_value.span());
+        NuNickelValue::compare(self, engine, &value, &other_value)
+    }
+
+    fn custom_value_follow_path_int(
+        &self,
+        engine: &EngineInterface,
+        custom_value: Spanned<Box<dyn CustomValue>>,
+        index: Spanned<usize>,
+        optional: bool,
+    ) -> Result<Value, LabeledError> {
+        let value = Value::custom(custom_value.item, custom_value.span);
+        let member = PathMember::Int {
+            val: index.item,
+            span: index.span,
+            optional,
+        };
+        NuNickelValue::follow_path(self, engine, &value, member)
+    }
+
+    fn custom_value_follow_path_string(
+        &self,
+        engine: &EngineInterface,
+        custom_value: Spanned<Box<dyn CustomValue>>,
+        column_name: Spanned<String>,
+        optional: bool,
+        casing: Casing,
+    ) -> Result<Value, LabeledError> {
+        let value = Value::custom(custom_value.item, custom_value.span);
+        let member = PathMember::String {
+            val: column_name.item,
+            span: column_name.span,
+            optional,
+            casing,
+        };
+        NuNickelValue::follow_path(self, engine, &value, member)
+    }
 }
 
 pub fn serve() {
+    if let Some(job) = std::env::var_os(nickel::worker::WORKER_VAR) {
+        command::core::serve_worker(Path::new(&job));
+    }
     serve_plugin(&NickelPlugin::default(), MsgPackSerializer {})
-}
\ No newline at end of file
+}
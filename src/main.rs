@@ -1,6 +1,10 @@
-use nu_plugin_nickel::serve;
+use nu_plugin_nickel::{measure::PeakAllocator, serve};
+
+/// Counts the memory allocated by evaluations, to measure and limit it
+#[global_allocator]
+static ALLOCATOR: PeakAllocator = PeakAllocator;
 
 fn main() {
     env_logger::init();
     serve();
-}
\ No newline at end of file
+}
@@ -0,0 +1,152 @@
+use nu_protocol::{Record, Span, Value};
+use serde::{Deserialize, Serialize};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+/// Allocated bytes past which the process exits, set by [`limit_memory`]
+static LIMIT: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// Exit code of a process that allocated more than the limit set by [`limit_memory`]
+pub const EXIT_MEMORY: i32 = 86;
+
+/// The system allocator, keeping track of the peak of allocated memory for [`measure`] and
+/// enforcing the limit of [`limit_memory`]
+pub struct PeakAllocator;
+
+impl PeakAllocator {
+    fn grow(size: usize) {
+        let allocated = ALLOCATED.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK.fetch_max(allocated, Ordering::Relaxed);
+        if allocated > LIMIT.load(Ordering::Relaxed) {
+            Self::exceeded();
+        }
+    }
+
+    fn shrink(size: usize) {
+        ALLOCATED.fetch_sub(size, Ordering::Relaxed);
+    }
+
+    /// End the process over its memory limit
+    ///
+    /// An allocator may neither unwind nor wait, as the allocating thread may hold locks, so the
+    /// process exits right away, without running destructors that could take them.
+    fn exceeded() -> ! {
+        // SAFETY: `_exit` neither allocates nor returns
+        unsafe { libc::_exit(EXIT_MEMORY) }
+    }
+}
+
+unsafe impl GlobalAlloc for PeakAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            Self::grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc_zeroed(layout) };
+        if !ptr.is_null() {
+            Self::grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        Self::shrink(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            Self::shrink(layout.size());
+            Self::grow(new_size);
+        }
+        new_ptr
+    }
+}
+
+/// Exit the process with [`EXIT_MEMORY`] once it allocates `max_memory` bytes more than now
+///
+/// The limit applies to the whole process, so it is only set by worker processes running a
+/// single evaluation, see [`crate::nickel::worker`].
+pub fn limit_memory(max_memory: usize) {
+    let allocated = ALLOCATED.load(Ordering::Relaxed);
+    LIMIT.store(allocated.saturating_add(max_memory), Ordering::Relaxed);
+}
+
+/// Stop limiting the memory of the process
+pub fn lift_memory_limit() {
+    LIMIT.store(usize::MAX, Ordering::Relaxed);
+}
+
+/// Resources used to run some code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Measure {
+    pub wall_time: Duration,
+    /// CPU time of the current thread, where the platform reports it
+    pub cpu_time: Option<Duration>,
+    /// Peak of the memory allocated on top of what was allocated before
+    pub peak_memory: usize,
+}
+
+impl Measure {
+    pub fn into_record(self, span: Span) -> Record {
+        let duration = |d: Duration| Value::duration(d.as_nanos() as i64, span);
+
+        let mut record = Record::new();
+        record.push("wall_time", duration(self.wall_time));
+        record.push(
+            "cpu_time",
+            self.cpu_time.map_or(Value::nothing(span), duration),
+        );
+        record.push(
+            "peak_memory",
+            Value::filesize(self.peak_memory as i64, span),
+        );
+        record
+    }
+}
+
+/// Run `f`, measuring the time and memory it takes
+///
+/// The peak of memory is shared by the whole plugin, so it also counts the allocations of
+/// commands running at the same time.
+pub fn measure<T>(f: impl FnOnce() -> T) -> (T, Measure) {
+    let allocated = ALLOCATED.load(Ordering::Relaxed);
+    PEAK.store(allocated, Ordering::Relaxed);
+    let cpu_start = thread_cpu_time();
+    let start = Instant::now();
+
+    let result = f();
+
+    let measure = Measure {
+        wall_time: start.elapsed(),
+        cpu_time: cpu_start
+            .zip(thread_cpu_time())
+            .map(|(start, end)| end.saturating_sub(start)),
+        peak_memory: PEAK.load(Ordering::Relaxed).saturating_sub(allocated),
+    };
+    (result, measure)
+}
+
+#[cfg(unix)]
+fn thread_cpu_time() -> Option<Duration> {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `time` is a valid timespec for the call to write into
+    let result = unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time) };
+    (result == 0).then(|| Duration::new(time.tv_sec as u64, time.tv_nsec as u32))
+}
+
+#[cfg(not(unix))]
+fn thread_cpu_time() -> Option<Duration> {
+    None
+}
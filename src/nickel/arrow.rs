@@ -0,0 +1,148 @@
+use arrow_array::{
+    ArrayRef, BooleanArray, Float64Array, Int64Array, NullArray, RecordBatch, RecordBatchOptions,
+    StringArray,
+};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+use nu_protocol::{LabeledError, Span, Value};
+#[cfg(feature = "parquet")]
+use parquet::{arrow::ArrowWriter, errors::ParquetError};
+use std::sync::Arc;
+
+/// Convert a table of flat records into an Arrow record batch
+///
+/// Columns are ordered by their first appearance, and a record lacking a column is null there.
+/// Integers and floats may share a column, which then holds floats, but any other mix of types is
+/// an error, as are nested records and lists.
+pub fn record_batch(value: &Value, span: Span) -> Result<RecordBatch, LabeledError> {
+    let not_a_table = |found: String| {
+        LabeledError::new("Not a table")
+            .with_label(format!("Expected a list of records, found {found}"), span)
+    };
+    let Value::List { vals: rows, .. } = value else {
+        return Err(not_a_table(value.get_type().to_string()));
+    };
+    let records = rows
+        .iter()
+        .map(|row| match row {
+            Value::Record { val, .. } => Ok(&**val),
+            other => Err(not_a_table(format!("a list holding {}", other.get_type()))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut names: Vec<&str> = Vec::new();
+    for record in &records {
+        for name in record.columns() {
+            if !names.contains(&name.as_str()) {
+                names.push(name);
+            }
+        }
+    }
+
+    let mut fields = Vec::new();
+    let mut columns = Vec::new();
+    for name in names {
+        let cells: Vec<_> = records
+            .iter()
+            .map(|record| record.get(name).filter(|cell| !cell.is_nothing()))
+            .collect();
+        let column = column(name, &cells, span)?;
+        fields.push(Field::new(name, column.data_type().clone(), true));
+        columns.push(column);
+    }
+
+    RecordBatch::try_new_with_options(
+        Arc::new(Schema::new(fields)),
+        columns,
+        &RecordBatchOptions::new().with_row_count(Some(records.len())),
+    )
+    .map_err(|e| arrow_error(e, span))
+}
+
+/// Write a table of flat records in the Arrow IPC file format, as read by `polars open`
+pub fn to_arrow_ipc(value: &Value, span: Span) -> Result<Vec<u8>, LabeledError> {
+    let batch = record_batch(value, span)?;
+    let mut writer =
+        FileWriter::try_new(Vec::new(), &batch.schema()).map_err(|e| arrow_error(e, span))?;
+    writer.write(&batch).map_err(|e| arrow_error(e, span))?;
+    writer.into_inner().map_err(|e| arrow_error(e, span))
+}
+
+/// Write a table of flat records as a Parquet file, uncompressed
+#[cfg(feature = "parquet")]
+pub fn to_parquet(value: &Value, span: Span) -> Result<Vec<u8>, LabeledError> {
+    let batch = record_batch(value, span)?;
+    let parquet_error = |e: ParquetError| {
+        LabeledError::new("Failed to write Parquet data").with_label(e.to_string(), span)
+    };
+    let mut writer =
+        ArrowWriter::try_new(Vec::new(), batch.schema(), None).map_err(parquet_error)?;
+    writer.write(&batch).map_err(parquet_error)?;
+    writer.into_inner().map_err(parquet_error)
+}
+
+/// Build the Arrow array of a column from its cells, `None` where they are null or missing
+fn column(name: &str, cells: &[Option<&Value>], span: Span) -> Result<ArrayRef, LabeledError> {
+    let mut data_type = DataType::Null;
+    for cell in cells.iter().flatten() {
+        let cell_type = match cell {
+            Value::Int { .. } => DataType::Int64,
+            Value::Float { .. } => DataType::Float64,
+            Value::Bool { .. } => DataType::Boolean,
+            Value::String { .. } => DataType::Utf8,
+            other => {
+                return Err(LabeledError::new("Not a flat table")
+                    .with_label(
+                        format!("Column `{name}` holds a {}", other.get_type()),
+                        span,
+                    )
+                    .with_help("select or flatten nested fields before converting the table"));
+            }
+        };
+        data_type = match (data_type, cell_type) {
+            (DataType::Null, cell_type) => cell_type,
+            (DataType::Int64, DataType::Float64) | (DataType::Float64, DataType::Int64) => {
+                DataType::Float64
+            }
+            (data_type, cell_type) if data_type == cell_type => data_type,
+            (data_type, cell_type) => {
+                return Err(LabeledError::new("Mixed column types").with_label(
+                    format!("Column `{name}` holds both {data_type} and {cell_type} values"),
+                    span,
+                ));
+            }
+        };
+    }
+
+    Ok(match data_type {
+        DataType::Int64 => Arc::new(
+            cells
+                .iter()
+                .map(|cell| cell.and_then(|cell| cell.as_int().ok()))
+                .collect::<Int64Array>(),
+        ),
+        DataType::Float64 => Arc::new(
+            cells
+                .iter()
+                .map(|cell| cell.and_then(|cell| cell.coerce_float().ok()))
+                .collect::<Float64Array>(),
+        ),
+        DataType::Boolean => Arc::new(
+            cells
+                .iter()
+                .map(|cell| cell.and_then(|cell| cell.as_bool().ok()))
+                .collect::<BooleanArray>(),
+        ),
+        DataType::Utf8 => Arc::new(
+            cells
+                .iter()
+                .map(|cell| cell.and_then(|cell| cell.as_str().ok()))
+                .collect::<StringArray>(),
+        ),
+        _ => Arc::new(NullArray::new(cells.len())),
+    })
+}
+
+fn arrow_error(error: ArrowError, span: Span) -> LabeledError {
+    LabeledError::new("Failed to write Arrow data").with_label(error.to_string(), span)
+}
@@ -0,0 +1,353 @@
+use nickel_lang_core::{
+    identifier::LocIdent,
+    term::{RichTerm, Term, record::Field},
+    typ::{EnumRowsIteratorItem, RecordRowsIteratorItem, Type, TypeF},
+};
+use std::collections::HashSet;
+
+/// The Rust type of values that no contract describes, or whose contract is not understood
+const ANY_TYPE: &str = "serde_json::Value";
+
+/// Contracts of the standard library with a closer Rust type than their base type
+const KNOWN_CONTRACTS: [(&str, &str); 5] = [
+    ("std.number.Integer", "i64"),
+    ("std.number.Nat", "u64"),
+    ("std.number.PosNat", "u64"),
+    ("std.string.NonEmpty", "String"),
+    ("std.string.Stringable", "String"),
+];
+
+/// Strict and reserved Rust keywords, which are not allowed as field names and are written as
+/// raw identifiers
+const KEYWORDS: [&str; 50] = [
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while", "abstract", "become", "box", "do", "final", "gen", "macro",
+    "override", "priv", "try", "typeof", "unsized", "virtual", "yield",
+];
+
+/// Keywords that cannot be raw identifiers either, and are followed by an underscore instead
+const NOT_RAW: [&str; 4] = ["crate", "self", "Self", "super"];
+
+/// Rust definitions generated from a Nickel record contract, named after the record fields
+///
+/// Fields become struct fields with serde attributes matching the exported Nickel data: renamed
+/// when their name is not a snake case identifier, and `Option`s when they are optional. Fields
+/// with a default value are always exported, so they are not optional in Rust.
+#[derive(Debug, Default)]
+pub struct RustCodegen {
+    /// The definitions, the root struct first
+    items: Vec<String>,
+    names: HashSet<String>,
+    uses_map: bool,
+}
+
+impl RustCodegen {
+    /// Generate the structs of an evaluated record spine, the root one called `name`
+    pub fn generate(schema: &RichTerm, name: &str) -> Option<String> {
+        let mut codegen = Self::default();
+        let fields = record_fields(schema)?;
+        let name = codegen.reserve(pascal_case(name), "");
+        codegen.record_struct(&name, &fields);
+
+        let mut source = String::from("use serde::{Deserialize, Serialize};\n");
+        if codegen.uses_map {
+            source.push_str("use std::collections::HashMap;\n");
+        }
+        for item in codegen.items {
+            source.push('\n');
+            source.push_str(&item);
+        }
+        Some(source)
+    }
+
+    /// Reserve a name for a definition, qualifying it with its parent when it is taken
+    fn reserve(&mut self, name: String, parent: &str) -> String {
+        let mut candidate = name.clone();
+        if self.names.contains(&candidate) {
+            candidate = format!("{parent}{name}");
+        }
+        let mut n = 2;
+        while self.names.contains(&candidate) {
+            candidate = format!("{parent}{name}{n}");
+            n += 1;
+        }
+        self.names.insert(candidate.clone());
+        candidate
+    }
+
+    /// Generate a struct of record fields, before the definitions of their types
+    fn record_struct(&mut self, name: &str, fields: &[(&LocIdent, &Field)]) {
+        let slot = self.open_item();
+        let mut body = String::new();
+        let mut idents = HashSet::new();
+        for (id, field) in fields {
+            if field.metadata.not_exported {
+                continue;
+            }
+            let typ = self.field_type(field, name, id.label());
+            body.push_str(&struct_field(
+                id.label(),
+                &typ,
+                field.metadata.doc.as_deref(),
+                field.metadata.opt,
+                &mut idents,
+            ));
+        }
+        self.items[slot] = item("struct", name, &body);
+    }
+
+    /// Reserve the place of a definition, so that it comes before those it refers to
+    fn open_item(&mut self) -> usize {
+        self.items.push(String::new());
+        self.items.len() - 1
+    }
+
+    /// The Rust type of a field, from its type annotation, its contracts or its value
+    fn field_type(&mut self, field: &Field, parent: &str, label: &str) -> String {
+        let annotation = &field.metadata.annotation;
+        let declared = annotation
+            .typ
+            .iter()
+            .chain(&annotation.contracts)
+            .find_map(|labeled| self.rust_type(&labeled.typ, parent, label));
+        if let Some(typ) = declared {
+            return typ;
+        }
+        match field.value.as_ref().map(AsRef::as_ref) {
+            Some(term @ (Term::Record(_) | Term::RecRecord(..))) => {
+                self.nested_struct(term, parent, label)
+            }
+            Some(Term::Num(n)) if i64::try_from(n).is_ok() => "i64".to_string(),
+            Some(Term::Num(_)) => "f64".to_string(),
+            Some(Term::Str(_)) => "String".to_string(),
+            Some(Term::Bool(_)) => "bool".to_string(),
+            _ => ANY_TYPE.to_string(),
+        }
+    }
+
+    fn nested_struct(&mut self, term: &Term, parent: &str, label: &str) -> String {
+        let name = self.reserve(pascal_case(label), parent);
+        let fields = match term {
+            Term::Record(data) | Term::RecRecord(data, ..) => sorted_fields(&data.fields),
+            _ => Vec::new(),
+        };
+        self.record_struct(&name, &fields);
+        name
+    }
+
+    /// The Rust type of a Nickel type, or `None` when it has no Rust equivalent
+    fn rust_type(&mut self, typ: &Type, parent: &str, label: &str) -> Option<String> {
+        Some(match &typ.typ {
+            TypeF::Dyn => ANY_TYPE.to_string(),
+            TypeF::Number => "f64".to_string(),
+            TypeF::Bool => "bool".to_string(),
+            TypeF::String => "String".to_string(),
+            TypeF::Array(element) => {
+                let element = self
+                    .rust_type(element, parent, label)
+                    .unwrap_or_else(|| ANY_TYPE.to_string());
+                format!("Vec<{element}>")
+            }
+            TypeF::Dict { type_fields, .. } => {
+                self.uses_map = true;
+                let value = self
+                    .rust_type(type_fields, parent, label)
+                    .unwrap_or_else(|| ANY_TYPE.to_string());
+                format!("HashMap<String, {value}>")
+            }
+            TypeF::Record(rows) => {
+                let name = self.reserve(pascal_case(label), parent);
+                let slot = self.open_item();
+                let mut body = String::new();
+                let mut idents = HashSet::new();
+                for row in rows.iter() {
+                    let RecordRowsIteratorItem::Row(row) = row else {
+                        continue;
+                    };
+                    let field = row.id.label();
+                    let typ = self
+                        .rust_type(row.typ, &name, field)
+                        .unwrap_or_else(|| ANY_TYPE.to_string());
+                    body.push_str(&struct_field(field, &typ, None, false, &mut idents));
+                }
+                self.items[slot] = item("struct", &name, &body);
+                name
+            }
+            TypeF::Enum(rows) => {
+                let name = self.reserve(pascal_case(label), parent);
+                let slot = self.open_item();
+                let mut body = String::new();
+                for row in rows.iter() {
+                    let EnumRowsIteratorItem::Row(row) = row else {
+                        continue;
+                    };
+                    let tag = row.id.label();
+                    let variant = pascal_case(tag);
+                    if variant != tag {
+                        body.push_str(&format!("    #[serde(rename = {tag:?})]\n"));
+                    }
+                    match row.typ {
+                        Some(arg) => {
+                            let arg = self
+                                .rust_type(arg, &name, tag)
+                                .unwrap_or_else(|| ANY_TYPE.to_string());
+                            body.push_str(&format!("    {variant}({arg}),\n"));
+                        }
+                        None => body.push_str(&format!("    {variant},\n")),
+                    }
+                }
+                self.items[slot] = item("enum", &name, &body);
+                name
+            }
+            TypeF::Contract(term) => match term.as_ref() {
+                record @ (Term::Record(_) | Term::RecRecord(..)) => {
+                    self.nested_struct(record, parent, label)
+                }
+                _ => {
+                    let text = typ.to_string();
+                    let (_, rust) = KNOWN_CONTRACTS
+                        .iter()
+                        .find(|(contract, _)| *contract == text)?;
+                    rust.to_string()
+                }
+            },
+            TypeF::Symbol
+            | TypeF::ForeignId
+            | TypeF::Arrow(..)
+            | TypeF::Var(_)
+            | TypeF::Forall { .. }
+            | TypeF::Wildcard(_) => return None,
+        })
+    }
+}
+
+/// A struct or enum definition deriving the serde traits
+fn item(keyword: &str, name: &str, body: &str) -> String {
+    format!(
+        "#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]\npub {keyword} {name} {{\n{body}}}\n"
+    )
+}
+
+/// A field of a struct, with its documentation and the serde attributes it needs
+///
+/// `idents` holds the identifiers of the previous fields of the struct, which the field's is kept
+/// apart from.
+fn struct_field(
+    label: &str,
+    typ: &str,
+    doc: Option<&str>,
+    optional: bool,
+    idents: &mut HashSet<String>,
+) -> String {
+    let mut lines = String::new();
+    for line in doc.unwrap_or_default().trim_end().lines() {
+        lines.push_str(&format!("    /// {line}").trim_end().to_string());
+        lines.push('\n');
+    }
+    let ident = unique_ident(field_ident(label), idents);
+    if ident.trim_start_matches("r#") != label {
+        lines.push_str(&format!("    #[serde(rename = {label:?})]\n"));
+    }
+    if optional {
+        lines.push_str("    #[serde(default, skip_serializing_if = \"Option::is_none\")]\n");
+        lines.push_str(&format!("    pub {ident}: Option<{typ}>,\n"));
+    } else {
+        lines.push_str(&format!("    pub {ident}: {typ},\n"));
+    }
+    lines
+}
+
+/// The fields of an evaluated record, sorted by name so that the output does not change
+fn record_fields(term: &RichTerm) -> Option<Vec<(&LocIdent, &Field)>> {
+    match term.as_ref() {
+        Term::Record(data) | Term::RecRecord(data, ..) => Some(sorted_fields(&data.fields)),
+        _ => None,
+    }
+}
+
+fn sorted_fields<'a>(
+    fields: impl IntoIterator<Item = (&'a LocIdent, &'a Field)>,
+) -> Vec<(&'a LocIdent, &'a Field)> {
+    let mut fields: Vec<_> = fields.into_iter().collect();
+    fields.sort_by_key(|(id, _)| id.label());
+    fields
+}
+
+/// A name in `PascalCase`, from any mix of cases, dashes, dots and underscores
+pub fn pascal_case(name: &str) -> String {
+    let pascal: String = words(name)
+        .iter()
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        })
+        .collect();
+    match pascal.chars().next() {
+        Some(first) if first.is_ascii_alphabetic() => pascal,
+        _ => format!("T{pascal}"),
+    }
+}
+
+/// A field name as a `snake_case` Rust identifier
+fn field_ident(name: &str) -> String {
+    let snake = words(name)
+        .iter()
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join("_");
+    match snake.chars().next() {
+        Some(first) if first.is_ascii_alphabetic() || first == '_' => {
+            if NOT_RAW.contains(&snake.as_str()) {
+                format!("{snake}_")
+            } else if KEYWORDS.contains(&snake.as_str()) {
+                format!("r#{snake}")
+            } else {
+                snake
+            }
+        }
+        _ => format!("field_{snake}"),
+    }
+}
+
+/// An identifier numbered apart from those already in `idents`, like fields whose names only
+/// differ in case or separators, such as `foo-bar` and `foo_bar`
+fn unique_ident(ident: String, idents: &mut HashSet<String>) -> String {
+    let mut candidate = ident.clone();
+    let mut n = 2;
+    while idents.contains(&candidate) {
+        candidate = format!("{}_{n}", ident.trim_start_matches("r#"));
+        n += 1;
+    }
+    idents.insert(candidate.clone());
+    candidate
+}
+
+/// The words of a name, split at separators and at lowercase to uppercase changes
+fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut previous_lower = false;
+    for c in name.chars() {
+        if !c.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            previous_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && previous_lower {
+            words.push(std::mem::take(&mut word));
+        }
+        previous_lower = c.is_lowercase() || c.is_ascii_digit();
+        word.push(c);
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
@@ -0,0 +1,104 @@
+use crate::NickelPlugin;
+use crate::nickel::values::NuNickelValueCustomValue;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, LabeledError, PipelineData, Record, Signature, Type, Value};
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct NickelCacheClear;
+
+impl PluginCommand for NickelCacheClear {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel cache clear"
+    }
+
+    fn signature(&self) -> Signature {
+        let nickel_value = Type::Custom("NickelValue".to_string().into());
+        Signature::build("nickel cache clear")
+            .input_output_types(vec![
+                (Type::Nothing, Type::record()),
+                (nickel_value.clone(), Type::record()),
+                (Type::List(Box::new(nickel_value)), Type::record()),
+            ])
+            .switch(
+                "force",
+                "Also drop entries still referenced by a Nickel value",
+                Some('f'),
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "Drop entries from the plugin cache"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Without input, every entry of the cache is considered, otherwise only the entries of the \
+         piped Nickel values. An entry is unreferenced once every Nushell value holding it was \
+         dropped, and stays in the cache until it is cleared or evicted. Entries still referenced \
+         by a value are kept unless `--force` is given, in which case the values referencing them \
+         can no longer be used."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Drop the unreferenced entries of the cache",
+                example: "nickel cache clear",
+                result: None,
+            },
+            Example {
+                description: "Drop every entry of the cache",
+                example: "nickel cache clear --force",
+                result: None,
+            },
+            Example {
+                description: "Drop the entry of a parsed value",
+                example: r#""{ foo = 1 }" | nickel parse | nickel cache clear --force"#,
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        plugin: &NickelPlugin,
+        _engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+
+        let ids = match input {
+            PipelineData::Empty | PipelineData::Value(Value::Nothing { .. }, _) => None,
+            input => Some(
+                input
+                    .into_iter()
+                    .map(|value| nickel_value_id(&value))
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+        };
+        let removed = plugin.cache.clear(ids.as_deref(), call.has_flag("force")?);
+
+        let mut record = Record::new();
+        record.push("removed", Value::int(removed as i64, span));
+        record.push("remaining", Value::int(plugin.cache.len() as i64, span));
+        Ok(PipelineData::Value(Value::record(record, span), None))
+    }
+}
+
+fn nickel_value_id(value: &Value) -> Result<Uuid, LabeledError> {
+    value
+        .as_custom_value()
+        .ok()
+        .and_then(|custom| custom.as_any().downcast_ref::<NuNickelValueCustomValue>())
+        .map(|custom| custom.id)
+        .ok_or_else(|| {
+            LabeledError::new("Invalid input type").with_label(
+                format!("Expected a Nickel value, found {}", value.get_type()),
+                value.span(),
+            )
+        })
+}
@@ -0,0 +1,8 @@
+mod clear;
+mod stats;
+
+#[cfg(test)]
+mod tests;
+
+pub use clear::NickelCacheClear;
+pub use stats::NickelCacheStats;
@@ -0,0 +1,65 @@
+use crate::NickelPlugin;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, LabeledError, PipelineData, Record, Signature, Type, Value};
+
+#[derive(Clone)]
+pub struct NickelCacheStats;
+
+impl PluginCommand for NickelCacheStats {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel cache stats"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel cache stats")
+            .input_output_types(vec![(Type::Any, Type::record())])
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "Show the size of the plugin cache and its hit, miss, insert and eviction counters"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Counters start at zero when the plugin starts. A miss is a lookup of an entry that was \
+         already evicted, which is what a \"value no longer available\" error comes from."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![Example {
+            description: "Show the cache statistics",
+            example: "nickel cache stats",
+            result: None,
+        }]
+    }
+
+    fn run(
+        &self,
+        plugin: &NickelPlugin,
+        _engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let stats = plugin.cache.stats();
+        let lookups = stats.hits + stats.misses;
+
+        let mut record = Record::new();
+        record.push("entries", Value::int(stats.entries as i64, span));
+        record.push("hits", Value::int(stats.hits as i64, span));
+        record.push("misses", Value::int(stats.misses as i64, span));
+        record.push("inserts", Value::int(stats.inserts as i64, span));
+        record.push("evictions", Value::int(stats.evictions as i64, span));
+        record.push(
+            "hit_rate",
+            if lookups == 0 {
+                Value::nothing(span)
+            } else {
+                Value::float(stats.hits as f64 / lookups as f64, span)
+            },
+        );
+        Ok(PipelineData::Value(Value::record(record, span), None))
+    }
+}
@@ -0,0 +1,41 @@
+use crate::nickel::command::test_support::eval;
+use nu_protocol::Value;
+
+#[test]
+fn test_nickel_cache_clear() {
+    let result = eval(r#""{ foo = 1 }" | nickel parse | nickel cache clear"#);
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("removed"), Some(&Value::test_int(0)));
+    assert_eq!(record.get("remaining"), Some(&Value::test_int(1)));
+
+    let result = eval(r#""{ foo = 1 }" | nickel parse | ignore; nickel cache clear"#);
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("removed"), Some(&Value::test_int(1)));
+    assert_eq!(record.get("remaining"), Some(&Value::test_int(0)));
+
+    let result = eval(r#""{ foo = 1 }" | nickel parse | nickel cache clear --force"#);
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("removed"), Some(&Value::test_int(1)));
+    assert_eq!(record.get("remaining"), Some(&Value::test_int(0)));
+
+    let result = eval("nickel cache clear --force");
+    assert_eq!(
+        result.as_record().unwrap().get("removed"),
+        Some(&Value::test_int(0))
+    );
+}
+
+#[test]
+fn test_nickel_cache_stats() {
+    let result = eval("nickel cache stats");
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("entries"), Some(&Value::test_int(0)));
+    assert_eq!(record.get("inserts"), Some(&Value::test_int(0)));
+    assert_eq!(record.get("hit_rate"), Some(&Value::test_nothing()));
+
+    let result = eval(r#""{ foo = 1 }" | nickel parse | nickel cache stats"#);
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("entries"), Some(&Value::test_int(1)));
+    assert_eq!(record.get("inserts"), Some(&Value::test_int(1)));
+    assert_eq!(record.get("evictions"), Some(&Value::test_int(0)));
+}
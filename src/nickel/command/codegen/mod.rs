@@ -0,0 +1,6 @@
+mod rust;
+
+#[cfg(test)]
+mod tests;
+
+pub use rust::NickelCodegenRust;
@@ -0,0 +1,122 @@
+use crate::NickelPlugin;
+use crate::nickel::{
+    codegen::RustCodegen,
+    error::nickel_error,
+    input::{NickelInput, import_paths, no_imports, working_dir},
+    program::new_program,
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Signature, SyntaxShape, Type, Value,
+};
+
+/// The name of the root struct of piped code, when `--name` is not given
+const DEFAULT_NAME: &str = "Config";
+
+#[derive(Clone)]
+pub struct NickelCodegenRust;
+
+impl PluginCommand for NickelCodegenRust {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel codegen rust"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel codegen rust")
+            .input_output_types(vec![
+                (Type::String, Type::String),
+                (Type::Nothing, Type::String),
+            ])
+            .optional(
+                "path",
+                SyntaxShape::Filepath,
+                "Nickel file of the record contract to generate structs for",
+            )
+            .named(
+                "name",
+                SyntaxShape::String,
+                "Name of the root struct, the file name in PascalCase by default",
+                None,
+            )
+            .named(
+                "import-path",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Directories to look up imports in, before those of `NICKEL_IMPORT_PATH`",
+                Some('I'),
+            )
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths and imports",
+                None,
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "Generate serde-annotated Rust structs matching a Nickel record contract"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Only the record spine of the contract is evaluated, so fields without a value are \
+         fine. A field gets its Rust type from its type annotation, or from the first of its \
+         contracts with a Rust equivalent: `Number` is `f64`, `std.number.Integer` is `i64`, \
+         arrays are `Vec`s, dictionaries `HashMap`s, and record contracts, enums and nested \
+         records are structs and enums of their own, named after their field. Otherwise the type \
+         comes from the value of the field, or is `serde_json::Value`, like for contracts bound \
+         to a name.\n\n\
+         Optional fields are `Option`s. Fields with a default value are always exported, so they \
+         are not. Field documentation becomes doc comments, and fields whose name is not a \
+         snake case identifier are renamed with `#[serde(rename)]`."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Keep the config struct of a service in sync with its schema",
+                example: "nickel codegen rust schema.ncl --name ServiceConfig | save -f src/config.rs",
+                result: None,
+            },
+            Example {
+                description: "Generate a struct from a contract written inline",
+                example: r#""{ host | String, port | std.number.Integer }" | nickel codegen rust"#,
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let mut input = NickelInput::from_call(call, input, 0, Some(working_dir(engine, call)?))?;
+        input.import_paths = import_paths(engine, call)?;
+        input.no_imports = no_imports(engine, call)?;
+        let name = match call.get_flag::<String>("name")? {
+            Some(name) => name,
+            None => input
+                .path
+                .as_deref()
+                .and_then(|path| path.file_stem())
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| DEFAULT_NAME.to_string()),
+        };
+
+        let mut program = new_program(&input, span)?;
+        let schema = program
+            .eval_record_spine()
+            .map_err(|e| nickel_error(&mut program.files(), e, "Nickel evaluation failed", span))?;
+        let source = RustCodegen::generate(&schema, &name).ok_or_else(|| {
+            LabeledError::new("Not a record contract")
+                .with_label("The program does not evaluate to a record", span)
+                .with_help("generate structs from a record like `{ port | Number }`")
+        })?;
+        Ok(PipelineData::Value(Value::string(source, span), None))
+    }
+}
@@ -0,0 +1,61 @@
+use crate::nickel::command::test_support::{eval, eval_error, temp_dir};
+
+#[test]
+fn test_nickel_codegen_rust() {
+    let dir = temp_dir();
+    std::fs::write(
+        dir.join("service-schema.ncl"),
+        r#"{
+  name | doc "Name of the service" | String,
+  replicas | Number | default = 1,
+  port | std.number.Integer,
+  tags | Array String | optional,
+  "log-level" | [| 'debug, 'info |],
+  server | { host | String },
+  labels | { _ : String },
+  secret | not_exported = "s",
+}"#,
+    )
+    .unwrap();
+
+    let result = eval(&format!(
+        "nickel codegen rust service-schema.ncl --cwd '{}'",
+        dir.display()
+    ));
+    let source = result.as_str().unwrap();
+    assert!(
+        source
+            .starts_with("use serde::{Deserialize, Serialize};\nuse std::collections::HashMap;\n")
+    );
+    assert!(source.contains("pub struct ServiceSchema {\n"));
+    assert!(source.contains("    /// Name of the service\n    pub name: String,\n"));
+    assert!(source.contains("    pub replicas: f64,\n"));
+    assert!(source.contains("    pub port: i64,\n"));
+    assert!(source.contains(
+        "    #[serde(default, skip_serializing_if = \"Option::is_none\")]\n    pub tags: Option<Vec<String>>,\n"
+    ));
+    assert!(
+        source.contains("    #[serde(rename = \"log-level\")]\n    pub log_level: LogLevel,\n")
+    );
+    assert!(source.contains("pub enum LogLevel {\n    #[serde(rename = \"debug\")]\n    Debug,\n"));
+    assert!(source.contains("    pub server: Server,\n"));
+    assert!(source.contains("pub struct Server {\n    pub host: String,\n}"));
+    assert!(source.contains("    pub labels: HashMap<String, String>,\n"));
+    assert!(!source.contains("secret"));
+
+    let result = eval(r#""{ host | String }" | nickel codegen rust --name web"#);
+    assert!(result.as_str().unwrap().contains("pub struct Web {\n"));
+
+    // Keywords are raw identifiers, or renamed when they cannot be, and colliding names numbered
+    let result = eval(
+        r#""{ try | Bool, self | Bool, \"foo-bar\" | Number, foo_bar | String }" | nickel codegen rust"#,
+    );
+    let source = result.as_str().unwrap();
+    assert!(source.contains("    pub r#try: bool,\n"));
+    assert!(source.contains("    #[serde(rename = \"self\")]\n    pub self_: bool,\n"));
+    assert!(source.contains("    #[serde(rename = \"foo-bar\")]\n    pub foo_bar: f64,\n"));
+    assert!(source.contains("    #[serde(rename = \"foo_bar\")]\n    pub foo_bar_2: String,\n"));
+
+    let error = eval_error(r#""[1, 2]" | nickel codegen rust"#);
+    assert_eq!(error.msg, "Not a record contract");
+}
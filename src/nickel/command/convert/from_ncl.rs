@@ -0,0 +1,107 @@
+use crate::NickelPlugin;
+use crate::nickel::{
+    convert::nickel_to_nu_value,
+    input::{NickelInput, no_imports, working_dir},
+    program::{eval_for_export, new_program},
+    values::NuNickelValue,
+};
+use nickel_lang_core::cache::InputFormat;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, DataSource, Example, LabeledError, PipelineData, Signature, Type};
+
+#[derive(Clone)]
+pub struct FromNcl;
+
+impl PluginCommand for FromNcl {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "from ncl"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("from ncl")
+            .input_output_types(vec![(Type::String, Type::Any)])
+            .switch(
+                "handle",
+                "Return a Nickel value of the parsed source instead of evaluating it",
+                Some('H'),
+            )
+            .category(Category::Formats)
+    }
+
+    fn description(&self) -> &str {
+        "Evaluate Nickel source into structured data"
+    }
+
+    fn extra_description(&self) -> &str {
+        "This makes `open` evaluate `.ncl` files. The imports of a file opened this way are \
+         resolved next to it, and the imports of other input from the current directory."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Open a Nickel file as structured data",
+                example: "open config.ncl",
+                result: None,
+            },
+            Example {
+                description: "Evaluate Nickel source",
+                example: "'{ replicas = 1 + 2 }' | from ncl",
+                result: None,
+            },
+            Example {
+                description: "Open a Nickel file without evaluating it",
+                example: "open --raw config.ncl | from ncl --handle",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+
+        // `open` records the file it read, whose directory the imports are relative to
+        let base_dir = match input.metadata().map(|metadata| metadata.data_source) {
+            Some(DataSource::FilePath(path)) => path.parent().map(|dir| dir.to_path_buf()),
+            _ => None,
+        };
+        let base_dir = match base_dir {
+            Some(dir) => dir,
+            None => working_dir(engine, call)?,
+        };
+
+        let value = input.into_value(span)?;
+        let source = value.as_str().map_err(|_| {
+            LabeledError::new("Invalid input type").with_label(
+                format!("Expected Nickel source, found {}", value.get_type()),
+                value.span(),
+            )
+        })?;
+        let input = NickelInput {
+            source: source.to_string(),
+            path: None,
+            format: InputFormat::Nickel,
+            base_dir: Some(base_dir),
+            import_paths: Vec::new(),
+            no_imports: no_imports(engine, call)?,
+            source_name: None,
+        };
+
+        let result = if call.has_flag("handle")? {
+            NuNickelValue::cache_parsed(plugin, input, span)?
+        } else {
+            let mut program = new_program(&input, span)?;
+            let term = eval_for_export(&mut program, span)?;
+            nickel_to_nu_value(&term, span)?
+        };
+        Ok(PipelineData::Value(result, None))
+    }
+}
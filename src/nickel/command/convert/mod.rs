@@ -0,0 +1,8 @@
+mod from_ncl;
+mod to_ncl;
+
+#[cfg(test)]
+mod tests;
+
+pub use from_ncl::FromNcl;
+pub use to_ncl::ToNcl;
@@ -0,0 +1,89 @@
+use crate::nickel::command::test_support::{eval, eval_error};
+use nu_protocol::Value;
+
+#[test]
+fn test_to_ncl() {
+    let result = eval(
+        r#"{ name: web, "app.io/tier": front, ports: [80, 443], ratio: 2.0, extra: null, nested: { if: true, items: [] } } | to ncl"#,
+    );
+    assert_eq!(
+        result,
+        Value::test_string(
+            r#"{
+  name = "web",
+  "app.io/tier" = "front",
+  ports = [80, 443],
+  ratio = 2.0,
+  extra = null,
+  nested = {
+    "if" = true,
+    items = [],
+  },
+}"#
+        )
+    );
+}
+
+#[test]
+fn test_to_ncl_multiline() {
+    let result = eval(r#"{ script: "set -e\nmake" } | to ncl"#);
+    assert_eq!(
+        result,
+        Value::test_string("{\n  script = m%\"\n    set -e\n    make\n  \"%,\n}")
+    );
+}
+
+#[test]
+fn test_to_ncl_round_trip() {
+    let strings = [
+        r#"plain"#,
+        r#"quote \" and backslash \\ and %{interpolation}"#,
+        r#"first\nsecond\n\nthird"#,
+        r#"trailing newline\n"#,
+        r#"\n  leading newline and indent"#,
+        r#"  indented\n  lines"#,
+        r#"multiline with \"% and %{ and \"%%\nend"#,
+        r#"tab\tand\r\nreturn"#,
+    ];
+    for string in strings {
+        let result = eval(&format!(r#"{{ s: "{string}" }} | to ncl | nickel eval"#));
+        let record = result.as_record().unwrap();
+        assert_eq!(
+            record.get("s").unwrap().as_str().unwrap(),
+            unescape(string),
+            "{string}"
+        );
+    }
+}
+
+/// Resolve the escapes of a Nushell double-quoted string
+fn unescape(string: &str) -> String {
+    string
+        .replace("\\n", "\n")
+        .replace("\\t", "\t")
+        .replace("\\r", "\r")
+        .replace("\\\"", "\"")
+        .replace("\\\\", "\\")
+}
+
+#[test]
+fn test_to_ncl_unsupported() {
+    let error = eval_error("0x[01] | to ncl");
+    assert_eq!(error.msg, "Cannot convert to Nickel");
+}
+
+#[test]
+fn test_from_ncl() {
+    let result = eval(r#""{ replicas = 1 + 2 }" | from ncl"#);
+    assert_eq!(
+        result.as_record().unwrap().get("replicas"),
+        Some(&Value::test_int(3))
+    );
+
+    let result = eval(r#""{ replicas = 1 + 2 }" | from ncl --handle | nickel source"#);
+    assert_eq!(result, Value::test_string("{ replicas = 1 + 2 }"));
+
+    let result = eval(r#"{ name: web, ports: [80] } | to ncl | from ncl"#);
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("name"), Some(&Value::test_string("web")));
+}
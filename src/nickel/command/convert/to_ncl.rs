@@ -0,0 +1,61 @@
+use crate::NickelPlugin;
+use crate::nickel::convert::value_to_nickel;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, LabeledError, PipelineData, Signature, Type, Value};
+
+#[derive(Clone)]
+pub struct ToNcl;
+
+impl PluginCommand for ToNcl {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "to ncl"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("to ncl")
+            .input_output_types(vec![(Type::Any, Type::String)])
+            .category(Category::Formats)
+    }
+
+    fn description(&self) -> &str {
+        "Convert Nushell data into Nickel source"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Records and lists become Nickel records and arrays, and strings with several lines become \
+         multiline strings. Dates are written as RFC 3339 strings, file sizes as bytes and \
+         durations as nanoseconds. Binary data, closures and ranges have no Nickel equivalent."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Convert a record into Nickel",
+                example: "{ name: web, replicas: 3 } | to ncl",
+                result: Some(Value::test_string(
+                    "{\n  name = \"web\",\n  replicas = 3,\n}",
+                )),
+            },
+            Example {
+                description: "Generate a Nickel file from a JSON file",
+                example: "open settings.json | to ncl | save settings.ncl",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &NickelPlugin,
+        _engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let value = input.into_value(span)?;
+        let source = value_to_nickel(&value, span)?;
+        Ok(PipelineData::Value(Value::string(source, span), None))
+    }
+}
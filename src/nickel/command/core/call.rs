@@ -0,0 +1,115 @@
+use crate::NickelPlugin;
+use crate::nickel::{
+    convert::nickel_to_nu_value,
+    input::{NickelInput, import_paths, no_imports, working_dir},
+    program::{apply_source, eval_for_export, is_function, new_program},
+    values::NuNickelValue,
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Signature, SyntaxShape, Type, Value,
+};
+
+#[derive(Clone)]
+pub struct NickelCall;
+
+impl PluginCommand for NickelCall {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel call"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel call")
+            .input_output_types(vec![(
+                Type::Custom("NickelValue".to_string().into()),
+                Type::Any,
+            )])
+            .rest(
+                "args",
+                SyntaxShape::Any,
+                "Values to apply the function to, converted to Nickel",
+            )
+            .named(
+                "import-path",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Directories to look up imports in, before those of `NICKEL_IMPORT_PATH`",
+                Some('I'),
+            )
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths and imports",
+                None,
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "Apply a Nickel function to Nushell values"
+    }
+
+    fn extra_description(&self) -> &str {
+        "The function comes from `nickel eval` or `nickel parse` of code whose value is a \
+         function, which they return as a Nickel value rather than converting it. The arguments \
+         are converted like the values of `nickel eval --arg`, and the function is applied to \
+         them in order. A result that is a function again, as when fewer arguments are given than \
+         the function takes, is returned as a Nickel value to call later."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Apply a function of two arguments",
+                example: r#""fun a b => a + b" | nickel eval | nickel call 1 2"#,
+                result: Some(Value::test_int(3)),
+            },
+            Example {
+                description: "Use a function of a library file from Nushell",
+                example: r#"let slug = ("(import \"lib.ncl\").slug" | nickel eval); $slug | nickel call "My Service""#,
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let args: Vec<Value> = call.rest(0)?;
+
+        let value = input.into_value(span)?;
+        let not_a_function = |found: String| {
+            LabeledError::new("Not a function")
+                .with_label(
+                    format!("Expected a Nickel function, found {found}"),
+                    value.span(),
+                )
+                .with_help("make one with `nickel eval` or `nickel parse` of a function")
+        };
+        let cached = match NuNickelValue::try_get_cached_value(plugin, &value)? {
+            Some(cached) if cached.is_function() => cached,
+            Some(cached) => return Err(not_a_function(cached.object_type().to_string())),
+            None => return Err(not_a_function(value.get_type().to_string())),
+        };
+        let mut input = NickelInput::from_cached(&cached, Some(working_dir(engine, call)?))
+            .ok_or_else(|| not_a_function(cached.object_type().to_string()))?;
+        input.source = apply_source(&input.source, &args, span)?;
+        input.import_paths = import_paths(engine, call)?;
+        input.no_imports = no_imports(engine, call)?;
+
+        let mut program = new_program(&input, span)?;
+        let term = eval_for_export(&mut program, span)?;
+        let result = if is_function(&term) {
+            NuNickelValue::cache_function(&plugin.cache, input.source, input.path, span)
+        } else {
+            nickel_to_nu_value(&term, span)?
+        };
+        Ok(PipelineData::Value(result, None))
+    }
+}
@@ -0,0 +1,139 @@
+use super::typecheck::MAX_FIX_ROUNDS;
+use crate::NickelPlugin;
+use crate::nickel::{
+    convert::MAX_DEPTH,
+    format::{OutputFormat, TableFormat},
+    suggest::MAX_CANDIDATES,
+};
+use nickel_lang_core::cache::InputFormat;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Record, Signature, Span, Type, Value,
+};
+
+/// Formats of the files and piped input read by the commands
+const INPUT_FORMATS: [InputFormat; 5] = [
+    InputFormat::Nickel,
+    InputFormat::Json,
+    InputFormat::Yaml,
+    InputFormat::Toml,
+    InputFormat::Text,
+];
+
+#[derive(Clone)]
+pub struct NickelCapabilities;
+
+impl PluginCommand for NickelCapabilities {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel capabilities"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel capabilities")
+            .input_output_types(vec![(Type::Nothing, Type::record())])
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "List what this build of the plugin supports"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Wrapper scripts can check for the `features`, `formats`, `resolvers` and `limits` they \
+         rely on, rather than comparing version numbers. Features are only listed once \
+         supported, as `true` when this build has them and `false` when the platform does not."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Check that YAML files can be read",
+                example: "'yaml' in (nickel capabilities).formats.input",
+                result: Some(Value::test_bool(true)),
+            },
+            Example {
+                description: "Only measure CPU time where it is available",
+                example: "if (nickel capabilities).features.cpu_time { nickel eval config.ncl --measure }",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &NickelPlugin,
+        _engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+
+        let mut features = Record::new();
+        features.push("cpu_time", Value::bool(cfg!(unix), span));
+        features.push("peak_memory", Value::bool(true, span));
+        features.push("atomic_writes", Value::bool(true, span));
+        features.push("dry_run", Value::bool(true, span));
+        features.push("backup", Value::bool(true, span));
+        for format in TableFormat::ALL {
+            features.push(format.feature(), Value::bool(format.is_available(), span));
+        }
+        features.push("sqlite", Value::bool(cfg!(feature = "sqlite"), span));
+
+        let mut formats = Record::new();
+        formats.push(
+            "input",
+            strings(
+                INPUT_FORMATS
+                    .iter()
+                    .map(|format| format.to_str().to_lowercase()),
+                span,
+            ),
+        );
+        // Nickel source is written by `to ncl`, rather than with `--format`
+        formats.push(
+            "output",
+            strings(
+                OutputFormat::ALL
+                    .map(OutputFormat::name)
+                    .into_iter()
+                    .chain(["nickel"])
+                    .chain(
+                        TableFormat::ALL
+                            .into_iter()
+                            .filter(|format| format.is_available())
+                            .map(TableFormat::name),
+                    ),
+                span,
+            ),
+        );
+
+        let mut resolvers = Record::new();
+        resolvers.push("imports", strings(["file", "import_path"], span));
+        resolvers.push("dependencies", strings(["path"], span));
+
+        let mut limits = Record::new();
+        limits.push("max_fix_rounds", Value::int(MAX_FIX_ROUNDS as i64, span));
+        limits.push("max_suggestions", Value::int(MAX_CANDIDATES as i64, span));
+        limits.push("max_depth", Value::int(MAX_DEPTH as i64, span));
+
+        let mut record = Record::new();
+        record.push("version", Value::string(env!("CARGO_PKG_VERSION"), span));
+        record.push("features", Value::record(features, span));
+        record.push("formats", Value::record(formats, span));
+        record.push("resolvers", Value::record(resolvers, span));
+        record.push("limits", Value::record(limits, span));
+        Ok(PipelineData::Value(Value::record(record, span), None))
+    }
+}
+
+fn strings(strings: impl IntoIterator<Item = impl Into<String>>, span: Span) -> Value {
+    Value::list(
+        strings
+            .into_iter()
+            .map(|string| Value::string(string, span))
+            .collect(),
+        span,
+    )
+}
@@ -0,0 +1,178 @@
+use crate::NickelPlugin;
+use crate::nickel::{
+    convert::{json_to_value, value_to_nickel},
+    format::{OutputFormat, content_metadata, parse_data},
+    input::{NickelInput, import_paths, no_imports, working_dir},
+    program::{eval_for_export, export_term, new_program},
+};
+use nickel_lang_core::cache::InputFormat;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Signature, Spanned, SyntaxShape, Type, Value,
+};
+
+#[derive(Clone)]
+pub struct NickelConvert;
+
+impl PluginCommand for NickelConvert {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel convert"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel convert")
+            .input_output_types(vec![
+                (Type::String, Type::String),
+                (Type::Binary, Type::String),
+                (Type::Nothing, Type::String),
+            ])
+            .optional(
+                "path",
+                SyntaxShape::Filepath,
+                "Path to a JSON, YAML or TOML file to convert, instead of the input",
+            )
+            .named(
+                "to",
+                SyntaxShape::String,
+                format!(
+                    "Format to convert to: {}",
+                    OutputFormat::ALL.map(OutputFormat::name).join(", ")
+                ),
+                Some('t'),
+            )
+            .named(
+                "from",
+                SyntaxShape::String,
+                "Format of the input: json, yaml or toml, detected by default",
+                None,
+            )
+            .named(
+                "contract",
+                SyntaxShape::Filepath,
+                "Nickel file of a contract to apply to the data before converting it",
+                Some('c'),
+            )
+            .named(
+                "import-path",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Directories to look up imports in, before those of `NICKEL_IMPORT_PATH`",
+                Some('I'),
+            )
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths and imports",
+                None,
+            )
+            .category(Category::Formats)
+    }
+
+    fn description(&self) -> &str {
+        "Convert data between JSON, YAML and TOML with Nickel's serializers"
+    }
+
+    fn extra_description(&self) -> &str {
+        "The data goes through Nickel, so the output is the same as exporting it with Nickel \
+         would give, for example TOML tables or YAML strings written as Nickel writes them. \
+         With `--contract`, the data must satisfy the contract, and the defaults it sets are \
+         filled in. The media type of the output format is set as the `content_type` of the \
+         output metadata, for `save` and `http post`."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Convert a YAML file to TOML",
+                example: "nickel convert config.yaml --to toml",
+                result: None,
+            },
+            Example {
+                description: "Check JSON data against a schema while converting it to YAML",
+                example: "open --raw values.json | nickel convert --to yaml --contract schema.ncl",
+                result: None,
+            },
+            Example {
+                description: "Convert JSON to TOML",
+                example: r#"'{"name": "web", "port": 80}' | nickel convert --to toml"#,
+                result: Some(Value::test_string("name = \"web\"\nport = 80\n")),
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let base_dir = working_dir(engine, call)?;
+
+        let format = match call.get_flag::<Spanned<String>>("to")? {
+            Some(name) => OutputFormat::parse(&name)?,
+            None => {
+                return Err(LabeledError::new("Missing output format")
+                    .with_label("Pass the format to convert to with `--to`", span));
+            }
+        };
+        let mut input = NickelInput::from_call(call, input, 0, Some(base_dir.clone()))?;
+        if let Some(name) = call.get_flag::<Spanned<String>>("from")? {
+            input.format = input_format(&name)?;
+        }
+
+        let json = parse_data(&input.source, input.format)
+            .filter(|_| input.is_data() && input.format != InputFormat::Text)
+            .ok_or_else(|| {
+                LabeledError::new("Cannot convert Nickel code")
+                    .with_label("Expected JSON, YAML or TOML input", span)
+                    .with_help("use `nickel eval --format` to export a Nickel program")
+            })?
+            .map_err(|e| {
+                LabeledError::new(format!("Failed to parse {} input", input.format.to_str()))
+                    .with_label(e, span)
+            })?;
+
+        let data = value_to_nickel(&json_to_value(&json, span)?, span)?;
+        let source = match call.get_flag::<String>("contract")? {
+            Some(contract) => format!(
+                "({data}) | (import {})",
+                value_to_nickel(
+                    &Value::string(base_dir.join(contract).display().to_string(), span),
+                    span
+                )?
+            ),
+            None => data,
+        };
+        let input = NickelInput {
+            source,
+            path: None,
+            format: InputFormat::Nickel,
+            base_dir: Some(base_dir),
+            import_paths: import_paths(engine, call)?,
+            no_imports: no_imports(engine, call)?,
+            source_name: None,
+        };
+
+        let mut program = new_program(&input, span)?;
+        let term = eval_for_export(&mut program, span)?;
+        Ok(PipelineData::Value(
+            Value::string(export_term(&program, &term, format, span)?, span),
+            Some(content_metadata(format.content_type())),
+        ))
+    }
+}
+
+/// The data format named by `--from`
+fn input_format(name: &Spanned<String>) -> Result<InputFormat, LabeledError> {
+    match name.item.to_ascii_lowercase().as_str() {
+        "json" => Ok(InputFormat::Json),
+        "yaml" | "yml" => Ok(InputFormat::Yaml),
+        "toml" => Ok(InputFormat::Toml),
+        _ => Err(LabeledError::new("Unknown input format")
+            .with_label(format!("'{}' is not a data format", name.item), name.span)
+            .with_help("use one of json, yaml, toml")),
+    }
+}
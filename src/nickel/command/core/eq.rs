@@ -0,0 +1,237 @@
+use super::cached_json;
+use crate::NickelPlugin;
+use crate::nickel::{
+    convert::json_to_value,
+    input::{NickelInput, import_paths, no_imports, working_dir},
+    program::eval_to_json,
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Record, Signature, Span, SyntaxShape, Type,
+    Value,
+    ast::{CellPath, PathMember},
+    casing::Casing,
+};
+use std::collections::BTreeSet;
+
+#[derive(Clone)]
+pub struct NickelEq;
+
+impl PluginCommand for NickelEq {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel eq"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel eq")
+            .input_output_types(vec![
+                (Type::String, Type::Any),
+                (Type::Binary, Type::Any),
+                (Type::Nothing, Type::Any),
+                (Type::Custom("NickelValue".to_string().into()), Type::Any),
+            ])
+            .required(
+                "other",
+                SyntaxShape::Any,
+                "Path to a file or Nickel value to compare with",
+            )
+            .optional(
+                "path",
+                SyntaxShape::Filepath,
+                "Path to a file to compare, instead of the input",
+            )
+            .switch(
+                "divergence",
+                "Return a record with the first `path` where the values differ, and the `left` \
+                 and `right` values there",
+                Some('d'),
+            )
+            .named(
+                "import-path",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Directories to look up imports in, before those of `NICKEL_IMPORT_PATH`",
+                Some('I'),
+            )
+            .named(
+                "source-name",
+                SyntaxShape::String,
+                "Name piped code is reported under in errors, instead of `<input>`",
+                None,
+            )
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths and imports",
+                None,
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "Check whether two configurations evaluate to the same data"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Both sides are fully evaluated, so configurations written differently but producing the \
+         same data are equal. The order of record fields does not matter, and numbers are \
+         compared by value, `1` being equal to `1.0`. Nickel files and JSON, YAML or TOML data \
+         files can be compared with each other.\n\n\
+         With `--divergence`, the `path` of the first difference is a cell path, the fields \
+         being visited in alphabetical order. It is empty when the values differ at their root, \
+         and null when they are equal."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Check that a refactored configuration renders the same data",
+                example: "git show HEAD:config.ncl | nickel parse | nickel eq config.ncl",
+                result: None,
+            },
+            Example {
+                description: "Compare a Nickel file with the JSON it should produce",
+                example: "nickel eq expected.json config.ncl",
+                result: None,
+            },
+            Example {
+                description: "Find where two configurations differ",
+                example: r#""{ a = { b = 1, c = 2 } }" | nickel eq other.ncl --divergence"#,
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let base_dir = working_dir(engine, call)?;
+        let import_paths = import_paths(engine, call)?;
+        let no_imports = no_imports(engine, call)?;
+        let with_imports = |input: NickelInput| NickelInput {
+            import_paths: import_paths.clone(),
+            no_imports,
+            ..input
+        };
+
+        let right = match call.req::<Value>(0)? {
+            Value::String { val, .. } => eval_to_json(
+                &with_imports(NickelInput::from_path(
+                    val.into(),
+                    Some(base_dir.clone()),
+                    span,
+                )?),
+                span,
+            )?,
+            value => cached_json(plugin, &value, base_dir.clone(), &with_imports, span)?,
+        };
+        let left = match input {
+            PipelineData::Value(value @ Value::Custom { .. }, _) => {
+                cached_json(plugin, &value, base_dir, &with_imports, span)?
+            }
+            input => eval_to_json(
+                &with_imports(NickelInput::from_call(call, input, 1, Some(base_dir))?),
+                span,
+            )?,
+        };
+
+        let divergence = first_divergence(&left, &right, &mut Vec::new(), span);
+        let equal = Value::bool(divergence.is_none(), span);
+        if !call.has_flag("divergence")? {
+            return Ok(PipelineData::Value(equal, None));
+        }
+
+        let mut record = Record::new();
+        record.push("equal", equal);
+        match divergence {
+            Some((members, left, right)) => {
+                record.push("path", Value::cell_path(CellPath { members }, span));
+                record.push("left", json_or_nothing(left, span)?);
+                record.push("right", json_or_nothing(right, span)?);
+            }
+            None => {
+                record.push("path", Value::nothing(span));
+                record.push("left", Value::nothing(span));
+                record.push("right", Value::nothing(span));
+            }
+        }
+        Ok(PipelineData::Value(Value::record(record, span), None))
+    }
+}
+
+/// The path of the first difference between two values, with the values found there
+///
+/// Record fields are visited in alphabetical order, so that the result does not depend on the
+/// order in which they were defined.
+fn first_divergence<'a>(
+    left: &'a serde_json::Value,
+    right: &'a serde_json::Value,
+    path: &mut Vec<PathMember>,
+    span: Span,
+) -> Option<(
+    Vec<PathMember>,
+    Option<&'a serde_json::Value>,
+    Option<&'a serde_json::Value>,
+)> {
+    use serde_json::Value as Json;
+
+    let diverged = |path: &Vec<PathMember>| Some((path.clone(), Some(left), Some(right)));
+    match (left, right) {
+        (Json::Number(l), Json::Number(r)) => {
+            let equal = match (l.as_i64(), r.as_i64()) {
+                (Some(l), Some(r)) => l == r,
+                _ => l.as_f64() == r.as_f64(),
+            };
+            if equal { None } else { diverged(path) }
+        }
+        (Json::Array(l), Json::Array(r)) => {
+            for (i, (l, r)) in l.iter().zip(r).enumerate() {
+                path.push(PathMember::int(i, false, span));
+                let divergence = first_divergence(l, r, path, span);
+                path.pop();
+                if divergence.is_some() {
+                    return divergence;
+                }
+            }
+            if l.len() != r.len() {
+                path.push(PathMember::int(l.len().min(r.len()), false, span));
+                let divergence = Some((path.clone(), l.get(r.len()), r.get(l.len())));
+                path.pop();
+                return divergence;
+            }
+            None
+        }
+        (Json::Object(l), Json::Object(r)) => {
+            let fields: BTreeSet<_> = l.keys().chain(r.keys()).collect();
+            for field in fields {
+                path.push(PathMember::string(
+                    field.clone(),
+                    false,
+                    Casing::Sensitive,
+                    span,
+                ));
+                let divergence = match (l.get(field), r.get(field)) {
+                    (Some(l), Some(r)) => first_divergence(l, r, path, span),
+                    (l, r) => Some((path.clone(), l, r)),
+                };
+                path.pop();
+                if divergence.is_some() {
+                    return divergence;
+                }
+            }
+            None
+        }
+        (l, r) if l == r => None,
+        _ => diverged(path),
+    }
+}
+
+fn json_or_nothing(json: Option<&serde_json::Value>, span: Span) -> Result<Value, LabeledError> {
+    json.map_or(Ok(Value::nothing(span)), |json| json_to_value(json, span))
+}
@@ -1,8 +1,48 @@
+use super::get::{get_json, parse_field_path};
 use crate::NickelPlugin;
+use crate::cache::NickelCache;
+use crate::measure::{Measure, measure};
+#[cfg(not(feature = "sqlite"))]
+use crate::nickel::format::unavailable;
+#[cfg(feature = "sqlite")]
+use crate::nickel::sqlite;
+use crate::nickel::{
+    convert::{
+        ArrayPolicy, Conversion, EnumPolicy, KeyOrder, MAX_DEPTH, MAX_NESTING, NumberPolicy,
+        array_elements, json_to_value_within, nickel_to_nu_value_with, truncate_depth,
+        value_to_nickel,
+    },
+    error::{diagnostics, nickel_error},
+    format::{OutputFormat, TableFormat, content_metadata, parse_data},
+    history::{History, Parameters},
+    input::{
+        NickelInput, expand_paths, import_paths, is_pattern, no_imports, plugin_settings,
+        working_dir,
+    },
+    jobs::Jobs,
+    program::{
+        Evaluated, add_assignments, bind_prelude, bind_values, disable_contracts, eval_for_export,
+        export_later, export_term, forbid_imports, interruptible, interruptible_stream,
+        is_function, new_program, parse_args, restrict_stdlib,
+    },
+    stamp::stamp,
+    stdlib::StdlibFilter,
+    values::{NuNickelValue, NuNickelValueCustomValue},
+    worker::{self, Limits, WorkerCommand, isolated},
+    write::WriteSet,
+};
+use nickel_lang_core::{
+    term::{MergePriority, Term},
+    typecheck::TypecheckMode,
+};
 use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
 use nu_protocol::{
-    Category, Example, LabeledError, PipelineData, Record, Signature, Span, SyntaxShape, Type, Value,
+    Category, Example, Filesize, LabeledError, ListStream, PipelineData, Record, Signals,
+    Signature, Span, Spanned, SyntaxShape, Type, Value,
 };
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
 #[derive(Clone)]
 pub struct NickelEval;
@@ -18,12 +58,263 @@ impl PluginCommand for NickelEval {
         Signature::build("nickel eval")
             .input_output_types(vec![
                 (Type::String, Type::Any),
+                (Type::Binary, Type::Any),
                 (Type::Nothing, Type::Any),
+                (
+                    Type::List(Box::new(Type::String)),
+                    Type::List(Box::new(Type::Any)),
+                ),
             ])
-            .optional("path", SyntaxShape::Filepath, "Path to nickel file to evaluate")
-            .switch("json", "Output as JSON", Some('j'))
-            .switch("yaml", "Output as YAML", Some('y'))
-            .switch("toml", "Output as TOML", Some('t'))
+            .rest(
+                "paths",
+                SyntaxShape::Filepath,
+                "Paths or glob patterns of files to evaluate, JSON/YAML/TOML are detected automatically",
+            )
+            .named(
+                "assign",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Field assignments `path.to.field=value` merged into the program",
+                Some('a'),
+            )
+            .named(
+                "override",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Field assignments `path.to.field=value` replacing any existing definition",
+                Some('o'),
+            )
+            .named(
+                "arg",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Variables `name=value` bound in scope of the program, with NUON or string values",
+                None,
+            )
+            .named(
+                "env",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Environment variables bound as the fields of an `env` record in the program",
+                None,
+            )
+            .switch(
+                "env-all",
+                "Bind all the environment variables in the `env` record",
+                None,
+            )
+            .named(
+                "field",
+                SyntaxShape::String,
+                "Only evaluate and return the field at this path, e.g. `server.port`",
+                None,
+            )
+            .named(
+                "stamp",
+                SyntaxShape::String,
+                "Add the provenance of the result at this path, e.g. `meta.build`",
+                None,
+            )
+            .named(
+                "prelude",
+                SyntaxShape::Filepath,
+                "Nickel file whose record programs can use as `prelude`, without importing it",
+                None,
+            )
+            .switch(
+                "no-config",
+                "Ignore the plugin settings, as if nothing was configured",
+                None,
+            )
+            .switch(
+                "no-imports",
+                "Fail on any import, for code that should not read files",
+                None,
+            )
+            .named(
+                "stdlib-allow",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Parts of the standard library programs may use, like `std.string`, and no others",
+                None,
+            )
+            .named(
+                "stdlib-deny",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Parts of the standard library programs may not use, like `std.trace`",
+                None,
+            )
+            .named(
+                "typecheck",
+                SyntaxShape::String,
+                "How strictly to typecheck: walk (default) or enforce",
+                None,
+            )
+            .switch(
+                "typecheck-only",
+                "Only parse and typecheck, returning whether the program is valid and its errors",
+                None,
+            )
+            .switch(
+                "no-contracts",
+                "Skip runtime contract checks, static types are still checked",
+                None,
+            )
+            .switch(
+                "measure",
+                "Return a record of the value with the time and memory its evaluation took",
+                Some('m'),
+            )
+            .named(
+                "format",
+                SyntaxShape::String,
+                format!(
+                    "Output the result in this format: {}, or {} for tables",
+                    OutputFormat::ALL.map(OutputFormat::name).join(", "),
+                    TableFormat::ALL.map(TableFormat::name).join(", ")
+                ),
+                Some('f'),
+            )
+            .named(
+                "arrays",
+                SyntaxShape::String,
+                format!(
+                    "How to convert arrays of records with different columns: {}",
+                    ArrayPolicy::ALL.map(ArrayPolicy::name).join(", ")
+                ),
+                None,
+            )
+            .named(
+                "enums",
+                SyntaxShape::String,
+                format!(
+                    "How to convert enum variants with a payload: {}",
+                    EnumPolicy::ALL.map(EnumPolicy::name).join(", ")
+                ),
+                None,
+            )
+            .named(
+                "number-mode",
+                SyntaxShape::String,
+                format!(
+                    "How to convert numbers an int or a float cannot hold: {}",
+                    NumberPolicy::ALL.map(NumberPolicy::name).join(", ")
+                ),
+                None,
+            )
+            .switch(
+                "sort-keys",
+                "Sort the fields of records by name, like `--key-order sorted`",
+                None,
+            )
+            .named(
+                "key-order",
+                SyntaxShape::String,
+                format!(
+                    "Order of the fields of records: {}",
+                    KeyOrder::ALL.map(KeyOrder::name).join(", ")
+                ),
+                None,
+            )
+            .named(
+                "max-nesting",
+                SyntaxShape::Int,
+                format!(
+                    "Nesting depth past which converting the result fails, {MAX_NESTING} by default"
+                ),
+                None,
+            )
+            .named(
+                "depth",
+                SyntaxShape::Int,
+                format!(
+                    "Nesting depth past which records and lists are truncated, {MAX_DEPTH} by default"
+                ),
+                Some('d'),
+            )
+            .switch(
+                "to-dataframe",
+                "Return a table as Arrow IPC data for the polars plugin, like `--format arrow`",
+                None,
+            )
+            .named(
+                "max-memory",
+                SyntaxShape::Filesize,
+                "Memory the evaluation may allocate before it is stopped",
+                None,
+            )
+            .named(
+                "max-stack",
+                SyntaxShape::Filesize,
+                "Stack the evaluation may use before it is stopped",
+                None,
+            )
+            .named(
+                "output",
+                SyntaxShape::Filepath,
+                "Write the result to this file, in the format of its extension by default",
+                None,
+            )
+            .switch(
+                "create-dirs",
+                "With --output, create the missing parent directories of the file",
+                None,
+            )
+            .switch(
+                "dry-run",
+                "With --output, return the file that would be written, with its diff, instead",
+                None,
+            )
+            .named(
+                "sqlite",
+                SyntaxShape::Filepath,
+                "Write a table of flat records into this SQLite database, in the `--table` table",
+                None,
+            )
+            .named(
+                "table",
+                SyntaxShape::String,
+                "With --sqlite, the table to replace with the records",
+                None,
+            )
+            .named(
+                "history",
+                SyntaxShape::Directory,
+                "With --output, record the written file in this output history directory",
+                None,
+            )
+            .switch(
+                "multi-doc",
+                "With --format yaml, write each element of an array as its own YAML document",
+                None,
+            )
+            .named(
+                "jobs",
+                SyntaxShape::Int,
+                "Number of snippets or files to evaluate at once, the number of CPUs by default",
+                None,
+            )
+            .switch(
+                "nice",
+                "Evaluate snippets or files at a lower CPU priority, for shared machines",
+                None,
+            )
+            .switch("json", "Deprecated, use `--format json`", Some('j'))
+            .switch("yaml", "Deprecated, use `--format yaml`", Some('y'))
+            .switch("toml", "Deprecated, use `--format toml`", Some('t'))
+            .named(
+                "import-path",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Directories to look up imports in, before those of `NICKEL_IMPORT_PATH`",
+                Some('I'),
+            )
+            .named(
+                "source-name",
+                SyntaxShape::String,
+                "Name piped code is reported under in errors, instead of `<input>`",
+                None,
+            )
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths and imports",
+                None,
+            )
             .category(Category::Conversions)
     }
 
@@ -31,7 +322,110 @@ impl PluginCommand for NickelEval {
         "Evaluate Nickel code and return the result"
     }
 
-    fn examples(&self) -> Vec<Example> {
+    fn extra_description(&self) -> &str {
+        "With `--output`, the result is written to a file rather than returned, and a record of \
+         the file's `path`, `format` and `size` is returned instead. The file is replaced \
+         atomically, and its format is taken from `--format` or from its extension.\n\n\
+         With `--history`, or the `history` plugin setting, each file written with `--output` \
+         is also recorded in that directory, with the time and the flags it was rendered with, \
+         and the record returned gets its `hash`. `nickel outputs log` lists the recorded files \
+         and `nickel outputs show` returns one of them. With `--dry-run`, nothing is recorded, \
+         and the preview lists the file the history would store as well.\n\n\
+         The environment is hidden from programs unless `--env` or `--env-all` bind it as an \
+         `env` record, whose fields are the variables that are set.\n\n\
+         Arrays mixing records with different columns are kept as lists by default. With \
+         `--arrays pad`, their records get the columns of all the others, null where they lack \
+         them, and with `--arrays error` they are an error.\n\n\
+         Enum tags like `'Debug` become strings. Enum variants with a payload like `'Port 8080` \
+         become a `{tag, value}` record by default, and with `--enums keyed` a record with the \
+         payload in a column named after the tag.\n\n\
+         Nickel numbers are exact fractions. Integral ones that fit in an int become ints and \
+         the others are rounded to floats, unless `--number-mode float` makes them all floats. \
+         With `--number-mode string`, larger integers and numbers a float would round, like \
+         `0.1`, become strings of their exact decimals instead, or of their fraction when their \
+         decimals never end. Text formats are exported by Nickel itself.\n\n\
+         Record fields are in the order evaluation leaves them in, which merging can change. \
+         With `--sort-keys`, or `--key-order sorted`, they are sorted by name so that output \
+         diffs cleanly, and with `--key-order source` they are in the order they are written \
+         in. The `key_order` plugin setting gives the order when neither flag is given. JSON, \
+         YAML and TOML output is written by Nickel's exporter, which always sorts fields.\n\n\
+         Results serialized with `--format json`, `yaml` or `toml` are returned as a stream of \
+         text, written as it is read, so that exporting a huge configuration does not hold its \
+         text in memory at once. Their media type, like `application/json`, is set as the \
+         `content_type` of the output metadata, so that `save` and `http post` handle them as \
+         that format rather than as plain text. Warnings about the output, such as the \
+         contracts left unchecked by `--no-contracts`, are listed in the `warnings` of that \
+         metadata, which `metadata` shows.\n\n\
+         An array is returned as a stream, its elements converted as they are read, so that a \
+         large generated dataset is never held in memory twice. With `--arrays pad` or \
+         `--arrays error`, which look at all the elements, it is converted at once.\n\n\
+         Records and lists nested deeper than `--depth` are replaced with `{...}` and `[...]` \
+         markers, so that a very deep configuration cannot hang the terminal. The markers are \
+         the only sign of it, look for them before relying on a deep value. The depth only \
+         applies to values returned as Nushell data, not to text formats. Values nested deeper \
+         than `--max-nesting`, 4096 levels by default, cannot be converted at all and are an \
+         error, as Nushell would overflow its stack handling them.\n\n\
+         With `--prelude`, or the `prelude` plugin setting, programs are evaluated with the \
+         record of that file bound as `prelude`, so that shared helpers like \
+         `prelude.service_name` need no import. `--no-imports` allows this one import.\n\n\
+         With `--no-imports`, or the `no_imports` plugin setting, a program that imports a file \
+         or a package fails before it is evaluated, naming the import. This is meant for \
+         evaluating untrusted code.\n\n\
+         With `--stdlib-allow`, or the `stdlib_allow` plugin setting, a program may only use \
+         the parts of the standard library under the names given, and with `--stdlib-deny`, or \
+         `stdlib_deny`, none of those under the names given. Uses are found before evaluation, \
+         so `std` may only appear as the start of a path like `std.string.length`. Imported \
+         files are not checked, so pair this with `--no-imports` to offer a curated subset of \
+         Nickel to less trusted authors.\n\n\
+         Like `nickel export`, evaluation typechecks the statically typed blocks of the program \
+         and leaves the rest to runtime contracts. With `--typecheck enforce`, or the \
+         `typecheck` plugin setting, the whole program is typechecked as if it were statically \
+         typed, failing before it is evaluated.\n\n\
+         With `--no-config`, the plugin settings are ignored, and only the flags of the call \
+         apply, to tell whether a problem comes from the configuration or from the program. \
+         The evaluation reads no project files, only `NICKEL_IMPORT_PATH`, as Nickel does.\n\n\
+         With `--typecheck-only`, the program is parsed and typechecked but not evaluated, and \
+         the result is a record of whether it is `valid` and of its `errors`, as reported by \
+         `nickel typecheck`. Fields of the program that would fail to evaluate, like a missing \
+         input, are not an error then.\n\n\
+         With `--stamp`, a record of provenance is added to the result at the path given: the \
+         `commit`, `describe` and `dirty` state of the git repository of the program, or null \
+         outside of one, the `rendered_at` time and the `plugin_version`. It overrides any \
+         definition of the field, and like `--override` only applies to Nickel programs.\n\n\
+         With `--field`, only the records along the path and the field are evaluated, like \
+         `nickel get`, so the rest of a large configuration is never built.\n\n\
+         With `--max-memory`, an evaluation allocating more than the limit, like a runaway \
+         recursion or an accidental cartesian product, fails instead of exhausting the memory of \
+         the plugin. With `--max-stack`, an evaluation recursing past a stack of that size fails \
+         instead of crashing the plugin. Nickel does not count calls, so the depth a program may \
+         reach is bounded by this size rather than by a number of calls. A limited evaluation \
+         runs in a process of its own, which is ended at the limit and sends its whole result \
+         back rather than streaming it.\n\n\
+         With `--format arrow` or `--to-dataframe`, a table of flat records is returned as Arrow \
+         IPC data, for the polars plugin to read with `polars open` once saved to a `.arrow` \
+         file, and with `--format parquet` as a Parquet file. These need the plugin to be built \
+         with the `dataframe` and `parquet` features.\n\n\
+         With `--sqlite` and `--table`, a table of flat records replaces the rows of that table \
+         in a SQLite database, created if needed, so that many configurations can be queried \
+         with SQL. This needs the plugin to be built with the `sqlite` feature.\n\n\
+         A program whose value is a function is returned as a Nickel value, which \
+         `nickel call` applies to Nushell values. Text and table formats cannot hold a \
+         function, so they still fail on one.\n\n\
+         Pressing Ctrl-C cancels a long evaluation. The plugin stops waiting for it right away, \
+         though Nickel may keep computing in the background until it is done.\n\n\
+         A piped list of strings is evaluated item by item, each one as a program of its own, \
+         into a stream of results in the same order, each one coming as soon as it and the \
+         ones before it are done. A snippet that fails gives an error in its place rather than \
+         stopping the others. Snippets are read as they are evaluated, `--jobs` at a time, and \
+         with `--nice` at a lower priority. Several paths, or a glob pattern like \
+         `'configs/**/*.ncl'`, evaluate each file the same way, into a list of results in the \
+         order of the paths, the files a pattern matches sorted. A pattern gives a list even \
+         when it matches a single file.\n\n\
+         With `--multi-doc`, an array is written as a stream of YAML documents, each starting \
+         with `---`, as expected by tools like `kubectl apply`."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
         vec![
             Example {
                 description: "Evaluate Nickel code from string",
@@ -44,60 +438,1188 @@ impl PluginCommand for NickelEval {
                 result: None,
             },
             Example {
-                description: "Evaluate and output as JSON",
-                example: r#""{ foo = 42 }" | nickel eval --json"#,
+                description: "Evaluate the output of another program",
+                example: "^cat config.ncl | nickel eval",
+                result: None,
+            },
+            Example {
+                description: "Read a data file, detecting its format",
+                example: "nickel eval settings.yaml",
+                result: None,
+            },
+            Example {
+                description: "Check that a program typechecks, without evaluating it",
+                example: "(nickel eval config.ncl --typecheck-only).valid",
+                result: None,
+            },
+            Example {
+                description: "Evaluate every configuration under a directory",
+                example: "nickel eval 'configs/**/*.ncl'",
+                result: None,
+            },
+            Example {
+                description: "Evaluate each expression of a list",
+                example: "open snippets.json | get exprs | nickel eval",
+                result: None,
+            },
+            Example {
+                description: "Set an undefined field and force another one",
+                example: r#"nickel eval config.ncl --assign [port=8080] --override ['image.tag="latest"']"#,
+                result: None,
+            },
+            Example {
+                description: "Parameterize a program with variables it uses",
+                example: r#""{ url = \"http://%{host}:%{std.to_string port}\" }" | nickel eval --arg [host=localhost port=8080]"#,
+                result: None,
+            },
+            Example {
+                description: "Let a program read some environment variables",
+                example: r#""{ home = env.HOME }" | nickel eval --env [HOME USER]"#,
+                result: None,
+            },
+            Example {
+                description: "Evaluate a file of another project, resolving imports from there",
+                example: r#""(import \"lib.ncl\").version" | nickel eval --cwd ../other-project"#,
+                result: None,
+            },
+            Example {
+                description: "Render a trusted configuration without checking its contracts",
+                example: "nickel eval config.ncl --no-contracts",
+                result: None,
+            },
+            Example {
+                description: "Measure the time and memory taken to render a file",
+                example: "nickel eval config.ncl --measure | reject value",
+                result: None,
+            },
+            Example {
+                description: "Export one section of a configuration",
+                example: "nickel eval config.ncl --field services.web --format yaml",
+                result: None,
+            },
+            Example {
+                description: "Render a large configuration straight to a file",
+                example: "nickel eval cluster.ncl --output out/cluster.yaml --create-dirs",
+                result: None,
+            },
+            Example {
+                description: "Render a deployment and keep it in the output history",
+                example: "nickel eval deploy.ncl --output deploy.json --history .nickel-history",
+                result: None,
+            },
+            Example {
+                description: "Write a list of Kubernetes resources as a multi-document YAML file",
+                example: "nickel eval resources.ncl --format yaml --multi-doc | kubectl apply -f -",
                 result: None,
             },
+            Example {
+                description: "Load an inventory of hosts into a polars dataframe",
+                example: "nickel eval hosts.ncl --to-dataframe | save hosts.arrow; polars open hosts.arrow",
+                result: None,
+            },
+            Example {
+                description: "Evaluate and output as JSON",
+                example: r#""{ foo = 42 }" | nickel eval --format json"#,
+                result: Some(Value::test_string("{\n  \"foo\": 42\n}")),
+            },
         ]
     }
 
     fn run(
         &self,
-        _plugin: &NickelPlugin,
-        _engine: &EngineInterface,
+        plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let warnings = warnings(call)?;
+        let data = self.run_call(plugin, engine, call, input)?;
+        Ok(with_warnings(data, warnings, call.head))
+    }
+}
+
+impl NickelEval {
+    fn run_call(
+        &self,
+        plugin: &NickelPlugin,
+        engine: &EngineInterface,
         call: &EvaluatedCall,
         input: PipelineData,
     ) -> Result<PipelineData, LabeledError> {
         let span = call.head;
 
-        // Get the source code - either from input or from file
-        let source = if let Some(path) = call.opt::<String>(0)? {
-            // Read from file
-            std::fs::read_to_string(&path).map_err(|e| {
-                LabeledError::new(format!("Failed to read file: {}", e))
-                    .with_label(format!("Cannot read file '{}'", path), span)
-            })?
-        } else {
-            // Read from input
-            match input {
-                PipelineData::Value(Value::String { val, .. }, _) => val,
-                PipelineData::Empty => {
-                    return Err(LabeledError::new("No input provided")
-                        .with_label("Provide Nickel code as input or specify a file path", span))
+        let base_dir = working_dir(engine, call)?;
+        let import_paths = import_paths(engine, call)?;
+        let paths = call.rest::<Spanned<String>>(0)?;
+        // Several paths or a pattern evaluate each file, even when a pattern matches a single one
+        let many = paths.len() > 1 || paths.iter().any(|path| is_pattern(&path.item, &base_dir));
+        let source = match input {
+            input @ (PipelineData::ListStream(..) | PipelineData::Value(Value::List { .. }, _))
+                if paths.is_empty() =>
+            {
+                Source::Many(Box::new(input.into_iter().map(Item::Snippet)))
+            }
+            _ if many => Source::Many(Box::new(files(paths, &base_dir)?.into_iter())),
+            input => {
+                let mut input = NickelInput::from_call(call, input, 0, Some(base_dir.clone()))?;
+                input.import_paths = import_paths.clone();
+                Source::Program(input)
+            }
+        };
+        let mut bindings = Vec::new();
+        if let Some(env) = env_record(engine, call)? {
+            bindings.push(("env".to_string(), env));
+        }
+        bindings.extend(parse_args(
+            call.get_flag::<Vec<String>>("arg")?.unwrap_or_default(),
+            span,
+        )?);
+        let checks = Checks::from_call(engine, call, &base_dir)?;
+        let conversion = conversion(engine, call)?;
+        let jobs = Jobs::from_call(call)?;
+        if call.has_flag("dry-run")?
+            && let Some(database) = call.get_flag_value("sqlite")
+        {
+            return Err(LabeledError::new("Cannot preview a database")
+                .with_label(
+                    "--dry-run previews the file of --output, not a SQLite table",
+                    database.span(),
+                )
+                .with_help("drop `--dry-run`, or `--sqlite` to preview the file instead"));
+        }
+        if call.has_flag("dry-run")? && call.get_flag_value("output").is_none() {
+            return Err(LabeledError::new("Nothing to preview")
+                .with_label(
+                    "--dry-run previews the file of --output, which is missing",
+                    span,
+                )
+                .with_help("pass `--output`, or drop `--dry-run`"));
+        }
+        if call.has_flag("typecheck-only")? {
+            for flag in ["output", "sqlite"] {
+                if let Some(value) = call.get_flag_value(flag) {
+                    return Err(LabeledError::new("Nothing to write").with_label(
+                        format!(
+                            "--typecheck-only does not evaluate, there is no result for --{flag}"
+                        ),
+                        value.span(),
+                    ));
+                }
+            }
+            return Ok(match source {
+                Source::Program(input) => {
+                    PipelineData::Value(typecheck_only(input, bindings, checks, span)?, None)
+                }
+                Source::Many(items) => each_program(
+                    engine,
+                    jobs,
+                    items,
+                    &base_dir,
+                    &import_paths,
+                    span,
+                    move |input| typecheck_only(input, bindings.clone(), checks.clone(), span),
+                )?,
+            });
+        }
+        let output = call
+            .get_flag::<Spanned<String>>("output")?
+            .map(|path| Spanned {
+                item: base_dir.join(path.item),
+                span: path.span,
+            });
+        let (format, table) = match (output_format(call)?, table_format(call)?, &output) {
+            (None, None, Some(output)) => match table_extension(output) {
+                Some(table) => (None, Some(table)),
+                None => (Some(extension_format(output)?), None),
+            },
+            (format, table, _) => (format, table),
+        };
+        if let (Some(format), Some(table)) = (format, table) {
+            // An extension is only looked at without flags, so the conflict is between two flags
+            let format_flag = if call.has_flag(format.name())? {
+                format.name()
+            } else {
+                "format"
+            };
+            let table_flag = if call.has_flag("to-dataframe")? {
+                "to-dataframe"
+            } else {
+                "format"
+            };
+            return Err(LabeledError::new("Conflicting output formats")
+                .with_label(
+                    format!("`--{format_flag}` selects {}", format.name()),
+                    flag_span(call, format_flag),
+                )
+                .with_label(
+                    format!("`--{table_flag}` selects {}", table.name()),
+                    flag_span(call, table_flag),
+                )
+                .with_help(format!("drop `--{format_flag}` or `--{table_flag}`")));
+        }
+        let result_format = ResultFormat {
+            conversion,
+            format,
+            table,
+        };
+        let sqlite = sqlite_table(call, &base_dir)?;
+        if sqlite.is_some()
+            && let Some(name) = format
+                .map(OutputFormat::name)
+                .or(table.map(TableFormat::name))
+        {
+            return Err(LabeledError::new("Conflicting output formats")
+                .with_label(format!("--sqlite writes a database, not {name}"), span)
+                .with_help("drop `--sqlite`, or `--format`, `--output` and `--to-dataframe`"));
+        }
+        let written_as = format
+            .map(OutputFormat::name)
+            .or(table.map(TableFormat::name));
+        if call.has_flag("multi-doc")? && format != Some(OutputFormat::Yaml) {
+            return Err(LabeledError::new("Multiple documents need YAML output")
+                .with_label("--multi-doc only applies to --format yaml", span));
+        }
+
+        let signals = engine.signals().clone();
+        let size = |flag| {
+            call.get_flag::<Filesize>(flag)
+                .map(|size| size.map(|size| size.get().max(0) as usize))
+        };
+        let limits = Limits {
+            memory: size("max-memory")?,
+            stack: size("max-stack")?,
+        };
+        let measured = call.has_flag("measure")?;
+        let input = match source {
+            Source::Program(input) => input,
+            Source::Many(items) => {
+                for flag in ["output", "sqlite"] {
+                    if let Some(value) = call.get_flag_value(flag) {
+                        return Err(LabeledError::new("Cannot write many results").with_label(
+                            format!("--{flag} takes a single program, not a list of them"),
+                            value.span(),
+                        ));
+                    }
+                }
+                if measured {
+                    return Err(LabeledError::new("Cannot measure many results")
+                        .with_label("--measure takes a single program, not a list of them", span));
                 }
+                return each_program(engine, jobs, items, &base_dir, &import_paths, span, {
+                    let (call, cache, worker) =
+                        (call.clone(), plugin.cache.clone(), plugin.worker.clone());
+                    move |input| {
+                        let job = EvalJob {
+                            call: call.clone(),
+                            input,
+                            bindings: bindings.clone(),
+                            checks: checks.clone(),
+                            result_format,
+                            measured: false,
+                        };
+                        job.run_interruptible(&cache, &worker, &signals, limits)?.0
+                    }
+                });
+            }
+        };
+        let recording = match History::from_call(engine, call, &base_dir)? {
+            Some(history) => Some(Recording {
+                history,
+                source: input.path.clone(),
+                parameters: Parameters::from_call(call)?,
+            }),
+            None => None,
+        };
+        let job = EvalJob {
+            call: call.clone(),
+            input,
+            bindings,
+            checks,
+            result_format,
+            measured,
+        };
+
+        let save = |result: Value| match (&output, written_as, &sqlite) {
+            (Some(output), Some(written_as), _) => {
+                write_output(call, result, output, written_as, recording.as_ref(), span)
+            }
+            (_, _, Some(target)) => write_sqlite(result, target, span),
+            _ => Ok(result),
+        };
+
+        if measured {
+            let (result, measure) =
+                job.run_interruptible(&plugin.cache, &plugin.worker, &signals, limits)?;
+            let mut record = Record::new();
+            record.push("value", save(result?)?);
+            if let Some(measure) = measure {
+                record.extend(measure.into_record(span));
+            }
+            return Ok(PipelineData::Value(Value::record(record, span), None));
+        }
+
+        if output.is_none() && sqlite.is_none() {
+            let content_type = format
+                .map(OutputFormat::content_type)
+                .or(table.map(TableFormat::content_type));
+            // A worker process sends its whole result back, so it is not streamed
+            let data = if limits.is_set() {
+                PipelineData::Value(
+                    job.run_interruptible(&plugin.cache, &plugin.worker, &signals, limits)?
+                        .0?,
+                    None,
+                )
+            } else {
+                let cache = plugin.cache.clone();
+                interruptible_stream(&signals, span, move || job.evaluate(cache))?
+            };
+            return Ok(data.set_metadata(content_type.map(content_metadata)));
+        }
+        let result = job
+            .run_interruptible(&plugin.cache, &plugin.worker, &signals, limits)?
+            .0?;
+        Ok(PipelineData::Value(save(result)?, None))
+    }
+}
+
+/// What to evaluate: a program, or many programs each evaluated on its own
+enum Source {
+    Program(NickelInput),
+    Many(Items),
+}
+
+/// Programs read as they are evaluated, like the snippets of a piped stream
+type Items = Box<dyn Iterator<Item = Item> + Send>;
+
+/// One of many programs: a snippet of a piped list, or one of the files given
+enum Item {
+    Snippet(Value),
+    File(Spanned<PathBuf>),
+}
+
+impl Item {
+    fn read(&self, base_dir: &Path) -> Result<NickelInput, LabeledError> {
+        match self {
+            Item::Snippet(snippet) => Ok(NickelInput::from_source(
+                snippet.as_str()?.to_string(),
+                Some(base_dir.to_path_buf()),
+            )),
+            Item::File(path) => {
+                NickelInput::from_path(path.item.clone(), Some(base_dir.to_path_buf()), path.span)
+            }
+        }
+    }
+
+    fn span(&self) -> Span {
+        match self {
+            Item::Snippet(snippet) => snippet.span(),
+            Item::File(path) => path.span,
+        }
+    }
+}
+
+/// The files of path arguments, each with the span of the argument it comes from
+fn files(paths: Vec<Spanned<String>>, base_dir: &Path) -> Result<Vec<Item>, LabeledError> {
+    let mut files = Vec::new();
+    for path in paths {
+        let span = path.span;
+        let expanded = expand_paths(vec![path], base_dir)?;
+        files.extend(
+            expanded
+                .into_iter()
+                .map(|item| Item::File(Spanned { item, span })),
+        );
+    }
+    Ok(files)
+}
+
+/// Evaluate many programs, each on its own, `--jobs` at a time, into a stream of their results
+///
+/// The results keep the order of the programs, each one coming as soon as it and the ones before
+/// it are done. A snippet that is not a string, or a program that fails to read or evaluate, gives
+/// an error value in its place, so that it does not hide the results of the others.
+fn each_program(
+    engine: &EngineInterface,
+    jobs: Jobs,
+    items: Items,
+    base_dir: &Path,
+    import_paths: &[PathBuf],
+    span: Span,
+    evaluate: impl Fn(NickelInput) -> Result<Value, LabeledError> + Send + Sync + 'static,
+) -> Result<PipelineData, LabeledError> {
+    let (base_dir, import_paths) = (base_dir.to_path_buf(), import_paths.to_vec());
+    let results = jobs.stream(items, engine.signals(), span, move |item| {
+        item.read(&base_dir)
+            .and_then(|mut input| {
+                input.import_paths = import_paths.clone();
+                evaluate(input)
+            })
+            .unwrap_or_else(|error| Value::error(error.into(), item.span()))
+    })?;
+    Ok(PipelineData::ListStream(
+        ListStream::new(results, span, engine.signals().clone()),
+        None,
+    ))
+}
+
+/// The static checks run before evaluation, and the prelude programs get, from the flags of the
+/// call or the plugin settings
+#[derive(Clone, Serialize, Deserialize)]
+struct Checks {
+    /// The file bound as `prelude` in programs, by `--prelude` or the `prelude` setting
+    prelude: Option<PathBuf>,
+    /// Whether imports are forbidden, by `--no-imports` or the `no_imports` setting
+    no_imports: bool,
+    /// The parts of the standard library programs may use, by `--stdlib-allow` and
+    /// `--stdlib-deny` or the `stdlib_allow` and `stdlib_deny` settings
+    stdlib: StdlibFilter,
+    /// How strictly to typecheck, by `--typecheck` or the `typecheck` setting
+    #[serde(with = "enforced")]
+    typecheck: TypecheckMode,
+}
+
+/// A [`TypecheckMode`] written as whether typechecking is enforced, as Nickel does not serialize it
+mod enforced {
+    use nickel_lang_core::typecheck::TypecheckMode;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        mode: &TypecheckMode,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bool(matches!(mode, TypecheckMode::Enforce))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<TypecheckMode, D::Error> {
+        Ok(match bool::deserialize(deserializer)? {
+            true => TypecheckMode::Enforce,
+            false => TypecheckMode::Walk,
+        })
+    }
+}
+
+impl Checks {
+    fn from_call(
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        base_dir: &Path,
+    ) -> Result<Self, LabeledError> {
+        let config = plugin_settings(engine, call)?;
+        let setting = |key: &str| {
+            config
+                .as_ref()
+                .and_then(|config| config.get_data_by_key(key))
+        };
+
+        let no_imports = no_imports(engine, call)?;
+        let typecheck = match call.get_flag::<Spanned<String>>("typecheck")? {
+            Some(mode) => Some(mode),
+            None => setting("typecheck")
+                .map(|value| -> Result<_, LabeledError> {
+                    let span = value.span();
+                    Ok(Spanned {
+                        item: value.coerce_into_string()?,
+                        span,
+                    })
+                })
+                .transpose()?,
+        };
+        let typecheck = match typecheck {
+            None => TypecheckMode::Walk,
+            Some(mode) => match mode.item.as_str() {
+                "walk" => TypecheckMode::Walk,
+                "enforce" => TypecheckMode::Enforce,
                 _ => {
-                    return Err(LabeledError::new("Invalid input type")
-                        .with_label("Expected string input", span))
+                    return Err(LabeledError::new("Unknown typecheck mode")
+                        .with_label(
+                            format!("`{}` is not a typecheck mode", mode.item),
+                            mode.span,
+                        )
+                        .with_help("use walk or enforce"));
                 }
+            },
+        };
+        let names = |flag: &str, key: &str| -> Result<Option<Vec<String>>, LabeledError> {
+            match call.get_flag::<Vec<String>>(flag)? {
+                Some(names) => Ok(Some(names)),
+                None => setting(key)
+                    .map(|value| {
+                        value
+                            .as_list()?
+                            .iter()
+                            .map(|name| name.coerce_string())
+                            .collect::<Result<Vec<_>, _>>()
+                    })
+                    .transpose()
+                    .map_err(LabeledError::from),
             }
         };
+        let prelude = match call.get_flag::<String>("prelude")? {
+            Some(path) => Some(path),
+            None => setting("prelude").map(Value::coerce_string).transpose()?,
+        };
+        let stdlib = StdlibFilter::new(
+            names("stdlib-allow", "stdlib_allow")?,
+            names("stdlib-deny", "stdlib_deny")?.unwrap_or_default(),
+        );
+        Ok(Self {
+            prelude: prelude.map(|path| base_dir.join(path)),
+            no_imports,
+            stdlib,
+            typecheck,
+        })
+    }
+}
+
+/// What the result of an evaluation is returned as
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct ResultFormat {
+    /// How the result is converted to Nushell values
+    conversion: Conversion,
+    /// The text format the result is serialized to, if any
+    format: Option<OutputFormat>,
+    /// The table format the result is encoded to, if any
+    table: Option<TableFormat>,
+}
+
+/// How the result is converted to Nushell values, from the flags of the call
+///
+/// The order of record fields can also come from the `key_order` plugin setting.
+fn conversion(engine: &EngineInterface, call: &EvaluatedCall) -> Result<Conversion, LabeledError> {
+    let parse = |flag: &str| call.get_flag::<Spanned<String>>(flag);
+    let key_order = match (call.has_flag("sort-keys")?, parse("key-order")?) {
+        (true, Some(order)) if KeyOrder::parse(&order)? != KeyOrder::Sorted => {
+            return Err(LabeledError::new("Conflicting key orders")
+                .with_label("--sort-keys sorts the fields by name", call.head)
+                .with_label(
+                    format!("--key-order wants them in {} order", order.item),
+                    order.span,
+                ));
+        }
+        (true, _) => Some(KeyOrder::Sorted),
+        (false, Some(order)) => Some(KeyOrder::parse(&order)?),
+        (false, None) => None,
+    };
+    let keys = match key_order {
+        Some(order) => order,
+        None => match plugin_settings(engine, call)?
+            .and_then(|config| config.get_data_by_key("key_order"))
+        {
+            Some(value) => {
+                let span = value.span();
+                KeyOrder::parse(&Spanned {
+                    item: value.coerce_into_string()?,
+                    span,
+                })?
+            }
+            None => KeyOrder::default(),
+        },
+    };
+    Ok(Conversion {
+        enums: parse("enums")?
+            .map(|name| EnumPolicy::parse(&name))
+            .transpose()?
+            .unwrap_or_default(),
+        numbers: parse("number-mode")?
+            .map(|name| NumberPolicy::parse(&name))
+            .transpose()?
+            .unwrap_or_default(),
+        keys,
+        max_nesting: call.get_flag("max-nesting")?.unwrap_or(MAX_NESTING),
+    })
+}
+
+/// The environment variables selected with `--env` or `--env-all`, as a record
+///
+/// Variables that are not set are left out, as are values with no Nickel equivalent, like
+/// closures, when all the variables are selected.
+fn env_record(
+    engine: &EngineInterface,
+    call: &EvaluatedCall,
+) -> Result<Option<Value>, LabeledError> {
+    let span = call.head;
+    let names = call.get_flag::<Vec<String>>("env")?;
+    let all = call.has_flag("env-all")?;
+    if names.is_none() && !all {
+        return Ok(None);
+    }
+
+    let vars: BTreeMap<_, _> = engine.get_env_vars()?.into_iter().collect();
+    let mut record = Record::new();
+    for (name, value) in vars {
+        let selected = all || names.as_ref().is_some_and(|names| names.contains(&name));
+        if selected && value_to_nickel(&value, span).is_ok() {
+            record.push(name, value);
+        }
+    }
+    Ok(Some(Value::record(record, span)))
+}
+
+/// The table format selected with `--format` or `--to-dataframe`
+fn table_format(call: &EvaluatedCall) -> Result<Option<TableFormat>, LabeledError> {
+    let named = call
+        .get_flag::<String>("format")?
+        .and_then(|name| TableFormat::from_name(&name));
+    if call.has_flag("to-dataframe")? {
+        return match named {
+            Some(TableFormat::Parquet) => Err(LabeledError::new("Conflicting output formats")
+                .with_label("--to-dataframe returns arrow data, not parquet", call.head)
+                .with_help("pass either `--format parquet` or `--to-dataframe`")),
+            _ => Ok(Some(TableFormat::Arrow)),
+        };
+    }
+    Ok(named)
+}
+
+/// The table format of a file, from its extension
+fn table_extension(path: &Spanned<PathBuf>) -> Option<TableFormat> {
+    path.item
+        .extension()
+        .and_then(|ext| TableFormat::from_name(&ext.to_string_lossy()))
+}
+
+/// The database and table given with `--sqlite` and `--table`
+#[cfg_attr(not(feature = "sqlite"), allow(dead_code))]
+struct SqliteTable {
+    database: Spanned<PathBuf>,
+    table: Spanned<String>,
+}
+
+/// The `--sqlite` table to write, the two flags going together
+fn sqlite_table(
+    call: &EvaluatedCall,
+    base_dir: &Path,
+) -> Result<Option<SqliteTable>, LabeledError> {
+    let database = call.get_flag::<Spanned<String>>("sqlite")?;
+    let table = call.get_flag::<Spanned<String>>("table")?;
+    match (database, table) {
+        (Some(database), Some(table)) => Ok(Some(SqliteTable {
+            database: Spanned {
+                item: base_dir.join(database.item),
+                span: database.span,
+            },
+            table,
+        })),
+        (Some(database), None) => Err(LabeledError::new("Missing table name")
+            .with_label("--sqlite needs the table to write", database.span)
+            .with_help("name it with `--table`")),
+        (None, Some(table)) => Err(LabeledError::new("Missing database")
+            .with_label("--table only applies to --sqlite", table.span)),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Write a table to the `--sqlite` database, returning a record of the table written
+#[cfg_attr(not(feature = "sqlite"), allow(unused_variables))]
+fn write_sqlite(result: Value, target: &SqliteTable, span: Span) -> Result<Value, LabeledError> {
+    #[cfg(feature = "sqlite")]
+    {
+        let SqliteTable { database, table } = target;
+        let rows = sqlite::write_table(&result, &database.item, &table.item, span)?;
+        let mut record = Record::new();
+        record.push(
+            "path",
+            Value::string(database.item.display().to_string(), span),
+        );
+        record.push("table", Value::string(&table.item, span));
+        record.push("rows", Value::int(rows as i64, span));
+        Ok(Value::record(record, span))
+    }
+    #[cfg(not(feature = "sqlite"))]
+    Err(unavailable("sqlite", "sqlite", span))
+}
+
+/// The output format of a file, from its extension
+fn extension_format(path: &Spanned<PathBuf>) -> Result<OutputFormat, LabeledError> {
+    path.item
+        .extension()
+        .and_then(|ext| OutputFormat::from_name(&ext.to_string_lossy()))
+        .ok_or_else(|| {
+            LabeledError::new("Unknown output format")
+                .with_label(
+                    format!("The format of '{}' cannot be guessed", path.item.display()),
+                    path.span,
+                )
+                .with_help("set it with `--format`")
+        })
+}
+
+/// Where `--output` files are recorded, with what they were rendered from
+struct Recording {
+    history: History,
+    source: Option<PathBuf>,
+    parameters: Parameters,
+}
+
+/// Write a serialized result to the `--output` file, returning a record of the file
+fn write_output(
+    call: &EvaluatedCall,
+    result: Value,
+    output: &Spanned<PathBuf>,
+    format: &str,
+    recording: Option<&Recording>,
+    span: Span,
+) -> Result<Value, LabeledError> {
+    let bytes = match result {
+        Value::Binary { val, .. } => val,
+        result => result.into_string()?.into_bytes(),
+    };
+    let path = &output.item;
+
+    let missing_parent = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty() && !dir.is_dir());
+    if let Some(dir) = missing_parent
+        && !call.has_flag("create-dirs")?
+    {
+        return Err(LabeledError::new("Output directory does not exist")
+            .with_label(format!("'{}' does not exist", dir.display()), output.span)
+            .with_help("pass `--create-dirs` to create it"));
+    }
+
+    let mut writes = WriteSet::new();
+    let size = bytes.len();
+    let recorded = recording.map(|recording| (recording, bytes.clone()));
+    writes.add(path.clone(), bytes);
+    if call.has_flag("dry-run")? {
+        // The history would store the output too, so the preview lists it beside the file
+        if let Some((recording, bytes)) = &recorded
+            && let Some(object) = recording.history.unstored(bytes)
+        {
+            writes.add(object, bytes.clone());
+        }
+        return Ok(writes.preview(span));
+    }
+    writes.commit().map_err(|e| {
+        LabeledError::new("Failed to write output").with_label(e.to_string(), output.span)
+    })?;
+
+    let mut record = Record::new();
+    record.push("path", Value::string(path.display().to_string(), span));
+    record.push("format", Value::string(format, span));
+    record.push("size", Value::filesize(size as i64, span));
+    if let Some((recording, bytes)) = recorded {
+        let entry = recording
+            .history
+            .record(
+                &bytes,
+                path,
+                format,
+                recording.source.as_deref(),
+                recording.parameters.clone(),
+            )
+            .map_err(|e| {
+                LabeledError::new("Failed to record output")
+                    .with_label(e.to_string(), span)
+                    .with_help("the file was written, but is missing from the output history")
+            })?;
+        record.push("hash", Value::string(entry.hash, span));
+    }
+    Ok(Value::record(record, span))
+}
+
+/// The switches that selected an output format before `--format`
+const DEPRECATED_SWITCHES: [OutputFormat; 3] =
+    [OutputFormat::Json, OutputFormat::Yaml, OutputFormat::Toml];
+
+/// The span of a flag of the call, or of the command if it was not passed
+fn flag_span(call: &EvaluatedCall, name: &str) -> Span {
+    call.named
+        .iter()
+        .find(|(flag, _)| flag.item == name)
+        .map_or(call.head, |(flag, _)| flag.span)
+}
+
+/// The output format given with `--format`, or with one of the deprecated switches
+///
+/// Flags asking for different formats are an error pointing at each of them, rather than one of
+/// them silently winning.
+fn output_format(call: &EvaluatedCall) -> Result<Option<OutputFormat>, LabeledError> {
+    let mut requested = Vec::new();
 
-        // For now, return a simple evaluation result
-        // TODO: Implement actual Nickel evaluation
-        let result = if call.has_flag("json")? {
-            Value::string(format!(r#"{{"nickel_source": "{}"}}"#, source), span)
-        } else if call.has_flag("yaml")? {
-            Value::string(format!("nickel_source: {}", source), span)
-        } else if call.has_flag("toml")? {
-            Value::string(format!("nickel_source = \"{}\"", source), span)
+    // Table formats are binary, and are handled apart by `table_format`
+    if let Some(name) = call.get_flag::<Spanned<String>>("format")?
+        && TableFormat::from_name(&name.item).is_none()
+    {
+        requested.push((OutputFormat::parse(&name)?, "format", name.span));
+    }
+    for format in DEPRECATED_SWITCHES {
+        let name = format.name();
+        if call.has_flag(name)? {
+            requested.push((format, name, flag_span(call, name)));
+        }
+    }
+
+    let Some(&(format, ..)) = requested.first() else {
+        return Ok(None);
+    };
+    if requested.iter().all(|(other, ..)| *other == format) {
+        return Ok(Some(format));
+    }
+    Err(requested.into_iter().fold(
+        LabeledError::new("Conflicting output formats")
+            .with_help("pass a single `--format`, the deprecated switches are aliases of it"),
+        |error, (format, flag, span)| {
+            error.with_label(format!("`--{flag}` selects {}", format.name()), span)
+        },
+    ))
+}
+
+/// Parse and typecheck the input without evaluating it, as a record of its validity and errors
+fn typecheck_only(
+    mut input: NickelInput,
+    bindings: Vec<(String, Value)>,
+    checks: Checks,
+    span: Span,
+) -> Result<Value, LabeledError> {
+    let errors = match parse_data(&input.source, input.format) {
+        // Data files have no types, they only need to parse
+        Some(data) => {
+            data.map_err(|e| {
+                LabeledError::new(format!("Failed to parse {} input", input.format.to_str()))
+                    .with_label(e, span)
+            })?;
+            Vec::new()
+        }
+        None => {
+            bind_values(&mut input, bindings, span)?;
+            if let Some(prelude) = &checks.prelude {
+                bind_prelude(&mut input, prelude, span)?;
+            }
+            let mut program = new_program(&input, span)?;
+            if checks.no_imports {
+                forbid_imports(&mut program, checks.prelude.as_deref(), span)?;
+            }
+            restrict_stdlib(&mut program, &checks.stdlib, span)?;
+            match program.typecheck(checks.typecheck) {
+                Ok(()) => Vec::new(),
+                Err(e) => diagnostics(&mut program.files(), e)
+                    .into_iter()
+                    .map(|diagnostic| diagnostic.into_value(span))
+                    .collect(),
+            }
+        }
+    };
+
+    let mut record = Record::new();
+    record.push("valid", Value::bool(errors.is_empty(), span));
+    record.push("errors", Value::list(errors, span));
+    Ok(Value::record(record, span))
+}
+
+/// An evaluation of a program, as sent to a worker process when its memory is limited
+#[derive(Serialize, Deserialize)]
+struct EvalJob {
+    call: EvaluatedCall,
+    input: NickelInput,
+    bindings: Vec<(String, Value)>,
+    checks: Checks,
+    result_format: ResultFormat,
+    /// Whether the time and memory the evaluation takes are measured, by `--measure`
+    measured: bool,
+}
+
+/// The result of an [`EvalJob`], as sent back by a worker process
+#[derive(Serialize, Deserialize)]
+struct Outcome {
+    result: Result<Value, LabeledError>,
+    /// The source and file of a function result, as its handle is only valid in the worker
+    function: Option<(String, Option<PathBuf>)>,
+    measure: Option<Measure>,
+}
+
+impl EvalJob {
+    fn evaluate(self, cache: NickelCache) -> Result<Evaluated, LabeledError> {
+        let span = self.call.head;
+        evaluate(
+            &self.call,
+            self.input,
+            self.bindings,
+            self.checks,
+            self.result_format,
+            cache,
+            span,
+        )
+    }
+
+    /// Evaluate to a single value, measured with `--measure`
+    fn run(self, cache: NickelCache) -> (Result<Value, LabeledError>, Option<Measure>) {
+        let span = self.call.head;
+        let measured = self.measured;
+        let evaluation = move || {
+            self.evaluate(cache)
+                .and_then(|result| result.into_value(span))
+        };
+        if measured {
+            // Measured on the evaluation thread, as the CPU time is that of the current thread
+            let (result, measure) = measure(evaluation);
+            (result, Some(measure))
         } else {
-            // Return as a record for now
-            let mut record = Record::new();
-            record.push("nickel_source", Value::string(source, span));
-            record.push("status", Value::string("parsed", span));
-            Value::record(record, span)
+            (evaluation(), None)
+        }
+    }
+
+    /// Evaluate on a worker thread, or in a worker process when its resources are limited
+    fn run_interruptible(
+        self,
+        cache: &NickelCache,
+        worker: &WorkerCommand,
+        signals: &Signals,
+        limits: Limits,
+    ) -> Result<(Result<Value, LabeledError>, Option<Measure>), LabeledError> {
+        let span = self.call.head;
+        if !limits.is_set() {
+            let cache = cache.clone();
+            return interruptible(signals, span, move || self.run(cache));
+        }
+        let outcome: Outcome = isolated(worker, signals, limits, span, &self)?;
+        let result = match outcome.function {
+            Some((source, path)) => Ok(NuNickelValue::cache_function(cache, source, path, span)),
+            None => outcome.result,
+        };
+        Ok((result, outcome.measure))
+    }
+}
+
+/// Serve an evaluation of `nickel eval` in a worker process, see [`isolated`]
+pub fn serve_worker(job: &Path) -> ! {
+    worker::serve(job, |job: EvalJob| {
+        let cache = NickelCache::default();
+        let (result, measure) = job.run(cache.clone());
+        let function = result
+            .as_ref()
+            .ok()
+            .and_then(|value| value.as_custom_value().ok())
+            .and_then(|value| value.as_any().downcast_ref::<NuNickelValueCustomValue>())
+            .and_then(|handle| cache.get(&handle.id))
+            .and_then(|cached| {
+                let path = cached.source_path().map(Path::to_path_buf);
+                Some((cached.as_source_code()?.clone(), path))
+            });
+        Outcome {
+            result,
+            function,
+            measure,
+        }
+    })
+}
+
+/// Evaluate the input as requested by the flags of the call
+///
+/// An array converted to Nushell values is left to convert element by element, unless the
+/// `--arrays` policy needs all of them at once.
+fn evaluate(
+    call: &EvaluatedCall,
+    mut input: NickelInput,
+    bindings: Vec<(String, Value)>,
+    checks: Checks,
+    result_format: ResultFormat,
+    cache: NickelCache,
+    span: Span,
+) -> Result<Evaluated, LabeledError> {
+    let ResultFormat {
+        conversion,
+        format,
+        table,
+    } = result_format;
+    let assignments = call.get_flag::<Vec<String>>("assign")?.unwrap_or_default();
+    let overrides = call
+        .get_flag::<Vec<String>>("override")?
+        .unwrap_or_default();
+    let field = call.get_flag::<String>("field")?;
+    let arrays = call
+        .get_flag::<Spanned<String>>("arrays")?
+        .map(|name| ArrayPolicy::parse(&name))
+        .transpose()?
+        .unwrap_or_default();
+    let depth = call.get_flag::<usize>("depth")?;
+    let stamp_path = call.get_flag::<String>("stamp")?;
+    let into_nu = |value: Value| {
+        let mut value = arrays.apply(value, span)?;
+        if let Some(table) = table {
+            return Ok(Value::binary(table.encode(&value, span)?, span));
+        }
+        truncate_depth(&mut value, depth.unwrap_or(MAX_DEPTH), span);
+        Ok::<_, LabeledError>(value)
+    };
+
+    if input.is_data()
+        && !(assignments.is_empty()
+            && overrides.is_empty()
+            && bindings.is_empty()
+            && stamp_path.is_none())
+    {
+        return Err(LabeledError::new("Cannot customize data input").with_label(
+            "--assign, --override, --arg, --env and --stamp only apply to Nickel programs",
+            span,
+        ));
+    }
+
+    // Data files are already values, they only need converting
+    if let Some(data) = parse_data(&input.source, input.format) {
+        let json = data.map_err(|e| {
+            LabeledError::new(format!("Failed to parse {} input", input.format.to_str()))
+                .with_label(e, span)
+        })?;
+        let json = match field {
+            Some(field) => get_json(&json, field, span)?.clone(),
+            None => json,
+        };
+        let serialize = |format: OutputFormat, json: &serde_json::Value| {
+            format.serialize(json).map_err(|e| {
+                LabeledError::new(format!("Failed to serialize as {}", format.name()))
+                    .with_label(e, span)
+            })
         };
+        let result = match format {
+            Some(format) if call.has_flag("multi-doc")? => {
+                let serde_json::Value::Array(documents) = &json else {
+                    return Err(not_an_array(span));
+                };
+                let documents = documents
+                    .iter()
+                    .map(|document| serialize(format, document))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Value::string(yaml_stream(documents), span)
+            }
+            Some(format) => Value::string(serialize(format, &json)?, span),
+            None => into_nu(json_to_value_within(&json, conversion.max_nesting, span)?)?,
+        };
+        return Ok(Evaluated::Value(result));
+    }
 
-        Ok(PipelineData::Value(result, None))
+    bind_values(&mut input, bindings, span)?;
+    if let Some(prelude) = &checks.prelude {
+        bind_prelude(&mut input, prelude, span)?;
+    }
+    let mut program = new_program(&input, span)?;
+    if checks.no_imports {
+        forbid_imports(&mut program, checks.prelude.as_deref(), span)?;
+    }
+    restrict_stdlib(&mut program, &checks.stdlib, span)?;
+    if let TypecheckMode::Enforce = checks.typecheck {
+        program.typecheck(TypecheckMode::Enforce).map_err(|e| {
+            nickel_error(&mut program.files(), e, "Nickel typechecking failed", span)
+        })?;
+    }
+    add_assignments(&mut program, assignments, MergePriority::Neutral, span)?;
+    add_assignments(&mut program, overrides, MergePriority::Top, span)?;
+    if let Some(path) = stamp_path {
+        let dir = match (&input.path, &input.base_dir) {
+            (Some(file), _) => file.parent().unwrap_or(Path::new(".")),
+            (None, Some(dir)) => dir.as_path(),
+            (None, None) => Path::new("."),
+        };
+        let stamp = value_to_nickel(&stamp(dir, span), span)?;
+        add_assignments(
+            &mut program,
+            vec![format!("{path}={stamp}")],
+            MergePriority::Top,
+            span,
+        )?;
+    }
+    if call.has_flag("no-contracts")? {
+        disable_contracts(&mut program, span)?;
+    }
+    if let Some(field) = &field {
+        program.field = parse_field_path(&mut program, field.clone(), span)?;
     }
-}
\ No newline at end of file
+    let term = eval_for_export(&mut program, span)?;
+
+    match format {
+        Some(format) if call.has_flag("multi-doc")? => {
+            let Term::Array(documents, _) = term.as_ref() else {
+                return Err(not_an_array(span));
+            };
+            let documents = documents
+                .iter()
+                .map(|document| export_term(&program, document, format, span))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Evaluated::Value(Value::string(
+                yaml_stream(documents),
+                span,
+            )))
+        }
+        Some(format) => match format.export_format() {
+            Some(export_format) => export_later(program, term, export_format, span),
+            None => Ok(Evaluated::Value(Value::string(
+                export_term(&program, &term, format, span)?,
+                span,
+            ))),
+        },
+        None => match term.as_ref() {
+            Term::Array(items, _)
+                if arrays == ArrayPolicy::Any && table.is_none() && depth != Some(0) =>
+            {
+                // The list is the first level, its elements are one level deeper
+                let element_depth = depth.unwrap_or(MAX_DEPTH) - 1;
+                let elements = array_elements(items, conversion, span).map(move |element| {
+                    let mut element = element?;
+                    truncate_depth(&mut element, element_depth, span);
+                    Ok(element)
+                });
+                Ok(Evaluated::Elements(Box::new(elements)))
+            }
+            _ if is_function(&term) && table.is_none() => {
+                // A function has no data, the program is kept for `nickel call` to apply it
+                let source = match &field {
+                    Some(field) => format!("({}\n).{field}", input.source),
+                    None => input.source,
+                };
+                Ok(Evaluated::Value(NuNickelValue::cache_function(
+                    &cache, source, input.path, span,
+                )))
+            }
+            _ => into_nu(nickel_to_nu_value_with(&term, conversion, span)?).map(Evaluated::Value),
+        },
+    }
+}
+
+/// Warnings known before evaluating, for the `warnings` of the output metadata
+fn warnings(call: &EvaluatedCall) -> Result<Vec<String>, LabeledError> {
+    let mut warnings = Vec::new();
+    for format in DEPRECATED_SWITCHES {
+        let name = format.name();
+        if call.has_flag(name)? {
+            warnings.push(format!("--{name} is deprecated, use --format {name}"));
+        }
+    }
+    if call.has_flag("no-contracts")? {
+        warnings.push(
+            "--no-contracts: runtime contracts were not checked, the result may not satisfy them"
+                .to_string(),
+        );
+    }
+    Ok(warnings)
+}
+
+/// Add warnings to the custom metadata of an output, as a `warnings` list
+fn with_warnings(data: PipelineData, warnings: Vec<String>, span: Span) -> PipelineData {
+    if warnings.is_empty() {
+        return data;
+    }
+    let mut metadata = data.metadata().unwrap_or_default();
+    let warnings = warnings
+        .into_iter()
+        .map(|warning| Value::string(warning, span))
+        .collect();
+    metadata
+        .custom
+        .push("warnings", Value::list(warnings, span));
+    data.set_metadata(Some(metadata))
+}
+
+/// Join YAML documents into a stream, each of them starting with a `---` marker
+fn yaml_stream(documents: Vec<String>) -> String {
+    documents
+        .into_iter()
+        .map(|document| format!("---\n{document}"))
+        .collect()
+}
+
+fn not_an_array(span: Span) -> LabeledError {
+    LabeledError::new("Cannot split the result into documents")
+        .with_label("--multi-doc needs the result to be an array", span)
+}
@@ -0,0 +1,88 @@
+use crate::NickelPlugin;
+use crate::nickel::error::{ERROR_CODES, ErrorCode, find_error_code};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Record, Signature, Span, SyntaxShape, Type,
+    Value,
+};
+
+#[derive(Clone)]
+pub struct NickelExplain;
+
+impl PluginCommand for NickelExplain {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel explain"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel explain")
+            .input_output_types(vec![
+                (Type::Nothing, Type::record()),
+                (Type::Nothing, Type::table()),
+            ])
+            .optional(
+                "code",
+                SyntaxShape::String,
+                "Error code, e.g. `nickel::contract` or `contract`",
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "Show the extended explanation of a Nickel error code"
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Explain a broken contract",
+                example: "nickel explain nickel::contract",
+                result: None,
+            },
+            Example {
+                description: "List the error codes",
+                example: "nickel explain | select code title",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &NickelPlugin,
+        _engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+
+        let Some(code) = call.opt::<String>(0)? else {
+            let codes = ERROR_CODES
+                .iter()
+                .map(|code| error_code_record(code, span))
+                .collect();
+            return Ok(PipelineData::Value(Value::list(codes, span), None));
+        };
+
+        let error_code = find_error_code(&code).ok_or_else(|| {
+            LabeledError::new("Unknown error code")
+                .with_label(format!("`{code}` is not a Nickel error code"), span)
+                .with_help("use `nickel explain` to list the error codes")
+        })?;
+
+        Ok(PipelineData::Value(
+            error_code_record(error_code, span),
+            None,
+        ))
+    }
+}
+
+fn error_code_record(code: &ErrorCode, span: Span) -> Value {
+    let mut record = Record::new();
+    record.push("code", Value::string(code.code(), span));
+    record.push("title", Value::string(code.title, span));
+    record.push("explanation", Value::string(code.explanation, span));
+    Value::record(record, span)
+}
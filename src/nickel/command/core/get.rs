@@ -0,0 +1,184 @@
+use crate::NickelPlugin;
+use crate::nickel::{
+    convert::{json_to_value, nickel_to_nu_value},
+    error::nickel_error,
+    input::{NickelInput, import_paths, no_imports, working_dir},
+    program::{eval_for_export, eval_to_json, new_program},
+    values::NuNickelValue,
+};
+use nickel_lang_core::{
+    cache::InputFormat,
+    eval::cache::CacheImpl,
+    program::{FieldPath, Program},
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Signature, Span, SyntaxShape, Type, Value,
+};
+
+#[derive(Clone)]
+pub struct NickelGet;
+
+impl PluginCommand for NickelGet {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel get"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel get")
+            .input_output_types(vec![
+                (Type::String, Type::Any),
+                (Type::Binary, Type::Any),
+                (Type::Nothing, Type::Any),
+                (Type::Custom("NickelValue".to_string().into()), Type::Any),
+            ])
+            .required(
+                "field_path",
+                SyntaxShape::String,
+                "Path of the field to get, e.g. `server.port`",
+            )
+            .optional(
+                "path",
+                SyntaxShape::Filepath,
+                "Path to nickel file to get the field from",
+            )
+            .named(
+                "import-path",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Directories to look up imports in, before those of `NICKEL_IMPORT_PATH`",
+                Some('I'),
+            )
+            .named(
+                "source-name",
+                SyntaxShape::String,
+                "Name piped code is reported under in errors, instead of `<input>`",
+                None,
+            )
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths and imports",
+                None,
+            )
+            .category(Category::Filters)
+    }
+
+    fn description(&self) -> &str {
+        "Evaluate a single field of a Nickel program"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Only the records along the path and the requested field are evaluated, so the rest of a \
+         large configuration is neither computed nor checked. Field names containing dots or \
+         spaces can be quoted, as in `nickel get 'labels.\"app.kubernetes.io/name\"'`."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Get a nested field of a file",
+                example: "nickel get server.port config.ncl",
+                result: None,
+            },
+            Example {
+                description: "Get a field without evaluating the failing rest of the program",
+                example: r#""{ a.b = 1, c = std.fail_with \"unused\" }" | nickel get a.b"#,
+                result: Some(Value::test_int(1)),
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let field_path: String = call.req(0)?;
+        let base_dir = working_dir(engine, call)?;
+
+        let mut input = match input {
+            PipelineData::Value(value @ Value::Custom { .. }, _) => {
+                let cached =
+                    NuNickelValue::try_get_cached_value(plugin, &value)?.ok_or_else(|| {
+                        LabeledError::new("Invalid input type")
+                            .with_label("Expected a Nickel value", value.span())
+                    })?;
+                match (
+                    NickelInput::from_cached(&cached, Some(base_dir)),
+                    cached.as_json(),
+                ) {
+                    (Some(input), _) => input,
+                    // Evaluated data has no program left to evaluate lazily
+                    (None, Some(json)) => {
+                        let result = get_json(json, field_path, span)?;
+                        return Ok(PipelineData::Value(json_to_value(result, span)?, None));
+                    }
+                    (None, None) => {
+                        return Err(LabeledError::new("Cannot get a field of this Nickel value")
+                            .with_label(
+                                "This Nickel value has neither source nor data",
+                                value.span(),
+                            ));
+                    }
+                }
+            }
+            input => NickelInput::from_call(call, input, 1, Some(base_dir))?,
+        };
+        input.import_paths = import_paths(engine, call)?;
+        input.no_imports = no_imports(engine, call)?;
+        if input.is_data() {
+            let json = eval_to_json(&input, span)?;
+            let result = get_json(&json, field_path, span)?;
+            return Ok(PipelineData::Value(json_to_value(result, span)?, None));
+        }
+
+        let mut program = new_program(&input, span)?;
+        program.field = parse_field_path(&mut program, field_path, span)?;
+        let term = eval_for_export(&mut program, span)?;
+
+        Ok(PipelineData::Value(nickel_to_nu_value(&term, span)?, None))
+    }
+}
+
+pub(super) fn parse_field_path(
+    program: &mut Program<CacheImpl>,
+    field_path: String,
+    span: Span,
+) -> Result<FieldPath, LabeledError> {
+    program
+        .parse_field_path(field_path)
+        .map_err(|e| nickel_error(&mut program.files(), e, "Invalid field path", span))
+}
+
+/// Follow a field path through JSON data
+pub(super) fn get_json(
+    json: &serde_json::Value,
+    field_path: String,
+    span: Span,
+) -> Result<&serde_json::Value, LabeledError> {
+    // The program is only there to parse the path with Nickel's syntax
+    let input = NickelInput {
+        source: "null".to_string(),
+        path: None,
+        format: InputFormat::Nickel,
+        base_dir: None,
+        import_paths: Vec::new(),
+        no_imports: false,
+        source_name: None,
+    };
+    let path = parse_field_path(&mut new_program(&input, span)?, field_path, span)?;
+
+    path.0.iter().try_fold(json, |json, field| {
+        json.get(field.label()).ok_or_else(|| {
+            LabeledError::new("Missing field").with_label(
+                format!("There is no field `{}` in {json}", field.label()),
+                span,
+            )
+        })
+    })
+}
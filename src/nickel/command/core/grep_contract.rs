@@ -0,0 +1,171 @@
+use crate::NickelPlugin;
+use crate::nickel::{
+    convert::nickel_to_nu_value,
+    input::{NickelInput, import_paths, no_imports, working_dir},
+    program::{eval_for_export, new_program},
+};
+use nickel_lang_core::term::{RichTerm, Term};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Record, Signature, Span, SyntaxShape, Type,
+    Value,
+};
+
+#[derive(Clone)]
+pub struct NickelGrepContract;
+
+impl PluginCommand for NickelGrepContract {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel grep-contract"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel grep-contract")
+            .input_output_types(vec![
+                (Type::String, Type::table()),
+                (Type::Binary, Type::table()),
+                (Type::Nothing, Type::table()),
+            ])
+            .required(
+                "contract",
+                SyntaxShape::String,
+                "Contract to look for, as written in annotations, e.g. `Port` or `k8s.Port`",
+            )
+            .optional(
+                "path",
+                SyntaxShape::Filepath,
+                "Path to nickel file to search",
+            )
+            .named(
+                "import-path",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Directories to look up imports in, before those of `NICKEL_IMPORT_PATH`",
+                Some('I'),
+            )
+            .named(
+                "source-name",
+                SyntaxShape::String,
+                "Name piped code is reported under in errors, instead of `<input>`",
+                None,
+            )
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths and imports",
+                None,
+            )
+            .category(Category::Filters)
+    }
+
+    fn description(&self) -> &str {
+        "Find the fields of an evaluated configuration annotated with a contract"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Each field whose type annotation or contracts include the contract gets a row with its \
+         `path`, the `contract` as written and its `value`. Fields get the contracts of the \
+         record contracts applied to them, so `server | Server` finds `server.port` when \
+         `Server` has `port | Port`. A contract matches when it is written as given, or when its \
+         last part is, so `Port` matches `k8s.Port` too. Elements of arrays are searched, with \
+         their index in the path."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "List every externally exposed port of a configuration",
+                example: "nickel grep-contract ExposedPort deploy.ncl",
+                result: None,
+            },
+            Example {
+                description: "Check that no secret is written in plain text",
+                example: "nickel grep-contract Secret config.ncl | where ($it.value | str starts-with 'vault:') == false",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let contract: String = call.req(0)?;
+
+        let mut input = NickelInput::from_call(call, input, 1, Some(working_dir(engine, call)?))?;
+        input.import_paths = import_paths(engine, call)?;
+        input.no_imports = no_imports(engine, call)?;
+        let mut program = new_program(&input, span)?;
+        let term = eval_for_export(&mut program, span)?;
+
+        let mut rows = Vec::new();
+        find_contract(&term, &contract, &mut Vec::new(), &mut rows, span)?;
+        Ok(PipelineData::Value(Value::list(rows, span), None))
+    }
+}
+
+/// Add a row for each field under `term` with a contract matching `wanted`
+///
+/// The fields of a record are searched sorted by name, so that the rows do not change from one
+/// run to the next, and each one before the fields nested in it.
+fn find_contract(
+    term: &RichTerm,
+    wanted: &str,
+    path: &mut Vec<String>,
+    rows: &mut Vec<Value>,
+    span: Span,
+) -> Result<(), LabeledError> {
+    match term.as_ref() {
+        Term::Record(data) => {
+            let mut fields: Vec<_> = data.fields.iter().collect();
+            fields.sort_by_key(|(id, _)| id.label());
+            for (id, field) in fields {
+                let Some(value) = &field.value else {
+                    continue;
+                };
+                if field.metadata.not_exported {
+                    continue;
+                }
+                path.push(id.label().to_string());
+                let annotation = &field.metadata.annotation;
+                let contract = annotation
+                    .typ
+                    .iter()
+                    .chain(&annotation.contracts)
+                    .map(|labeled| labeled.typ.to_string())
+                    .find(|written| matches(written, wanted));
+                if let Some(contract) = contract {
+                    let mut record = Record::new();
+                    record.push("path", Value::string(path.join("."), span));
+                    record.push("contract", Value::string(contract, span));
+                    record.push("value", nickel_to_nu_value(value, span)?);
+                    rows.push(Value::record(record, span));
+                }
+                find_contract(value, wanted, path, rows, span)?;
+                path.pop();
+            }
+        }
+        Term::Array(items, _) => {
+            for (i, item) in items.iter().enumerate() {
+                path.push(i.to_string());
+                find_contract(item, wanted, path, rows, span)?;
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Whether a contract as written is the one wanted, or a path ending with it
+fn matches(written: &str, wanted: &str) -> bool {
+    written == wanted
+        || written
+            .strip_suffix(wanted)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
@@ -0,0 +1,87 @@
+use crate::NickelPlugin;
+use crate::nickel::{
+    convert::json_to_value,
+    input::{NickelInput, import_paths, no_imports, working_dir},
+    program::eval_to_json,
+    values::NuNickelValue,
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, LabeledError, PipelineData, Signature, Type, Value};
+
+#[derive(Clone)]
+pub struct NickelIntoRecord;
+
+impl PluginCommand for NickelIntoRecord {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel into record"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel into record")
+            .input_output_types(vec![(
+                Type::Custom("NickelValue".to_string().into()),
+                Type::Any,
+            )])
+            .category(Category::Conversions)
+    }
+
+    fn description(&self) -> &str {
+        "Convert a Nickel value into native Nushell data"
+    }
+
+    fn extra_description(&self) -> &str {
+        "The cached data of the value is converted deeply into records, lists and primitive \
+         values. A Nickel term cached without data, like the result of `nickel parse`, is \
+         evaluated from its source first, with its relative imports resolved next to the file \
+         it was read from."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![Example {
+            description: "Inspect a parsed value as a record",
+            example: r#""{ foo = 42 }" | nickel parse | nickel into record"#,
+            result: Some(Value::test_record(nu_protocol::record! {
+                "foo" => Value::test_int(42),
+            })),
+        }]
+    }
+
+    fn run(
+        &self,
+        plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let value = input.into_value(span)?;
+
+        let cached = NuNickelValue::try_get_cached_value(plugin, &value)?.ok_or_else(|| {
+            LabeledError::new("Invalid input type").with_label(
+                format!("Expected a Nickel value, found {}", value.get_type()),
+                value.span(),
+            )
+        })?;
+
+        let json = match cached.as_json() {
+            Some(json) => json.clone(),
+            None => {
+                let mut input = NickelInput::from_cached(&cached, Some(working_dir(engine, call)?))
+                    .ok_or_else(|| {
+                        LabeledError::new("Cannot convert Nickel value").with_label(
+                            "This Nickel value has neither data nor source",
+                            value.span(),
+                        )
+                    })?;
+                input.import_paths = import_paths(engine, call)?;
+                input.no_imports = no_imports(engine, call)?;
+                eval_to_json(&input, span)?
+            }
+        };
+        let result = json_to_value(&json, span)?;
+
+        Ok(PipelineData::Value(result, None))
+    }
+}
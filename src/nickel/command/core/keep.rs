@@ -0,0 +1,109 @@
+use crate::NickelPlugin;
+use crate::cache::NickelPluginObject;
+use crate::nickel::values::{NuNickelValue, NuNickelValueCustomValue};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, LabeledError, PipelineData, Signature, Type, Value};
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct NickelKeep;
+
+impl PluginCommand for NickelKeep {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel keep"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel keep")
+            .input_output_types(vec![
+                (
+                    Type::Custom("NickelValue".to_string().into()),
+                    Type::Custom("NickelValue".to_string().into()),
+                ),
+                (
+                    Type::record(),
+                    Type::Custom("NickelValue".to_string().into()),
+                ),
+            ])
+            .category(Category::Conversions)
+    }
+
+    fn description(&self) -> &str {
+        "Make a Nickel value self-contained, carrying its data instead of referring to the cache"
+    }
+
+    fn extra_description(&self) -> &str {
+        "A Nickel value normally refers to an entry of the plugin cache, and stops working once \
+         the entry is evicted or the plugin restarts. A kept value carries its data and source \
+         itself, so it can be stored in `$env` for the whole session. Its record form, from \
+         `to nuon` for example, has the data as a `kept` field, and is turned back into a Nickel \
+         value by `nickel keep`. Imports of the source are still read from disk when the value \
+         is evaluated."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Keep a parsed configuration for the whole session",
+                example: "$env.CONFIG = (open config.ncl | nickel parse | nickel keep)",
+                result: None,
+            },
+            Example {
+                description: "Save a Nickel value to disk and load it back",
+                example: "$value | nickel keep | to nuon | save value.nuon; open value.nuon | nickel keep",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        plugin: &NickelPlugin,
+        _engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let value = input.into_value(span)?;
+
+        let object = match &value {
+            Value::Record { val, .. } => kept_object(val.get("kept"), &value)?,
+            _ => {
+                NuNickelValue::try_get_cached_value(plugin, &value)?
+                    .ok_or_else(|| invalid_input(&value))?
+                    .value
+            }
+        };
+        let type_name = object.object_type().to_string();
+        let kept =
+            NuNickelValueCustomValue::kept(NuNickelValue::new(Uuid::new_v4(), type_name), object);
+        Ok(PipelineData::Value(
+            Value::custom(Box::new(kept), span),
+            None,
+        ))
+    }
+}
+
+/// The data of the record form of a kept value, from its `kept` field
+fn kept_object(kept: Option<&Value>, value: &Value) -> Result<NickelPluginObject, LabeledError> {
+    let kept = kept
+        .and_then(|kept| kept.as_str().ok())
+        .ok_or_else(|| invalid_input(value))?;
+    serde_json::from_str(kept).map_err(|e| {
+        LabeledError::new("Invalid kept Nickel value")
+            .with_label(e.to_string(), value.span())
+            .with_help("the record should come from a value made by `nickel keep`")
+    })
+}
+
+fn invalid_input(value: &Value) -> LabeledError {
+    LabeledError::new("Invalid input type").with_label(
+        format!(
+            "Expected a Nickel value or a kept one's record, found {}",
+            value.get_type()
+        ),
+        value.span(),
+    )
+}
@@ -0,0 +1,97 @@
+use crate::NickelPlugin;
+use crate::nickel::{
+    input::{NickelInput, working_dir},
+    lex::lex,
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Record, Signature, SyntaxShape, Type, Value,
+};
+
+#[derive(Clone)]
+pub struct NickelLex;
+
+impl PluginCommand for NickelLex {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel lex"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel lex")
+            .input_output_types(vec![
+                (Type::String, Type::table()),
+                (Type::Binary, Type::table()),
+                (Type::Nothing, Type::table()),
+            ])
+            .optional("path", SyntaxShape::Filepath, "Path to nickel file to lex")
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths",
+                None,
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "Lex Nickel code and return its tokens as a table"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Each token has its kind, the lexer mode it was produced in, its text, its byte range \
+         (`start`, `end`) and the 1-based line and column it starts at. Comments and whitespace \
+         have no tokens."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Lex Nickel code from a string",
+                example: r#""let x = 1 in x" | nickel lex"#,
+                result: None,
+            },
+            Example {
+                description: "List the identifiers of a file",
+                example: "nickel lex config.ncl | where kind == Identifier | get text | uniq",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let input = NickelInput::from_call(call, input, 0, Some(working_dir(engine, call)?))?;
+
+        let tokens = lex(&input.source).map_err(|e| {
+            LabeledError::new("Failed to lex Nickel code").with_label(
+                format!("{} at line {}, column {}", e.message, e.line, e.column),
+                span,
+            )
+        })?;
+
+        let rows = tokens
+            .into_iter()
+            .map(|token| {
+                let mut record = Record::new();
+                record.push("kind", Value::string(token.kind, span));
+                record.push("mode", Value::string(token.mode, span));
+                record.push("text", Value::string(token.text, span));
+                record.push("start", Value::int(token.start as i64, span));
+                record.push("end", Value::int(token.end as i64, span));
+                record.push("line", Value::int(token.line as i64, span));
+                record.push("column", Value::int(token.column as i64, span));
+                Value::record(record, span)
+            })
+            .collect();
+
+        Ok(PipelineData::Value(Value::list(rows, span), None))
+    }
+}
@@ -1,8 +1,77 @@
+mod call;
+mod capabilities;
+mod convert;
+mod eq;
 mod eval;
+mod explain;
+mod get;
+mod grep_contract;
+mod into_record;
+mod keep;
+mod lex;
+mod open;
 mod parse;
+mod patch;
+mod render;
+mod source;
+mod top_level_type;
+mod type_of;
+mod typecheck;
+mod why;
 
 #[cfg(test)]
 mod tests;
 
-pub use eval::NickelEval;
-pub use parse::NickelParse;
\ No newline at end of file
+pub use call::NickelCall;
+pub use capabilities::NickelCapabilities;
+pub use convert::NickelConvert;
+pub use eq::NickelEq;
+pub use eval::{NickelEval, serve_worker};
+pub use explain::NickelExplain;
+pub use get::NickelGet;
+pub use grep_contract::NickelGrepContract;
+pub use into_record::NickelIntoRecord;
+pub use keep::NickelKeep;
+pub use lex::NickelLex;
+pub use open::NickelOpen;
+pub use parse::NickelParse;
+pub use patch::NickelPatch;
+pub use render::NickelRender;
+pub use source::NickelSource;
+pub use top_level_type::NickelTopLevelType;
+pub use type_of::NickelTypeOf;
+pub use typecheck::NickelTypecheck;
+pub use why::NickelWhy;
+
+use crate::NickelPlugin;
+use crate::nickel::{input::NickelInput, program::eval_to_json, values::NuNickelValue};
+use nu_protocol::{LabeledError, Span, Value};
+use std::path::PathBuf;
+
+/// The data of a Nickel value, evaluating it if it only has source code
+///
+/// The source is evaluated as `with_imports` sets its imports up, like the other inputs of the
+/// command.
+fn cached_json(
+    plugin: &NickelPlugin,
+    value: &Value,
+    base_dir: PathBuf,
+    with_imports: impl Fn(NickelInput) -> NickelInput,
+    span: Span,
+) -> Result<serde_json::Value, LabeledError> {
+    let invalid = || {
+        LabeledError::new("Invalid input type").with_label(
+            format!(
+                "Expected a file path or a Nickel value, found {}",
+                value.get_type()
+            ),
+            value.span(),
+        )
+    };
+    let cached = NuNickelValue::try_get_cached_value(plugin, value)?.ok_or_else(invalid)?;
+
+    match NickelInput::from_cached(&cached, Some(base_dir)) {
+        Some(input) => eval_to_json(&with_imports(input), span),
+        None => cached.as_json().cloned().ok_or_else(invalid),
+    }
+}
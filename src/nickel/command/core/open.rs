@@ -0,0 +1,89 @@
+use crate::NickelPlugin;
+use crate::nickel::{
+    error::nickel_error,
+    input::{NickelInput, import_paths, no_imports, working_dir},
+    program::new_program,
+    values::NuNickelValue,
+};
+use nickel_lang_core::typecheck::TypecheckMode;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, LabeledError, PipelineData, Signature, SyntaxShape, Type};
+use std::path::PathBuf;
+
+#[derive(Clone)]
+pub struct NickelOpen;
+
+impl PluginCommand for NickelOpen {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel open"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel open")
+            .input_output_types(vec![(
+                Type::Nothing,
+                Type::Custom("NickelValue".to_string().into()),
+            )])
+            .required("path", SyntaxShape::Filepath, "Path to nickel file to open")
+            .named(
+                "import-path",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Directories to look up imports in, before those of `NICKEL_IMPORT_PATH`",
+                Some('I'),
+            )
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths and imports",
+                None,
+            )
+            .category(Category::FileSystem)
+    }
+
+    fn description(&self) -> &str {
+        "Parse and typecheck a Nickel file, returning a value whose fields are evaluated on demand"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Nothing is evaluated when the file is opened, so a large configuration costs only its \
+         parsing and typechecking, and their errors are reported right away. `nickel get` then \
+         evaluates the records along the path it is given and the field at its end, and none \
+         of the other fields. A cell path like `$config.network` evaluates its first field the \
+         same way, and follows the rest of the path through the data of that field.\n\n\
+         Nickel terms cannot be shared between the evaluations of the plugin, so each access \
+         evaluates its path again from the source. Evaluate the whole file with `nickel eval` \
+         when most of its fields are needed."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![Example {
+            description: "Open a large configuration and read a few fields of it",
+            example: "let config = (nickel open cluster.ncl); $config | nickel get nodes.count; $config | nickel get network.cidr",
+            result: None,
+        }]
+    }
+
+    fn run(
+        &self,
+        plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let path: PathBuf = call.req(0)?;
+
+        let mut input = NickelInput::from_path(path, Some(working_dir(engine, call)?), span)?;
+        input.import_paths = import_paths(engine, call)?;
+        input.no_imports = no_imports(engine, call)?;
+        let mut program = new_program(&input, span)?;
+        program.typecheck(TypecheckMode::Walk).map_err(|e| {
+            nickel_error(&mut program.files(), e, "Nickel typechecking failed", span)
+        })?;
+
+        let result = NuNickelValue::cache_parsed(plugin, input, span)?;
+        Ok(PipelineData::Value(result, None))
+    }
+}
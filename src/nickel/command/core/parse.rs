@@ -1,9 +1,11 @@
-use crate::nickel::values::NuNickelValue;
 use crate::NickelPlugin;
-use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
-use nu_protocol::{
-    Category, Example, LabeledError, PipelineData, Signature, Span, SyntaxShape, Type, Value,
+use crate::nickel::{
+    input::{NickelInput, working_dir},
+    program::{is_function, new_program},
+    values::NuNickelValue,
 };
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, LabeledError, PipelineData, Signature, SyntaxShape, Type};
 
 #[derive(Clone)]
 pub struct NickelParse;
@@ -19,9 +21,23 @@ impl PluginCommand for NickelParse {
         Signature::build("nickel parse")
             .input_output_types(vec![
                 (Type::String, Type::Custom("NickelValue".to_string().into())),
-                (Type::Nothing, Type::Custom("NickelValue".to_string().into())),
+                (Type::Binary, Type::Custom("NickelValue".to_string().into())),
+                (
+                    Type::Nothing,
+                    Type::Custom("NickelValue".to_string().into()),
+                ),
             ])
-            .optional("path", SyntaxShape::Filepath, "Path to nickel file to parse")
+            .optional(
+                "path",
+                SyntaxShape::Filepath,
+                "Path to nickel file to parse",
+            )
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths and imports",
+                None,
+            )
             .category(Category::Conversions)
     }
 
@@ -29,7 +45,7 @@ impl PluginCommand for NickelParse {
         "Parse Nickel code and return the AST as a Nickel value"
     }
 
-    fn examples(&self) -> Vec<Example> {
+    fn examples(&self) -> Vec<Example<'_>> {
         vec![
             Example {
                 description: "Parse Nickel code from string",
@@ -41,49 +57,36 @@ impl PluginCommand for NickelParse {
                 example: "nickel parse config.ncl",
                 result: None,
             },
+            Example {
+                description: "Parse a function, to apply it with `nickel call`",
+                example: r#""fun x => x + 1" | nickel parse | nickel call 41"#,
+                result: None,
+            },
         ]
     }
 
     fn run(
         &self,
         plugin: &NickelPlugin,
-        _engine: &EngineInterface,
+        engine: &EngineInterface,
         call: &EvaluatedCall,
         input: PipelineData,
     ) -> Result<PipelineData, LabeledError> {
         let span = call.head;
 
-        // Get the source code - either from input or from file
-        let source = if let Some(path) = call.opt::<String>(0)? {
-            // Read from file
-            std::fs::read_to_string(&path).map_err(|e| {
-                LabeledError::new(format!("Failed to read file: {}", e))
-                    .with_label(format!("Cannot read file '{}'", path), span)
-            })?
+        let input = NickelInput::from_call(call, input, 0, Some(working_dir(engine, call)?))?;
+
+        // Code that is a function has nothing to export, but `nickel call` can apply it
+        let function = new_program(&input, span)
+            .ok()
+            .and_then(|mut program| program.parse().ok())
+            .is_some_and(|term| is_function(&term));
+        let result = if function {
+            NuNickelValue::cache_function(&plugin.cache, input.source, input.path, span)
         } else {
-            // Read from input
-            match input {
-                PipelineData::Value(Value::String { val, .. }, _) => val,
-                PipelineData::Empty => {
-                    return Err(LabeledError::new("No input provided")
-                        .with_label("Provide Nickel code as input or specify a file path", span))
-                }
-                _ => {
-                    return Err(LabeledError::new("Invalid input type")
-                        .with_label("Expected string input", span))
-                }
-            }
+            NuNickelValue::cache_parsed(plugin, input, span)?
         };
 
-        // For now, create a simple JSON representation of the parse
-        let json_value = serde_json::json!({
-            "source": source,
-            "ast": "placeholder_ast",
-            "status": "parsed"
-        });
-
-        let result = NuNickelValue::cache_json_value(plugin, json_value, span)?;
-
         Ok(PipelineData::Value(result, None))
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,167 @@
+use super::cached_json;
+use crate::NickelPlugin;
+use crate::nickel::{
+    convert::json_to_value,
+    input::{NickelInput, import_paths, no_imports, working_dir},
+    program::eval_to_json,
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Record, Signature, Span, SyntaxShape, Type,
+    Value, record,
+};
+
+#[derive(Clone)]
+pub struct NickelPatch;
+
+impl PluginCommand for NickelPatch {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel patch"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel patch")
+            .input_output_types(vec![
+                (Type::record(), Type::Any),
+                (Type::String, Type::Any),
+                (Type::Binary, Type::Any),
+                (Type::Nothing, Type::Any),
+                (Type::Custom("NickelValue".to_string().into()), Type::Any),
+            ])
+            .required(
+                "patch",
+                SyntaxShape::Any,
+                "Record, path to a file or Nickel value to apply as a merge patch",
+            )
+            .optional(
+                "path",
+                SyntaxShape::Filepath,
+                "Path to a file to patch, instead of the input",
+            )
+            .named(
+                "import-path",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Directories to look up imports in, before those of `NICKEL_IMPORT_PATH`",
+                Some('I'),
+            )
+            .named(
+                "source-name",
+                SyntaxShape::String,
+                "Name piped code is reported under in errors, instead of `<input>`",
+                None,
+            )
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths and imports",
+                None,
+            )
+            .category(Category::Filters)
+    }
+
+    fn description(&self) -> &str {
+        "Apply a JSON merge patch to an evaluated configuration"
+    }
+
+    fn extra_description(&self) -> &str {
+        "The patch follows RFC 7386: its fields are merged recursively into the records of the \
+         configuration, a null field removes the field, and any other value, arrays included, \
+         replaces what was there. The configuration is evaluated first, so the patch applies to \
+         its data and is not checked against its contracts."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Change a nested field and remove another one",
+                example: "{ server: { port: 80, debug: true } } | nickel patch { server: { port: 8080, debug: null } }",
+                result: Some(Value::test_record(record! {
+                    "server" => Value::test_record(record! {
+                        "port" => Value::test_int(8080),
+                    }),
+                })),
+            },
+            Example {
+                description: "Apply the patch file of an environment to a configuration",
+                example: "nickel patch prod-patch.json config.ncl | to json",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let base_dir = working_dir(engine, call)?;
+        let import_paths = import_paths(engine, call)?;
+        let no_imports = no_imports(engine, call)?;
+        let with_imports = |input: NickelInput| NickelInput {
+            import_paths: import_paths.clone(),
+            no_imports,
+            ..input
+        };
+
+        let patch = match call.req::<Value>(0)? {
+            patch @ Value::Record { .. } => patch,
+            Value::String { val, .. } => {
+                let input = with_imports(NickelInput::from_path(
+                    val.into(),
+                    Some(base_dir.clone()),
+                    span,
+                )?);
+                json_to_value(&eval_to_json(&input, span)?, span)?
+            }
+            patch => json_to_value(
+                &cached_json(plugin, &patch, base_dir.clone(), &with_imports, span)?,
+                span,
+            )?,
+        };
+        let target = match input {
+            PipelineData::Value(target @ Value::Record { .. }, _) => target,
+            PipelineData::Value(value @ Value::Custom { .. }, _) => json_to_value(
+                &cached_json(plugin, &value, base_dir, &with_imports, span)?,
+                span,
+            )?,
+            input => {
+                let input = with_imports(NickelInput::from_call(call, input, 1, Some(base_dir))?);
+                json_to_value(&eval_to_json(&input, span)?, span)?
+            }
+        };
+
+        Ok(PipelineData::Value(merge_patch(target, patch, span), None))
+    }
+}
+
+/// Apply a merge patch to a value, as described by RFC 7386
+fn merge_patch(target: Value, patch: Value, span: Span) -> Value {
+    let Value::Record { val: patch, .. } = patch else {
+        return patch;
+    };
+    let mut target = match target {
+        Value::Record { val, .. } => val.into_owned(),
+        _ => Record::new(),
+    };
+
+    for (field, value) in patch.into_owned() {
+        if value.is_nothing() {
+            target.remove(&field);
+        } else if let Some(current) = target.get_mut(&field) {
+            *current = merge_patch(
+                std::mem::replace(current, Value::nothing(span)),
+                value,
+                span,
+            );
+        } else {
+            // Merging into nothing drops the null fields of nested records
+            target.push(field, merge_patch(Value::nothing(span), value, span));
+        }
+    }
+    Value::record(target, span)
+}
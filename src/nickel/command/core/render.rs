@@ -0,0 +1,145 @@
+use crate::NickelPlugin;
+use crate::nickel::{
+    convert::value_to_nickel,
+    input::{NickelInput, import_paths, no_imports, working_dir},
+    program::{eval_for_export, forbid_file_imports, new_program},
+};
+use nickel_lang_core::{cache::InputFormat, term::Term};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Record, Signature, SyntaxShape, Type, Value,
+};
+
+#[derive(Clone)]
+pub struct NickelRender;
+
+impl PluginCommand for NickelRender {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel render"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel render")
+            .input_output_types(vec![
+                (Type::record(), Type::String),
+                (Type::Nothing, Type::String),
+            ])
+            .required(
+                "template",
+                SyntaxShape::Filepath,
+                "Path to the Nickel template to render",
+            )
+            .named(
+                "import-path",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Directories to look up imports in, before those of `NICKEL_IMPORT_PATH`",
+                Some('I'),
+            )
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths and imports",
+                None,
+            )
+            .category(Category::Strings)
+    }
+
+    fn description(&self) -> &str {
+        "Render a Nickel template into text, with a record as its context"
+    }
+
+    fn extra_description(&self) -> &str {
+        "The template evaluates to a string or a symbolic string (like `sh-s%\"...\"%`), or to a \
+         function taking the piped record and returning one. Symbolic strings are joined into \
+         text, their interpolated numbers, booleans and enum tags converted to strings."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Render a script from a template of the form `fun ctx => sh-s%\"...\"%`",
+                example: "{ name: web, replicas: 3 } | nickel render deploy.sh.ncl",
+                result: None,
+            },
+            Example {
+                description: "Render a template that takes no context",
+                example: "nickel render motd.ncl | save motd",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+
+        let base_dir = working_dir(engine, call)?;
+        let template = base_dir.join(call.req::<String>(0)?);
+        if !template.is_file() {
+            return Err(LabeledError::new("Template not found")
+                .with_label(format!("Cannot read file '{}'", template.display()), span));
+        }
+
+        if no_imports(engine, call)? {
+            forbid_file_imports(&template, span)?;
+        }
+
+        let context = match input.into_value(span)? {
+            Value::Nothing { .. } => Value::record(Record::new(), span),
+            value => value,
+        };
+
+        // Templates cannot see bindings of the importing code, so the context is applied to them
+        let source = format!(
+            "let template = import {path} in\n\
+             let context = {context} in\n\
+             {RENDER}",
+            path = value_to_nickel(&Value::string(template.display().to_string(), span), span)?,
+            context = value_to_nickel(&context, span)?,
+        );
+        let input = NickelInput {
+            source,
+            path: None,
+            format: InputFormat::Nickel,
+            base_dir: Some(base_dir),
+            import_paths: import_paths(engine, call)?,
+            // The program imports the template, which is what rendering is, while the imports of
+            // the template itself were checked above
+            no_imports: false,
+            source_name: None,
+        };
+
+        let mut program = new_program(&input, span)?;
+        let term = eval_for_export(&mut program, span)?;
+        match term.as_ref() {
+            Term::Str(text) => Ok(PipelineData::Value(
+                Value::string(text.to_string(), span),
+                None,
+            )),
+            _ => Err(LabeledError::new("Template did not render to text")
+                .with_label("Expected a string or a symbolic string", span)),
+        }
+    }
+}
+
+/// Nickel code turning the `template` applied to the `context` into a string
+const RENDER: &str = r#"let rendered = if std.is_function template then template context else template in
+let fragment_to_string = fun fragment =>
+  if std.is_string fragment then fragment else std.to_string fragment
+in
+if std.is_string rendered then
+  rendered
+else if std.is_record rendered
+  && std.record.has_field "tag" rendered
+  && rendered.tag == 'SymbolicString then
+  std.string.join "" (std.array.map fragment_to_string rendered.fragments)
+else
+  std.fail_with "the template must be a string, a symbolic string or a function returning one"
+"#;
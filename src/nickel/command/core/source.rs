@@ -0,0 +1,62 @@
+use crate::NickelPlugin;
+use crate::nickel::values::NuNickelValue;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, LabeledError, PipelineData, Signature, Type, Value};
+
+#[derive(Clone)]
+pub struct NickelSource;
+
+impl PluginCommand for NickelSource {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel source"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel source")
+            .input_output_types(vec![(
+                Type::Custom("NickelValue".to_string().into()),
+                Type::String,
+            )])
+            .category(Category::Conversions)
+    }
+
+    fn description(&self) -> &str {
+        "Return the Nickel source a Nickel value was created from"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Only values created from Nickel code, such as the output of `nickel parse`, keep their \
+         source. The source is returned as it was given, comments and formatting included."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![Example {
+            description: "Recover the source of a parsed value",
+            example: r#""{ foo = 42 }" | nickel parse | nickel source"#,
+            result: Some(Value::test_string("{ foo = 42 }")),
+        }]
+    }
+
+    fn run(
+        &self,
+        plugin: &NickelPlugin,
+        _engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let value = input.into_value(span)?;
+
+        let source =
+            NuNickelValue::try_get_cached_source_code(plugin, &value)?.ok_or_else(|| {
+                LabeledError::new("Invalid input type").with_label(
+                    format!("Expected a Nickel value, found {}", value.get_type()),
+                    value.span(),
+                )
+            })?;
+
+        Ok(PipelineData::Value(Value::string(source, span), None))
+    }
+}
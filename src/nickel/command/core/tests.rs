@@ -1,12 +1,1918 @@
-use super::*;
-use crate::NickelPlugin;
-use nu_plugin::Plugin;
+use crate::nickel::command::test_support::{
+    eval, eval_content_type, eval_error, eval_metadata, temp_dir,
+};
+use crate::nickel::format::TableFormat;
+use nu_protocol::Value;
 
 #[test]
 fn test_nickel_eval_simple_record() {
-    let plugin = NickelPlugin::default();
-    let eval_cmd = NickelEval;
+    let result = eval(r#""{ foo = 42, bar = \"hello\" }" | nickel eval"#);
+    let record = result.as_record().unwrap();
 
-    // Test parsing a simple record
-    let source = "{ foo = 42, bar = \"hello\" }";
+    assert_eq!(record.get("foo"), Some(&Value::test_int(42)));
+    assert_eq!(record.get("bar"), Some(&Value::test_string("hello")));
+}
+
+#[test]
+fn test_nickel_eval_detects_data_formats() {
+    let json = eval(r#"'{"foo": 42}' | nickel eval"#);
+    assert_eq!(
+        json.as_record().unwrap().get("foo"),
+        Some(&Value::test_int(42))
+    );
+
+    let yaml = eval(r#""foo: 42\nbar: [1, 2]" | nickel eval"#);
+    assert_eq!(
+        yaml.as_record().unwrap().get("foo"),
+        Some(&Value::test_int(42))
+    );
+
+    let toml = eval(r#""[package]\nname = \"crate\"" | nickel eval"#);
+    let package = toml.as_record().unwrap().get("package").unwrap();
+    assert_eq!(
+        package.as_record().unwrap().get("name"),
+        Some(&Value::test_string("crate"))
+    );
+}
+
+#[test]
+fn test_nickel_eval_let_binding() {
+    let result = eval(r#""let x = 1 in { foo = x + 1 }" | nickel eval"#);
+    assert_eq!(
+        result.as_record().unwrap().get("foo"),
+        Some(&Value::test_int(2))
+    );
+}
+
+#[test]
+fn test_nickel_eval_imports_data_files() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/cargo-eval.ncl");
+    let result = eval(&format!("nickel eval '{path}'"));
+    let package = result.as_record().unwrap().get("package").unwrap();
+
+    assert_eq!(
+        package.as_record().unwrap().get("name"),
+        Some(&Value::test_string("workspace-crate"))
+    );
+}
+
+#[test]
+fn test_nickel_eval_imports_text_files() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/text-import.ncl");
+    let result = eval(&format!("nickel eval '{path}'"));
+    let record = result.as_record().unwrap();
+
+    assert!(
+        record
+            .get("script")
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .starts_with("#!/bin/sh\n")
+    );
+    assert_eq!(
+        record.get("motd"),
+        Some(&Value::test_string("Welcome to the build host.\n"))
+    );
+}
+
+#[test]
+fn test_nickel_eval_assign_and_override() {
+    let result = eval(
+        r#""{ port | Number, host = \"localhost\" }" | nickel eval --assign [port=8080] --override ['host="example.org"']"#,
+    );
+    let record = result.as_record().unwrap();
+
+    assert_eq!(record.get("port"), Some(&Value::test_int(8080)));
+    assert_eq!(record.get("host"), Some(&Value::test_string("example.org")));
+}
+
+#[test]
+fn test_nickel_eval_cwd() {
+    let examples = concat!(env!("CARGO_MANIFEST_DIR"), "/examples");
+
+    let result = eval(&format!("nickel eval text-import.ncl --cwd '{examples}'"));
+    assert!(result.as_record().unwrap().get("motd").is_some());
+
+    let result = eval(&format!(
+        r#""(import \"workspace-crate/Cargo.toml\").package.name" | nickel eval --cwd '{examples}'"#
+    ));
+    assert_eq!(result, Value::test_string("workspace-crate"));
+}
+
+#[test]
+fn test_nickel_eval_relative_to_caller_dir() {
+    let result = eval("nickel eval examples/text-import.ncl");
+    assert!(result.as_record().unwrap().get("motd").is_some());
+
+    let result = eval(r#""import \"motd.txt\"" | nickel eval --cwd examples"#);
+    assert_eq!(result, Value::test_string("Welcome to the build host.\n"));
+}
+
+#[test]
+fn test_nickel_explain() {
+    let result = eval("nickel explain nickel::contract");
+    let record = result.as_record().unwrap();
+    assert_eq!(
+        record.get("code"),
+        Some(&Value::test_string("nickel::contract"))
+    );
+    assert!(
+        !record
+            .get("explanation")
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .is_empty()
+    );
+
+    let result = eval("nickel explain missing-field");
+    assert_eq!(
+        result.as_record().unwrap().get("code"),
+        Some(&Value::test_string("nickel::missing-field"))
+    );
+
+    let result = eval("nickel explain");
+    assert!(result.as_list().unwrap().len() > 1);
+}
+
+#[test]
+fn test_nickel_eval_error_code() {
+    let error = eval_error(r#""{ port | Number = \"80\" }" | nickel eval"#);
+    assert_eq!(error.code.as_deref(), Some("nickel::contract"));
+}
+
+#[test]
+fn test_nickel_typecheck() {
+    let result = eval(r#""{ a = 1 }" | nickel typecheck"#);
+    assert!(result.as_list().unwrap().is_empty());
+
+    let dir = temp_dir();
+    std::fs::write(dir.join("ok.ncl"), "{ a : Number = 1 }").unwrap();
+    std::fs::write(dir.join("type.ncl"), "{\n  a : Number = \"1\"\n}").unwrap();
+    std::fs::write(dir.join("syntax.ncl"), "{ a = , b = }").unwrap();
+
+    let result = eval(&format!(
+        "nickel typecheck ok.ncl type.ncl syntax.ncl --cwd '{}'",
+        dir.display()
+    ));
+    let rows = result.as_list().unwrap();
+    let codes: Vec<_> = rows
+        .iter()
+        .map(|row| {
+            row.as_record()
+                .unwrap()
+                .get("code")
+                .unwrap()
+                .as_str()
+                .unwrap()
+        })
+        .collect();
+    assert!(codes.contains(&"nickel::typecheck"));
+    assert!(codes.contains(&"nickel::parse"));
+
+    let type_error = rows
+        .iter()
+        .map(|row| row.as_record().unwrap())
+        .find(|row| row.get("code") == Some(&Value::test_string("nickel::typecheck")))
+        .unwrap();
+    assert!(
+        type_error
+            .get("file")
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .ends_with("type.ncl")
+    );
+    assert_eq!(type_error.get("line"), Some(&Value::test_int(2)));
+}
+
+#[test]
+fn test_nickel_typecheck_source_name() {
+    let source = r#""{ a : Number = \"1\" }""#;
+    let file = |result: Value| {
+        result.as_list().unwrap()[0]
+            .as_record()
+            .unwrap()
+            .get("file")
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+    assert!(file(eval(&format!("{source} | nickel typecheck"))).ends_with("<input>"));
+    assert!(
+        file(eval(&format!(
+            "{source} | nickel typecheck --source-name deploy.ncl"
+        )))
+        .ends_with("deploy.ncl")
+    );
+}
+
+#[test]
+fn test_nickel_file_globs() {
+    let dir = temp_dir();
+    std::fs::create_dir_all(dir.join("b")).unwrap();
+    std::fs::create_dir_all(dir.join("a")).unwrap();
+    std::fs::write(dir.join("b/web.ncl"), "{ a : Number = \"1\" }").unwrap();
+    std::fs::write(dir.join("a/db.ncl"), "{ a : Number = \"1\" }").unwrap();
+    std::fs::write(dir.join("a/notes.txt"), "not nickel").unwrap();
+
+    let result = eval(&format!(
+        "nickel typecheck '**/*.ncl' --cwd '{}'",
+        dir.display()
+    ));
+    let files: Vec<_> = result
+        .as_list()
+        .unwrap()
+        .iter()
+        .map(|row| {
+            let file = row.as_record().unwrap().get("file").unwrap();
+            file.as_str()
+                .unwrap()
+                .strip_prefix(dir.to_str().unwrap())
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    assert_eq!(files, ["/a/db.ncl", "/b/web.ncl"]);
+
+    // A pattern gives a list of results, even for a single file
+    let result = eval(&format!(
+        "nickel eval 'b/*.ncl' --cwd '{}' --typecheck-only",
+        dir.display()
+    ));
+    assert_eq!(
+        result.as_list().unwrap()[0]
+            .as_record()
+            .unwrap()
+            .get("valid"),
+        Some(&Value::test_bool(false))
+    );
+
+    std::fs::write(dir.join("b/web.ncl"), "{ name = \"web\" }").unwrap();
+    let result = eval(&format!("nickel eval '*/*.ncl' --cwd '{}'", dir.display()));
+    let results = result.as_list().unwrap();
+    assert_eq!(results.len(), 2);
+    assert!(matches!(results[0], Value::Error { .. }));
+    assert_eq!(
+        results[1].as_record().unwrap().get("name"),
+        Some(&Value::test_string("web"))
+    );
+
+    let result = eval(&format!(
+        "nickel eval b/web.ncl 'a/*.ncl' --cwd '{}'",
+        dir.display()
+    ));
+    let results = result.as_list().unwrap();
+    assert_eq!(results.len(), 2);
+    assert!(matches!(results[1], Value::Error { .. }));
+
+    let error = eval_error(&format!(
+        "nickel get name '*/*.ncl' --cwd '{}'",
+        dir.display()
+    ));
+    assert_eq!(error.msg, "Ambiguous file argument");
+
+    let error = eval_error(&format!(
+        "nickel typecheck 'c/*.ncl' --cwd '{}'",
+        dir.display()
+    ));
+    assert_eq!(error.msg, "No files found");
+}
+
+#[test]
+fn test_nickel_lex() {
+    let result = eval(r#""let x = 1 in\n  x # done" | nickel lex"#);
+    let tokens: Vec<_> = result
+        .as_list()
+        .unwrap()
+        .iter()
+        .map(|token| token.as_record().unwrap())
+        .collect();
+    let kinds: Vec<_> = tokens
+        .iter()
+        .map(|token| token.get("kind").unwrap().as_str().unwrap())
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            "Let",
+            "Identifier",
+            "Equals",
+            "DecNumLiteral",
+            "In",
+            "Identifier"
+        ]
+    );
+
+    let last = tokens.last().unwrap();
+    assert_eq!(last.get("text"), Some(&Value::test_string("x")));
+    assert_eq!(last.get("start"), Some(&Value::test_int(15)));
+    assert_eq!(last.get("line"), Some(&Value::test_int(2)));
+    assert_eq!(last.get("column"), Some(&Value::test_int(3)));
+}
+
+#[test]
+fn test_nickel_typecheck_suggestions() {
+    let result = eval(r#""let length = 1 in lenght + 1" | nickel typecheck"#);
+    let row = result.as_list().unwrap()[0].as_record().unwrap().clone();
+    let suggestion = row.get("suggestion").unwrap().as_record().unwrap();
+    assert_eq!(
+        suggestion.get("replacement"),
+        Some(&Value::test_string("length"))
+    );
+    assert_eq!(suggestion.get("safe"), Some(&Value::test_bool(true)));
+
+    // A name bound elsewhere is not offered, as it is not in scope where the typo is
+    let result = eval(r#""let f = fun length => length in lenght + 1" | nickel typecheck"#);
+    let row = result.as_list().unwrap()[0].as_record().unwrap().clone();
+    let suggestion = row.get("suggestion").unwrap().as_record().unwrap();
+    assert_ne!(
+        suggestion.get("replacement"),
+        Some(&Value::test_string("length"))
+    );
+    assert_eq!(suggestion.get("safe"), Some(&Value::test_bool(false)));
+
+    let result = eval(r#""{ name = web }" | nickel typecheck"#);
+    let row = result.as_list().unwrap()[0].as_record().unwrap().clone();
+    let suggestion = row.get("suggestion").unwrap().as_record().unwrap();
+    assert_eq!(
+        suggestion.get("replacement"),
+        Some(&Value::test_string("\"web\""))
+    );
+    assert_eq!(suggestion.get("safe"), Some(&Value::test_bool(false)));
+}
+
+#[test]
+fn test_nickel_typecheck_apply_fixes() {
+    let dir = temp_dir();
+    let path = dir.join("typos.ncl");
+    std::fs::write(
+        &path,
+        "let port = 80 in\nlet host = \"localhost\" in\n{ url = \"%{hots}:%{std.to_string prot}\" }",
+    )
+    .unwrap();
+    let original = std::fs::read_to_string(&path).unwrap();
+
+    let result = eval(&format!(
+        "nickel typecheck '{}' --apply-fixes --dry-run",
+        path.display()
+    ));
+    let row = result.as_list().unwrap()[0].as_record().unwrap().clone();
+    assert_eq!(row.get("action"), Some(&Value::test_string("modify")));
+    let diff = row.get("diff").unwrap().as_str().unwrap();
+    assert!(diff.contains("+{ url = \"%{host}:%{std.to_string port}\" }"));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), original);
+
+    let result = eval(&format!(
+        "nickel typecheck '{}' --apply-fixes",
+        path.display()
+    ));
+    let rows = result.as_list().unwrap();
+    assert_eq!(rows.len(), 2);
+    assert!(
+        rows.iter()
+            .all(|row| row.as_record().unwrap().get("fixed") == Some(&Value::test_bool(true)))
+    );
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "let port = 80 in\nlet host = \"localhost\" in\n{ url = \"%{host}:%{std.to_string port}\" }"
+    );
+
+    // The backup keeps the file as it was before every round of fixes
+    std::fs::write(&path, &original).unwrap();
+    eval(&format!(
+        "nickel typecheck '{}' --apply-fixes --backup",
+        path.display()
+    ));
+    assert_eq!(
+        std::fs::read_to_string(dir.join("typos.ncl.orig")).unwrap(),
+        original
+    );
+}
+
+#[test]
+fn test_nickel_eval_missing_field_candidates() {
+    let error = eval_error(r#""{ port = 80, ports = [], host = \"a\" }.prot" | nickel eval"#);
+    assert_eq!(error.code.as_deref(), Some("nickel::missing-field"));
+    assert!(
+        error
+            .help
+            .unwrap()
+            .starts_with("did you mean `port` or `ports`?")
+    );
+}
+
+#[test]
+fn test_nickel_into_record() {
+    let result = eval(r#""{ foo = 42 }" | nickel parse | nickel into record"#);
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("foo"), Some(&Value::test_int(42)));
+
+    // Relative imports resolve next to the parsed file, not the working directory
+    let dir = temp_dir();
+    std::fs::create_dir(dir.join("conf")).unwrap();
+    std::fs::write(dir.join("conf/lib.ncl"), "{ port = 80 }").unwrap();
+    std::fs::write(
+        dir.join("conf/main.ncl"),
+        r#"{ server = import "lib.ncl" }"#,
+    )
+    .unwrap();
+    let result = eval(&format!(
+        "nickel parse '{}' | nickel into record",
+        dir.join("conf/main.ncl").display()
+    ));
+    let server = result.as_record().unwrap().get("server").unwrap();
+    assert_eq!(
+        server.as_record().unwrap().get("port"),
+        Some(&Value::test_int(80))
+    );
+
+    // Data is converted from its own format
+    let result = eval(r#""port: 80\nhosts: [a, b]" | nickel parse | nickel into record"#);
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("port"), Some(&Value::test_int(80)));
+    assert_eq!(
+        record.get("hosts"),
+        Some(&Value::test_list(vec![
+            Value::test_string("a"),
+            Value::test_string("b")
+        ]))
+    );
+
+    std::fs::write(dir.join("conf/server.toml"), "[server]\nport = 8080\n").unwrap();
+    let result = eval(&format!(
+        "nickel parse '{}' | nickel into record",
+        dir.join("conf/server.toml").display()
+    ));
+    let server = result.as_record().unwrap().get("server").unwrap();
+    assert_eq!(
+        server.as_record().unwrap().get("port"),
+        Some(&Value::test_int(8080))
+    );
+}
+
+#[test]
+fn test_nickel_parse_data() {
+    // Parsed data is read back in its own format, not as Nickel code
+    let result = eval(r#""port: 80\nhost: web" | nickel parse | nickel get port"#);
+    assert_eq!(result, Value::test_int(80));
+
+    let result = eval(r#"("port: 80\nhost: web" | nickel parse).host"#);
+    assert_eq!(result, Value::test_string("web"));
+
+    let result = eval(r#"("port: 80" | nickel parse) + { debug: true } | nickel into record"#);
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("port"), Some(&Value::test_int(80)));
+    assert_eq!(record.get("debug"), Some(&Value::test_bool(true)));
+}
+
+#[test]
+fn test_nickel_keep() {
+    // The kept value still works once the cache entry it came from is gone
+    let source = "{ foo = 42 }";
+    let result = eval(&format!(
+        "let kept = ({source:?} | nickel parse | nickel keep); let _ = (nickel cache clear --force); $kept | nickel source"
+    ));
+    assert_eq!(result, Value::test_string(source));
+
+    let result = eval(r#"{ kept: '{"JsonValue": {"a": 1}}' } | nickel keep | nickel into record"#);
+    assert_eq!(
+        result.as_record().unwrap().get("a"),
+        Some(&Value::test_int(1))
+    );
+
+    let error = eval_error(r#"{ kept: "{" } | nickel keep"#);
+    assert_eq!(error.msg, "Invalid kept Nickel value");
+}
+
+#[test]
+fn test_nickel_call() {
+    let result = eval(r#""fun a b => a + b" | nickel eval | nickel call 1 2"#);
+    assert_eq!(result, Value::test_int(3));
+
+    let result = eval(
+        r#""{ greet = fun name => \"hello %{name}\" }" | nickel eval --field greet | nickel call world"#,
+    );
+    assert_eq!(result, Value::test_string("hello world"));
+
+    // Partial application gives a function again
+    let result =
+        eval(r#""fun a b => { sum = a + b }" | nickel parse | nickel call 1 | nickel call 2"#);
+    assert_eq!(
+        result.as_record().unwrap().get("sum"),
+        Some(&Value::test_int(3))
+    );
+
+    let error = eval_error(r#""{ a = 1 }" | nickel parse | nickel call 1"#);
+    assert_eq!(error.msg, "Not a function");
+}
+
+#[test]
+fn test_nickel_source() {
+    let source = "# a comment\n{ foo = 42 }";
+    let result = eval(&format!("{source:?} | nickel parse | nickel source"));
+    assert_eq!(result, Value::test_string(source));
+}
+
+#[test]
+fn test_nickel_typeof() {
+    let result = eval(r#""fun x => x + 1" | nickel typeof"#);
+    assert_eq!(result, Value::test_string("Number -> Number"));
+
+    let result = eval(r#""let x = 1 in { a = x, b = \"b\" } # comment" | nickel typeof --tree"#);
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("kind"), Some(&Value::test_string("Record")));
+    let fields = record.get("fields").unwrap().as_record().unwrap();
+    let a = fields.get("a").unwrap().as_record().unwrap();
+    assert_eq!(a.get("kind"), Some(&Value::test_string("Number")));
+
+    let result = eval(r#""{ foo = 42 }" | nickel parse | nickel typeof"#);
+    assert_eq!(result, Value::test_string("{ foo : Number }"));
+
+    let error = eval_error(r#""1 + \"a\"" | nickel typeof"#);
+    assert_eq!(error.msg, "Nickel typechecking failed");
+}
+
+#[test]
+fn test_nickel_eval_no_contracts() {
+    let source = r#""let Port = std.contract.from_predicate (fun p => p > 1024) in { port | Port = 80, host | String = \"localhost\" }""#;
+    let error = eval_error(&format!("{source} | nickel eval"));
+    assert_eq!(error.msg, "Nickel evaluation failed");
+
+    let result = eval(&format!("{source} | nickel eval --no-contracts"));
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("port"), Some(&Value::test_int(80)));
+    let metadata = eval_metadata(&format!("{source} | nickel eval --no-contracts")).unwrap();
+    assert_eq!(
+        metadata
+            .custom
+            .get("warnings")
+            .unwrap()
+            .as_list()
+            .unwrap()
+            .len(),
+        1
+    );
+
+    // Record contracts still give their defaults
+    let result = eval(
+        r#""let S = { port | Number | default = 80 } in { server | S = {} }" | nickel eval --no-contracts"#,
+    );
+    assert_eq!(
+        result.as_record().unwrap().get("server"),
+        Some(&Value::test_record(nu_protocol::record! {
+            "port" => Value::test_int(80),
+        }))
+    );
+
+    let error = eval_error(r#""(1 + \"a\" : Number)" | nickel eval --no-contracts"#);
+    assert_eq!(error.msg, "Nickel typechecking failed");
+}
+
+#[test]
+fn test_nickel_eval_typecheck() {
+    let source = r#""let x = if true then 1 else \"one\" in x""#;
+    let result = eval(&format!("{source} | nickel eval"));
+    assert_eq!(result, Value::test_int(1));
+
+    let error = eval_error(&format!("{source} | nickel eval --typecheck enforce"));
+    assert_eq!(error.msg, "Nickel typechecking failed");
+
+    let error = eval_error(&format!("{source} | nickel eval --typecheck strict"));
+    assert_eq!(error.msg, "Unknown typecheck mode");
+}
+
+#[test]
+fn test_nickel_eval_typecheck_only() {
+    // Not evaluated, so the failing field and the missing one are not errors
+    let result = eval(
+        r#""{ port | Number, crash = std.fail_with \"boom\" }" | nickel eval --typecheck-only"#,
+    );
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("valid"), Some(&Value::test_bool(true)));
+    assert!(record.get("errors").unwrap().as_list().unwrap().is_empty());
+
+    let result = eval(r#""{ a : Number = \"1\" }" | nickel eval --typecheck-only"#);
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("valid"), Some(&Value::test_bool(false)));
+    let errors = record.get("errors").unwrap().as_list().unwrap();
+    assert_eq!(
+        errors[0].as_record().unwrap().get("code"),
+        Some(&Value::test_string("nickel::typecheck"))
+    );
+
+    let error = eval_error(r#""{ a = 1 }" | nickel eval --typecheck-only --output out.json"#);
+    assert_eq!(error.msg, "Nothing to write");
+}
+
+#[test]
+fn test_nickel_get() {
+    let result = eval(r#""{ a.b = 1, c = std.fail_with \"unused\" }" | nickel get a.b"#);
+    assert_eq!(result, Value::test_int(1));
+
+    let result =
+        eval(r#""{ labels.\"app.io/name\" = \"web\" }" | nickel get 'labels."app.io/name"'"#);
+    assert_eq!(result, Value::test_string("web"));
+
+    let result = eval(r#""{ a = { b = [1, 2] } }" | nickel parse | nickel get a.b"#);
+    assert_eq!(
+        result,
+        Value::test_list(vec![Value::test_int(1), Value::test_int(2)])
+    );
+
+    let error = eval_error(r#""{ a = 1 }" | nickel get b"#);
+    assert_eq!(error.msg, "Nickel evaluation failed");
+}
+
+#[test]
+fn test_nickel_open() {
+    let dir = temp_dir();
+    std::fs::write(
+        dir.join("config.ncl"),
+        r#"{ a.b = 1, c = std.fail_with "unused" }"#,
+    )
+    .unwrap();
+    std::fs::write(dir.join("typo.ncl"), "let x : Number = \"1\" in { x }").unwrap();
+
+    // Only the field asked for is evaluated
+    let result = eval(&format!(
+        "nickel open config.ncl --cwd '{}' | nickel get a.b",
+        dir.display()
+    ));
+    assert_eq!(result, Value::test_int(1));
+    let result = eval(&format!(
+        "(nickel open config.ncl --cwd '{}').a.b",
+        dir.display()
+    ));
+    assert_eq!(result, Value::test_int(1));
+
+    let error = eval_error(&format!("nickel open typo.ncl --cwd '{}'", dir.display()));
+    assert_eq!(error.msg, "Nickel typechecking failed");
+}
+
+#[test]
+fn test_nickel_grep_contract() {
+    let dir = temp_dir();
+    std::fs::write(
+        dir.join("deploy.ncl"),
+        r#"let k8s = { Port = std.number.Nat } in
+let Server = { port | k8s.Port, host | String } in
+{
+  web | Server = { port = 80, host = "web" },
+  workers = [{ port | k8s.Port = 9000 }],
+  debug_port | Number = 5005,
+}"#,
+    )
+    .unwrap();
+
+    let result = eval(&format!(
+        "nickel grep-contract Port deploy.ncl --cwd '{}'",
+        dir.display()
+    ));
+    let rows: Vec<_> = result
+        .as_list()
+        .unwrap()
+        .iter()
+        .map(|row| {
+            let row = row.as_record().unwrap();
+            (
+                row.get("path").unwrap().as_str().unwrap().to_string(),
+                row.get("value").unwrap().clone(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        rows,
+        [
+            ("web.port".to_string(), Value::test_int(80)),
+            ("workers.0.port".to_string(), Value::test_int(9000)),
+        ]
+    );
+
+    let result = eval(r#""{ a | Number = 1 }" | nickel grep-contract Port"#);
+    assert!(result.as_list().unwrap().is_empty());
+}
+
+#[test]
+fn test_nickel_eval_measure() {
+    let result = eval(
+        r#""std.array.range 0 1000 |> std.array.map (fun x => { id = x })" | nickel eval --measure"#,
+    );
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("value").unwrap().as_list().unwrap().len(), 1000);
+    assert!(record.get("wall_time").unwrap().as_duration().unwrap() > 0);
+    assert!(
+        record
+            .get("peak_memory")
+            .unwrap()
+            .as_filesize()
+            .unwrap()
+            .get()
+            > 0
+    );
+    #[cfg(unix)]
+    assert!(record.get("cpu_time").unwrap().as_duration().unwrap() > 0);
+}
+
+#[test]
+fn test_nickel_render() {
+    let dir = temp_dir();
+    std::fs::write(
+        dir.join("deploy.sh.ncl"),
+        r#"fun ctx => sh-s%"
+  kubectl scale deploy/%{ctx.name} --replicas=%{ctx.replicas}
+  echo %{std.string.uppercase ctx.name} %{ctx.verbose}
+"%"#,
+    )
+    .unwrap();
+    std::fs::write(dir.join("motd.ncl"), r#""Welcome to %{"host"}""#).unwrap();
+
+    let result = eval(&format!(
+        "{{ name: web, replicas: 3, verbose: true }} | nickel render deploy.sh.ncl --cwd '{}'",
+        dir.display()
+    ));
+    assert_eq!(
+        result,
+        Value::test_string("kubectl scale deploy/web --replicas=3\necho WEB true")
+    );
+
+    let result = eval(&format!("nickel render motd.ncl --cwd '{}'", dir.display()));
+    assert_eq!(result, Value::test_string("Welcome to host"));
+
+    std::fs::write(dir.join("record.ncl"), "{ a = 1 }").unwrap();
+    let error = eval_error(&format!(
+        "nickel render record.ncl --cwd '{}'",
+        dir.display()
+    ));
+    assert_eq!(error.msg, "Nickel evaluation failed");
+
+    // With imports disabled, the template is still imported, but may not import anything itself
+    let no_imports = "$env.config.plugins = { nickel: { no_imports: true } }";
+    let result = eval(&format!(
+        "{no_imports}; nickel render motd.ncl --cwd '{}'",
+        dir.display()
+    ));
+    assert_eq!(result, Value::test_string("Welcome to host"));
+
+    std::fs::write(dir.join("secret.ncl"), r#"import "secret.txt""#).unwrap();
+    let error = eval_error(&format!(
+        "{no_imports}; nickel render secret.ncl --cwd '{}'",
+        dir.display()
+    ));
+    assert_eq!(error.msg, "Imports are disabled");
+}
+
+#[test]
+fn test_nickel_eq() {
+    let dir = temp_dir();
+    std::fs::write(
+        dir.join("config.ncl"),
+        "let default_port = 80 in { server = { port = default_port, hosts = [\"a\", \"b\"] }, ratio = 1 }",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("expected.json"),
+        r#"{ "ratio": 1.0, "server": { "hosts": ["a", "b"], "port": 80 } }"#,
+    )
+    .unwrap();
+    let cwd = dir.display();
+
+    let result = eval(&format!("nickel eq expected.json config.ncl --cwd '{cwd}'"));
+    assert_eq!(result, Value::test_bool(true));
+
+    let result = eval(&format!(
+        r#""{{ ratio = 1, server = {{ port = 8080, hosts = [\"a\"] }} }}" | nickel parse | nickel eq config.ncl --cwd '{cwd}'"#
+    ));
+    assert_eq!(result, Value::test_bool(false));
+
+    let result = eval(&format!(
+        r#""{{ ratio = 1, server = {{ port = 80, hosts = [\"a\"] }} }}" | nickel eq config.ncl --divergence --cwd '{cwd}'"#
+    ));
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("equal"), Some(&Value::test_bool(false)));
+    assert_eq!(
+        record
+            .get("path")
+            .unwrap()
+            .to_expanded_string("", &Default::default()),
+        "$.server.hosts.1"
+    );
+    assert_eq!(record.get("left"), Some(&Value::test_nothing()));
+    assert_eq!(record.get("right"), Some(&Value::test_string("b")));
+}
+
+#[test]
+fn test_nickel_patch() {
+    let result = eval(
+        r#"{ a: { b: 1, c: 2 }, list: [1, 2], keep: x } | nickel patch { a: { b: null, d: { e: 3, f: null } }, list: [3] }"#,
+    );
+    let record = result.as_record().unwrap();
+    assert_eq!(record.columns().collect::<Vec<_>>(), ["a", "list", "keep"]);
+    let a = record.get("a").unwrap().as_record().unwrap();
+    assert_eq!(a.columns().collect::<Vec<_>>(), ["c", "d"]);
+    assert_eq!(
+        a.get("d")
+            .unwrap()
+            .as_record()
+            .unwrap()
+            .columns()
+            .collect::<Vec<_>>(),
+        ["e"]
+    );
+    assert_eq!(
+        record.get("list"),
+        Some(&Value::test_list(vec![Value::test_int(3)]))
+    );
+
+    let dir = temp_dir();
+    std::fs::write(
+        dir.join("config.ncl"),
+        "{ port = 80, tags = { env = \"dev\" } }",
+    )
+    .unwrap();
+    std::fs::write(dir.join("prod.json"), r#"{ "tags": { "env": "prod" } }"#).unwrap();
+    let result = eval(&format!(
+        "nickel patch prod.json config.ncl --cwd '{}'",
+        dir.display()
+    ));
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("port"), Some(&Value::test_int(80)));
+    let tags = record.get("tags").unwrap().as_record().unwrap();
+    assert_eq!(tags.get("env"), Some(&Value::test_string("prod")));
+}
+
+#[test]
+fn test_nickel_capabilities() {
+    let result = eval("nickel capabilities");
+    let record = result.as_record().unwrap();
+    assert_eq!(
+        record.get("version"),
+        Some(&Value::test_string(env!("CARGO_PKG_VERSION")))
+    );
+    let formats = record.get("formats").unwrap().as_record().unwrap();
+    assert!(
+        formats
+            .get("input")
+            .unwrap()
+            .as_list()
+            .unwrap()
+            .contains(&Value::test_string("yaml"))
+    );
+    // Every output format but Nickel source is accepted by `--format`
+    for format in formats.get("output").unwrap().as_list().unwrap() {
+        let format = format.as_str().unwrap();
+        if format != "nickel" {
+            // Table formats need a table, which TOML cannot write
+            let code = match TableFormat::from_name(format) {
+                Some(_) => "[{ foo = 42 }]",
+                None => "{ foo = 42 }",
+            };
+            eval(&format!(r#""{code}" | nickel eval --format {format}"#));
+        }
+    }
+    let features = record.get("features").unwrap().as_record().unwrap();
+    assert_eq!(
+        features.get("cpu_time"),
+        Some(&Value::test_bool(cfg!(unix)))
+    );
+}
+
+#[test]
+fn test_nickel_eval_format() {
+    let result = eval(r#""{ foo = 42 }" | nickel eval --format yaml"#);
+    assert_eq!(result, Value::test_string("foo: 42\n"));
+
+    let result = eval(r#"'{"foo": 42}' | nickel eval --format toml"#);
+    assert_eq!(result, Value::test_string("foo = 42\n"));
+
+    // The deprecated switches still work, and agree with `--format`
+    let result = eval(r#""{ foo = 42 }" | nickel eval --json --format json"#);
+    assert_eq!(result, Value::test_string("{\n  \"foo\": 42\n}"));
+
+    let error = eval_error(r#""{ foo = 42 }" | nickel eval --json --yaml"#);
+    assert_eq!(error.msg, "Conflicting output formats");
+    let labels: Vec<_> = error
+        .labels
+        .iter()
+        .map(|label| label.text.as_str())
+        .collect();
+    assert_eq!(labels, ["`--json` selects json", "`--yaml` selects yaml"]);
+
+    let error = eval_error(r#""{ foo = 42 }" | nickel eval --format nuon --toml"#);
+    assert_eq!(error.labels.len(), 2);
+
+    // Text and table formats conflict whichever flags select them
+    let error = eval_error(r#""[{ a = 1 }]" | nickel eval --yaml --format parquet"#);
+    assert_eq!(error.msg, "Conflicting output formats");
+    let labels: Vec<_> = error
+        .labels
+        .iter()
+        .map(|label| label.text.as_str())
+        .collect();
+    assert_eq!(
+        labels,
+        ["`--yaml` selects yaml", "`--format` selects parquet"]
+    );
+
+    let error = eval_error(r#""[{ a = 1 }]" | nickel eval --format json --to-dataframe"#);
+    let labels: Vec<_> = error
+        .labels
+        .iter()
+        .map(|label| label.text.as_str())
+        .collect();
+    assert_eq!(
+        labels,
+        ["`--format` selects json", "`--to-dataframe` selects arrow"]
+    );
+
+    let error = eval_error(r#""{ foo = 42 }" | nickel eval --format xml"#);
+    assert_eq!(error.msg, "Unknown output format");
+}
+
+#[test]
+fn test_nickel_eval_nuon() {
+    let result = eval(
+        r#""{ name = \"web\", ports = [80, 443], ratio = 0.5, extra = null, \"a b\" = true }" | nickel eval --format nuon"#,
+    );
+    let nuon = result.as_str().unwrap();
+    assert_eq!(
+        nuon::from_nuon(nuon, None).unwrap(),
+        eval(
+            r#""{ name = \"web\", ports = [80, 443], ratio = 0.5, extra = null, \"a b\" = true }" | nickel eval"#
+        )
+    );
+
+    let result = eval(r#"'{"foo": [1, 2]}' | nickel eval --format nuon"#);
+    assert_eq!(result, Value::test_string("{foo: [1, 2]}"));
+}
+
+#[test]
+fn test_nickel_eval_output() {
+    let dir = temp_dir();
+    let cwd = dir.display();
+
+    let error = eval_error(&format!(
+        r#""{{ foo = 42 }}" | nickel eval --output out/config.yaml --cwd '{cwd}'"#
+    ));
+    assert_eq!(error.msg, "Output directory does not exist");
+
+    let result = eval(&format!(
+        r#""{{ foo = 42 }}" | nickel eval --output out/config.yaml --create-dirs --dry-run --cwd '{cwd}'"#
+    ));
+    let row = result.as_list().unwrap()[0].as_record().unwrap().clone();
+    assert_eq!(row.get("action"), Some(&Value::test_string("create")));
+    assert!(!dir.join("out").exists());
+
+    let error = eval_error(&format!(
+        r#""{{ foo = 42 }}" | nickel eval --dry-run --cwd '{cwd}'"#
+    ));
+    assert_eq!(error.msg, "Nothing to preview");
+
+    let result = eval(&format!(
+        r#""{{ foo = 42 }}" | nickel eval --output out/config.yaml --create-dirs --cwd '{cwd}'"#
+    ));
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("format"), Some(&Value::test_string("yaml")));
+    assert_eq!(
+        std::fs::read_to_string(dir.join("out/config.yaml")).unwrap(),
+        "foo: 42\n"
+    );
+
+    let result = eval(&format!(
+        r#""{{ foo = 42 }}" | nickel eval --output out/config.txt --format json --cwd '{cwd}'"#
+    ));
+    assert_eq!(
+        result.as_record().unwrap().get("size"),
+        Some(&Value::test_filesize(15))
+    );
+
+    let error = eval_error(&format!(
+        r#""{{ foo = 42 }}" | nickel eval --output out/config.txt --cwd '{cwd}'"#
+    ));
+    assert_eq!(error.msg, "Unknown output format");
+
+    // A replaced file keeps its permissions, and no temporary or backup file is left behind
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let config = dir.join("out/config.yaml");
+        std::fs::set_permissions(&config, std::fs::Permissions::from_mode(0o600)).unwrap();
+        eval(&format!(
+            r#""{{ foo = 43 }}" | nickel eval --output out/config.yaml --cwd '{cwd}'"#
+        ));
+        assert_eq!(std::fs::read_to_string(&config).unwrap(), "foo: 43\n");
+        let mode = std::fs::metadata(&config).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(std::fs::read_dir(dir.join("out")).unwrap().count(), 2);
+    }
+}
+
+#[test]
+fn test_nickel_eval_multi_doc() {
+    let result = eval(
+        r#""[{ kind = \"Service\" }, { kind = \"Deployment\", replicas = 2 }]" | nickel eval --format yaml --multi-doc"#,
+    );
+    assert_eq!(
+        result,
+        Value::test_string("---\nkind: Service\n---\nkind: Deployment\nreplicas: 2\n")
+    );
+
+    let result = eval(r#"'[{"a": 1}, [2]]' | nickel eval --format yaml --multi-doc"#);
+    assert_eq!(result, Value::test_string("---\na: 1\n---\n- 2\n"));
+
+    let error = eval_error(r#""{ a = 1 }" | nickel eval --format yaml --multi-doc"#);
+    assert_eq!(error.msg, "Cannot split the result into documents");
+
+    let error = eval_error(r#""[1]" | nickel eval --format json --multi-doc"#);
+    assert_eq!(error.msg, "Multiple documents need YAML output");
+}
+
+#[test]
+fn test_nickel_eval_field() {
+    let result = eval(
+        r#""{ server.port = 8080, broken = std.fail_with \"unused\" }" | nickel eval --field server.port"#,
+    );
+    assert_eq!(result, Value::test_int(8080));
+
+    let result =
+        eval(r#""{ server = { port = 8080 } }" | nickel eval --field server --format json"#);
+    assert_eq!(result, Value::test_string("{\n  \"port\": 8080\n}"));
+
+    let result = eval(r#"'{"a": {"b": [1]}}' | nickel eval --field a.b"#);
+    assert_eq!(result, Value::test_list(vec![Value::test_int(1)]));
+}
+
+#[test]
+fn test_nickel_eval_arg() {
+    let result = eval(
+        r#""{ url = \"http://%{host}:%{std.to_string port}\", labels = tags }" | nickel eval --arg [host=localhost port=8080 'tags=[a, b]']"#,
+    );
+    let record = result.as_record().unwrap();
+    assert_eq!(
+        record.get("url"),
+        Some(&Value::test_string("http://localhost:8080"))
+    );
+    assert_eq!(
+        record.get("labels"),
+        Some(&Value::test_list(vec![
+            Value::test_string("a"),
+            Value::test_string("b")
+        ]))
+    );
+
+    let error = eval_error(r#""1" | nickel eval --arg [port]"#);
+    assert_eq!(error.msg, "Invalid argument");
+    let error = eval_error(r#""1" | nickel eval --arg ['a.b=1']"#);
+    assert_eq!(error.msg, "Invalid argument");
+}
+
+#[test]
+fn test_nickel_convert() {
+    let result = eval(r#""name: web\nports: [80, 443]" | nickel convert --to json"#);
+    assert_eq!(
+        result,
+        Value::test_string("{\n  \"name\": \"web\",\n  \"ports\": [\n    80,\n    443\n  ]\n}")
+    );
+
+    let dir = temp_dir();
+    std::fs::write(
+        dir.join("schema.ncl"),
+        "{ name | String, replicas | Number | default = 1 }",
+    )
+    .unwrap();
+    let result = eval(&format!(
+        r#"'{{"name": "web"}}' | nickel convert --to toml --contract schema.ncl --cwd '{}'"#,
+        dir.display()
+    ));
+    assert_eq!(result, Value::test_string("name = \"web\"\nreplicas = 1\n"));
+
+    let error = eval_error(&format!(
+        r#"'{{"name": 1}}' | nickel convert --to toml --contract schema.ncl --cwd '{}'"#,
+        dir.display()
+    ));
+    assert_eq!(error.msg, "Nickel evaluation failed");
+
+    let error = eval_error(r#""{ a = 1 }" | nickel convert --to json"#);
+    assert_eq!(error.msg, "Cannot convert Nickel code");
+}
+
+#[test]
+fn test_nickel_eval_env() {
+    let result = eval(
+        r#"$env.APP_NAME = "web"; $env.SECRET = "hidden"; "env" | nickel eval --env [APP_NAME UNSET]"#,
+    );
+    assert_eq!(
+        result,
+        Value::test_record(nu_protocol::record! {
+            "APP_NAME" => Value::test_string("web"),
+        })
+    );
+
+    let result = eval(r#"$env.APP_NAME = "web"; "env.APP_NAME" | nickel eval --env-all"#);
+    assert_eq!(result, Value::test_string("web"));
+
+    let error = eval_error(r#""env" | nickel eval"#);
+    assert_eq!(error.msg, "Nickel evaluation failed");
+}
+
+#[test]
+fn test_nickel_eval_table_columns_follow_contract() {
+    let result = eval(
+        r#""let Row = { port | Number, kind | [| 'tcp, 'udp |], note | optional | String } in [{ port = 80, kind = 'tcp }, { port = 8.5, kind = 'udp, note = \"alt\" }] | Array Row" | nickel eval"#,
+    );
+    assert_eq!(
+        result,
+        Value::test_list(vec![
+            Value::test_record(nu_protocol::record! {
+                "kind" => Value::test_string("tcp"),
+                "port" => Value::test_float(80.0),
+                "note" => Value::test_nothing(),
+            }),
+            Value::test_record(nu_protocol::record! {
+                "kind" => Value::test_string("udp"),
+                "note" => Value::test_string("alt"),
+                "port" => Value::test_float(8.5),
+            }),
+        ])
+    );
+
+    let result = eval(r#""[{ a = 1 }, { a = 2.5 }]" | nickel eval"#);
+    assert_eq!(
+        result,
+        Value::test_list(vec![
+            Value::test_record(nu_protocol::record! { "a" => Value::test_int(1) }),
+            Value::test_record(nu_protocol::record! { "a" => Value::test_float(2.5) }),
+        ])
+    );
+}
+
+#[test]
+fn test_nickel_eval_import_path() {
+    let dir = temp_dir();
+    std::fs::create_dir(dir.join("lib")).unwrap();
+    std::fs::write(dir.join("lib/util.ncl"), "{ double = fun x => 2 * x }").unwrap();
+    let program = r#""(import \"util.ncl\").double 21""#;
+
+    let result = eval(&format!(
+        "{program} | nickel eval -I [lib] --cwd '{}'",
+        dir.display()
+    ));
+    assert_eq!(result, Value::test_int(42));
+
+    let result = eval(&format!(
+        "$env.NICKEL_IMPORT_PATH = '{}'; {program} | nickel eval",
+        dir.join("lib").display()
+    ));
+    assert_eq!(result, Value::test_int(42));
+
+    let error = eval_error(&format!(
+        "{program} | nickel eval --cwd '{}'",
+        dir.display()
+    ));
+    assert_eq!(error.msg, "Nickel evaluation failed");
+}
+
+#[test]
+fn test_nickel_eval_arrays() {
+    let program = r#""{ rows = [{ a = 1 }, { b = 2 }] }""#;
+
+    let result = eval(&format!("{program} | nickel eval --arrays pad"));
+    let rows = result.as_record().unwrap().get("rows").unwrap();
+    assert_eq!(
+        rows,
+        &Value::test_list(vec![
+            Value::test_record(nu_protocol::record! {
+                "a" => Value::test_int(1),
+                "b" => Value::test_nothing(),
+            }),
+            Value::test_record(nu_protocol::record! {
+                "b" => Value::test_int(2),
+                "a" => Value::test_nothing(),
+            }),
+        ])
+    );
+
+    let error = eval_error(&format!("{program} | nickel eval --arrays error"));
+    assert_eq!(error.msg, "Heterogeneous array");
+    assert_eq!(
+        error.labels[0].text,
+        "Element 0 of the array at `rows` lacks `b`"
+    );
+
+    let error = eval_error(r#"'[{"a": 1}, 2]' | nickel eval --arrays error"#);
+    assert_eq!(
+        error.labels[0].text,
+        "The array at the result mixes records with other values"
+    );
+
+    let result = eval(r#""[1, \"a\"]" | nickel eval --arrays error"#);
+    assert_eq!(
+        result,
+        Value::test_list(vec![Value::test_int(1), Value::test_string("a")])
+    );
+}
+
+#[test]
+fn test_nickel_parse_keeps_file_imports() {
+    let dir = temp_dir();
+    std::fs::create_dir(dir.join("app")).unwrap();
+    std::fs::write(dir.join("app/util.ncl"), "{ port = 8080 }").unwrap();
+    std::fs::write(
+        dir.join("app/main.ncl"),
+        r#"{ server = import "util.ncl" }"#,
+    )
+    .unwrap();
+
+    let parsed = format!("nickel parse app/main.ncl --cwd '{}'", dir.display());
+    let result = eval(&format!("{parsed} | nickel get server.port"));
+    assert_eq!(result, Value::test_int(8080));
+
+    let result = eval(&format!("{parsed} | nickel typeof"));
+    assert!(result.as_str().unwrap().contains("server"));
+}
+
+#[test]
+fn test_nickel_eval_key_order() {
+    let source = r#""{ zone = 1, name = { b = 2, a = 3 }, id = 4 }""#;
+    let columns = |result: Value| {
+        let record = result.as_record().unwrap().clone();
+        let name = record.get("name").unwrap().as_record().unwrap().clone();
+        (
+            record.columns().cloned().collect::<Vec<_>>(),
+            name.columns().cloned().collect::<Vec<_>>(),
+        )
+    };
+    assert_eq!(
+        columns(eval(&format!("{source} | nickel eval --sort-keys"))),
+        (
+            vec!["id".into(), "name".into(), "zone".into()],
+            vec!["a".into(), "b".into()]
+        )
+    );
+    assert_eq!(
+        columns(eval(&format!("{source} | nickel eval --key-order source"))),
+        (
+            vec!["zone".into(), "name".into(), "id".into()],
+            vec!["b".into(), "a".into()]
+        )
+    );
+
+    let error = eval_error(&format!(
+        "{source} | nickel eval --sort-keys --key-order source"
+    ));
+    assert_eq!(error.msg, "Conflicting key orders");
+    let error = eval_error(&format!("{source} | nickel eval --key-order random"));
+    assert_eq!(error.msg, "Unknown key order");
+}
+
+#[test]
+fn test_nickel_eval_enums() {
+    let source = r#""{ level = 'Debug, listen = 'Port 8080 }""#;
+    let result = eval(&format!("{source} | nickel eval"));
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("level"), Some(&Value::test_string("Debug")));
+    assert_eq!(
+        record.get("listen"),
+        Some(&Value::test_record(nu_protocol::record! {
+            "tag" => Value::test_string("Port"),
+            "value" => Value::test_int(8080),
+        }))
+    );
+
+    let result = eval(&format!("{source} | nickel eval --enums keyed"));
+    assert_eq!(
+        result.as_record().unwrap().get("listen"),
+        Some(&Value::test_record(nu_protocol::record! {
+            "Port" => Value::test_int(8080),
+        }))
+    );
+
+    let error = eval_error(&format!("{source} | nickel eval --enums tagged"));
+    assert_eq!(error.msg, "Unknown enum policy");
+}
+
+#[test]
+fn test_nickel_eval_number_mode() {
+    let source =
+        r#""{ price = 0.1, half = 0.5, big = 100000000000000000000, third = 1 / 3, count = 3 }""#;
+    let result = eval(&format!("{source} | nickel eval"));
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("price"), Some(&Value::test_float(0.1)));
+    assert_eq!(record.get("big"), Some(&Value::test_float(1e20)));
+    assert_eq!(record.get("count"), Some(&Value::test_int(3)));
+
+    let result = eval(&format!("{source} | nickel eval --number-mode string"));
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("price"), Some(&Value::test_string("0.1")));
+    assert_eq!(record.get("half"), Some(&Value::test_float(0.5)));
+    assert_eq!(
+        record.get("big"),
+        Some(&Value::test_string("100000000000000000000"))
+    );
+    assert_eq!(record.get("third"), Some(&Value::test_string("1/3")));
+    assert_eq!(record.get("count"), Some(&Value::test_int(3)));
+
+    let result = eval(&format!("{source} | nickel eval --number-mode float"));
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("count"), Some(&Value::test_float(3.0)));
+
+    let error = eval_error(&format!("{source} | nickel eval --number-mode exact"));
+    assert_eq!(error.msg, "Unknown number mode");
+}
+
+#[test]
+fn test_nickel_eval_depth() {
+    let result = eval(r#""{ a = { b = [1, { c = 2 }] }, d = 3 }" | nickel eval --depth 2"#);
+    assert_eq!(
+        result,
+        Value::test_record(nu_protocol::record! {
+            "a" => Value::test_record(nu_protocol::record! {
+                "b" => Value::test_string("[...]"),
+            }),
+            "d" => Value::test_int(3),
+        })
+    );
+
+    let result = eval(r#"'{"a": {"b": 1}}' | nickel eval --depth 0"#);
+    assert_eq!(result, Value::test_string("{...}"));
+}
+
+#[test]
+fn test_nickel_eval_max_nesting() {
+    let nested = |levels: usize| {
+        format!(
+            r#""{{ deep = std.array.fold_left (fun acc _ => [acc]) 0 (std.array.range 0 {levels}) }}""#
+        )
+    };
+    // Deeper than the stack of the plugin allowed when converting by recursion
+    let result = eval(&format!("{} | nickel eval --depth 1", nested(3000)));
+    assert_eq!(
+        result,
+        Value::test_record(nu_protocol::record! {
+            "deep" => Value::test_string("[...]"),
+        })
+    );
+
+    let error = eval_error(&format!("{} | nickel eval --max-nesting 10", nested(20)));
+    assert_eq!(error.msg, "Value nested too deeply");
+
+    let dir = temp_dir();
+    std::fs::write(dir.join("nested.json"), "[[[[1]]]]").unwrap();
+    let error = eval_error(&format!(
+        "nickel eval nested.json --max-nesting 3 --cwd '{}'",
+        dir.display()
+    ));
+    assert_eq!(error.msg, "Value nested too deeply");
+}
+
+#[test]
+fn test_nickel_eval_no_imports() {
+    let error =
+        eval_error(r#""{ a = 1, b = import \"secrets.json\" }" | nickel eval --no-imports"#);
+    assert_eq!(error.msg, "Imports are disabled");
+    assert_eq!(
+        error.labels[0].text,
+        r#"The program imports "secrets.json""#
+    );
+
+    let result = eval(r#""{ a = std.string.length \"abc\" }" | nickel eval --no-imports"#);
+    assert_eq!(
+        result.as_record().unwrap().get("a"),
+        Some(&Value::test_int(3))
+    );
+}
+
+#[test]
+fn test_nickel_eval_stdlib_filter() {
+    let source = r#""{ a = std.string.length \"abc\", b = std.array.length [1] }""#;
+    let result = eval(&format!(
+        "{source} | nickel eval --stdlib-allow [string std.array.length]"
+    ));
+    assert_eq!(
+        result.as_record().unwrap().get("a"),
+        Some(&Value::test_int(3))
+    );
+
+    let error = eval_error(&format!("{source} | nickel eval --stdlib-allow [string]"));
+    assert_eq!(error.msg, "Standard library restricted");
+    assert_eq!(
+        error.labels[0].text,
+        "`std.array.length` is not under an allowed name"
+    );
+
+    let error = eval_error(&format!(
+        "{source} | nickel eval --stdlib-deny [std.string]"
+    ));
+    assert_eq!(
+        error.labels[0].text,
+        "`std.string.length` is under the denied `std.string`"
+    );
+
+    let error =
+        eval_error(r#""let s = std in s.trace \"x\" 1" | nickel eval --stdlib-deny [trace]"#);
+    assert_eq!(error.labels[0].text, "`std` holds the denied `std.trace`");
+}
+
+#[test]
+fn test_nickel_eval_prelude() {
+    let dir = temp_dir();
+    std::fs::write(dir.join("helpers.ncl"), "{ double = fun x => x * 2 }").unwrap();
+
+    let result = eval(&format!(
+        r#""{{ a = prelude.double 21 }}" | nickel eval --prelude helpers.ncl --no-imports --cwd '{}'"#,
+        dir.display()
+    ));
+    assert_eq!(
+        result.as_record().unwrap().get("a"),
+        Some(&Value::test_int(42))
+    );
+
+    let error = eval_error(&format!(
+        r#""{{ a = import \"helpers.ncl\" }}" | nickel eval --prelude helpers.ncl --no-imports --cwd '{}'"#,
+        dir.display()
+    ));
+    assert_eq!(error.msg, "Imports are disabled");
+}
+
+#[test]
+fn test_nickel_eval_stamp() {
+    let result = eval(r#""{ a = 1, meta.build = null }" | nickel eval --stamp meta.build"#);
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("a"), Some(&Value::test_int(1)));
+    let meta = record.get("meta").unwrap().as_record().unwrap();
+    let build = meta.get("build").unwrap().as_record().unwrap();
+    assert_eq!(
+        build.get("plugin_version"),
+        Some(&Value::test_string(env!("CARGO_PKG_VERSION")))
+    );
+    assert!(build.get("rendered_at").unwrap().as_str().is_ok());
+
+    let dir = temp_dir();
+    let result = eval(&format!(
+        r#""{{ a = 1 }}" | nickel eval --stamp build --cwd '{}'"#,
+        dir.display()
+    ));
+    let build = result.as_record().unwrap().get("build").unwrap();
+    assert_eq!(
+        build.as_record().unwrap().get("commit"),
+        Some(&Value::test_nothing())
+    );
+
+    let error = eval_error(r#"'{"a": 1}' | nickel eval --stamp build"#);
+    assert_eq!(error.msg, "Cannot customize data input");
+}
+
+#[test]
+fn test_nickel_eval_cycle() {
+    let error = eval_error(r#""let rec x = { a = { next = x } } in x" | nickel eval"#);
+    assert_eq!(error.msg, "Cyclic value");
+    assert_eq!(
+        error.labels[0].text,
+        "`a.next.a` contains a record it is in"
+    );
+
+    let result = eval(r#""let rec x = { next | not_exported = x, value = 1 } in x" | nickel eval"#);
+    assert_eq!(
+        result.as_record().unwrap().get("value"),
+        Some(&Value::test_int(1))
+    );
+}
+
+#[test]
+fn test_nickel_eval_interrupted() {
+    use crate::nickel::program::{detached_evaluations, interruptible};
+    use nu_protocol::{Signals, Span};
+    use std::sync::{Arc, atomic::AtomicBool};
+    use std::time::Duration;
+
+    let running = Signals::new(Arc::new(AtomicBool::new(false)));
+    let result = interruptible(&running, Span::test_data(), || 42);
+    assert_eq!(result.unwrap(), 42);
+
+    let interrupted = Signals::new(Arc::new(AtomicBool::new(true)));
+    let result = interruptible(&interrupted, Span::test_data(), || {
+        std::thread::sleep(Duration::from_secs(5));
+    });
+    assert_eq!(result.unwrap_err().msg, "Operation interrupted");
+    // The abandoned thread is counted until it finishes
+    assert!(detached_evaluations() >= 1);
+}
+
+#[test]
+fn test_nickel_top_level_type() {
+    let shape = |code: &str| eval(&format!("{code} | nickel top-level-type"));
+    assert_eq!(shape(r#""{ a = 1 }""#), Value::test_string("record"));
+    assert_eq!(shape(r#""[1, 2] @ [3]""#), Value::test_string("array"));
+    assert_eq!(shape(r#""fun x => x""#), Value::test_string("function"));
+    assert_eq!(shape(r#""Number""#), Value::test_string("contract"));
+    assert_eq!(shape(r#""1 + 1""#), Value::test_string("scalar"));
+    assert_eq!(shape(r#"'[{"a": 1}]'"#), Value::test_string("array"));
+
+    // Fields are left unevaluated, so a failing field does not fail the probe
+    assert_eq!(
+        shape(r#""{ a = std.fail_with \"boom\" }""#),
+        Value::test_string("record")
+    );
+}
+
+#[test]
+fn test_nickel_eval_max_memory() {
+    let error = eval_error(
+        r#""let rec f = fun n => 1 + f (n + 1) in f 0" | nickel eval --max-memory 20MB"#,
+    );
+    assert_eq!(error.msg, "Memory limit exceeded");
+
+    // Within the limit, the result comes back from the worker process, functions included
+    let result = eval(r#""{ a = 1 }" | nickel eval --max-memory 100MB"#);
+    assert_eq!(
+        result.as_record().unwrap().get("a"),
+        Some(&Value::test_int(1))
+    );
+    let result = eval(r#""fun a b => a + b" | nickel eval --max-memory 100MB | nickel call 1 2"#);
+    assert_eq!(result, Value::test_int(3));
+}
+
+#[test]
+fn test_nickel_eval_max_stack() {
+    // Deeply nested values recurse deeply when they are exported
+    let nested =
+        "let rec nest = fun n => if n == 0 then {} else { a = nest (n - 1) } in nest 100000";
+    let error = eval_error(&format!("{nested:?} | nickel eval --max-stack 256KB"));
+    assert_eq!(error.msg, "Stack limit exceeded");
+
+    let result = eval(r#""{ a = 1 }" | nickel eval --max-stack 8MB"#);
+    assert_eq!(
+        result.as_record().unwrap().get("a"),
+        Some(&Value::test_int(1))
+    );
+}
+
+#[cfg(feature = "dataframe")]
+#[test]
+fn test_nickel_eval_to_dataframe() {
+    use arrow_array::{Array, Float64Array, StringArray};
+    use arrow_ipc::reader::FileReader;
+
+    let result = eval(
+        r#""[{ host = \"a\", cpus = 2 }, { host = \"b\", cpus = 1.5, zone = \"eu\" }]" | nickel eval --to-dataframe"#,
+    );
+    let bytes = result.as_binary().unwrap().to_vec();
+    let batch = FileReader::try_new(std::io::Cursor::new(bytes), None)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+
+    let schema = batch.schema();
+    let names: Vec<_> = schema.fields().iter().map(|field| field.name()).collect();
+    assert_eq!(names, ["host", "cpus", "zone"]);
+    let cpus = batch
+        .column(1)
+        .as_any()
+        .downcast_ref::<Float64Array>()
+        .unwrap();
+    assert_eq!(cpus.values(), &[2.0, 1.5]);
+    let zone = batch
+        .column(2)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert!(zone.is_null(0));
+    assert_eq!(zone.value(1), "eu");
+
+    let error = eval_error(r#""[{ a = { b = 1 } }]" | nickel eval --to-dataframe"#);
+    assert_eq!(error.msg, "Not a flat table");
+}
+
+#[cfg(not(feature = "dataframe"))]
+#[test]
+fn test_nickel_eval_to_dataframe() {
+    let error = eval_error(r#""[{ a = 1 }]" | nickel eval --to-dataframe"#);
+    assert_eq!(error.msg, "arrow output is not available");
+}
+
+#[cfg(feature = "parquet")]
+#[test]
+fn test_nickel_eval_parquet() {
+    use arrow_array::{Array, Int64Array};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    let dir = temp_dir();
+    let path = dir.join("hosts.parquet");
+    let result = eval(&format!(
+        r#""[{{ host = \"a\", cpus = 2 }}, {{ host = \"b\" }}]" | nickel eval --output '{}'"#,
+        path.display()
+    ));
+    assert_eq!(
+        result.as_record().unwrap().get("format"),
+        Some(&Value::test_string("parquet"))
+    );
+
+    let file = std::fs::File::open(&path).unwrap();
+    let batch = ParquetRecordBatchReaderBuilder::try_new(file)
+        .unwrap()
+        .build()
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    let cpus = batch
+        .column_by_name("cpus")
+        .unwrap()
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap();
+    assert_eq!(cpus.value(0), 2);
+    assert!(cpus.is_null(1));
+}
+
+#[cfg(not(feature = "parquet"))]
+#[test]
+fn test_nickel_eval_parquet() {
+    let error = eval_error(r#""[{ a = 1 }]" | nickel eval --format parquet"#);
+    assert_eq!(error.msg, "parquet output is not available");
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_nickel_eval_sqlite() {
+    let dir = temp_dir();
+    let path = dir.join("fleet.db");
+    let source =
+        r#""[{ name = \"web\", port = 80 }, { name = \"db\", port = 5432.5, tls = true }]""#;
+    let command = format!(
+        "{source} | nickel eval --sqlite '{}' --table services",
+        path.display()
+    );
+    // Exporting again replaces the rows rather than adding to them
+    eval(&command);
+    let result = eval(&command);
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("table"), Some(&Value::test_string("services")));
+    assert_eq!(record.get("rows"), Some(&Value::test_int(2)));
+
+    let connection = rusqlite::Connection::open(&path).unwrap();
+    let rows: Vec<(String, f64, Option<bool>)> = connection
+        .prepare("SELECT name, port, tls FROM services ORDER BY port")
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        rows,
+        [
+            ("web".to_string(), 80.0, None),
+            ("db".to_string(), 5432.5, Some(true))
+        ]
+    );
+
+    let error = eval_error(&format!(
+        r#""[{{ a = {{ b = 1 }} }}]" | nickel eval --sqlite '{}' --table nested"#,
+        path.display()
+    ));
+    assert_eq!(error.msg, "Not a flat table");
+}
+
+#[cfg(not(feature = "sqlite"))]
+#[test]
+fn test_nickel_eval_sqlite() {
+    let error = eval_error(r#""[{ a = 1 }]" | nickel eval --sqlite fleet.db --table services"#);
+    assert_eq!(error.msg, "sqlite output is not available");
+}
+
+#[test]
+fn test_nickel_eval_sqlite_flags() {
+    let error = eval_error(r#""[{ a = 1 }]" | nickel eval --sqlite fleet.db"#);
+    assert_eq!(error.msg, "Missing table name");
+
+    let error = eval_error(r#""[{ a = 1 }]" | nickel eval --table services"#);
+    assert_eq!(error.msg, "Missing database");
+
+    let error =
+        eval_error(r#""[{ a = 1 }]" | nickel eval --sqlite fleet.db --table t --format json"#);
+    assert_eq!(error.msg, "Conflicting output formats");
+
+    let error = eval_error(r#""[{ a = 1 }]" | nickel eval --sqlite fleet.db --table t --dry-run"#);
+    assert_eq!(error.msg, "Cannot preview a database");
+}
+
+#[test]
+fn test_nickel_eval_list_of_snippets() {
+    let result = eval(r#"["1 + 1", "{ foo = 42 }", "1 + \"a\"", "foo: bar"] | nickel eval"#);
+    let results = result.as_list().unwrap();
+    assert_eq!(results.len(), 4);
+    assert_eq!(results[0], Value::test_int(2));
+    assert_eq!(
+        results[1].as_record().unwrap().get("foo"),
+        Some(&Value::test_int(42))
+    );
+    assert!(matches!(results[2], Value::Error { .. }));
+    assert_eq!(
+        results[3].as_record().unwrap().get("foo"),
+        Some(&Value::test_string("bar"))
+    );
+
+    let result = eval(r#"["{ a = 1 }", "[true]"] | nickel eval --format json"#);
+    assert_eq!(
+        result,
+        Value::test_list(vec![
+            Value::test_string("{\n  \"a\": 1\n}"),
+            Value::test_string("[\n  true\n]"),
+        ])
+    );
+
+    let error = eval_error(r#"["{ a = 1 }"] | nickel eval --output out.json"#);
+    assert_eq!(error.msg, "Cannot write many results");
+
+    // Results keep the order of the snippets however many are evaluated at once
+    let snippets = r#"["std.array.fold_left (+) 0 (std.array.range 0 100000)", "1", "2", "3"]"#;
+    let parallel = eval(&format!("{snippets} | nickel eval --jobs 4"));
+    let sequential = eval(&format!("{snippets} | nickel eval --jobs 1 --nice"));
+    assert_eq!(parallel, sequential);
+    assert_eq!(
+        parallel.as_list().unwrap()[1..],
+        [Value::test_int(1), Value::test_int(2), Value::test_int(3)]
+    );
+}
+
+#[test]
+fn test_nickel_eval_binary_input() {
+    // `1 + 1` as UTF-8
+    let result = eval("0x[31 20 2b 20 31] | nickel eval");
+    assert_eq!(result, Value::test_int(2));
+
+    let error = eval_error("0x[ff fe 00] | nickel eval");
+    assert_eq!(error.msg, "Invalid input encoding");
+}
+
+#[test]
+fn test_nickel_eval_streams_arrays() {
+    let result = eval(
+        r#""std.array.generate (fun i => { n = i, deep = [[i]] }) 3" | nickel eval --depth 2"#,
+    );
+    let rows = result.as_list().unwrap();
+    assert_eq!(rows.len(), 3);
+    assert_eq!(
+        rows[2],
+        Value::test_record(nu_protocol::record! {
+            "n" => Value::test_int(2),
+            "deep" => Value::test_string("[...]"),
+        })
+    );
+
+    // Typed columns are found before the first element is converted
+    let result = eval(r#""[{ a | Number = 1 }, { a | Number = 1.5 }, { b = 2 }]" | nickel eval"#);
+    let column: Vec<_> = result
+        .as_list()
+        .unwrap()
+        .iter()
+        .map(|row| row.as_record().unwrap().get("a").cloned())
+        .collect();
+    assert_eq!(
+        column,
+        [
+            Some(Value::test_float(1.0)),
+            Some(Value::test_float(1.5)),
+            Some(Value::test_nothing())
+        ]
+    );
+}
+
+#[test]
+fn test_nickel_eval_streams_exports() {
+    // Large enough to be written in several chunks
+    let result =
+        eval(r#""std.array.generate (fun i => { n = i }) 20000" | nickel eval --format json"#);
+    let text = result.as_str().unwrap();
+    assert!(text.starts_with("[\n  {\n    \"n\": 0\n  },"));
+    assert!(text.ends_with("{\n    \"n\": 19999\n  }\n]"));
+
+    // Values that cannot be exported fail before anything is written
+    let error = eval_error(r#""{ f = fun x => x }" | nickel eval --format json"#);
+    assert!(error.msg.starts_with("Failed to export"));
+}
+
+#[test]
+fn test_nickel_eval_content_type() {
+    assert_eq!(
+        eval_content_type(r#""{ a = 1 }" | nickel eval --format json"#).as_deref(),
+        Some("application/json")
+    );
+    assert_eq!(
+        eval_content_type(r#""{ a = 1 }" | nickel eval --format nuon"#).as_deref(),
+        Some("application/x-nuon")
+    );
+    assert_eq!(
+        eval_content_type(r#""a: 1" | nickel convert --to toml"#).as_deref(),
+        Some("application/toml")
+    );
+    // Nushell data has no media type
+    assert_eq!(eval_content_type(r#""{ a = 1 }" | nickel eval"#), None);
+}
+
+#[test]
+fn test_nickel_why() {
+    let dir = temp_dir();
+    std::fs::write(
+        dir.join("base.ncl"),
+        "let Server = { port | Number | default = 80 } in\n{ server | Server = {} }",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("deploy.ncl"),
+        "let base = import \"base.ncl\" in\nbase & { server.port | force = 8080 }",
+    )
+    .unwrap();
+
+    let result = eval(&format!(
+        "nickel why deploy.ncl server.port --cwd '{}'",
+        dir.display()
+    ));
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("value"), Some(&Value::test_int(8080)));
+    let definitions: Vec<_> = record
+        .get("definitions")
+        .unwrap()
+        .as_list()
+        .unwrap()
+        .iter()
+        .map(|definition| {
+            let definition = definition.as_record().unwrap();
+            (
+                definition
+                    .get("file")
+                    .unwrap()
+                    .as_str()
+                    .unwrap()
+                    .to_string(),
+                definition
+                    .get("priority")
+                    .unwrap()
+                    .as_str()
+                    .unwrap()
+                    .to_string(),
+                definition.get("wins").unwrap().as_bool().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        definitions,
+        [
+            ("base.ncl".to_string(), "default".to_string(), false),
+            ("deploy.ncl".to_string(), "force".to_string(), true),
+        ]
+    );
+    let why = record.get("why").unwrap().as_str().unwrap();
+    assert!(why.starts_with("`server.port` is 8080."), "{why}");
+    assert!(why.contains("It is checked by Number."), "{why}");
+
+    let result = eval(&format!(
+        "nickel why deploy.ncl server.host --cwd '{}'",
+        dir.display()
+    ));
+    let record = result.as_record().unwrap();
+    assert!(record.get("error").unwrap().as_str().is_ok());
+    assert!(
+        record
+            .get("definitions")
+            .unwrap()
+            .as_list()
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_nickel_value_operators() {
+    let result = eval(
+        r#"let base = ("{ port | default = 80, host = \"web\" }" | nickel parse); $base + ("{ port = 8080 }" | nickel parse) | nickel into record"#,
+    );
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("port"), Some(&Value::test_int(8080)));
+    assert_eq!(record.get("host"), Some(&Value::test_string("web")));
+
+    let result = eval(r#"("{ a = 1 }" | nickel parse) + { b: 2 } | nickel into record"#);
+    assert_eq!(
+        result.as_record().unwrap().get("b"),
+        Some(&Value::test_int(2))
+    );
+
+    assert_eq!(
+        eval(r#"("40 + 2" | nickel parse) == 42"#),
+        Value::test_bool(true)
+    );
+    assert_eq!(
+        eval(r#"("40 + 2" | nickel parse) < 10"#),
+        Value::test_bool(false)
+    );
+    assert_eq!(
+        eval(r#"("\"a\"" | nickel parse) != "b""#),
+        Value::test_bool(true)
+    );
+
+    let error = eval_error(r#"("{ a = 1 }" | nickel parse) + ("{ a = 2 }" | nickel parse)"#);
+    assert_eq!(error.msg, "Nickel evaluation failed");
 }
@@ -0,0 +1,100 @@
+use crate::NickelPlugin;
+use crate::nickel::{
+    input::{NickelInput, import_paths, no_imports, working_dir},
+    program::top_level_shape,
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Signature, SyntaxShape, Type, Value,
+};
+
+#[derive(Clone)]
+pub struct NickelTopLevelType;
+
+impl PluginCommand for NickelTopLevelType {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel top-level-type"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel top-level-type")
+            .input_output_types(vec![
+                (Type::String, Type::String),
+                (Type::Binary, Type::String),
+                (Type::Nothing, Type::String),
+            ])
+            .optional(
+                "path",
+                SyntaxShape::Filepath,
+                "Path to a Nickel, JSON, YAML or TOML file to probe",
+            )
+            .named(
+                "import-path",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Directories to look up imports in, before those of `NICKEL_IMPORT_PATH`",
+                Some('I'),
+            )
+            .named(
+                "source-name",
+                SyntaxShape::String,
+                "Name piped code is reported under in errors, instead of `<input>`",
+                None,
+            )
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths and imports",
+                None,
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "Tell whether Nickel code evaluates to a record, an array, a function, a contract or a scalar"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Only the outermost value is evaluated, so this is cheap even for a large configuration, \
+         and scripts can check the shape of a program before converting it with `nickel eval`. \
+         The fields of a record and the elements of an array are not evaluated, so they may \
+         still fail to evaluate later."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Probe a record",
+                example: r#""{ port = 80 }" | nickel top-level-type"#,
+                result: Some(Value::test_string("record")),
+            },
+            Example {
+                description: "Probe a function",
+                example: r#""fun x => x + 1" | nickel top-level-type"#,
+                result: Some(Value::test_string("function")),
+            },
+            Example {
+                description: "Only convert a file that evaluates to a record",
+                example: "if (nickel top-level-type config.ncl) == record { nickel eval config.ncl }",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let mut input = NickelInput::from_call(call, input, 0, Some(working_dir(engine, call)?))?;
+        input.import_paths = import_paths(engine, call)?;
+        input.no_imports = no_imports(engine, call)?;
+
+        let shape = top_level_shape(&input, span)?;
+        Ok(PipelineData::Value(Value::string(shape, span), None))
+    }
+}
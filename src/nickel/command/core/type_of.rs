@@ -0,0 +1,124 @@
+use crate::NickelPlugin;
+use crate::nickel::{
+    convert::type_to_value,
+    input::{NickelInput, import_paths, no_imports, working_dir},
+    program::infer_type,
+    values::NuNickelValue,
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Signature, SyntaxShape, Type, Value,
+};
+
+#[derive(Clone)]
+pub struct NickelTypeOf;
+
+impl PluginCommand for NickelTypeOf {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel typeof"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel typeof")
+            .input_output_types(vec![
+                (Type::String, Type::Any),
+                (Type::Binary, Type::Any),
+                (Type::Nothing, Type::Any),
+                (Type::Custom("NickelValue".to_string().into()), Type::Any),
+            ])
+            .optional(
+                "path",
+                SyntaxShape::Filepath,
+                "Path to nickel file to infer the type of",
+            )
+            .named(
+                "import-path",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Directories to look up imports in, before those of `NICKEL_IMPORT_PATH`",
+                Some('I'),
+            )
+            .named(
+                "source-name",
+                SyntaxShape::String,
+                "Name piped code is reported under in errors, instead of `<input>`",
+                None,
+            )
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths and imports",
+                None,
+            )
+            .switch(
+                "tree",
+                "Return the type as a tree of records instead of a string",
+                Some('t'),
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "Infer the type of Nickel code with the typechecker"
+    }
+
+    fn extra_description(&self) -> &str {
+        "The code is typechecked as a statically typed block, as if annotated with `: _`, so it \
+         must be well typed even where Nickel would not check it otherwise. A Nickel value is \
+         typechecked from the source it was created from."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Infer the type of a function",
+                example: r#""fun x => x + 1" | nickel typeof"#,
+                result: Some(Value::test_string("Number -> Number")),
+            },
+            Example {
+                description: "Inspect the fields of the type of a file",
+                example: "nickel typeof config.ncl --tree | get fields",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let base_dir = working_dir(engine, call)?;
+
+        let mut input = match input {
+            PipelineData::Value(value @ Value::Custom { .. }, _) => {
+                let invalid = || {
+                    LabeledError::new("Invalid input type")
+                        .with_label("Expected a Nickel value", value.span())
+                };
+                let cached =
+                    NuNickelValue::try_get_cached_value(plugin, &value)?.ok_or_else(invalid)?;
+                NickelInput::from_cached(&cached, Some(base_dir)).ok_or_else(invalid)?
+            }
+            input => NickelInput::from_call(call, input, 0, Some(base_dir))?,
+        };
+        input.import_paths = import_paths(engine, call)?;
+        input.no_imports = no_imports(engine, call)?;
+        if input.is_data() {
+            return Err(LabeledError::new("Cannot infer the type of data")
+                .with_label("Only Nickel code can be typechecked", span));
+        }
+
+        let typ = infer_type(&input, span)?;
+        let result = if call.has_flag("tree")? {
+            type_to_value(&typ, span)
+        } else {
+            Value::string(typ.to_string(), span)
+        };
+        Ok(PipelineData::Value(result, None))
+    }
+}
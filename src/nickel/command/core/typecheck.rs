@@ -0,0 +1,274 @@
+use crate::NickelPlugin;
+use crate::nickel::{
+    error::{NickelDiagnostic, diagnostics},
+    input::{NickelInput, expand_paths, import_paths, no_imports, working_dir},
+    program::{INPUT_SOURCE_NAME, new_program},
+    suggest::apply_suggestions,
+    write::{DEFAULT_BACKUP_SUFFIX, WriteSet, backup},
+};
+use nickel_lang_core::typecheck::TypecheckMode;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Signature, Span, SyntaxShape, Type, Value,
+};
+use std::path::Path;
+
+#[derive(Clone)]
+pub struct NickelTypecheck;
+
+impl PluginCommand for NickelTypecheck {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel typecheck"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel typecheck")
+            .input_output_types(vec![
+                (Type::String, Type::table()),
+                (Type::Binary, Type::table()),
+                (Type::Nothing, Type::table()),
+            ])
+            .rest(
+                "paths",
+                SyntaxShape::Filepath,
+                "Nickel files to typecheck, each one independently, or glob patterns matching them",
+            )
+            .named(
+                "import-path",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Directories to look up imports in, before those of `NICKEL_IMPORT_PATH`",
+                Some('I'),
+            )
+            .named(
+                "source-name",
+                SyntaxShape::String,
+                "Name piped code is reported under in errors, instead of `<input>`",
+                None,
+            )
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths and imports",
+                None,
+            )
+            .switch(
+                "apply-fixes",
+                "Rewrite the files with the fixes that are safe to apply, e.g. one-letter typos",
+                None,
+            )
+            .switch(
+                "backup",
+                "With --apply-fixes, save a copy of each file before rewriting it",
+                Some('b'),
+            )
+            .named(
+                "backup-suffix",
+                SyntaxShape::String,
+                "Suffix added to the names of the backups, `.orig` by default",
+                None,
+            )
+            .switch(
+                "dry-run",
+                "With --apply-fixes, return the files that would be written, with their diffs, \
+                 instead of writing them",
+                None,
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "Typecheck Nickel code and return the diagnostics as a table"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Every file is parsed and typechecked on its own, so a broken file does not hide the \
+         errors of the others. All syntax errors of a file are reported, but at most one type \
+         error per file: Nickel stops typechecking a file at its first type error, so the next \
+         one only shows once that one is fixed. An empty table means everything typechecks.\n\n\
+         Diagnostics pointing at a typo'd identifier or field, or at a word that looks like an unquoted \
+         string, come with a `suggestion` to replace a byte range of the file. With \
+         `--apply-fixes`, the safe suggestions are applied to the files, which are typechecked \
+         again until no safe fix is left. Adding `--dry-run` returns the `path`, `action`, `size` \
+         and `diff` of the files instead of writing them. With `--backup`, each file is copied \
+         next to itself before its first rewrite, which `nickel restore-backup` undoes."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Typecheck Nickel code from a string",
+                example: r#""(1 + \"a\") : Number" | nickel typecheck"#,
+                result: None,
+            },
+            Example {
+                description: "Typecheck every Nickel file of a project in CI",
+                example: "nickel typecheck **/*.ncl | where severity == error",
+                result: None,
+            },
+            Example {
+                description: "Fix the typos of a file",
+                example: "nickel typecheck config.ncl --apply-fixes",
+                result: None,
+            },
+            Example {
+                description: "Review the fixes before applying them",
+                example: "nickel typecheck config.ncl --apply-fixes --dry-run | get diff",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let base_dir = working_dir(engine, call)?;
+        let paths = expand_paths(call.rest(0)?, &base_dir)?;
+
+        let mut inputs: Vec<NickelInput> = if paths.is_empty() {
+            vec![NickelInput::from_call(call, input, 0, Some(base_dir))?]
+        } else {
+            paths
+                .into_iter()
+                .map(|path| NickelInput::from_path(path, Some(base_dir.clone()), span))
+                .collect::<Result<_, _>>()?
+        };
+        let import_paths = import_paths(engine, call)?;
+        let no_imports = no_imports(engine, call)?;
+        for input in &mut inputs {
+            input.import_paths = import_paths.clone();
+            input.no_imports = no_imports;
+        }
+
+        let rows = if call.has_flag("apply-fixes")? {
+            if inputs.iter().any(|input| input.path.is_none()) {
+                return Err(LabeledError::new("Cannot apply fixes to piped input")
+                    .with_label("--apply-fixes rewrites files, pass them as arguments", span));
+            }
+            let dry_run = call.has_flag("dry-run")?;
+            // Every file is fixed before any is written, so that they are all written or none
+            let mut writes = WriteSet::new();
+            let mut rows = Vec::new();
+            for input in inputs {
+                let path = input.path.clone().expect("only files are fixed");
+                let original = input.source.clone();
+                let (fixed_rows, source) = apply_fixes(input, dry_run, span)?;
+                rows.extend(fixed_rows);
+                if dry_run || source != original {
+                    writes.add(path, source);
+                }
+            }
+            if dry_run {
+                return Ok(PipelineData::Value(writes.preview(span), None));
+            }
+            let write_error = |e: std::io::Error| {
+                LabeledError::new("Failed to write fixes").with_label(e.to_string(), span)
+            };
+            if call.has_flag("backup")? {
+                let suffix = call
+                    .get_flag::<String>("backup-suffix")?
+                    .unwrap_or_else(|| DEFAULT_BACKUP_SUFFIX.to_string());
+                for path in writes.paths() {
+                    backup(path, &suffix).map_err(write_error)?;
+                }
+            }
+            writes.commit().map_err(write_error)?;
+            rows
+        } else {
+            inputs
+                .iter()
+                .map(|input| typecheck(input, span))
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter()
+                .flatten()
+                .map(|diagnostic| diagnostic.into_value(span))
+                .collect()
+        };
+
+        Ok(PipelineData::Value(Value::list(rows, span), None))
+    }
+}
+
+/// Upper bound on the typecheck and fix rounds of a file, each round fixing at least one error
+pub const MAX_FIX_ROUNDS: usize = 32;
+
+fn typecheck(input: &NickelInput, span: Span) -> Result<Vec<NickelDiagnostic>, LabeledError> {
+    let mut program = new_program(input, span)?;
+    Ok(match program.typecheck(TypecheckMode::Walk) {
+        Ok(()) => Vec::new(),
+        Err(e) => diagnostics(&mut program.files(), e),
+    })
+}
+
+/// Typecheck a file, applying its safe fixes and typechecking it again until none are left
+///
+/// Since Nickel stops at the first type error, fixing one error can reveal the next one. The
+/// diagnostics that were fixed are returned with `fixed` set, followed by the remaining ones,
+/// along with the fixed source, which is left for the caller to write. On a dry run, the fixed
+/// source is typechecked as piped code resolving its imports next to the file.
+fn apply_fixes(
+    mut input: NickelInput,
+    dry_run: bool,
+    span: Span,
+) -> Result<(Vec<Value>, String), LabeledError> {
+    let path = input.path.clone().expect("only files are fixed");
+    let checked_path = match (dry_run, path.parent()) {
+        (true, Some(dir)) => {
+            input.path = None;
+            input.base_dir = Some(dir.to_path_buf());
+            dir.join(INPUT_SOURCE_NAME)
+        }
+        _ => path.clone(),
+    };
+    let mut rows = Vec::new();
+
+    for _ in 0..MAX_FIX_ROUNDS {
+        let (fixed, remaining): (Vec<_>, Vec<_>) =
+            typecheck(&input, span)?
+                .into_iter()
+                .partition(|diagnostic| {
+                    diagnostic.file.as_deref().map(Path::new) == Some(checked_path.as_path())
+                        && diagnostic.suggestion.as_ref().is_some_and(|s| s.safe)
+                });
+
+        let with_fixed = |diagnostics: Vec<NickelDiagnostic>, fixed: bool| {
+            diagnostics.into_iter().map(move |diagnostic| {
+                let mut row = diagnostic.into_value(span);
+                if let Value::Record { val, .. } = &mut row {
+                    val.to_mut().push("fixed", Value::bool(fixed, span));
+                }
+                row
+            })
+        };
+
+        if fixed.is_empty() {
+            rows.extend(with_fixed(remaining, false));
+            return Ok((rows, input.source));
+        }
+
+        let suggestions: Vec<_> = fixed.iter().filter_map(|d| d.suggestion.as_ref()).collect();
+        let (source, applied) = apply_suggestions(&input.source, &suggestions);
+        input.source = source;
+        // A fix overlapping another one is left for the next round, which reports it again
+        let applied = fixed
+            .into_iter()
+            .zip(applied)
+            .filter_map(|(diagnostic, applied)| applied.then_some(diagnostic))
+            .collect();
+        rows.extend(with_fixed(applied, true));
+    }
+
+    Err(LabeledError::new("Too many fixes").with_label(
+        format!(
+            "'{}' still has errors to fix after {MAX_FIX_ROUNDS} rounds",
+            path.display()
+        ),
+        span,
+    ))
+}
@@ -0,0 +1,162 @@
+use super::get::parse_field_path;
+use crate::NickelPlugin;
+use crate::nickel::{
+    convert::nickel_to_nu_value,
+    format::to_nuon,
+    input::{NickelInput, import_paths, no_imports, working_dir},
+    program::{eval_for_export, new_program},
+    provenance::{narrative, provenance},
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Record, Signature, SyntaxShape, Type, Value,
+};
+use std::path::PathBuf;
+
+#[derive(Clone)]
+pub struct NickelWhy;
+
+impl PluginCommand for NickelWhy {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel why"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel why")
+            .input_output_types(vec![(Type::Nothing, Type::record())])
+            .required("path", SyntaxShape::Filepath, "Path to nickel file")
+            .required(
+                "field_path",
+                SyntaxShape::String,
+                "Path of the field to explain, e.g. `server.port`",
+            )
+            .named(
+                "import-path",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Directories to look up imports in, before those of `NICKEL_IMPORT_PATH`",
+                Some('I'),
+            )
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths and imports",
+                None,
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "Explain where the value of a field of a configuration comes from"
+    }
+
+    fn extra_description(&self) -> &str {
+        "The record has the `value` of the field, or the `error` evaluating it, and its \
+         `definitions`: every place in the file and the files it imports by a relative path that \
+         gives the field a value, with its priority and whether it `wins`. Defaults given by \
+         record contracts count as definitions. The `contracts` checking the field and its `doc` \
+         come with them, and `why` tells the whole story in a few sentences.\n\n\
+         The definitions are found by reading the code rather than evaluating it, following \
+         records, merges, `let` bindings and imports. A field computed by a function, such as \
+         one built by `std.record.map`, has no definition found."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Find out why a port is not the one expected",
+                example: "nickel why deploy.ncl server.port | get why",
+                result: None,
+            },
+            Example {
+                description: "List the definitions of a field that lose to another",
+                example: "nickel why deploy.ncl server.port | get definitions | where not wins",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let path: PathBuf = call.req(0)?;
+        let field_path: String = call.req(1)?;
+
+        let mut input = NickelInput::from_path(path, Some(working_dir(engine, call)?), span)?;
+        input.import_paths = import_paths(engine, call)?;
+        input.no_imports = no_imports(engine, call)?;
+
+        let mut program = new_program(&input, span)?;
+        program.field = parse_field_path(&mut program, field_path, span)?;
+        let labels: Vec<String> = program
+            .field
+            .0
+            .iter()
+            .map(|id| id.label().to_string())
+            .collect();
+        let found = provenance(&input, &labels, span)?;
+
+        // A field failing to evaluate is often why one asks, so the error is part of the answer
+        let value = eval_for_export(&mut program, span)
+            .and_then(|term| nickel_to_nu_value(&term, span))
+            .map_err(|e| match e.labels.first() {
+                Some(label) => label.text.clone(),
+                None => e.msg,
+            });
+        let written = value
+            .as_ref()
+            .map(|value| to_nuon(value).unwrap_or_else(|_| value.get_type().to_string()));
+        let why = narrative(
+            &labels.join("."),
+            written.as_deref().map_err(String::as_str),
+            &found,
+        );
+
+        let winners = found.winners();
+        let definitions = found
+            .definitions
+            .iter()
+            .map(|definition| {
+                let wins = winners
+                    .iter()
+                    .any(|winner| std::ptr::eq(*winner, definition));
+                definition.to_value(wins, span)
+            })
+            .collect();
+        let mut record = Record::new();
+        record.push("path", Value::string(labels.join("."), span));
+        let (value, error) = match value {
+            Ok(value) => (value, Value::nothing(span)),
+            Err(error) => (Value::nothing(span), Value::string(error, span)),
+        };
+        record.push("value", value);
+        record.push("error", error);
+        record.push("definitions", Value::list(definitions, span));
+        record.push(
+            "contracts",
+            Value::list(
+                found
+                    .contracts
+                    .iter()
+                    .map(|contract| Value::string(contract, span))
+                    .collect(),
+                span,
+            ),
+        );
+        record.push(
+            "doc",
+            match &found.doc {
+                Some(doc) => Value::string(doc, span),
+                None => Value::nothing(span),
+            },
+        );
+        record.push("why", Value::string(why, span));
+        Ok(PipelineData::Value(Value::record(record, span), None))
+    }
+}
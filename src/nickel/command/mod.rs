@@ -1,11 +1,90 @@
+pub mod cache;
+pub mod codegen;
+pub mod convert;
 pub mod core;
+pub mod outputs;
+pub mod package;
+pub mod project;
+pub mod stdlib;
+
+#[cfg(test)]
+pub(crate) mod test_support;
 
 use crate::NickelPlugin;
 use nu_plugin::PluginCommand;
 
 pub fn core_commands() -> Vec<Box<dyn PluginCommand<Plugin = NickelPlugin>>> {
     vec![
+        Box::new(core::NickelCall),
+        Box::new(core::NickelCapabilities),
+        Box::new(core::NickelConvert),
+        Box::new(core::NickelEq),
         Box::new(core::NickelEval),
+        Box::new(core::NickelExplain),
+        Box::new(core::NickelGet),
+        Box::new(core::NickelGrepContract),
+        Box::new(core::NickelIntoRecord),
+        Box::new(core::NickelKeep),
+        Box::new(core::NickelLex),
+        Box::new(core::NickelOpen),
         Box::new(core::NickelParse),
+        Box::new(core::NickelPatch),
+        Box::new(core::NickelRender),
+        Box::new(core::NickelSource),
+        Box::new(core::NickelTopLevelType),
+        Box::new(core::NickelTypeOf),
+        Box::new(core::NickelTypecheck),
+        Box::new(core::NickelWhy),
+    ]
+}
+
+pub fn stdlib_commands() -> Vec<Box<dyn PluginCommand<Plugin = NickelPlugin>>> {
+    vec![
+        Box::new(stdlib::NickelStdDoc),
+        Box::new(stdlib::NickelStdLs),
+    ]
+}
+
+pub fn codegen_commands() -> Vec<Box<dyn PluginCommand<Plugin = NickelPlugin>>> {
+    vec![Box::new(codegen::NickelCodegenRust)]
+}
+
+pub fn convert_commands() -> Vec<Box<dyn PluginCommand<Plugin = NickelPlugin>>> {
+    vec![Box::new(convert::FromNcl), Box::new(convert::ToNcl)]
+}
+
+pub fn package_commands() -> Vec<Box<dyn PluginCommand<Plugin = NickelPlugin>>> {
+    vec![
+        Box::new(package::NickelPackageInit),
+        Box::new(package::NickelPackageResolve),
+    ]
+}
+
+pub fn cache_commands() -> Vec<Box<dyn PluginCommand<Plugin = NickelPlugin>>> {
+    vec![
+        Box::new(cache::NickelCacheClear),
+        Box::new(cache::NickelCacheStats),
+    ]
+}
+
+pub fn outputs_commands() -> Vec<Box<dyn PluginCommand<Plugin = NickelPlugin>>> {
+    vec![
+        Box::new(outputs::NickelOutputsLog),
+        Box::new(outputs::NickelOutputsShow),
+    ]
+}
+
+pub fn project_commands() -> Vec<Box<dyn PluginCommand<Plugin = NickelPlugin>>> {
+    vec![
+        Box::new(project::NickelCompat),
+        Box::new(project::NickelDeadCode),
+        Box::new(project::NickelEntrypoints),
+        Box::new(project::NickelIndex),
+        Box::new(project::NickelInventory),
+        Box::new(project::NickelPlanRender),
+        Box::new(project::NickelPolicyCheck),
+        Box::new(project::NickelReport),
+        Box::new(project::NickelRestoreBackup),
+        Box::new(project::NickelUsages),
     ]
-}
\ No newline at end of file
+}
@@ -0,0 +1,93 @@
+use crate::NickelPlugin;
+use crate::nickel::{history::History, input::working_dir};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Signature, SyntaxShape, Type, Value,
+};
+
+#[derive(Clone)]
+pub struct NickelOutputsLog;
+
+impl PluginCommand for NickelOutputsLog {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel outputs log"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel outputs log")
+            .input_output_types(vec![(Type::Nothing, Type::table())])
+            .named(
+                "path",
+                SyntaxShape::Filepath,
+                "Only list the outputs written to this file",
+                None,
+            )
+            .named(
+                "history",
+                SyntaxShape::Directory,
+                "Output history directory, the `history` plugin setting by default",
+                None,
+            )
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths",
+                None,
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "List the outputs recorded by `nickel eval --output --history`, latest first"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Each row has the `hash` of the file, the `timestamp` it was written at, its `path`, \
+         `format` and `size`, the `source` program it was rendered from, and the `parameters` \
+         it was rendered with: the `--assign`, `--override`, `--arg` and `--field` flags. \
+         Environment bindings are not recorded."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Find what was deployed last Tuesday",
+                example: "nickel outputs log --path deploy.json | where timestamp < (date now) - 1wk | first",
+                result: None,
+            },
+            Example {
+                description: "List the outputs of a history kept in another directory",
+                example: "nickel outputs log --history ~/.cache/nickel-history",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let base_dir = working_dir(engine, call)?;
+        let history = History::required(engine, call, &base_dir)?;
+        let path = call
+            .get_flag::<String>("path")?
+            .map(|path| base_dir.join(path).display().to_string());
+
+        let entries = history.entries().map_err(|e| {
+            LabeledError::new("Failed to read the output history").with_label(e.to_string(), span)
+        })?;
+        let rows = entries
+            .into_iter()
+            .rev()
+            .filter(|entry| path.as_ref().is_none_or(|path| entry.path == *path))
+            .map(|entry| entry.into_value(span))
+            .collect();
+        Ok(PipelineData::Value(Value::list(rows, span), None))
+    }
+}
@@ -0,0 +1,8 @@
+mod log;
+mod show;
+
+#[cfg(test)]
+mod tests;
+
+pub use log::NickelOutputsLog;
+pub use show::NickelOutputsShow;
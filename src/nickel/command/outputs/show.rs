@@ -0,0 +1,106 @@
+use crate::NickelPlugin;
+use crate::nickel::{
+    format::{OutputFormat, TableFormat, content_metadata},
+    history::History,
+    input::working_dir,
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Signature, Spanned, SyntaxShape, Type, Value,
+};
+
+#[derive(Clone)]
+pub struct NickelOutputsShow;
+
+impl PluginCommand for NickelOutputsShow {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel outputs show"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel outputs show")
+            .input_output_types(vec![
+                (Type::Nothing, Type::String),
+                (Type::Nothing, Type::Binary),
+            ])
+            .required(
+                "hash",
+                SyntaxShape::String,
+                "Hash of the output, or the start of it",
+            )
+            .named(
+                "history",
+                SyntaxShape::Directory,
+                "Output history directory, the `history` plugin setting by default",
+                None,
+            )
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths",
+                None,
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "Return the contents of an output recorded by `nickel eval --output --history`"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Like a git commit, an output can be named by the first characters of its hash, as long \
+         as no other output starts with them. Text formats are returned as a string, with their \
+         media type as the `content_type` of the output metadata, and table formats as binary."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Show a past render",
+                example: "nickel outputs show 3f9a2c",
+                result: None,
+            },
+            Example {
+                description: "Roll a file back to the render before its latest one",
+                example: "nickel outputs show (nickel outputs log --path deploy.json).1.hash | save -f deploy.json",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let base_dir = working_dir(engine, call)?;
+        let history = History::required(engine, call, &base_dir)?;
+        let hash: Spanned<String> = call.req(0)?;
+
+        let entry = history.find(&hash)?;
+        let contents = history.contents(&entry).map_err(|e| {
+            LabeledError::new("Failed to read the output")
+                .with_label(e.to_string(), hash.span)
+                .with_help("its contents were removed from the output history")
+        })?;
+        let (value, content_type) = match TableFormat::from_name(&entry.format) {
+            Some(table) => (Value::binary(contents, span), Some(table.content_type())),
+            None => (
+                match String::from_utf8(contents) {
+                    Ok(text) => Value::string(text, span),
+                    Err(e) => Value::binary(e.into_bytes(), span),
+                },
+                OutputFormat::from_name(&entry.format).map(OutputFormat::content_type),
+            ),
+        };
+        Ok(PipelineData::Value(
+            value,
+            content_type.map(content_metadata),
+        ))
+    }
+}
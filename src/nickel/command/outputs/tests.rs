@@ -0,0 +1,63 @@
+use crate::nickel::command::test_support::{eval, eval_content_type, eval_error, temp_dir};
+use nu_protocol::Value;
+
+#[test]
+fn test_nickel_outputs_log_and_show() {
+    let dir = temp_dir();
+    let cwd = dir.display();
+
+    let result = eval(&format!(
+        r#""{{ replicas = 1 }}" | nickel eval --output deploy.json --history history --cwd '{cwd}'"#
+    ));
+    let first = result.as_record().unwrap().get("hash").unwrap().clone();
+    eval(&format!(
+        r#""{{ replicas = 2 }}" | nickel eval --output deploy.json --history history --arg [env=prod] --cwd '{cwd}'"#
+    ));
+    // Previewed, with the output the history would store, so not recorded
+    let result = eval(&format!(
+        r#""{{ replicas = 4 }}" | nickel eval --output deploy.json --history history --dry-run --cwd '{cwd}'"#
+    ));
+    assert_eq!(result.as_list().unwrap().len(), 2);
+    // Written without a history, so not recorded
+    eval(&format!(
+        r#""{{ replicas = 3 }}" | nickel eval --output deploy.json --cwd '{cwd}'"#
+    ));
+
+    let result = eval(&format!(
+        "nickel outputs log --history history --cwd '{cwd}'"
+    ));
+    let rows = result.as_list().unwrap();
+    assert_eq!(rows.len(), 2);
+    let latest = rows[0].as_record().unwrap();
+    assert_eq!(latest.get("format"), Some(&Value::test_string("json")));
+    assert_eq!(
+        latest
+            .get("parameters")
+            .unwrap()
+            .as_record()
+            .unwrap()
+            .get("arg"),
+        Some(&Value::test_list(vec![Value::test_string("env=prod")]))
+    );
+    assert_eq!(rows[1].as_record().unwrap().get("hash"), Some(&first));
+
+    let result = eval(&format!(
+        "nickel outputs log --history history --path other.json --cwd '{cwd}'"
+    ));
+    assert!(result.as_list().unwrap().is_empty());
+
+    let prefix = &first.as_str().unwrap()[..8];
+    let show = format!("nickel outputs show {prefix} --history history --cwd '{cwd}'");
+    assert_eq!(eval(&show), Value::test_string("{\n  \"replicas\": 1\n}"));
+    assert_eq!(
+        eval_content_type(&show).as_deref(),
+        Some("application/json")
+    );
+
+    let error = eval_error(&format!(
+        "nickel outputs show 0000 --history history --cwd '{cwd}'"
+    ));
+    assert_eq!(error.msg, "Output not found");
+    let error = eval_error(&format!("nickel outputs log --cwd '{cwd}'"));
+    assert_eq!(error.msg, "No output history");
+}
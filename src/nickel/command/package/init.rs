@@ -0,0 +1,104 @@
+use crate::NickelPlugin;
+use crate::nickel::input::working_dir;
+use crate::nickel::package::{MANIFEST_FILE, manifest_template};
+use crate::nickel::write::WriteSet;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Record, Signature, SyntaxShape, Type, Value,
+};
+
+#[derive(Clone)]
+pub struct NickelPackageInit;
+
+impl PluginCommand for NickelPackageInit {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel package init"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel package init")
+            .input_output_types(vec![(Type::Nothing, Type::Any)])
+            .optional(
+                "path",
+                SyntaxShape::Directory,
+                "Directory of the new package, defaults to the current directory",
+            )
+            .named(
+                "name",
+                SyntaxShape::String,
+                "Package name, defaults to the directory name",
+                Some('n'),
+            )
+            .switch(
+                "dry-run",
+                "Return the files that would be written, with their diffs, instead of writing them",
+                None,
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "Create a Nickel package manifest"
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![Example {
+            description: "Turn the current directory into a Nickel package",
+            example: "nickel package init --name my-config",
+            result: None,
+        }]
+    }
+
+    fn run(
+        &self,
+        _plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let dir = working_dir(engine, call)?.join(call.opt::<String>(0)?.unwrap_or_default());
+        let manifest_path = dir.join(MANIFEST_FILE);
+
+        if manifest_path.exists() {
+            return Err(LabeledError::new("Package already initialized")
+                .with_label(format!("{} already exists", manifest_path.display()), span));
+        }
+
+        let write_error = |e: std::io::Error| {
+            LabeledError::new("Failed to create manifest").with_label(
+                format!("Cannot write {}: {e}", manifest_path.display()),
+                span,
+            )
+        };
+        let name = match call.get_flag::<String>("name")? {
+            Some(name) => name,
+            // The directory may not exist yet, with `--dry-run`
+            None => dir
+                .canonicalize()
+                .unwrap_or_else(|_| dir.clone())
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "package".to_string()),
+        };
+
+        if call.has_flag("dry-run")? {
+            let mut writes = WriteSet::new();
+            writes.add(manifest_path.clone(), manifest_template(&name));
+            return Ok(PipelineData::Value(writes.preview(span), None));
+        }
+
+        std::fs::create_dir_all(&dir).map_err(write_error)?;
+        std::fs::write(&manifest_path, manifest_template(&name)).map_err(write_error)?;
+
+        let mut record = Record::new();
+        record.push("name", Value::string(name, span));
+        record.push(
+            "manifest",
+            Value::string(manifest_path.to_string_lossy(), span),
+        );
+        Ok(PipelineData::Value(Value::record(record, span), None))
+    }
+}
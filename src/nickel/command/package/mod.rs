@@ -0,0 +1,8 @@
+mod init;
+mod resolve;
+
+#[cfg(test)]
+mod tests;
+
+pub use init::NickelPackageInit;
+pub use resolve::NickelPackageResolve;
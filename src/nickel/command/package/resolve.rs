@@ -0,0 +1,114 @@
+use crate::NickelPlugin;
+use crate::nickel::input::working_dir;
+use crate::nickel::package::{Dependency, Resolution, resolve};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Record, Signature, Span, SyntaxShape, Type,
+    Value,
+};
+
+#[derive(Clone)]
+pub struct NickelPackageResolve;
+
+impl PluginCommand for NickelPackageResolve {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel package resolve"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel package resolve")
+            .input_output_types(vec![(Type::Nothing, Type::table())])
+            .optional(
+                "path",
+                SyntaxShape::Directory,
+                "Directory of the package, defaults to the current directory",
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "List the dependencies of a Nickel package, with the versions they resolve to"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Path dependencies are resolved through their manifests, recursively, and listed with \
+         their name, version and path relative to the package. Git and index dependencies are \
+         listed as they are declared, with their kind, as only the Nickel CLI fetches them. \
+         Nothing is written: the lockfile of a package, `Nickel-pkg.lock`, is the Nickel CLI's."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "List the dependencies of the package in the current directory",
+                example: "nickel package resolve",
+                result: None,
+            },
+            Example {
+                description: "List the dependencies the Nickel CLI has to fetch",
+                example: "nickel package resolve | where kind != path",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let dir = working_dir(engine, call)?.join(call.opt::<String>(0)?.unwrap_or_default());
+        let resolution = resolve(&dir)
+            .map_err(|e| LabeledError::new("Failed to resolve package").with_label(e, span))?;
+
+        Ok(PipelineData::Value(
+            resolution_table(&resolution, span),
+            None,
+        ))
+    }
+}
+
+/// Table of the packages of a resolution, followed by the dependencies it leaves to the Nickel CLI
+fn resolution_table(resolution: &Resolution, span: Span) -> Value {
+    let row = |name: &str, version: Option<&str>, path: Option<String>, kind: &str| {
+        let mut record = Record::new();
+        record.push("name", Value::string(name, span));
+        record.push(
+            "version",
+            version.map_or(Value::nothing(span), |version| Value::string(version, span)),
+        );
+        record.push(
+            "path",
+            path.map_or(Value::nothing(span), |path| Value::string(path, span)),
+        );
+        record.push("kind", Value::string(kind, span));
+        Value::record(record, span)
+    };
+
+    let rows = resolution
+        .packages
+        .iter()
+        .map(|(path, package)| {
+            row(
+                &package.name,
+                Some(&package.version),
+                Some(path.to_string_lossy().into_owned()),
+                "path",
+            )
+        })
+        .chain(resolution.external.iter().map(|dependency| {
+            let version = match dependency {
+                Dependency::Index { version, .. } => Some(version.as_str()),
+                _ => None,
+            };
+            row(&dependency.source(), version, None, dependency.kind())
+        }))
+        .collect();
+
+    Value::list(rows, span)
+}
@@ -0,0 +1,66 @@
+use crate::nickel::command::test_support::{eval, eval_error, temp_dir};
+use crate::nickel::package::MANIFEST_FILE;
+use nu_protocol::Value;
+
+#[test]
+fn test_nickel_package_init_and_resolve() {
+    let root = temp_dir();
+    let app = root.join("app");
+    let lib = root.join("lib");
+
+    let result = eval(&format!(
+        "nickel package init '{}' --name app --dry-run",
+        app.display()
+    ));
+    let row = result.as_list().unwrap()[0].as_record().unwrap().clone();
+    assert_eq!(row.get("action"), Some(&Value::test_string("create")));
+    assert!(!app.exists());
+
+    eval(&format!(
+        "nickel package init '{}' --name app",
+        app.display()
+    ));
+    eval(&format!("nickel package init '{}'", lib.display()));
+    let manifest = std::fs::read_to_string(app.join(MANIFEST_FILE)).unwrap();
+    std::fs::write(
+        app.join(MANIFEST_FILE),
+        manifest.replace(
+            "dependencies = {}",
+            r#"dependencies = { lib = 'Path "../lib" }"#,
+        ),
+    )
+    .unwrap();
+
+    let result = eval(&format!("nickel package resolve '{}'", app.display()));
+    let rows = result.as_list().unwrap();
+    assert_eq!(rows.len(), 1);
+    let lib_row = rows[0].as_record().unwrap();
+    assert_eq!(lib_row.get("name"), Some(&Value::test_string("lib")));
+    assert_eq!(lib_row.get("version"), Some(&Value::test_string("0.1.0")));
+    assert_eq!(lib_row.get("path"), Some(&Value::test_string("../lib")));
+    assert_eq!(lib_row.get("kind"), Some(&Value::test_string("path")));
+    // Nothing is written next to the manifest
+    assert_eq!(std::fs::read_dir(&app).unwrap().count(), 1);
+
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_nickel_package_resolve_missing_dependency() {
+    let root = temp_dir();
+    eval(&format!("nickel package init '{}'", root.display()));
+    let manifest = std::fs::read_to_string(root.join(MANIFEST_FILE)).unwrap();
+    std::fs::write(
+        root.join(MANIFEST_FILE),
+        manifest.replace(
+            "dependencies = {}",
+            r#"dependencies = { lib = 'Path "../missing" }"#,
+        ),
+    )
+    .unwrap();
+
+    let error = eval_error(&format!("nickel package resolve '{}'", root.display()));
+    assert_eq!(error.msg, "Failed to resolve package");
+
+    std::fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,169 @@
+use crate::NickelPlugin;
+use crate::nickel::{
+    error::nickel_error,
+    format::content_metadata,
+    input::{NickelInput, import_paths, working_dir},
+    program::new_program,
+    schema::{SchemaField, changelog, compare, schema_fields},
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Type,
+    Value,
+};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone)]
+pub struct NickelCompat;
+
+impl PluginCommand for NickelCompat {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel compat"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel compat")
+            .input_output_types(vec![
+                (Type::Nothing, Type::table()),
+                (Type::Nothing, Type::String),
+            ])
+            .required(
+                "old",
+                SyntaxShape::Filepath,
+                "Nickel file of the previous version of the schema",
+            )
+            .required(
+                "new",
+                SyntaxShape::Filepath,
+                "Nickel file of the next version of the schema",
+            )
+            .named(
+                "format",
+                SyntaxShape::String,
+                "Output the changes as a `table`, the default, or as a `markdown` changelog",
+                Some('f'),
+            )
+            .named(
+                "import-path",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Directories to look up imports in, before those of `NICKEL_IMPORT_PATH`",
+                Some('I'),
+            )
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths and imports",
+                None,
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "Report the changes between two versions of a record contract that break configurations"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Only the record spines of the schemas are evaluated, and their fields are compared by \
+         dotted path, nested records and record contracts included. Each change is a row with \
+         the `path` of the field, the `change`, its `severity`, the `old` and `new` contracts, \
+         and a `message`.\n\n\
+         Removed fields, fields that became required, and new required fields are `breaking`, \
+         as configurations valid for the old schema fail the new one. So are changed contracts, \
+         unless the new one accepts every value of the old one: `Dyn` or no contract, more enum \
+         tags, or a wider standard contract, like `Number` over `std.number.Integer`. A field \
+         removed from a record while one with the same contract is added to it is reported as \
+         `renamed`. Optional fields and fields with a default value are not required.\n\n\
+         With `--format markdown`, the changes are a changelog section instead, listing the \
+         breaking changes then the compatible ones under third level headings, to paste under \
+         the heading of a release."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "List the changes made to a schema since the last release",
+                example: "git show v1.0:schema.ncl | save -f /tmp/schema.ncl; nickel compat /tmp/schema.ncl schema.ncl",
+                result: None,
+            },
+            Example {
+                description: "Fail a CI job on breaking changes",
+                example: "if (nickel compat old.ncl new.ncl | where severity == breaking | is-not-empty) { exit 1 }",
+                result: None,
+            },
+            Example {
+                description: "Write the schema section of the release notes",
+                example: "nickel compat old.ncl new.ncl --format markdown | save -a CHANGELOG.md",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let base_dir = working_dir(engine, call)?;
+        let import_paths = import_paths(engine, call)?;
+        let markdown = match call.get_flag::<Spanned<String>>("format")? {
+            None => false,
+            Some(format) => match format.item.to_ascii_lowercase().as_str() {
+                "table" => false,
+                "markdown" | "md" => true,
+                _ => {
+                    return Err(LabeledError::new("Unknown output format")
+                        .with_label(
+                            format!("`{}` is not a known format", format.item),
+                            format.span,
+                        )
+                        .with_help("use one of table, markdown"));
+                }
+            },
+        };
+        let old: Spanned<PathBuf> = call.req(0)?;
+        let new: Spanned<PathBuf> = call.req(1)?;
+
+        let old = schema(&old, &base_dir, &import_paths, span)?;
+        let new = schema(&new, &base_dir, &import_paths, span)?;
+        let changes = compare(&old, &new);
+        if markdown {
+            return Ok(PipelineData::Value(
+                Value::string(changelog(&changes), span),
+                Some(content_metadata("text/markdown")),
+            ));
+        }
+        let changes = changes
+            .into_iter()
+            .map(|change| change.into_value(span))
+            .collect();
+        Ok(PipelineData::Value(Value::list(changes, span), None))
+    }
+}
+
+/// The fields of the record contract of a file
+fn schema(
+    path: &Spanned<PathBuf>,
+    base_dir: &Path,
+    import_paths: &[PathBuf],
+    span: Span,
+) -> Result<Vec<SchemaField>, LabeledError> {
+    let mut input = NickelInput::from_path(path.item.clone(), Some(base_dir.to_path_buf()), span)?;
+    input.import_paths = import_paths.to_vec();
+    let mut program = new_program(&input, span)?;
+    let term = program
+        .eval_record_spine()
+        .map_err(|e| nickel_error(&mut program.files(), e, "Nickel evaluation failed", span))?;
+    schema_fields(&term).ok_or_else(|| {
+        LabeledError::new("Not a record contract")
+            .with_label(
+                format!("'{}' does not evaluate to a record", path.item.display()),
+                path.span,
+            )
+            .with_help("compare records like `{ port | Number }`")
+    })
+}
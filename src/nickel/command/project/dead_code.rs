@@ -0,0 +1,160 @@
+use super::index_error;
+use crate::NickelPlugin;
+use crate::nickel::input::working_dir;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Record, Signature, Span, SyntaxShape, Type,
+    Value,
+};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone)]
+pub struct NickelDeadCode;
+
+impl PluginCommand for NickelDeadCode {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel dead-code"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel dead-code")
+            .input_output_types(vec![(Type::Nothing, Type::table())])
+            .optional(
+                "path",
+                SyntaxShape::Directory,
+                "Project directory, defaults to the current directory",
+            )
+            .named(
+                "entrypoint",
+                SyntaxShape::List(Box::new(SyntaxShape::Filepath)),
+                "Files the project is evaluated from, defaults to every file nothing imports",
+                Some('e'),
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "Report the files and bindings of a project never used from its entrypoints"
+    }
+
+    fn extra_description(&self) -> &str {
+        "A file is dead when no entrypoint reaches it through imports, and a `let` binding of a \
+         reachable file is dead when its name is never used in that file. Record fields are not \
+         reported, as they may be used by whatever consumes the exported configuration. Names \
+         are matched without regard to scope, so a binding shadowed by a used one is missed.\n\n\
+         Pass the files the project is actually evaluated from with `--entrypoint`. Without it, \
+         every file nothing imports counts as an entrypoint, so a library file that nothing \
+         imports anymore is taken for an entrypoint and is never reported as dead, along with \
+         whatever only it imports."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Report what the deployed configurations do not use",
+                example: "nickel dead-code --entrypoint [prod.ncl staging.ncl]",
+                result: None,
+            },
+            Example {
+                description: "Report the unused bindings of the project in the current directory, \
+                              taking every file nothing imports for an entrypoint",
+                example: "nickel dead-code",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let cwd = working_dir(engine, call)?;
+        let dir = cwd.join(call.opt::<String>(0)?.unwrap_or_default());
+        let entrypoints = call
+            .get_flag::<Vec<String>>("entrypoint")?
+            .map(|paths| {
+                paths
+                    .into_iter()
+                    .map(|path| canonical_entrypoint(&cwd.join(path), span))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+
+        let (_, _, rows) = plugin
+            .index
+            .with_index(&dir, |index| {
+                let roots = match &entrypoints {
+                    Some(paths) => paths.iter().collect(),
+                    None => index.entrypoints(),
+                };
+                let reachable = index.reachable(roots);
+
+                let mut rows = Vec::new();
+                for (path, file) in &index.files {
+                    if !reachable.contains(path) {
+                        rows.push(dead_row("file", path, None, None, span));
+                        continue;
+                    }
+                    for (name, usage) in file.unused_bindings() {
+                        let context = file.source.lines().nth(usage.line - 1).unwrap_or_default();
+                        rows.push(dead_row(
+                            "binding",
+                            path,
+                            Some(name),
+                            Some((usage.line, usage.column, context.trim())),
+                            span,
+                        ));
+                    }
+                }
+                rows
+            })
+            .map_err(|e| index_error(e, span))?;
+
+        Ok(PipelineData::Value(Value::list(rows, span), None))
+    }
+}
+
+fn canonical_entrypoint(path: &Path, span: Span) -> Result<PathBuf, LabeledError> {
+    path.canonicalize().map_err(|e| {
+        LabeledError::new(format!("Failed to read file: {e}"))
+            .with_label(format!("Cannot find entrypoint '{}'", path.display()), span)
+    })
+}
+
+fn dead_row(
+    kind: &str,
+    path: &Path,
+    name: Option<&str>,
+    location: Option<(usize, usize, &str)>,
+    span: Span,
+) -> Value {
+    let int = |n: usize| Value::int(n as i64, span);
+    let mut record = Record::new();
+    record.push("kind", Value::string(kind, span));
+    record.push("file", Value::string(path.display().to_string(), span));
+    record.push(
+        "name",
+        name.map_or(Value::nothing(span), |name| Value::string(name, span)),
+    );
+    record.push(
+        "line",
+        location.map_or(Value::nothing(span), |(line, _, _)| int(line)),
+    );
+    record.push(
+        "column",
+        location.map_or(Value::nothing(span), |(_, column, _)| int(column)),
+    );
+    record.push(
+        "context",
+        location.map_or(Value::nothing(span), |(_, _, context)| {
+            Value::string(context, span)
+        }),
+    );
+    Value::record(record, span)
+}
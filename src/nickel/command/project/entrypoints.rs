@@ -0,0 +1,132 @@
+use super::index_error;
+use crate::NickelPlugin;
+use crate::measure::measure;
+use crate::nickel::{
+    convert::nickel_to_nu_value,
+    input::{NickelInput, working_dir},
+    program::{eval_for_export, new_program},
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Record, Signature, Span, SyntaxShape, Type,
+    Value,
+};
+use std::path::PathBuf;
+
+#[derive(Clone)]
+pub struct NickelEntrypoints;
+
+impl PluginCommand for NickelEntrypoints {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel entrypoints"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel entrypoints")
+            .input_output_types(vec![(Type::Nothing, Type::table())])
+            .optional(
+                "path",
+                SyntaxShape::Directory,
+                "Project directory, defaults to the current directory",
+            )
+            .switch(
+                "render",
+                "Evaluate every entrypoint into a `value` column",
+                Some('r'),
+            )
+            .switch(
+                "measure",
+                "Render every entrypoint, with the time and memory each evaluation took",
+                Some('m'),
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "List the Nickel files of a project that no other file imports"
+    }
+
+    fn extra_description(&self) -> &str {
+        "In the common layout where each top-level file is a deliverable and shared code lives in \
+         imported libraries, these are the files to evaluate. Each row has the file and the \
+         number of files it reaches through imports. With `--render`, the first entrypoint \
+         failing to evaluate fails the whole batch. With `--measure`, the entrypoints are rendered \
+         along with the `wall_time`, `cpu_time` and `peak_memory` of each evaluation."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "List the entrypoints of a project",
+                example: "nickel entrypoints ./project",
+                result: None,
+            },
+            Example {
+                description: "Find the entrypoints taking the longest to render",
+                example: "nickel entrypoints --measure | sort-by wall_time --reverse | select file wall_time peak_memory",
+                result: None,
+            },
+            Example {
+                description: "Render every entrypoint to a JSON file next to it",
+                example: "nickel entrypoints ./project --render | each { |it| $it.value | save ($it.file | path parse | update extension json | path join) }",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let dir = working_dir(engine, call)?.join(call.opt::<String>(0)?.unwrap_or_default());
+
+        let (_, _, entrypoints) = plugin
+            .index
+            .with_index(&dir, |index| {
+                index
+                    .entrypoints()
+                    .into_iter()
+                    .map(|path| {
+                        // The reachable files include the entrypoint itself
+                        let dependencies = index.reachable([path]).len() - 1;
+                        (path.clone(), dependencies)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .map_err(|e| index_error(e, span))?;
+
+        let measured = call.has_flag("measure")?;
+        let render = measured || call.has_flag("render")?;
+        let rows = entrypoints
+            .into_iter()
+            .map(|(path, dependencies)| {
+                let mut record = Record::new();
+                record.push("file", Value::string(path.display().to_string(), span));
+                record.push("dependencies", Value::int(dependencies as i64, span));
+                if render {
+                    let (value, measure) = measure(|| render_file(path, span));
+                    record.push("value", value?);
+                    if measured {
+                        record.extend(measure.into_record(span));
+                    }
+                }
+                Ok(Value::record(record, span))
+            })
+            .collect::<Result<Vec<_>, LabeledError>>()?;
+
+        Ok(PipelineData::Value(Value::list(rows, span), None))
+    }
+}
+
+fn render_file(path: PathBuf, span: Span) -> Result<Value, LabeledError> {
+    let input = NickelInput::from_path(path, None, span)?;
+    let mut program = new_program(&input, span)?;
+    let term = eval_for_export(&mut program, span)?;
+    nickel_to_nu_value(&term, span)
+}
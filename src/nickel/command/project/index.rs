@@ -0,0 +1,86 @@
+use super::index_error;
+use crate::NickelPlugin;
+use crate::nickel::input::working_dir;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Record, Signature, SyntaxShape, Type, Value,
+};
+use std::collections::HashSet;
+
+#[derive(Clone)]
+pub struct NickelIndex;
+
+impl PluginCommand for NickelIndex {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel index"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel index")
+            .input_output_types(vec![(Type::Nothing, Type::record())])
+            .optional(
+                "path",
+                SyntaxShape::Directory,
+                "Project directory, defaults to the current directory",
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "Build the symbol and import index of the Nickel files of a project"
+    }
+
+    fn extra_description(&self) -> &str {
+        "The index is kept by the plugin and reused by the project-wide queries, such as \
+         `nickel usages --project`. Running it again only reindexes the files modified since."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![Example {
+            description: "Index the project in the current directory",
+            example: "nickel index",
+            result: None,
+        }]
+    }
+
+    fn run(
+        &self,
+        plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let dir = working_dir(engine, call)?.join(call.opt::<String>(0)?.unwrap_or_default());
+
+        let (root, indexed, record) = plugin
+            .index
+            .with_index(&dir, |index| {
+                let symbols: HashSet<_> = index
+                    .files
+                    .values()
+                    .flat_map(|file| file.symbols.keys())
+                    .collect();
+                let imports: usize = index.files.values().map(|file| file.imports.len()).sum();
+                let lex_errors = index.files.values().filter(|file| file.lex_error).count();
+
+                let mut record = Record::new();
+                record.push("files", Value::int(index.files.len() as i64, span));
+                record.push("symbols", Value::int(symbols.len() as i64, span));
+                record.push("imports", Value::int(imports as i64, span));
+                record.push("lex_errors", Value::int(lex_errors as i64, span));
+                record
+            })
+            .map_err(|e| index_error(e, span))?;
+
+        let mut summary = Record::new();
+        summary.push("root", Value::string(root.display().to_string(), span));
+        summary.push("indexed", Value::int(indexed as i64, span));
+        for (column, value) in record {
+            summary.push(column, value);
+        }
+        Ok(PipelineData::Value(Value::record(summary, span), None))
+    }
+}
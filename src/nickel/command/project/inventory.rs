@@ -0,0 +1,154 @@
+use crate::NickelPlugin;
+use crate::nickel::{
+    convert::nickel_to_nu_value,
+    error::nickel_error,
+    input::{NickelInput, expand_paths, import_paths, no_imports, working_dir},
+    jobs::Jobs,
+    program::{eval_for_export, new_program},
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Record, Signature, Span, SyntaxShape, Type,
+    Value,
+};
+
+#[derive(Clone)]
+pub struct NickelInventory;
+
+impl PluginCommand for NickelInventory {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel inventory"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel inventory")
+            .input_output_types(vec![(Type::Nothing, Type::table())])
+            .required(
+                "fields",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Field paths to extract from each file, e.g. `service.name`",
+            )
+            .rest(
+                "paths",
+                SyntaxShape::Filepath,
+                "Nickel or data files to extract the fields from, or glob patterns like `**/*.ncl`",
+            )
+            .named(
+                "import-path",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Directories to look up imports in, before those of `NICKEL_IMPORT_PATH`",
+                Some('I'),
+            )
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths and imports",
+                None,
+            )
+            .named(
+                "jobs",
+                SyntaxShape::Int,
+                "Number of files to evaluate at the same time, the number of CPUs by default",
+                Some('j'),
+            )
+            .switch(
+                "nice",
+                "Evaluate at a lower CPU priority, to leave room for other processes",
+                None,
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "Extract the same fields from many configurations into one table"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Each file gets a row with its `file` path and a column per field path, named after it. \
+         Like `nickel get`, only the records along each path are evaluated. A file where a field \
+         is missing or fails to evaluate has null there, and the first message is kept in the \
+         `error` column, so that one broken file does not hide the others.\n\n\
+         Files are evaluated `--jobs` at a time, and with `--nice` at a lower priority, so that \
+         a large inventory does not starve the other jobs of a shared machine."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Report the image deployed by every service",
+                example: "nickel inventory [service.name image.tag] services/*.ncl",
+                result: None,
+            },
+            Example {
+                description: "Find the services still running an old image",
+                example: "nickel inventory [image.tag] **/*.ncl | where 'image.tag' != '2.0'",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let fields: Vec<String> = call.req(0)?;
+        let base_dir = working_dir(engine, call)?;
+        let paths = expand_paths(call.rest(1)?, &base_dir)?;
+        let import_paths = import_paths(engine, call)?;
+        let no_imports = no_imports(engine, call)?;
+        let jobs = Jobs::from_call(call)?;
+
+        let rows = jobs
+            .run(&paths, engine.signals(), span, |path| {
+                let mut record = Record::new();
+                record.push("file", Value::string(path.display().to_string(), span));
+                let mut input = NickelInput::from_path(path.clone(), Some(base_dir.clone()), span)?;
+                input.import_paths = import_paths.clone();
+                input.no_imports = no_imports;
+
+                let mut error = None;
+                for field in &fields {
+                    let value = extract(&input, field, span).unwrap_or_else(|e| {
+                        error.get_or_insert_with(|| error_message(e));
+                        Value::nothing(span)
+                    });
+                    record.push(field, value);
+                }
+                record.push(
+                    "error",
+                    error.map_or(Value::nothing(span), |e| Value::string(e, span)),
+                );
+                Ok::<_, LabeledError>(Value::record(record, span))
+            })?
+            .into_iter()
+            .collect::<Result<Vec<_>, LabeledError>>()?;
+
+        Ok(PipelineData::Value(Value::list(rows, span), None))
+    }
+}
+
+/// Evaluate a single field of a file, in a program of its own
+///
+/// A failed evaluation leaves a program unusable, so fields are not evaluated from a shared one.
+fn extract(input: &NickelInput, field: &str, span: Span) -> Result<Value, LabeledError> {
+    let mut program = new_program(input, span)?;
+    program.field = program
+        .parse_field_path(field.to_string())
+        .map_err(|e| nickel_error(&mut program.files(), e, "Invalid field path", span))?;
+    let term = eval_for_export(&mut program, span)?;
+    nickel_to_nu_value(&term, span)
+}
+
+/// The most specific message of an error, its label when it has one
+fn error_message(error: LabeledError) -> String {
+    match error.labels.first() {
+        Some(label) => label.text.clone(),
+        None => error.msg,
+    }
+}
@@ -0,0 +1,72 @@
+mod compat;
+mod dead_code;
+mod entrypoints;
+mod index;
+mod inventory;
+mod plan_render;
+mod policy_check;
+mod report;
+mod restore_backup;
+mod usages;
+
+#[cfg(test)]
+mod tests;
+
+pub use compat::NickelCompat;
+pub use dead_code::NickelDeadCode;
+pub use entrypoints::NickelEntrypoints;
+pub use index::NickelIndex;
+pub use inventory::NickelInventory;
+pub use plan_render::NickelPlanRender;
+pub use policy_check::NickelPolicyCheck;
+pub use report::NickelReport;
+pub use restore_backup::NickelRestoreBackup;
+pub use usages::NickelUsages;
+
+use crate::nickel::{
+    lex::{LexedToken, lex},
+    symbols::Usage,
+};
+use nu_protocol::{LabeledError, Record, Span, Value};
+use std::path::Path;
+
+fn read_file(path: &Path, span: Span) -> Result<String, LabeledError> {
+    std::fs::read_to_string(path).map_err(|e| {
+        LabeledError::new(format!("Failed to read file: {e}"))
+            .with_label(format!("Cannot read file '{}'", path.display()), span)
+    })
+}
+
+fn lex_file(path: &Path, source: &str, span: Span) -> Result<Vec<LexedToken>, LabeledError> {
+    lex(source).map_err(|e| {
+        LabeledError::new("Failed to lex Nickel code").with_label(
+            format!(
+                "{} at {}:{}:{}",
+                e.message,
+                path.display(),
+                e.line,
+                e.column
+            ),
+            span,
+        )
+    })
+}
+
+fn index_error(error: std::io::Error, span: Span) -> LabeledError {
+    LabeledError::new("Failed to index project").with_label(error.to_string(), span)
+}
+
+/// A row locating a usage, with the line it is on as context
+fn usage_row(path: &Path, source: &str, usage: &Usage, span: Span) -> Value {
+    let context = source.lines().nth(usage.line - 1).unwrap_or_default();
+
+    let mut record = Record::new();
+    record.push("file", Value::string(path.display().to_string(), span));
+    record.push("line", Value::int(usage.line as i64, span));
+    record.push("column", Value::int(usage.column as i64, span));
+    record.push("start", Value::int(usage.start as i64, span));
+    record.push("end", Value::int(usage.end as i64, span));
+    record.push("kind", Value::string(usage.kind.as_str(), span));
+    record.push("context", Value::string(context.trim(), span));
+    Value::record(record, span)
+}
@@ -0,0 +1,265 @@
+use crate::NickelPlugin;
+use crate::nickel::{
+    format::{OutputFormat, parse_data},
+    input::{NickelInput, working_dir},
+    jobs::Jobs,
+    program::{add_assignments, eval_for_export, export_term, export_to_string, new_program},
+    write::WriteSet,
+};
+use nickel_lang_core::{serialize::ExportFormat, term::MergePriority};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Record, Signature, Span, Spanned, SyntaxShape,
+    Type, Value,
+};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// A render plan, listing the outputs to render
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    renders: Vec<RenderTarget>,
+}
+
+/// An output to render, with paths relative to the manifest
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RenderTarget {
+    entrypoint: PathBuf,
+    output: PathBuf,
+    /// `json`, `yaml` or `toml`, guessed from the extension of the output by default
+    #[serde(default)]
+    format: Option<String>,
+    #[serde(default)]
+    assign: Vec<String>,
+    #[serde(default, rename = "override")]
+    overrides: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct NickelPlanRender;
+
+impl PluginCommand for NickelPlanRender {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel plan-render"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel plan-render")
+            .input_output_types(vec![(Type::Nothing, Type::table())])
+            .required(
+                "manifest",
+                SyntaxShape::Filepath,
+                "Nickel, JSON, YAML or TOML file describing the outputs to render",
+            )
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths",
+                None,
+            )
+            .switch(
+                "dry-run",
+                "Return the files that would be written, with their diffs, instead of writing them",
+                None,
+            )
+            .named(
+                "jobs",
+                SyntaxShape::Int,
+                "Number of outputs to render at the same time, the number of CPUs by default",
+                Some('j'),
+            )
+            .switch(
+                "nice",
+                "Render at a lower CPU priority, to leave room for other processes",
+                None,
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "Render every output described by a manifest"
+    }
+
+    fn extra_description(&self) -> &str {
+        "The manifest has a `renders` array, each render having an `entrypoint` file to evaluate \
+         and an `output` file to write, both relative to the manifest. A render can set its \
+         `format` (`json`, `yaml`, `toml` or `nuon`, guessed from the output extension otherwise), \
+         and `assign` and `override` lists of `path.to.field=value` assignments, like the flags \
+         of `nickel eval`.\n\n\
+         Every render is attempted, and the table reports the `status` of each one with its \
+         `error`. The outputs are only written when every render succeeds, and then all at once, \
+         so that a failure leaves them as they were: the renders are then `failed` or \
+         `skipped`. With `--dry-run`, a plan whose renders all succeed returns the `path`, \
+         `action`, `size` and `diff` of the outputs instead of writing them.\n\n\
+         Outputs are rendered `--jobs` at a time, and with `--nice` at a lower priority, so that \
+         rendering hundreds of them does not starve the other jobs of a shared CI runner."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Render the outputs of a project",
+                example: "nickel plan-render render.ncl",
+                result: None,
+            },
+            Example {
+                description: "Review the outputs that a render would change",
+                example: "nickel plan-render render.ncl --dry-run | where action != unchanged",
+                result: None,
+            },
+            Example {
+                description: "List the renders that failed",
+                example: "nickel plan-render render.ncl | where status == failed",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let manifest_path = working_dir(engine, call)?.join(call.req::<String>(0)?);
+        let manifest = read_manifest(&manifest_path, span)?;
+        let base_dir = manifest_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        let renders =
+            Jobs::from_call(call)?.run(&manifest.renders, engine.signals(), span, |target| {
+                let entrypoint = base_dir.join(&target.entrypoint);
+                let output = base_dir.join(&target.output);
+                let format = target.format.clone().or_else(|| {
+                    output
+                        .extension()
+                        .map(|ext| ext.to_string_lossy().to_string())
+                });
+                let result = render(&entrypoint, format.as_deref(), target, span);
+                (entrypoint, output, format, result)
+            })?;
+
+        // Outputs are only written when every render succeeded, and then all at once
+        let failed = renders.iter().any(|(.., result)| result.is_err());
+        let write_error = if failed {
+            None
+        } else {
+            let mut writes = WriteSet::new();
+            for (_, output, _, result) in &renders {
+                if let Ok(rendered) = result {
+                    writes.add(output.clone(), rendered.as_bytes());
+                }
+            }
+            if call.has_flag("dry-run")? {
+                return Ok(PipelineData::Value(writes.preview(span), None));
+            }
+            writes
+                .commit()
+                .err()
+                .map(|e| format!("Failed to write the outputs, none was written: {e}"))
+        };
+
+        let rows = renders
+            .into_iter()
+            .map(|(entrypoint, output, format, result)| {
+                let (status, error) = match (result, &write_error) {
+                    (Err(error), _) => ("failed", Some(error_text(&error))),
+                    (Ok(_), Some(error)) => ("failed", Some(error.clone())),
+                    (Ok(_), None) if failed => (
+                        "skipped",
+                        Some("Not written, as another render failed".to_string()),
+                    ),
+                    (Ok(_), None) => ("rendered", None),
+                };
+
+                let mut record = Record::new();
+                record.push(
+                    "entrypoint",
+                    Value::string(entrypoint.display().to_string(), span),
+                );
+                record.push("output", Value::string(output.display().to_string(), span));
+                record.push(
+                    "format",
+                    format.map_or(Value::nothing(span), |f| Value::string(f, span)),
+                );
+                record.push("status", Value::string(status, span));
+                record.push(
+                    "error",
+                    error.map_or(Value::nothing(span), |e| Value::string(e, span)),
+                );
+                Value::record(record, span)
+            })
+            .collect();
+
+        Ok(PipelineData::Value(Value::list(rows, span), None))
+    }
+}
+
+fn read_manifest(path: &Path, span: Span) -> Result<Manifest, LabeledError> {
+    let invalid = |e: String| LabeledError::new("Invalid render manifest").with_label(e, span);
+
+    let input = NickelInput::from_path(path.to_path_buf(), None, span)?;
+    let json = match parse_data(&input.source, input.format) {
+        Some(data) => data.map_err(invalid)?,
+        None => {
+            let mut program = new_program(&input, span)?;
+            let term = eval_for_export(&mut program, span)?;
+            let json = export_to_string(&program, &term, ExportFormat::Json, span)?;
+            serde_json::from_str(&json).map_err(|e| invalid(e.to_string()))?
+        }
+    };
+    serde_json::from_value(json).map_err(|e| invalid(format!("{e} in '{}'", path.display())))
+}
+
+fn render(
+    entrypoint: &Path,
+    format: Option<&str>,
+    target: &RenderTarget,
+    span: Span,
+) -> Result<String, LabeledError> {
+    let format = match format {
+        Some(format) => OutputFormat::parse(&Spanned {
+            item: format.to_string(),
+            span,
+        })?,
+        None => {
+            return Err(LabeledError::new("Unknown output format").with_label(
+                "Set the format of the render, it cannot be guessed from its output",
+                span,
+            ));
+        }
+    };
+
+    let input = NickelInput::from_path(entrypoint.to_path_buf(), None, span)?;
+    let mut program = new_program(&input, span)?;
+    add_assignments(
+        &mut program,
+        target.assign.clone(),
+        MergePriority::Neutral,
+        span,
+    )?;
+    add_assignments(
+        &mut program,
+        target.overrides.clone(),
+        MergePriority::Top,
+        span,
+    )?;
+    let term = eval_for_export(&mut program, span)?;
+    export_term(&program, &term, format, span)
+}
+
+/// The message of an error with the text of its labels
+fn error_text(error: &LabeledError) -> String {
+    std::iter::once(error.msg.as_str())
+        .chain(error.labels.iter().map(|label| label.text.as_str()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,203 @@
+use crate::NickelPlugin;
+use crate::nickel::{
+    convert::{nickel_to_nu_value, value_to_nickel},
+    error::diagnostics,
+    input::{NickelInput, expand_paths, import_paths, no_imports, working_dir},
+    jobs::Jobs,
+    program::{eval_for_export, forbid_file_imports, new_program},
+};
+use nickel_lang_core::cache::InputFormat;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Record, Signature, Span, SyntaxShape, Type,
+    Value,
+};
+use std::path::Path;
+
+#[derive(Clone)]
+pub struct NickelPolicyCheck;
+
+impl PluginCommand for NickelPolicyCheck {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel policy check"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel policy check")
+            .input_output_types(vec![(Type::Nothing, Type::table())])
+            .required(
+                "policy",
+                SyntaxShape::Filepath,
+                "Nickel file of a record of named rules, each one a contract",
+            )
+            .rest(
+                "paths",
+                SyntaxShape::Filepath,
+                "Nickel or data files to check, or glob patterns like `**/*.ncl`",
+            )
+            .named(
+                "import-path",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Directories to look up imports in, before those of `NICKEL_IMPORT_PATH`",
+                Some('I'),
+            )
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths and imports",
+                None,
+            )
+            .named(
+                "jobs",
+                SyntaxShape::Int,
+                "Number of checks to run at the same time, the number of CPUs by default",
+                Some('j'),
+            )
+            .switch(
+                "nice",
+                "Run the checks at a lower CPU priority, to leave room for other processes",
+                None,
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "Check configurations against the rules of a Nickel policy"
+    }
+
+    fn extra_description(&self) -> &str {
+        "The policy is a record whose fields are rules, each a contract the whole configuration \
+         must satisfy, like `std.contract.from_predicate (fun config => config.replicas > 1)` or \
+         a record contract. Every rule is applied to every file on its own, so a file breaking \
+         several rules gets a row for each. The result is a table of the violations, with the \
+         `file`, the `rule`, the `reason`, which is the message of a contract failing with \
+         `std.contract.custom` or else the error, and the `label` and `notes` of the error. An \
+         empty table means every file follows the policy.\n\n\
+         Checks run `--jobs` at a time, in the order of the files and rules, and with `--nice` \
+         at a lower priority, for CI runners shared with other jobs."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Check every configuration of a repository in CI",
+                example: "let violations = nickel policy check policy.ncl **/*.ncl; if ($violations | is-not-empty) { $violations | print; exit 1 }",
+                result: None,
+            },
+            Example {
+                description: "Count the files breaking each rule",
+                example: "nickel policy check policy.ncl services/*.ncl | uniq-by file rule | group-by rule | transpose rule files | update files { length }",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let base_dir = working_dir(engine, call)?;
+        let policy = base_dir.join(call.req::<String>(0)?);
+        let paths = expand_paths(call.rest(1)?, &base_dir)?;
+        let import_paths = import_paths(engine, call)?;
+        let jobs = Jobs::from_call(call)?;
+        if no_imports(engine, call)? {
+            for path in std::iter::once(&policy).chain(&paths) {
+                forbid_file_imports(path, span)?;
+            }
+        }
+        let input = |source: String| NickelInput {
+            source,
+            path: None,
+            format: InputFormat::Nickel,
+            base_dir: Some(base_dir.clone()),
+            import_paths: import_paths.clone(),
+            // The program imports the policy and the checked files, which is what checking them
+            // is, while the imports of those files themselves were checked above
+            no_imports: false,
+            source_name: None,
+        };
+
+        let import_policy = format!("import {}", literal(&policy, span)?);
+        let mut program =
+            new_program(&input(format!("std.record.fields ({import_policy})")), span)?;
+        let rules = match nickel_to_nu_value(&eval_for_export(&mut program, span)?, span)? {
+            Value::List { vals, .. } => vals
+                .into_iter()
+                .map(Value::coerce_into_string)
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => unreachable!("std.record.fields returns a list"),
+        };
+
+        let checks: Vec<_> = paths
+            .iter()
+            .flat_map(|path| rules.iter().map(move |rule| (path, rule)))
+            .collect();
+        let rows = jobs
+            .run(&checks, engine.signals(), span, |(path, rule)| {
+                // Each rule is checked by a program of its own, as a failed one cannot go on
+                let rule_value = value_to_nickel(&Value::string(*rule, span), span)?;
+                let source = format!(
+                    "let rule = ({import_policy}).{rule_value} in (import {}) | rule",
+                    literal(path, span)?
+                );
+                let mut program = new_program(&input(source), span)?;
+                let Err(error) = program.eval_full_for_export() else {
+                    return Ok(None);
+                };
+                // Nickel reports the broken contract first, the other diagnostics locate it
+                let Some(diagnostic) = diagnostics(&mut program.files(), error).into_iter().next()
+                else {
+                    return Ok(None);
+                };
+                let mut record = Record::new();
+                record.push("file", Value::string(path.display().to_string(), span));
+                record.push("rule", Value::string(*rule, span));
+                record.push("reason", Value::string(reason(&diagnostic.message), span));
+                record.push(
+                    "label",
+                    diagnostic
+                        .label
+                        .map_or(Value::nothing(span), |label| Value::string(label, span)),
+                );
+                record.push(
+                    "notes",
+                    Value::list(
+                        diagnostic
+                            .notes
+                            .into_iter()
+                            .map(|note| Value::string(note, span))
+                            .collect(),
+                        span,
+                    ),
+                );
+                Ok::<_, LabeledError>(Some(Value::record(record, span)))
+            })?
+            .into_iter()
+            .filter_map(Result::transpose)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(PipelineData::Value(Value::list(rows, span), None))
+    }
+}
+
+/// The message a contract failed with, or the message of the error when it has none
+///
+/// Nickel appends the message of a custom contract to its own, on the following lines.
+fn reason(message: &str) -> String {
+    match message.split_once('\n') {
+        Some((_, custom)) => custom.trim().to_string(),
+        None => message.to_string(),
+    }
+}
+
+/// A Nickel string literal of a path, to import it
+fn literal(path: &Path, span: Span) -> Result<String, LabeledError> {
+    value_to_nickel(&Value::string(path.display().to_string(), span), span)
+}
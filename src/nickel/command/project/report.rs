@@ -0,0 +1,167 @@
+use crate::NickelPlugin;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Record, Signature, Span, SyntaxShape, Type,
+    Value,
+};
+
+/// Columns naming the file a row is about, in the order they are looked up
+const FILE_COLUMNS: [&str; 3] = ["file", "entrypoint", "path"];
+
+/// The number of slowest and largest files reported by default
+const DEFAULT_TOP: usize = 5;
+
+#[derive(Clone)]
+pub struct NickelReport;
+
+impl PluginCommand for NickelReport {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel report"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel report")
+            .input_output_types(vec![(Type::table(), Type::record())])
+            .named(
+                "top",
+                SyntaxShape::Int,
+                "Number of slowest and largest files to report, 5 by default",
+                Some('n'),
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "Summarize the results of a batch of checks or renders"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Takes the table of a command run over many files, like `nickel inventory`, \
+         `nickel plan-render` or `nickel policy check`, and returns the `total` of rows, how many \
+         `failed`, and the `errors` counted by category, the first line of their `error`, or \
+         their `rule` for policy violations. Rows with a `wall_time`, like those of \
+         `nickel eval --measure`, give the `slowest` files, and rows with a `size` the `largest` \
+         ones. Files are named by the `file`, `entrypoint` or `path` column of a row."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Summarize the renders of a project for a CI summary",
+                example: "nickel plan-render render.ncl | nickel report | to json",
+                result: None,
+            },
+            Example {
+                description: "Find the slowest configurations to evaluate",
+                example: "glob **/*.ncl | each { |file| nickel eval $file --measure | reject value | insert file $file } | nickel report --top 10 | get slowest",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &NickelPlugin,
+        _engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let top = match call.get_flag::<i64>("top")? {
+            Some(top) if top < 0 => {
+                return Err(LabeledError::new("Invalid number of files")
+                    .with_label("Expected a positive number", span));
+            }
+            Some(top) => top as usize,
+            None => DEFAULT_TOP,
+        };
+
+        let mut total = 0;
+        let mut failed = 0;
+        let mut errors: Vec<(String, i64)> = Vec::new();
+        let mut times = Vec::new();
+        let mut sizes = Vec::new();
+        for row in input {
+            let record = row.as_record().map_err(|_| {
+                LabeledError::new("Invalid input type").with_label(
+                    format!("Expected a table row, found {}", row.get_type()),
+                    row.span(),
+                )
+            })?;
+            total += 1;
+            let file = FILE_COLUMNS
+                .iter()
+                .find_map(|column| record.get(*column))
+                .cloned()
+                .unwrap_or(Value::nothing(span));
+
+            if let Some(category) = error_category(record) {
+                failed += 1;
+                match errors.iter_mut().find(|(name, _)| *name == category) {
+                    Some((_, count)) => *count += 1,
+                    None => errors.push((category, 1)),
+                }
+            }
+            if let Some(Value::Duration { val, .. }) = record.get("wall_time") {
+                times.push((file.clone(), *val));
+            }
+            if let Some(Value::Filesize { val, .. }) = record.get("size") {
+                sizes.push((file, val.get()));
+            }
+        }
+
+        // The most frequent categories first, ties keeping the order they were met in
+        errors.sort_by(|a, b| b.1.cmp(&a.1));
+        let errors = errors
+            .into_iter()
+            .map(|(category, count)| (category, Value::int(count, span)))
+            .collect();
+
+        let mut record = Record::new();
+        record.push("total", Value::int(total, span));
+        record.push("failed", Value::int(failed, span));
+        record.push("errors", Value::record(errors, span));
+        record.push(
+            "slowest",
+            ranking(times, top, "wall_time", |t| Value::duration(t, span), span),
+        );
+        record.push(
+            "largest",
+            ranking(sizes, top, "size", |s| Value::filesize(s, span), span),
+        );
+        Ok(PipelineData::Value(Value::record(record, span), None))
+    }
+}
+
+/// The category of the error of a row, or `None` when it did not fail
+fn error_category(record: &Record) -> Option<String> {
+    let error = record
+        .get("error")
+        .or_else(|| record.get("rule"))
+        .and_then(|value| value.as_str().ok())?;
+    Some(error.lines().next().unwrap_or_default().to_string())
+}
+
+/// A table of the `top` files with the greatest measure, greatest first
+fn ranking(
+    mut measures: Vec<(Value, i64)>,
+    top: usize,
+    column: &str,
+    to_value: impl Fn(i64) -> Value,
+    span: Span,
+) -> Value {
+    measures.sort_by(|a, b| b.1.cmp(&a.1));
+    let rows = measures
+        .into_iter()
+        .take(top)
+        .map(|(file, measure)| {
+            let mut record = Record::new();
+            record.push("file", file);
+            record.push(column, to_value(measure));
+            Value::record(record, span)
+        })
+        .collect();
+    Value::list(rows, span)
+}
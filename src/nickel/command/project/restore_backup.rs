@@ -0,0 +1,132 @@
+use crate::NickelPlugin;
+use crate::nickel::{
+    input::{expand_paths, working_dir},
+    write::{DEFAULT_BACKUP_SUFFIX, WriteSet, read_backup, restore_backup},
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Record, Signature, SyntaxShape, Type, Value,
+};
+
+#[derive(Clone)]
+pub struct NickelRestoreBackup;
+
+impl PluginCommand for NickelRestoreBackup {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel restore-backup"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel restore-backup")
+            .input_output_types(vec![(Type::Nothing, Type::table())])
+            .rest(
+                "paths",
+                SyntaxShape::Filepath,
+                "Files to restore from their backups, or glob patterns matching them",
+            )
+            .named(
+                "backup-suffix",
+                SyntaxShape::String,
+                "Suffix added to the names of the backups, `.orig` by default",
+                None,
+            )
+            .switch(
+                "dry-run",
+                "Return the files that would be restored, with their diff, instead",
+                None,
+            )
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths",
+                None,
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "Restore files edited in place from the backups saved with `--backup`"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Each backup is moved back over its file, so a file can only be restored once. When some \
+         files have no backup, the others are still restored before the error is reported.\n\n\
+         With `--dry-run`, nothing is restored, and the files are returned with the `action` \
+         restoring them would take and the `diff` of their contents."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Undo the fixes applied to a file",
+                example: "nickel typecheck config.ncl --apply-fixes --backup; nickel restore-backup config.ncl",
+                result: None,
+            },
+            Example {
+                description: "Restore files backed up with another suffix",
+                example: "nickel restore-backup a.ncl b.ncl --backup-suffix .bak",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let base_dir = working_dir(engine, call)?;
+        let suffix = call
+            .get_flag::<String>("backup-suffix")?
+            .unwrap_or_else(|| DEFAULT_BACKUP_SUFFIX.to_string());
+
+        let paths = expand_paths(call.rest(0)?, &base_dir)?;
+        if call.has_flag("dry-run")? {
+            let mut writes = WriteSet::new();
+            let mut errors = Vec::new();
+            for path in paths {
+                match read_backup(&path, &suffix) {
+                    Ok(contents) => writes.add(path, contents),
+                    Err(e) => errors.push(e.to_string()),
+                }
+            }
+            if !errors.is_empty() {
+                return Err(errors.into_iter().fold(
+                    LabeledError::new("Missing backups")
+                        .with_help(format!("{} other file(s) could be restored", writes.len())),
+                    |error, e| error.with_label(e, span),
+                ));
+            }
+            return Ok(PipelineData::Value(writes.preview(span), None));
+        }
+
+        let mut rows = Vec::new();
+        let mut errors = Vec::new();
+        for path in paths {
+            match restore_backup(&path, &suffix) {
+                Ok(backup) => {
+                    let mut record = Record::new();
+                    record.push("path", Value::string(path.display().to_string(), span));
+                    record.push("backup", Value::string(backup.display().to_string(), span));
+                    rows.push(Value::record(record, span));
+                }
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+
+        if !errors.is_empty() {
+            let restored = rows.len();
+            return Err(errors.into_iter().fold(
+                LabeledError::new("Failed to restore backups")
+                    .with_help(format!("{restored} other file(s) were restored")),
+                |error, e| error.with_label(e, span),
+            ));
+        }
+        Ok(PipelineData::Value(Value::list(rows, span), None))
+    }
+}
@@ -0,0 +1,606 @@
+use crate::nickel::command::test_support::{eval, eval_error, temp_dir};
+use nu_protocol::Value;
+
+#[test]
+fn test_nickel_usages() {
+    let dir = temp_dir();
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    std::fs::write(
+        dir.join("lib/net.ncl"),
+        "let port = 80 in\n{ port = port, url = \"http://localhost:%{std.to_string port}\" }",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("app.ncl"),
+        "let net = import \"lib/net.ncl\" in\n{ listen = net.port }",
+    )
+    .unwrap();
+    std::fs::write(dir.join("other.ncl"), "{ port = 8080 }").unwrap();
+
+    let result = eval(&format!(
+        "nickel usages port lib/net.ncl --cwd '{}'",
+        dir.display()
+    ));
+    let kinds: Vec<_> = result
+        .as_list()
+        .unwrap()
+        .iter()
+        .map(|row| {
+            row.as_record()
+                .unwrap()
+                .get("kind")
+                .unwrap()
+                .as_str()
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        ["definition", "definition", "reference", "reference"]
+    );
+
+    let result = eval(&format!(
+        "nickel usages port lib/net.ncl --project . --cwd '{}'",
+        dir.display()
+    ));
+    let rows = result.as_list().unwrap();
+    assert_eq!(rows.len(), 5);
+    let last = rows.last().unwrap().as_record().unwrap();
+    assert!(
+        last.get("file")
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .ends_with("app.ncl")
+    );
+    assert_eq!(last.get("kind"), Some(&Value::test_string("field_access")));
+    assert_eq!(last.get("line"), Some(&Value::test_int(2)));
+    assert_eq!(
+        last.get("context"),
+        Some(&Value::test_string("{ listen = net.port }"))
+    );
+
+    // An annotated field is defined, while an annotated expression uses the name
+    std::fs::write(
+        dir.join("schema.ncl"),
+        "let Schema = { port | Number } in\nlet cfg = { port = 80 } in\ncfg | Schema",
+    )
+    .unwrap();
+    let result = eval(&format!(
+        "nickel usages cfg schema.ncl --cwd '{}' | get kind",
+        dir.display()
+    ));
+    assert_eq!(
+        result,
+        Value::test_list(vec![
+            Value::test_string("definition"),
+            Value::test_string("reference")
+        ])
+    );
+    let result = eval(&format!(
+        "nickel usages port schema.ncl --cwd '{}' | get kind",
+        dir.display()
+    ));
+    assert_eq!(
+        result,
+        Value::test_list(vec![
+            Value::test_string("definition"),
+            Value::test_string("definition")
+        ])
+    );
+}
+
+#[test]
+fn test_nickel_index() {
+    let dir = temp_dir();
+    std::fs::write(dir.join("lib.ncl"), "{ port = 80 }").unwrap();
+    std::fs::write(dir.join("app.ncl"), "(import \"lib.ncl\").port").unwrap();
+
+    let result = eval(&format!("nickel index '{}'", dir.display()));
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("files"), Some(&Value::test_int(2)));
+    assert_eq!(record.get("indexed"), Some(&Value::test_int(2)));
+    assert_eq!(record.get("imports"), Some(&Value::test_int(1)));
+
+    let result = eval(&format!(
+        "nickel index '{dir}'; nickel index '{dir}'",
+        dir = dir.display()
+    ));
+    let record = result.as_record().unwrap();
+    assert_eq!(record.get("files"), Some(&Value::test_int(2)));
+    assert_eq!(record.get("indexed"), Some(&Value::test_int(0)));
+}
+
+#[test]
+fn test_nickel_dead_code() {
+    let dir = temp_dir();
+    std::fs::write(
+        dir.join("lib.ncl"),
+        "let unused = 1 in\nlet port = 80 in\n{ port = port }",
+    )
+    .unwrap();
+    std::fs::write(dir.join("app.ncl"), "(import \"lib.ncl\").port").unwrap();
+    std::fs::write(dir.join("old.ncl"), "{ port = 8080 }").unwrap();
+
+    let dead = |flags: &str| -> Vec<(String, Value)> {
+        eval(&format!("nickel dead-code '{}' {flags}", dir.display()))
+            .as_list()
+            .unwrap()
+            .iter()
+            .map(|row| {
+                let row = row.as_record().unwrap();
+                let file = row.get("file").unwrap().as_str().unwrap();
+                let file = file.rsplit('/').next().unwrap().to_string();
+                (file, row.get("name").unwrap().clone())
+            })
+            .collect()
+    };
+
+    assert_eq!(
+        dead(""),
+        [("lib.ncl".to_string(), Value::test_string("unused"))]
+    );
+    assert_eq!(
+        dead(&format!(
+            "--entrypoint ['{}']",
+            dir.join("app.ncl").display()
+        )),
+        [
+            ("lib.ncl".to_string(), Value::test_string("unused")),
+            ("old.ncl".to_string(), Value::test_nothing()),
+        ]
+    );
+}
+
+#[test]
+fn test_nickel_entrypoints() {
+    let dir = temp_dir();
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    std::fs::write(dir.join("lib/net.ncl"), "{ port = 80 }").unwrap();
+    std::fs::write(
+        dir.join("prod.ncl"),
+        "{ port = (import \"lib/net.ncl\").port }",
+    )
+    .unwrap();
+    std::fs::write(dir.join("dev.ncl"), "{ port = 8080 }").unwrap();
+
+    let result = eval(&format!("nickel entrypoints '{}' --render", dir.display()));
+    let rows: Vec<_> = result
+        .as_list()
+        .unwrap()
+        .iter()
+        .map(|row| {
+            let row = row.as_record().unwrap();
+            let file = row.get("file").unwrap().as_str().unwrap();
+            let port = row.get("value").unwrap().as_record().unwrap().get("port");
+            (
+                file.rsplit('/').next().unwrap().to_string(),
+                row.get("dependencies").unwrap().as_int().unwrap(),
+                port.unwrap().as_int().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        rows,
+        [
+            ("dev.ncl".to_string(), 0, 8080),
+            ("prod.ncl".to_string(), 1, 80)
+        ]
+    );
+
+    let result = eval(&format!("nickel entrypoints '{}' --measure", dir.display()));
+    for row in result.as_list().unwrap() {
+        let row = row.as_record().unwrap();
+        assert!(row.get("value").is_some());
+        assert!(row.get("wall_time").unwrap().as_duration().unwrap() > 0);
+    }
+}
+
+#[test]
+fn test_nickel_plan_render() {
+    let dir = temp_dir();
+    std::fs::write(dir.join("app.ncl"), "{ port | Number, image = \"web\" }").unwrap();
+    std::fs::write(
+        dir.join("render.ncl"),
+        r#"{
+  renders = [
+    { entrypoint = "app.ncl", output = "out/prod.json", assign = ["port=80"] },
+    { entrypoint = "app.ncl", output = "out/dev.yaml", assign = ["port=8080"], override = ["image=\"web-dev\""] },
+    { entrypoint = "app.ncl", output = "out/broken.toml" },
+  ],
+}"#,
+    )
+    .unwrap();
+
+    let plan_render = || -> Vec<(String, String)> {
+        eval(&format!(
+            "nickel plan-render render.ncl --cwd '{}'",
+            dir.display()
+        ))
+        .as_list()
+        .unwrap()
+        .iter()
+        .map(|row| {
+            let row = row.as_record().unwrap();
+            (
+                row.get("format").unwrap().as_str().unwrap().to_string(),
+                row.get("status").unwrap().as_str().unwrap().to_string(),
+            )
+        })
+        .collect()
+    };
+
+    // A failed render leaves every output untouched
+    std::fs::create_dir_all(dir.join("out")).unwrap();
+    std::fs::write(dir.join("out/prod.json"), "{}").unwrap();
+    assert_eq!(
+        plan_render(),
+        [
+            ("json".to_string(), "skipped".to_string()),
+            ("yaml".to_string(), "skipped".to_string()),
+            ("toml".to_string(), "failed".to_string()),
+        ]
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.join("out/prod.json")).unwrap(),
+        "{}"
+    );
+    assert!(!dir.join("out/dev.yaml").exists());
+
+    std::fs::write(
+        dir.join("render.ncl"),
+        r#"{
+  renders = [
+    { entrypoint = "app.ncl", output = "out/prod.json", assign = ["port=80"] },
+    { entrypoint = "app.ncl", output = "out/dev/app.yaml", assign = ["port=8080"], override = ["image=\"web-dev\""] },
+  ],
+}"#,
+    )
+    .unwrap();
+    let preview = eval(&format!(
+        "nickel plan-render render.ncl --dry-run --cwd '{}'",
+        dir.display()
+    ));
+    let actions: Vec<_> = preview
+        .as_list()
+        .unwrap()
+        .iter()
+        .map(|row| row.as_record().unwrap().get("action").unwrap().clone())
+        .collect();
+    assert_eq!(
+        actions,
+        [Value::test_string("modify"), Value::test_string("create")]
+    );
+    assert!(!dir.join("out/dev").exists());
+
+    assert_eq!(
+        plan_render(),
+        [
+            ("json".to_string(), "rendered".to_string()),
+            ("yaml".to_string(), "rendered".to_string()),
+        ]
+    );
+
+    let prod: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(dir.join("out/prod.json")).unwrap()).unwrap();
+    assert_eq!(prod, serde_json::json!({ "port": 80, "image": "web" }));
+    let dev = std::fs::read_to_string(dir.join("out/dev/app.yaml")).unwrap();
+    assert!(dev.contains("image: web-dev"));
+    let leftovers: Vec<_> = std::fs::read_dir(dir.join("out"))
+        .unwrap()
+        .filter(|entry| {
+            let name = entry.as_ref().unwrap().file_name();
+            name.to_string_lossy().starts_with('.')
+        })
+        .collect();
+    assert!(leftovers.is_empty());
+}
+
+#[test]
+fn test_nickel_restore_backup() {
+    let dir = temp_dir();
+    let path = dir.join("typo.ncl");
+    let original = "let port = 80 in { port = prot }";
+    std::fs::write(&path, original).unwrap();
+
+    eval(&format!(
+        "nickel typecheck typo.ncl --apply-fixes --backup --cwd '{}'",
+        dir.display()
+    ));
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "let port = 80 in { port = port }"
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.join("typo.ncl.orig")).unwrap(),
+        original
+    );
+
+    let result = eval(&format!(
+        "nickel restore-backup typo.ncl --dry-run --cwd '{}'",
+        dir.display()
+    ));
+    let row = result.as_list().unwrap()[0].as_record().unwrap().clone();
+    assert_eq!(row.get("action"), Some(&Value::test_string("modify")));
+    assert!(dir.join("typo.ncl.orig").exists());
+
+    let result = eval(&format!(
+        "nickel restore-backup typo.ncl --cwd '{}'",
+        dir.display()
+    ));
+    let row = result.as_list().unwrap()[0].as_record().unwrap().clone();
+    assert_eq!(
+        row.get("backup"),
+        Some(&Value::test_string(
+            dir.join("typo.ncl.orig").display().to_string()
+        ))
+    );
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), original);
+    assert!(!dir.join("typo.ncl.orig").exists());
+
+    let error = eval_error(&format!(
+        "nickel restore-backup typo.ncl --cwd '{}'",
+        dir.display()
+    ));
+    assert_eq!(error.msg, "Failed to restore backups");
+}
+
+#[test]
+fn test_nickel_inventory() {
+    let dir = temp_dir();
+    std::fs::write(
+        dir.join("web.ncl"),
+        "{ service.name = \"web\", image.tag = \"1.2\" }",
+    )
+    .unwrap();
+    std::fs::write(dir.join("db.yaml"), "service:\n  name: db\n").unwrap();
+    std::fs::write(
+        dir.join("broken.ncl"),
+        "{ service.name = \"cache\", image.tag = std.fail_with \"no tag\" }",
+    )
+    .unwrap();
+
+    let result = eval(&format!(
+        "nickel inventory [service.name image.tag] web.ncl db.yaml broken.ncl --cwd '{}'",
+        dir.display()
+    ));
+    let rows: Vec<_> = result
+        .as_list()
+        .unwrap()
+        .iter()
+        .map(|row| row.as_record().unwrap().clone())
+        .collect();
+    let column = |name: &str| -> Vec<_> { rows.iter().map(|row| row.get(name).cloned()).collect() };
+
+    assert!(
+        rows[0]
+            .get("file")
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .ends_with("web.ncl")
+    );
+    assert_eq!(
+        column("service.name"),
+        ["web", "db", "cache"].map(|name| Some(Value::test_string(name)))
+    );
+    assert_eq!(
+        column("image.tag"),
+        [
+            Some(Value::test_string("1.2")),
+            Some(Value::test_nothing()),
+            Some(Value::test_nothing())
+        ]
+    );
+    let errors = column("error");
+    assert_eq!(errors[0], Some(Value::test_nothing()));
+    assert!(
+        errors[2]
+            .as_ref()
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .contains("no tag")
+    );
+
+    // The order of the rows does not depend on how many files are evaluated at once
+    let sequential = eval(&format!(
+        "nickel inventory [service.name image.tag] web.ncl db.yaml broken.ncl --jobs 1 --nice \
+         --cwd '{}'",
+        dir.display()
+    ));
+    assert_eq!(sequential, result);
+
+    let error = eval_error(&format!(
+        "nickel inventory [service.name] web.ncl --jobs 0 --cwd '{}'",
+        dir.display()
+    ));
+    assert_eq!(error.msg, "Invalid number of jobs");
+}
+
+#[test]
+fn test_nickel_policy_check() {
+    let dir = temp_dir();
+    std::fs::write(
+        dir.join("policy.ncl"),
+        r#"{
+  replicated = std.contract.from_predicate (fun config => config.replicas > 1),
+  pinned = std.contract.custom (fun label config =>
+    if std.string.contains ":latest" config.image then
+      'Error { message = "images must be pinned" }
+    else
+      'Ok config),
+  shape = { replicas | Number, image | String },
+}"#,
+    )
+    .unwrap();
+    std::fs::write(dir.join("web.ncl"), "{ replicas = 3, image = \"web:1.2\" }").unwrap();
+    std::fs::write(dir.join("cache.yaml"), "replicas: 1\nimage: cache:latest\n").unwrap();
+    std::fs::write(
+        dir.join("db.ncl"),
+        "{ replicas = \"two\", image = \"db:9\" }",
+    )
+    .unwrap();
+
+    let result = eval(&format!(
+        "nickel policy check policy.ncl web.ncl cache.yaml db.ncl --cwd '{}'",
+        dir.display()
+    ));
+    let violations: Vec<_> = result
+        .as_list()
+        .unwrap()
+        .iter()
+        .map(|row| {
+            let row = row.as_record().unwrap();
+            let text = |name: &str| row.get(name).unwrap().as_str().unwrap().to_string();
+            let file = text("file");
+            (
+                file.rsplit('/').next().unwrap().to_string(),
+                text("rule"),
+                text("reason"),
+            )
+        })
+        .collect();
+    let violation = |file: &str, rule: &str, reason: &str| {
+        (file.to_string(), rule.to_string(), reason.to_string())
+    };
+    assert_eq!(
+        violations,
+        [
+            violation("cache.yaml", "pinned", "images must be pinned"),
+            violation("cache.yaml", "replicated", "contract broken by a value"),
+            violation("db.ncl", "replicated", "dynamic type error"),
+            violation(
+                "db.ncl",
+                "shape",
+                "contract broken by the value of `replicas`"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_nickel_report() {
+    let result = eval(
+        "[
+            [file error wall_time size];
+            [a.ncl null 2sec 10b]
+            [b.ncl \"Failed to evaluate\nmissing field\" 5sec 30b]
+            [c.ncl \"Failed to read file\" 1sec 20b]
+            [d.ncl \"Failed to evaluate\ninfinite recursion\" 3sec null]
+        ] | nickel report --top 2",
+    );
+    let report = result.as_record().unwrap();
+    assert_eq!(report.get("total"), Some(&Value::test_int(4)));
+    assert_eq!(report.get("failed"), Some(&Value::test_int(3)));
+
+    let errors = report.get("errors").unwrap().as_record().unwrap();
+    let errors: Vec<_> = errors
+        .iter()
+        .map(|(category, count)| (category.as_str(), count.as_int().unwrap()))
+        .collect();
+    assert_eq!(
+        errors,
+        [("Failed to evaluate", 2), ("Failed to read file", 1)]
+    );
+
+    let files = |column: &str| -> Vec<String> {
+        report
+            .get(column)
+            .unwrap()
+            .as_list()
+            .unwrap()
+            .iter()
+            .map(|row| {
+                let file = row.as_record().unwrap().get("file").unwrap();
+                file.as_str().unwrap().to_string()
+            })
+            .collect()
+    };
+    assert_eq!(files("slowest"), ["b.ncl", "d.ncl"]);
+    assert_eq!(files("largest"), ["b.ncl", "c.ncl"]);
+}
+
+#[test]
+fn test_nickel_compat() {
+    let dir = temp_dir();
+    std::fs::write(
+        dir.join("old.ncl"),
+        r#"{
+  name | String,
+  port | std.number.Integer,
+  level | [| 'debug, 'info |],
+  tags | Array String | optional,
+  host | String,
+  server = { timeout | Number | default = 5 },
+}"#,
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("new.ncl"),
+        r#"{
+  name | String,
+  port | Number,
+  level | [| 'debug |],
+  tags | Array String,
+  hostname | String,
+  region | String | default = "eu",
+  server = { timeout | Number, retries | Number },
+}"#,
+    )
+    .unwrap();
+
+    let result = eval(&format!(
+        "nickel compat old.ncl new.ncl --cwd '{}'",
+        dir.display()
+    ));
+    let changes: Vec<_> = result
+        .as_list()
+        .unwrap()
+        .iter()
+        .map(|row| {
+            let row = row.as_record().unwrap();
+            let text = |name: &str| row.get(name).unwrap().as_str().unwrap().to_string();
+            (text("path"), text("change"), text("severity"))
+        })
+        .collect();
+    let change = |path: &str, kind: &str, severity: &str| {
+        (path.to_string(), kind.to_string(), severity.to_string())
+    };
+    assert_eq!(
+        changes,
+        [
+            change("host", "renamed", "breaking"),
+            change("level", "contract", "breaking"),
+            change("port", "contract", "compatible"),
+            change("region", "added", "compatible"),
+            change("server.retries", "added", "breaking"),
+            change("server.timeout", "required", "breaking"),
+            change("tags", "required", "breaking"),
+        ]
+    );
+
+    let result = eval(&format!(
+        "nickel compat old.ncl new.ncl --format markdown --cwd '{}'",
+        dir.display()
+    ));
+    let changelog = result.as_str().unwrap();
+    assert!(changelog.starts_with(
+        "### Breaking changes\n\n- `host` seems renamed to `hostname`\n- The contract of `level`"
+    ));
+    assert!(changelog.contains(
+        "- `server.retries` was added and is required (`Number`)\n- `server.timeout` became required\n"
+    ));
+    assert!(changelog.ends_with(
+        "### Compatible changes\n\n\
+         - The contract of `port` was widened (`std.number.Integer` to `Number`)\n\
+         - `region` was added (`String`)\n"
+    ));
+
+    std::fs::write(dir.join("list.ncl"), "[1]").unwrap();
+    let error = eval_error(&format!(
+        "nickel compat old.ncl list.ncl --cwd '{}'",
+        dir.display()
+    ));
+    assert_eq!(error.msg, "Not a record contract");
+}
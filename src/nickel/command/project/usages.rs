@@ -0,0 +1,111 @@
+use super::{index_error, lex_file, read_file, usage_row};
+use crate::NickelPlugin;
+use crate::nickel::{
+    input::working_dir,
+    symbols::{UsageKind, usages},
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Signature, SyntaxShape, Type, Value,
+};
+
+#[derive(Clone)]
+pub struct NickelUsages;
+
+impl PluginCommand for NickelUsages {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel usages"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel usages")
+            .input_output_types(vec![(Type::Nothing, Type::table())])
+            .required("name", SyntaxShape::String, "Binding or field to look for")
+            .required("path", SyntaxShape::Filepath, "Nickel file defining it")
+            .named(
+                "project",
+                SyntaxShape::Directory,
+                "Also report field accesses in the files of this directory importing the file",
+                Some('p'),
+            )
+            .named(
+                "cwd",
+                SyntaxShape::Directory,
+                "Base directory for relative paths",
+                None,
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "Find the usages of a binding or record field of a Nickel file"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Usages are found by name from the tokens of the files, so a local variable shadowing the \
+         binding is reported as well. In importing files, only field accesses (`lib.name`) are \
+         reported. The project is indexed on first use, see `nickel index`."
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Find the usages of a binding in a file",
+                example: "nickel usages port config.ncl",
+                result: None,
+            },
+            Example {
+                description: "Find the usages of a field of a library across a project",
+                example: "nickel usages make_service lib/services.ncl --project .",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let base_dir = working_dir(engine, call)?;
+        let name: String = call.req(0)?;
+        let path = base_dir.join(call.req::<String>(1)?);
+
+        let source = read_file(&path, span)?;
+        let tokens = lex_file(&path, &source, span)?;
+        let mut rows: Vec<Value> = usages(&tokens, &name)
+            .iter()
+            .map(|usage| usage_row(&path, &source, usage, span))
+            .collect();
+
+        if let Some(project) = call.get_flag::<String>("project")? {
+            let target = path.canonicalize().ok();
+            let (_, _, importers) = plugin
+                .index
+                .with_index(&base_dir.join(project), |index| {
+                    let Some(target) = &target else {
+                        return Vec::new();
+                    };
+                    index
+                        .importers(target)
+                        .flat_map(|(file, indexed)| {
+                            indexed
+                                .usages(&name)
+                                .iter()
+                                .filter(|usage| usage.kind == UsageKind::FieldAccess)
+                                .map(|usage| usage_row(file, &indexed.source, usage, span))
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .map_err(|e| index_error(e, span))?;
+            rows.extend(importers);
+        }
+
+        Ok(PipelineData::Value(Value::list(rows, span), None))
+    }
+}
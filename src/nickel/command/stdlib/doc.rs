@@ -0,0 +1,104 @@
+use crate::NickelPlugin;
+use crate::nickel::stdlib::find_stdlib_entry;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Record, Signature, SyntaxShape, Type, Value,
+};
+
+#[derive(Clone)]
+pub struct NickelStdDoc;
+
+impl PluginCommand for NickelStdDoc {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel std doc"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel std doc")
+            .input_output_types(vec![(Type::Nothing, Type::record())])
+            .required(
+                "symbol",
+                SyntaxShape::String,
+                "Standard library symbol, e.g. `std.array.fold_left` or `array.fold_left`",
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "Show the documentation of a Nickel standard library symbol"
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Show the documentation of a function",
+                example: "nickel std doc std.array.fold_left",
+                result: None,
+            },
+            Example {
+                description: "Print only the doc string",
+                example: "nickel std doc string.split | get doc",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &NickelPlugin,
+        _engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let symbol: String = call.req(0)?;
+
+        let entry = find_stdlib_entry(&symbol)
+            .map_err(|e| {
+                LabeledError::new("Failed to load the standard library").with_label(e, span)
+            })?
+            .ok_or_else(|| {
+                LabeledError::new("Unknown standard library symbol")
+                    .with_label(
+                        format!("`{symbol}` is not part of the standard library"),
+                        span,
+                    )
+                    .with_help("use `nickel std ls` to list the available symbols")
+            })?;
+
+        let optional_string =
+            |s: Option<&String>| s.map_or(Value::nothing(span), |s| Value::string(s, span));
+
+        let mut record = Record::new();
+        record.push("name", Value::string(&entry.name, span));
+        record.push("kind", Value::string(entry.kind.as_str(), span));
+        record.push("signature", optional_string(entry.signature.as_ref()));
+        record.push(
+            "contracts",
+            Value::list(
+                entry
+                    .contracts
+                    .iter()
+                    .map(|c| Value::string(c, span))
+                    .collect(),
+                span,
+            ),
+        );
+        record.push("doc", optional_string(entry.doc.as_ref()));
+        record.push(
+            "examples",
+            Value::list(
+                entry
+                    .examples()
+                    .into_iter()
+                    .map(|e| Value::string(e, span))
+                    .collect(),
+                span,
+            ),
+        );
+
+        Ok(PipelineData::Value(Value::record(record, span), None))
+    }
+}
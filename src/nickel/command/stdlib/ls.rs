@@ -0,0 +1,88 @@
+use crate::NickelPlugin;
+use crate::nickel::stdlib::{qualified_name, stdlib_entries};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, PipelineData, Record, Signature, SyntaxShape, Type, Value,
+};
+
+#[derive(Clone)]
+pub struct NickelStdLs;
+
+impl PluginCommand for NickelStdLs {
+    type Plugin = NickelPlugin;
+
+    fn name(&self) -> &str {
+        "nickel std ls"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("nickel std ls")
+            .input_output_types(vec![(Type::Nothing, Type::table())])
+            .optional(
+                "module",
+                SyntaxShape::String,
+                "Only list symbols under this module, e.g. `array` or `std.string`",
+            )
+            .category(Category::Misc)
+    }
+
+    fn description(&self) -> &str {
+        "List the modules and functions of the Nickel standard library"
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "List the whole standard library",
+                example: "nickel std ls",
+                result: None,
+            },
+            Example {
+                description: "List the functions of the array module",
+                example: "nickel std ls array | where kind == function",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &NickelPlugin,
+        _engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let span = call.head;
+        let module = call.opt::<String>(0)?.map(|m| qualified_name(&m) + ".");
+
+        let entries = stdlib_entries().map_err(|e| {
+            LabeledError::new("Failed to load the standard library").with_label(e, span)
+        })?;
+
+        let rows = entries
+            .iter()
+            .filter(|entry| module.as_ref().is_none_or(|m| entry.name.starts_with(m)))
+            .map(|entry| {
+                let mut record = Record::new();
+                record.push("name", Value::string(&entry.name, span));
+                record.push("kind", Value::string(entry.kind.as_str(), span));
+                record.push(
+                    "signature",
+                    entry
+                        .signature
+                        .as_ref()
+                        .map_or(Value::nothing(span), |s| Value::string(s, span)),
+                );
+                record.push(
+                    "doc",
+                    entry
+                        .summary()
+                        .map_or(Value::nothing(span), |s| Value::string(s, span)),
+                );
+                Value::record(record, span)
+            })
+            .collect();
+
+        Ok(PipelineData::Value(Value::list(rows, span), None))
+    }
+}
@@ -0,0 +1,8 @@
+mod doc;
+mod ls;
+
+#[cfg(test)]
+mod tests;
+
+pub use doc::NickelStdDoc;
+pub use ls::NickelStdLs;
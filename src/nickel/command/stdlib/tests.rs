@@ -0,0 +1,39 @@
+use crate::nickel::command::test_support::eval;
+use nu_protocol::Value;
+
+#[test]
+fn test_nickel_std_ls() {
+    let result = eval("nickel std ls array");
+    let rows = result.as_list().unwrap();
+    let fold_left = rows
+        .iter()
+        .map(|row| row.as_record().unwrap())
+        .find(|row| row.get("name") == Some(&Value::test_string("std.array.fold_left")))
+        .expect("std.array.fold_left should be listed");
+
+    assert_eq!(fold_left.get("kind"), Some(&Value::test_string("function")));
+    assert!(fold_left.get("signature").unwrap().as_str().is_ok());
+}
+
+#[test]
+fn test_nickel_std_doc() {
+    let result = eval("nickel std doc array.fold_left");
+    let record = result.as_record().unwrap();
+
+    assert_eq!(
+        record.get("name"),
+        Some(&Value::test_string("std.array.fold_left"))
+    );
+    assert!(
+        record
+            .get("doc")
+            .and_then(|doc| doc.as_str().ok())
+            .is_some_and(|doc| doc.contains("Folds a function over an array"))
+    );
+    let examples = record.get("examples").unwrap().as_list().unwrap();
+    assert!(
+        examples
+            .iter()
+            .any(|e| e.as_str().unwrap().starts_with("std.array.fold_left"))
+    );
+}
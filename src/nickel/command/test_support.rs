@@ -0,0 +1,78 @@
+use crate::NickelPlugin;
+use crate::nickel::{
+    command::core::serve_worker,
+    worker::{WORKER_VAR, WorkerCommand},
+};
+use nu_plugin_test_support::PluginTest;
+use nu_protocol::{LabeledError, PipelineMetadata, Span, Value};
+use std::path::{Path, PathBuf};
+
+/// Run a Nushell pipeline against a fresh plugin and collect its output
+///
+/// The pipeline runs from the crate root, so relative paths like `examples/...` resolve.
+pub fn eval(source: &str) -> Value {
+    plugin_test()
+        .eval(source)
+        .expect("evaluation should succeed")
+        .into_value(Span::test_data())
+        .expect("output should collect into a value")
+}
+
+/// Run a Nushell pipeline and return the `content_type` of its output metadata
+pub fn eval_content_type(source: &str) -> Option<String> {
+    eval_metadata(source).and_then(|metadata| metadata.content_type)
+}
+
+/// Run a Nushell pipeline and return its output metadata
+pub fn eval_metadata(source: &str) -> Option<PipelineMetadata> {
+    plugin_test()
+        .eval(source)
+        .expect("evaluation should succeed")
+        .metadata()
+}
+
+/// Run a Nushell pipeline that is expected to fail, and return the error
+pub fn eval_error(source: &str) -> LabeledError {
+    let error = match plugin_test().eval(source) {
+        Ok(data) => data.into_value(Span::test_data()),
+        Err(error) => Err(error),
+    }
+    .expect_err("evaluation should fail");
+    LabeledError::from_diagnostic(&error)
+}
+
+/// Create a fresh, empty directory under the system temp directory
+pub fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("nu_plugin_nickel-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn plugin_test() -> PluginTest {
+    let plugin = NickelPlugin {
+        worker: WorkerCommand::new(
+            std::env::current_exe().unwrap(),
+            [
+                "nickel::command::test_support::worker",
+                "--exact",
+                "--quiet",
+            ],
+        ),
+        ..Default::default()
+    };
+    let mut test = PluginTest::new("nickel", plugin.into()).expect("plugin should register");
+    test.engine_state_mut()
+        .add_env_var("PWD".into(), Value::test_string(env!("CARGO_MANIFEST_DIR")));
+    test
+}
+
+/// Serve the job of a worker process started by a test
+///
+/// The test binary has no plugin entry point, so the plugin of the tests starts it to run this
+/// test alone as its worker.
+#[test]
+fn worker() {
+    if let Some(job) = std::env::var_os(WORKER_VAR) {
+        serve_worker(Path::new(&job));
+    }
+}
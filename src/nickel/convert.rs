@@ -0,0 +1,1000 @@
+use crate::nickel::lex::is_identifier;
+use malachite::base::{num::conversion::traits::RoundingFrom, rounding_modes::RoundingMode};
+use nickel_lang_core::{
+    identifier::LocIdent,
+    term::{Number, RichTerm, Term, array::Array},
+    typ::{DictTypeFlavour, EnumRowsIteratorItem, RecordRowsIteratorItem, Type, TypeF, VarKind},
+};
+use nu_protocol::{LabeledError, Record, Span, Spanned, Value};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// Nesting depth of records, arrays and enum variants past which converting a value fails
+///
+/// Converting does not recurse, but Nushell does when it displays or sends a value, so a value
+/// nested deeper than this is an error rather than a stack overflow later on.
+pub const MAX_NESTING: usize = 4096;
+
+/// Convert a JSON value into the equivalent Nushell value
+pub fn json_to_value(json: &serde_json::Value, span: Span) -> Result<Value, LabeledError> {
+    json_to_value_within(json, MAX_NESTING, span)
+}
+
+/// Like [`json_to_value`], failing on values nested more than `max_nesting` levels deep
+pub fn json_to_value_within(
+    json: &serde_json::Value,
+    max_nesting: usize,
+    span: Span,
+) -> Result<Value, LabeledError> {
+    use serde_json::Value as Json;
+
+    walk(
+        json,
+        max_nesting,
+        span,
+        |json| {
+            Ok(match json {
+                Json::Null => Step::Value(Value::nothing(span)),
+                Json::Bool(b) => Step::Value(Value::bool(*b, span)),
+                Json::Number(n) => Step::Value(match n.as_i64() {
+                    Some(i) => Value::int(i, span),
+                    None => Value::float(n.as_f64().unwrap_or(f64::NAN), span),
+                }),
+                Json::String(s) => Step::Value(Value::string(s, span)),
+                Json::Array(items) => Step::Container(None, items.iter().collect()),
+                Json::Object(fields) => {
+                    let (keys, values) = fields.iter().unzip();
+                    Step::Container(Some(keys), values)
+                }
+            })
+        },
+        |keys: Option<Vec<&String>>, values| match keys {
+            Some(keys) => Value::record(keys.into_iter().cloned().zip(values).collect(), span),
+            None => Value::list(values, span),
+        },
+    )
+}
+
+/// What converting a value starts with
+enum Step<'a, T, S> {
+    /// The value holds no other values, and is converted
+    Value(Value),
+    /// The value holds `children`, converted before it is
+    Container(S, Vec<&'a T>),
+}
+
+/// A value holding others being converted, with its children converted so far
+struct Pending<'a, T, S> {
+    shape: S,
+    children: Vec<&'a T>,
+    values: Vec<Value>,
+}
+
+/// Convert a tree of values with a stack of its own rather than by recursion
+///
+/// `open` converts a value or lists its children, and `close` builds a value holding others
+/// from its converted children, so that a deeply nested value does not overflow the stack of
+/// the plugin.
+fn walk<'a, T, S>(
+    root: &'a T,
+    max_nesting: usize,
+    span: Span,
+    mut open: impl FnMut(&'a T) -> Result<Step<'a, T, S>, LabeledError>,
+    mut close: impl FnMut(S, Vec<Value>) -> Value,
+) -> Result<Value, LabeledError> {
+    let mut stack: Vec<Pending<'a, T, S>> = Vec::new();
+    let mut next = Some(root);
+    let mut value = None;
+    loop {
+        if let Some(item) = next.take() {
+            match open(item)? {
+                Step::Value(converted) => value = Some(converted),
+                Step::Container(shape, children) => {
+                    if stack.len() == max_nesting {
+                        return Err(LabeledError::new("Value nested too deeply")
+                            .with_label(
+                                format!(
+                                    "Records, arrays or enum variants are nested more than \
+                                     {max_nesting} levels deep"
+                                ),
+                                span,
+                            )
+                            .with_help(
+                                "raise the limit with `nickel eval --max-nesting`, if the value \
+                                 is meant to be this deep",
+                            ));
+                    }
+                    stack.push(Pending {
+                        shape,
+                        children,
+                        values: Vec::new(),
+                    });
+                }
+            }
+        }
+
+        let Some(pending) = stack.last_mut() else {
+            return Ok(value.expect("a value is converted before the stack empties"));
+        };
+        pending.values.extend(value.take());
+        match pending.children.get(pending.values.len()) {
+            Some(child) => next = Some(*child),
+            None => {
+                let pending = stack.pop().expect("the stack is not empty");
+                value = Some(close(pending.shape, pending.values));
+            }
+        }
+    }
+}
+
+/// Convert a fully evaluated Nickel term into the equivalent Nushell value
+///
+/// Fields that are not exported, and optional fields without a definition, are skipped just like
+/// when exporting with the Nickel CLI.
+pub fn nickel_to_nu_value(term: &RichTerm, span: Span) -> Result<Value, LabeledError> {
+    nickel_to_nu_value_with(term, Conversion::default(), span)
+}
+
+/// How the values of a Nickel term without a single Nushell equivalent are converted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Conversion {
+    pub enums: EnumPolicy,
+    pub numbers: NumberPolicy,
+    pub keys: KeyOrder,
+    /// Nesting depth past which converting fails, [`MAX_NESTING`] by default
+    pub max_nesting: usize,
+}
+
+impl Default for Conversion {
+    fn default() -> Self {
+        Self {
+            enums: EnumPolicy::default(),
+            numbers: NumberPolicy::default(),
+            keys: KeyOrder::default(),
+            max_nesting: MAX_NESTING,
+        }
+    }
+}
+
+/// The values of a Nickel term holding others
+enum Shape {
+    Record(Vec<LocIdent>),
+    Array(TypedColumns),
+    Variant(LocIdent),
+}
+
+/// Like [`nickel_to_nu_value`], converting enum variants and numbers as `conversion` says
+///
+/// Enum tags without a payload, like `'Debug`, always become strings.
+pub fn nickel_to_nu_value_with(
+    term: &RichTerm,
+    conversion: Conversion,
+    span: Span,
+) -> Result<Value, LabeledError> {
+    walk(
+        term,
+        conversion.max_nesting,
+        span,
+        |term| {
+            Ok(match term.as_ref() {
+                Term::EnumVariant { tag, arg, .. } => {
+                    Step::Container(Shape::Variant(*tag), vec![arg])
+                }
+                Term::Array(items, _) => {
+                    let items: Vec<&RichTerm> = items.iter().collect();
+                    Step::Container(Shape::Array(TypedColumns::new(&items)), items)
+                }
+                Term::Record(data) => {
+                    // The fields `iter_serializable` gives, with the places they are defined at
+                    let mut fields = Vec::new();
+                    for (id, field) in &data.fields {
+                        match &field.value {
+                            _ if field.metadata.not_exported => {}
+                            Some(value) => fields.push((*id, value)),
+                            None if field.metadata.opt => {}
+                            None => {
+                                return Err(LabeledError::new("Missing field definition")
+                                    .with_label(format!("Field `{id}` has no value"), span));
+                            }
+                        }
+                    }
+                    conversion.keys.sort(&mut fields);
+                    let (labels, children) = fields.into_iter().unzip();
+                    Step::Container(Shape::Record(labels), children)
+                }
+                other => Step::Value(scalar_to_value(other, conversion.numbers, span)?),
+            })
+        },
+        |shape, mut values| match shape {
+            Shape::Record(labels) => Value::record(
+                labels
+                    .iter()
+                    .map(|id| id.label().to_string())
+                    .zip(values)
+                    .collect(),
+                span,
+            ),
+            Shape::Array(columns) => {
+                for value in &mut values {
+                    columns.apply(value, span);
+                }
+                Value::list(values, span)
+            }
+            Shape::Variant(tag) => {
+                let value = values.pop().unwrap_or(Value::nothing(span));
+                conversion.enums.variant(tag.label(), value, span)
+            }
+        },
+    )
+}
+
+/// Convert a term that holds no other values
+fn scalar_to_value(term: &Term, numbers: NumberPolicy, span: Span) -> Result<Value, LabeledError> {
+    match term {
+        Term::Null => Ok(Value::nothing(span)),
+        Term::Bool(b) => Ok(Value::bool(*b, span)),
+        Term::Num(n) => Ok(numbers.convert(n, span)),
+        Term::Str(s) => Ok(Value::string(s.as_str(), span)),
+        Term::Enum(tag) => Ok(Value::string(tag.label(), span)),
+        other => Err(LabeledError::new("Unsupported Nickel value").with_label(
+            format!(
+                "Cannot convert a value of type {} to a Nushell value",
+                other.type_of().unwrap_or_else(|| "unknown".to_string())
+            ),
+            span,
+        )),
+    }
+}
+
+/// Convert the elements of a fully evaluated Nickel array one at a time
+///
+/// The elements convert like the whole array does with [`nickel_to_nu_value_with`], their typed
+/// columns being found before the first one is converted.
+pub fn array_elements(
+    items: &Array,
+    conversion: Conversion,
+    span: Span,
+) -> impl Iterator<Item = Result<Value, LabeledError>> + use<> {
+    let items: Vec<RichTerm> = items.iter().cloned().collect();
+    let columns = TypedColumns::new(&items.iter().collect::<Vec<_>>());
+    // The elements are one level deeper than the array
+    let conversion = Conversion {
+        max_nesting: conversion.max_nesting.saturating_sub(1),
+        ..conversion
+    };
+    items.into_iter().map(move |item| {
+        let mut value = nickel_to_nu_value_with(&item, conversion, span)?;
+        columns.apply(&mut value, span);
+        Ok(value)
+    })
+}
+
+/// The annotated fields of the records of an array, which become columns of all of them
+///
+/// Every annotated field becomes a column of all the rows, null where a row omits it, and the
+/// numbers of a `Number` column all become floats as soon as one of them is not integral, so
+/// that a column does not mix ints and floats.
+struct TypedColumns {
+    /// The name of each column, and whether its numbers become floats
+    columns: Vec<(String, bool)>,
+}
+
+impl TypedColumns {
+    fn new(items: &[&RichTerm]) -> Self {
+        let mut columns: Vec<(&str, bool)> = Vec::new();
+        for &item in items {
+            let Term::Record(data) = item.as_ref() else {
+                continue;
+            };
+            for (id, field) in &data.fields {
+                let annotation = &field.metadata.annotation;
+                let mut declared = annotation.typ.iter().chain(&annotation.contracts);
+                if field.metadata.not_exported
+                    || declared.clone().next().is_none()
+                    || columns.iter().any(|(name, _)| *name == id.label())
+                {
+                    continue;
+                }
+                let number = declared.any(|labeled| matches!(labeled.typ.typ, TypeF::Number));
+                columns.push((id.label(), number));
+            }
+        }
+
+        // Numbers that are not integral are the ones converted to floats
+        let has_float = |name: &str| {
+            items.iter().any(|&item| {
+                let Term::Record(data) = item.as_ref() else {
+                    return false;
+                };
+                let value = data
+                    .fields
+                    .get(&LocIdent::new(name))
+                    .and_then(|field| field.value.as_ref());
+                matches!(value.map(AsRef::as_ref), Some(Term::Num(n)) if i64::try_from(n).is_err())
+            })
+        };
+        let columns = columns
+            .into_iter()
+            .map(|(name, number)| (name.to_string(), number && has_float(name)))
+            .collect();
+        Self { columns }
+    }
+
+    /// Give a converted element the columns, converting the numbers of float columns
+    fn apply(&self, value: &mut Value, span: Span) {
+        let Value::Record { val, .. } = value else {
+            return;
+        };
+        let record = val.to_mut();
+        for (name, floats) in &self.columns {
+            match record.get_mut(name) {
+                Some(number) if *floats => {
+                    if let Value::Int { val, .. } = number {
+                        let val = *val;
+                        *number = Value::float(val as f64, span);
+                    }
+                }
+                Some(_) => {}
+                None => record.push(name, Value::nothing(span)),
+            }
+        }
+    }
+}
+
+/// Nesting depth past which converted values are truncated, unless a command is told otherwise
+pub const MAX_DEPTH: usize = 128;
+
+/// Replace the records and lists nested more than `depth` levels deep with a marker string
+///
+/// Records become `{...}` and lists `[...]`, so that a deep value still displays quickly. Returns
+/// whether anything was truncated.
+pub fn truncate_depth(value: &mut Value, depth: usize, span: Span) -> bool {
+    match value {
+        Value::Record { .. } | Value::List { .. } if depth == 0 => {
+            let marker = if value.as_record().is_ok() {
+                "{...}"
+            } else {
+                "[...]"
+            };
+            *value = Value::string(marker, span);
+            true
+        }
+        Value::Record { val, .. } => val
+            .to_mut()
+            .iter_mut()
+            .fold(false, |truncated, (_, value)| {
+                truncate_depth(value, depth - 1, span) | truncated
+            }),
+        Value::List { vals, .. } => vals.iter_mut().fold(false, |truncated, value| {
+            truncate_depth(value, depth - 1, span) | truncated
+        }),
+        _ => false,
+    }
+}
+
+/// How enum variants with a payload, like `'Port 8080`, are converted to Nushell values
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EnumPolicy {
+    /// A record of the `tag` and of the payload as `value`
+    #[default]
+    Record,
+    /// A record with the payload in a column named after the tag, like serde's default enums
+    Keyed,
+}
+
+impl EnumPolicy {
+    pub const ALL: [EnumPolicy; 2] = [EnumPolicy::Record, EnumPolicy::Keyed];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EnumPolicy::Record => "record",
+            EnumPolicy::Keyed => "keyed",
+        }
+    }
+
+    /// The policy named by the value of an `--enums` flag
+    pub fn parse(name: &Spanned<String>) -> Result<Self, LabeledError> {
+        Self::ALL
+            .into_iter()
+            .find(|policy| policy.name() == name.item.to_ascii_lowercase())
+            .ok_or_else(|| {
+                LabeledError::new("Unknown enum policy")
+                    .with_label(format!("`{}` is not a known policy", name.item), name.span)
+                    .with_help(format!(
+                        "use one of {}",
+                        Self::ALL.map(EnumPolicy::name).join(", ")
+                    ))
+            })
+    }
+
+    fn variant(self, tag: &str, value: Value, span: Span) -> Value {
+        let mut record = Record::new();
+        match self {
+            EnumPolicy::Record => {
+                record.push("tag", Value::string(tag, span));
+                record.push("value", value);
+            }
+            EnumPolicy::Keyed => record.push(tag, value),
+        }
+        Value::record(record, span)
+    }
+}
+
+/// How Nickel numbers, which are arbitrary-precision rationals, are converted to Nushell values
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NumberPolicy {
+    /// Every number is rounded to a float
+    Float,
+    /// Integral numbers that fit in an `i64` become ints, anything else is rounded to a float
+    #[default]
+    IntOrFloat,
+    /// Like `IntOrFloat`, except that integers past an `i64` and numbers a float cannot hold
+    /// exactly become strings, written as decimals when they have an end and as fractions
+    /// otherwise
+    String,
+}
+
+impl NumberPolicy {
+    pub const ALL: [NumberPolicy; 3] = [
+        NumberPolicy::Float,
+        NumberPolicy::IntOrFloat,
+        NumberPolicy::String,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NumberPolicy::Float => "float",
+            NumberPolicy::IntOrFloat => "int-or-float",
+            NumberPolicy::String => "string",
+        }
+    }
+
+    /// The policy named by the value of a `--number-mode` flag
+    pub fn parse(name: &Spanned<String>) -> Result<Self, LabeledError> {
+        Self::ALL
+            .into_iter()
+            .find(|policy| policy.name() == name.item.to_ascii_lowercase())
+            .ok_or_else(|| {
+                LabeledError::new("Unknown number mode")
+                    .with_label(format!("`{}` is not a known mode", name.item), name.span)
+                    .with_help(format!(
+                        "use one of {}",
+                        Self::ALL.map(NumberPolicy::name).join(", ")
+                    ))
+            })
+    }
+
+    fn convert(self, n: &Number, span: Span) -> Value {
+        let (float, rounding) = f64::rounding_from(n, RoundingMode::Nearest);
+        match (self, i64::try_from(n)) {
+            (NumberPolicy::Float, _) => Value::float(float, span),
+            (_, Ok(i)) => Value::int(i, span),
+            (NumberPolicy::String, Err(_)) => {
+                let fraction = n.to_string();
+                // Integers past an `i64` are not floats, even when one holds them exactly
+                if rounding == Ordering::Equal && fraction.contains('/') {
+                    Value::float(float, span)
+                } else {
+                    Value::string(exact_decimal(n, fraction), span)
+                }
+            }
+            (NumberPolicy::IntOrFloat, Err(_)) => Value::float(float, span),
+        }
+    }
+}
+
+/// The exact decimal writing of a number written as `fraction`, kept when its decimals never end
+fn exact_decimal(n: &Number, fraction: String) -> String {
+    let Some((_, denominator)) = fraction.split_once('/') else {
+        return fraction;
+    };
+    // A denominator of `2^a * 5^b` needs `max(a, b)` decimals, fewer than 4 per digit of it
+    let mut scaled = n.clone();
+    for places in 1..=4 * denominator.len() {
+        scaled *= Number::from(10u32);
+        let digits = scaled.to_string();
+        if digits.contains('/') {
+            continue;
+        }
+        let (sign, digits) = match digits.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None => ("", digits.as_str()),
+        };
+        let digits = format!("{digits:0>width$}", width = places + 1);
+        let (units, decimals) = digits.split_at(digits.len() - places);
+        return format!("{sign}{units}.{decimals}");
+    }
+    fraction
+}
+
+/// The order the fields of a converted record are in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyOrder {
+    /// The order of the evaluated record, which depends on how it was merged
+    #[default]
+    Evaluation,
+    /// Sorted by name, so that the same data always converts the same way
+    Sorted,
+    /// The order the fields are written in, fields without a place in the source last
+    Source,
+}
+
+impl KeyOrder {
+    pub const ALL: [KeyOrder; 3] = [KeyOrder::Evaluation, KeyOrder::Sorted, KeyOrder::Source];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyOrder::Evaluation => "evaluation",
+            KeyOrder::Sorted => "sorted",
+            KeyOrder::Source => "source",
+        }
+    }
+
+    /// The order named by the value of a `--key-order` flag, or a `key_order` setting
+    pub fn parse(name: &Spanned<String>) -> Result<Self, LabeledError> {
+        Self::ALL
+            .into_iter()
+            .find(|order| order.name() == name.item.to_ascii_lowercase())
+            .ok_or_else(|| {
+                LabeledError::new("Unknown key order")
+                    .with_label(format!("`{}` is not a known order", name.item), name.span)
+                    .with_help(format!(
+                        "use one of {}",
+                        Self::ALL.map(KeyOrder::name).join(", ")
+                    ))
+            })
+    }
+
+    fn sort<T>(self, fields: &mut [(LocIdent, T)]) {
+        match self {
+            KeyOrder::Evaluation => {}
+            KeyOrder::Sorted => fields.sort_by(|(a, _), (b, _)| a.label().cmp(b.label())),
+            // Fields merged in from other files are placed by their offset in their own file
+            KeyOrder::Source => fields.sort_by_key(|(id, _)| {
+                let start = id.pos.into_opt().map(|span| span.start);
+                (start.is_none(), start)
+            }),
+        }
+    }
+}
+
+/// How arrays whose elements have different shapes are converted to Nushell values
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArrayPolicy {
+    /// Keep the elements as they are, as a list of any values
+    #[default]
+    Any,
+    /// Give the records of an array the union of their columns, null where they lack one
+    Pad,
+    /// Fail on arrays mixing records with other values, or records with different columns
+    Error,
+}
+
+impl ArrayPolicy {
+    pub const ALL: [ArrayPolicy; 3] = [ArrayPolicy::Any, ArrayPolicy::Pad, ArrayPolicy::Error];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ArrayPolicy::Any => "any",
+            ArrayPolicy::Pad => "pad",
+            ArrayPolicy::Error => "error",
+        }
+    }
+
+    /// The policy named by the value of an `--arrays` flag
+    pub fn parse(name: &Spanned<String>) -> Result<Self, LabeledError> {
+        Self::ALL
+            .into_iter()
+            .find(|policy| policy.name() == name.item.to_ascii_lowercase())
+            .ok_or_else(|| {
+                LabeledError::new("Unknown array policy")
+                    .with_label(format!("`{}` is not a known policy", name.item), name.span)
+                    .with_help(format!(
+                        "use one of {}",
+                        Self::ALL.map(ArrayPolicy::name).join(", ")
+                    ))
+            })
+    }
+
+    /// Apply the policy to the arrays of a converted value, at any depth
+    pub fn apply(self, value: Value, span: Span) -> Result<Value, LabeledError> {
+        if self == ArrayPolicy::Any {
+            return Ok(value);
+        }
+        self.apply_at(value, &mut Vec::new(), span)
+    }
+
+    fn apply_at(
+        self,
+        value: Value,
+        path: &mut Vec<String>,
+        span: Span,
+    ) -> Result<Value, LabeledError> {
+        let value_span = value.span();
+        match value {
+            Value::Record { val, .. } => {
+                let mut record = Record::new();
+                for (column, value) in val.into_owned() {
+                    path.push(column.clone());
+                    let value = self.apply_at(value, path, span)?;
+                    path.pop();
+                    record.push(column, value);
+                }
+                Ok(Value::record(record, value_span))
+            }
+            Value::List { vals, .. } => {
+                let mut vals = vals
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, value)| {
+                        path.push(i.to_string());
+                        let value = self.apply_at(value, path, span);
+                        path.pop();
+                        value
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.reshape(&mut vals, path, span)?;
+                Ok(Value::list(vals, value_span))
+            }
+            value => Ok(value),
+        }
+    }
+
+    /// Pad the records of an array, or check that they all have the same columns
+    fn reshape(self, vals: &mut [Value], path: &[String], span: Span) -> Result<(), LabeledError> {
+        let records = vals
+            .iter()
+            .filter(|value| value.as_record().is_ok())
+            .count();
+        if records == 0 {
+            return Ok(());
+        }
+        let location = || match path {
+            [] => "the result".to_string(),
+            path => format!("`{}`", path.join(".")),
+        };
+        if records != vals.len() {
+            return match self {
+                ArrayPolicy::Error => Err(LabeledError::new("Heterogeneous array")
+                    .with_label(
+                        format!(
+                            "The array at {} mixes records with other values",
+                            location()
+                        ),
+                        span,
+                    )
+                    .with_help("pass `--arrays any` to keep it as a list")),
+                _ => Ok(()),
+            };
+        }
+
+        let mut columns: Vec<String> = Vec::new();
+        for value in vals.iter() {
+            for column in value.as_record().map(Record::columns).into_iter().flatten() {
+                if !columns.contains(column) {
+                    columns.push(column.clone());
+                }
+            }
+        }
+        for (i, value) in vals.iter_mut().enumerate() {
+            let Value::Record { val, .. } = value else {
+                continue;
+            };
+            if val.len() == columns.len() {
+                continue;
+            }
+            if self == ArrayPolicy::Error {
+                let missing: Vec<_> = columns
+                    .iter()
+                    .filter(|column| !val.contains(column))
+                    .map(|column| format!("`{column}`"))
+                    .collect();
+                return Err(LabeledError::new("Heterogeneous array")
+                    .with_label(
+                        format!(
+                            "Element {i} of the array at {} lacks {}",
+                            location(),
+                            missing.join(", ")
+                        ),
+                        span,
+                    )
+                    .with_help("pass `--arrays pad` to fill the missing columns with null"));
+            }
+            let record = val.to_mut();
+            for column in &columns {
+                if !record.contains(column) {
+                    record.push(column.clone(), Value::nothing(span));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Convert a Nickel type into a tree of records
+///
+/// Every node has the `kind` of the type, such as `Number`, `Arrow` or `Record`, and the `type`
+/// as written in Nickel, along with the nodes of its component types.
+pub fn type_to_value(typ: &Type, span: Span) -> Value {
+    let mut record = Record::new();
+    let kind = match &typ.typ {
+        TypeF::Dyn => "Dyn",
+        TypeF::Number => "Number",
+        TypeF::Bool => "Bool",
+        TypeF::String => "String",
+        TypeF::Symbol => "Symbol",
+        TypeF::ForeignId => "ForeignId",
+        TypeF::Contract(_) => "Contract",
+        TypeF::Arrow(domain, codomain) => {
+            record.push("domain", type_to_value(domain, span));
+            record.push("codomain", type_to_value(codomain, span));
+            "Arrow"
+        }
+        TypeF::Var(name) => {
+            record.push("name", Value::string(name.label(), span));
+            "Var"
+        }
+        TypeF::Forall {
+            var,
+            var_kind,
+            body,
+        } => {
+            record.push("var", Value::string(var.label(), span));
+            let var_kind = match var_kind {
+                VarKind::Type => "Type",
+                VarKind::EnumRows { .. } => "EnumRows",
+                VarKind::RecordRows { .. } => "RecordRows",
+            };
+            record.push("var_kind", Value::string(var_kind, span));
+            record.push("body", type_to_value(body, span));
+            "Forall"
+        }
+        TypeF::Enum(rows) => {
+            let mut variants = Vec::new();
+            let mut tail = Value::nothing(span);
+            for item in rows.iter() {
+                match item {
+                    EnumRowsIteratorItem::Row(row) => {
+                        let mut variant = Record::new();
+                        variant.push("tag", Value::string(row.id.label(), span));
+                        variant.push(
+                            "argument",
+                            row.typ
+                                .map_or(Value::nothing(span), |typ| type_to_value(typ, span)),
+                        );
+                        variants.push(Value::record(variant, span));
+                    }
+                    EnumRowsIteratorItem::TailVar(var) => tail = Value::string(var.label(), span),
+                }
+            }
+            record.push("variants", Value::list(variants, span));
+            record.push("tail", tail);
+            "Enum"
+        }
+        TypeF::Record(rows) => {
+            let mut fields = Record::new();
+            let mut tail = Value::nothing(span);
+            for item in rows.iter() {
+                match item {
+                    RecordRowsIteratorItem::Row(row) => {
+                        fields.push(row.id.label(), type_to_value(row.typ, span));
+                    }
+                    RecordRowsIteratorItem::TailDyn => tail = Value::string("Dyn", span),
+                    RecordRowsIteratorItem::TailVar(var) => tail = Value::string(var.label(), span),
+                }
+            }
+            record.push("fields", Value::record(fields, span));
+            record.push("tail", tail);
+            "Record"
+        }
+        TypeF::Dict {
+            type_fields,
+            flavour,
+        } => {
+            record.push("fields", type_to_value(type_fields, span));
+            let contract = matches!(flavour, DictTypeFlavour::Contract);
+            record.push("contract", Value::bool(contract, span));
+            "Dict"
+        }
+        TypeF::Array(element) => {
+            record.push("element", type_to_value(element, span));
+            "Array"
+        }
+        TypeF::Wildcard(_) => "Wildcard",
+    };
+
+    let mut node = Record::new();
+    node.push("kind", Value::string(kind, span));
+    node.push("type", Value::string(typ.to_string(), span));
+    node.extend(record);
+    Value::record(node, span)
+}
+
+/// Maximum width of an array of scalars written on a single line by [`value_to_nickel`]
+const MAX_INLINE_WIDTH: usize = 80;
+
+/// Write a Nushell value as Nickel source
+///
+/// Records and arrays are written one element per line, except arrays of scalars short enough to
+/// fit on a line. Strings with several lines are written as multiline strings when that keeps
+/// their content as is. Dates become strings, file sizes bytes and durations nanoseconds.
+pub fn value_to_nickel(value: &Value, span: Span) -> Result<String, LabeledError> {
+    let mut source = String::new();
+    write_nickel(value, 0, &mut source, span)?;
+    Ok(source)
+}
+
+fn write_nickel(
+    value: &Value,
+    indent: usize,
+    out: &mut String,
+    span: Span,
+) -> Result<(), LabeledError> {
+    let pad = |level: usize| "  ".repeat(level);
+
+    match value {
+        Value::Record { val, .. } if val.is_empty() => out.push_str("{}"),
+        Value::Record { val, .. } => {
+            out.push_str("{\n");
+            for (key, value) in val.iter() {
+                out.push_str(&pad(indent + 1));
+                out.push_str(&field_name(key));
+                out.push_str(" = ");
+                write_nickel(value, indent + 1, out, span)?;
+                out.push_str(",\n");
+            }
+            out.push_str(&pad(indent));
+            out.push('}');
+        }
+        Value::List { vals, .. } if vals.is_empty() => out.push_str("[]"),
+        Value::List { vals, .. } => {
+            if let Some(inline) = inline_array(vals, span)? {
+                out.push_str(&inline);
+                return Ok(());
+            }
+            out.push_str("[\n");
+            for value in vals {
+                out.push_str(&pad(indent + 1));
+                write_nickel(value, indent + 1, out, span)?;
+                out.push_str(",\n");
+            }
+            out.push_str(&pad(indent));
+            out.push(']');
+        }
+        Value::String { val, .. } => match multiline_string(val, &pad(indent + 1)) {
+            Some(string) => {
+                out.push_str(&string);
+                out.push_str(&pad(indent));
+                out.push_str(&string_end(val));
+            }
+            None => out.push_str(&quote(val)),
+        },
+        value => out.push_str(&scalar(value, span)?),
+    }
+    Ok(())
+}
+
+fn scalar(value: &Value, span: Span) -> Result<String, LabeledError> {
+    Ok(match value {
+        Value::Nothing { .. } => "null".to_string(),
+        Value::Bool { val, .. } => val.to_string(),
+        Value::Int { val, .. } => val.to_string(),
+        Value::Float { val, .. } if val.is_finite() => {
+            // Keep a decimal point so that the number reads as a decimal
+            let float = format!("{val:?}");
+            if float.contains(['.', 'e']) {
+                float
+            } else {
+                format!("{float}.0")
+            }
+        }
+        Value::Float { val, .. } => {
+            return Err(LabeledError::new("Cannot convert to Nickel")
+                .with_label(format!("Nickel numbers cannot be {val}"), value.span()));
+        }
+        Value::String { val, .. } => quote(val),
+        Value::Date { val, .. } => quote(&val.to_rfc3339()),
+        Value::Filesize { val, .. } => val.get().to_string(),
+        Value::Duration { val, .. } => val.to_string(),
+        value => {
+            return Err(LabeledError::new("Cannot convert to Nickel")
+                .with_label(
+                    format!("{} values have no Nickel equivalent", value.get_type()),
+                    value.span(),
+                )
+                .with_label("while converting this input", span));
+        }
+    })
+}
+
+/// An array of scalars written on a single line, if it fits
+fn inline_array(vals: &[Value], span: Span) -> Result<Option<String>, LabeledError> {
+    let nested = |v: &Value| {
+        matches!(v, Value::Record { .. } | Value::List { .. })
+            || matches!(v, Value::String { val, .. } if val.contains('\n'))
+    };
+    if vals.iter().any(nested) {
+        return Ok(None);
+    }
+    let items = vals
+        .iter()
+        .map(|value| scalar(value, span))
+        .collect::<Result<Vec<_>, _>>()?;
+    let inline = format!("[{}]", items.join(", "));
+    Ok((inline.len() <= MAX_INLINE_WIDTH).then_some(inline))
+}
+
+fn field_name(name: &str) -> String {
+    if is_identifier(name) {
+        name.to_string()
+    } else {
+        quote(name)
+    }
+}
+
+/// A string literal, escaping what Nickel would otherwise interpret
+fn quote(string: &str) -> String {
+    let mut quoted = String::with_capacity(string.len() + 2);
+    quoted.push('"');
+    let mut chars = string.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            '%' if chars.peek() == Some(&'{') => quoted.push_str("\\%"),
+            c if c.is_ascii_control() => quoted.push_str(&format!("\\x{:02x}", c as u8)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// The number of `%` delimiting a multiline string, so that its content is not interpolated nor
+/// closes it early
+fn percent_count(string: &str) -> usize {
+    (1..)
+        .find(|&n| {
+            let percents = "%".repeat(n);
+            !string.contains(&format!("{percents}{{")) && !string.contains(&format!("\"{percents}"))
+        })
+        .unwrap_or(1)
+}
+
+/// The opening and content of a multiline string, with every line indented by `pad`
+///
+/// Nickel removes the common indentation of the lines of a multiline string, as well as
+/// whitespace-only first and last lines, so strings that would not survive this are written as
+/// regular string literals.
+fn multiline_string(string: &str, pad: &str) -> Option<String> {
+    let lines: Vec<_> = string.split('\n').collect();
+    let unindented = lines
+        .iter()
+        .any(|line| !line.is_empty() && !line.starts_with([' ', '\t']));
+    let blank = lines
+        .iter()
+        .any(|line| !line.is_empty() && line.trim().is_empty());
+    if lines.len() < 2 || !unindented || blank || string.contains(['\r', '\t']) {
+        return None;
+    }
+    if string.chars().any(|c| c.is_control() && c != '\n') {
+        return None;
+    }
+
+    let mut multiline = format!("m{}\"\n", "%".repeat(percent_count(string)));
+    for line in lines {
+        if !line.is_empty() {
+            multiline.push_str(pad);
+            multiline.push_str(line);
+        }
+        multiline.push('\n');
+    }
+    Some(multiline)
+}
+
+fn string_end(string: &str) -> String {
+    format!("\"{}", "%".repeat(percent_count(string)))
+}
@@ -0,0 +1,326 @@
+use crate::nickel::suggest::{Suggestion, closest_names, suggest};
+use nickel_lang_core::error::{
+    Error, EvalError, IntoDiagnostics, LabelStyle, TypecheckError,
+    report::{ColorOpt, report_as_str},
+};
+use nickel_lang_core::files::Files;
+use nu_protocol::{LabeledError, Record, Span, Value};
+
+/// Prefix of the codes attached to the errors surfaced by the plugin
+pub const ERROR_CODE_PREFIX: &str = "nickel::";
+
+/// A class of Nickel errors, with the extended explanation shown by `nickel explain`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorCode {
+    pub name: &'static str,
+    pub title: &'static str,
+    pub explanation: &'static str,
+}
+
+impl ErrorCode {
+    /// The code as attached to errors, e.g. `nickel::contract`
+    pub fn code(&self) -> String {
+        format!("{ERROR_CODE_PREFIX}{}", self.name)
+    }
+}
+
+pub const ERROR_CODES: &[ErrorCode] = &[
+    ErrorCode {
+        name: "parse",
+        title: "Syntax error",
+        explanation: "\
+The source could not be parsed as Nickel. This is usually an unbalanced brace or quote, a missing \
+comma between record fields or array elements, or a keyword used as an identifier.
+
+Field assignments given to `--assign` or `--override` must have the form `path.to.field=value`, \
+where the value is a Nickel expression: strings need their own quotes, e.g. \
+`--assign 'name=\"web\"'`.",
+    },
+    ErrorCode {
+        name: "typecheck",
+        title: "Type error",
+        explanation: "\
+A statically typed part of the program does not typecheck. Typechecking only applies to \
+expressions annotated with a type (`expr : Type`) and to their subexpressions; the rest of the \
+program is checked dynamically by contracts.
+
+Either fix the expression so it has the annotated type, or, for code that is correct but that the \
+typechecker cannot prove so, replace the type annotation `:` by a contract annotation `|`.",
+    },
+    ErrorCode {
+        name: "contract",
+        title: "Contract broken",
+        explanation: "\
+A value does not satisfy a contract attached to it, either by a `| Contract` annotation, a type \
+annotation crossing into untyped code, or a function of the standard library checking its \
+arguments.
+
+The report points at the value and at the contract that rejected it. When the contract is a \
+record contract, the problem is often a misspelled field name or a value of the wrong type.",
+    },
+    ErrorCode {
+        name: "missing-definition",
+        title: "Field without a definition",
+        explanation: "\
+A field was accessed, or the program was exported, while a field declared by a record contract \
+has no value. Fields declared without a value must be defined before they are used, either in the \
+configuration itself or from the command line with `--assign path.to.field=value`.
+
+Mark the field as `optional` in the contract if it can legitimately be left undefined.",
+    },
+    ErrorCode {
+        name: "missing-field",
+        title: "Missing field",
+        explanation: "\
+A field was accessed with `record.field` but the record has no such field. Check the spelling of \
+the field, or use `record.field or default` / `std.record.get_or` when the field is not always \
+present.",
+    },
+    ErrorCode {
+        name: "unbound-identifier",
+        title: "Unbound identifier",
+        explanation: "\
+A variable is used but not defined. Identifiers must be bound by a `let`, a function argument or \
+a pattern before they are used; fields of a record are only in scope inside a recursive record.
+
+Standard library functions live under `std`, e.g. `std.string.join`: use `nickel std ls` to list \
+them.",
+    },
+    ErrorCode {
+        name: "infinite-recursion",
+        title: "Infinite recursion",
+        explanation: "\
+The evaluation of a value depends on itself, for example a record field defined in terms of \
+itself (`{ a = a + 1 }`) or two fields defined in terms of each other.
+
+When the intent is to refine a value, merge a new definition with a higher priority instead, \
+e.g. `--override field=value`.",
+    },
+    ErrorCode {
+        name: "dynamic-type",
+        title: "Dynamic type error",
+        explanation: "\
+An operation received a value of the wrong type at runtime: adding a string to a number, calling \
+something that is not a function, or merging values that cannot be merged. Since untyped code is \
+only checked when evaluated, the report points at the value that had the wrong type.
+
+Adding a type annotation to the surrounding expression catches such errors before evaluation.",
+    },
+    ErrorCode {
+        name: "eval",
+        title: "Evaluation error",
+        explanation: "\
+The evaluation of the program failed, for instance because of a call to `std.fail_with` or a \
+non-exhaustive `match`. The report gives the reason and points at the offending expression.",
+    },
+    ErrorCode {
+        name: "import",
+        title: "Import error",
+        explanation: "\
+An imported file could not be read or parsed. Relative imports are resolved next to the importing \
+file, and piped code resolves them from the current directory (or from `--cwd`).
+
+The format of an imported file is deduced from its extension (`.ncl`, `.json`, `.yaml`, `.toml`, \
+`.txt`), or given explicitly with `import \"file\" as 'Json`.",
+    },
+    ErrorCode {
+        name: "export",
+        title: "Export error",
+        explanation: "\
+The result of the evaluation cannot be converted to the requested format. Functions cannot be \
+exported, TOML has no `null` and requires a record at the top level, and numbers must fit the \
+target format.
+
+Remove the offending value from the exported result, or mark the field `| not_exported`.",
+    },
+    ErrorCode {
+        name: "io",
+        title: "I/O error",
+        explanation: "\
+A file could not be read or written. Check that the path exists and is readable; relative paths \
+are resolved from the current directory (or from `--cwd`).",
+    },
+];
+
+/// Look up an error code, with or without its `nickel::` prefix
+pub fn find_error_code(code: &str) -> Option<&'static ErrorCode> {
+    let name = code.strip_prefix(ERROR_CODE_PREFIX).unwrap_or(code);
+    ERROR_CODES.iter().find(|c| c.name == name)
+}
+
+/// The class of a Nickel error
+pub fn error_code(error: &Error) -> &'static ErrorCode {
+    let name = match error {
+        Error::ParseErrors(_) | Error::EvalError(EvalError::ParseError(_)) => "parse",
+        Error::TypecheckError(TypecheckError::UnboundIdentifier(_)) => "unbound-identifier",
+        Error::TypecheckError(_) => "typecheck",
+        Error::EvalError(error) => match error {
+            EvalError::BlameError { .. } => "contract",
+            EvalError::MissingFieldDef { .. } => "missing-definition",
+            EvalError::FieldMissing { .. } => "missing-field",
+            EvalError::UnboundIdentifier(..) => "unbound-identifier",
+            EvalError::InfiniteRecursion(..) => "infinite-recursion",
+            EvalError::TypeError { .. }
+            | EvalError::UnaryPrimopTypeError { .. }
+            | EvalError::NAryPrimopTypeError { .. }
+            | EvalError::NotAFunc(..)
+            | EvalError::MergeIncompatibleArgs { .. } => "dynamic-type",
+            EvalError::SerializationError(_) => "export",
+            _ => "eval",
+        },
+        Error::ImportError(_) => "import",
+        Error::ExportError(_) => "export",
+        Error::IOError(_) => "io",
+        Error::ReplError(_) => "eval",
+    };
+    find_error_code(name).expect("every error class has an error code")
+}
+
+/// Render a Nickel error with its source snippets into a plain-text report
+pub fn render_nickel_error(files: &mut Files, error: impl IntoDiagnostics) -> String {
+    report_as_str(files, error, ColorOpt::Never)
+        .trim_end()
+        .to_string()
+}
+
+/// Convert a Nickel error into a `LabeledError` pointing at the command call
+///
+/// The error carries the code of its class, which `nickel explain` describes in more detail. When
+/// a field is missing from a record, the help lists the closest existing fields.
+pub fn nickel_error(
+    files: &mut Files,
+    error: impl Into<Error>,
+    msg: &str,
+    span: Span,
+) -> LabeledError {
+    let error = error.into();
+    let code = error_code(&error).code();
+
+    let mut help = format!("run `nickel explain {code}` for more details");
+    if let Error::EvalError(EvalError::FieldMissing {
+        id, field_names, ..
+    }) = &error
+    {
+        let candidates = closest_names(id.label(), field_names.iter().map(|f| f.label()));
+        if !candidates.is_empty() {
+            help = format!("did you mean {}? {help}", or_list(&candidates));
+        }
+    }
+
+    LabeledError::new(msg)
+        .with_label(render_nickel_error(files, error), span)
+        .with_help(help)
+        .with_code(code)
+}
+
+/// Format names as "`a`, `b` or `c`"
+fn or_list(names: &[&str]) -> String {
+    let quoted: Vec<_> = names.iter().map(|name| format!("`{name}`")).collect();
+    match quoted.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} or {last}", rest.join(", ")),
+        None => String::new(),
+    }
+}
+
+/// A diagnostic reported by Nickel, located by its primary label when it has one
+#[derive(Debug, Clone, PartialEq)]
+pub struct NickelDiagnostic {
+    pub severity: String,
+    pub code: String,
+    pub message: String,
+    pub file: Option<String>,
+    /// 1-based line of the start of the primary label
+    pub line: Option<usize>,
+    /// 1-based column of the start of the primary label
+    pub column: Option<usize>,
+    /// Byte range of the primary label
+    pub start: Option<usize>,
+    pub end: Option<usize>,
+    pub label: Option<String>,
+    pub notes: Vec<String>,
+    pub suggestion: Option<Suggestion>,
+}
+
+impl NickelDiagnostic {
+    pub fn into_value(self, span: Span) -> Value {
+        let optional_int =
+            |i: Option<usize>| i.map_or(Value::nothing(span), |i| Value::int(i as i64, span));
+        let optional_string =
+            |s: Option<String>| s.map_or(Value::nothing(span), |s| Value::string(s, span));
+
+        let mut record = Record::new();
+        record.push("severity", Value::string(self.severity, span));
+        record.push("code", Value::string(self.code, span));
+        record.push("message", Value::string(self.message, span));
+        record.push("file", optional_string(self.file));
+        record.push("line", optional_int(self.line));
+        record.push("column", optional_int(self.column));
+        record.push("start", optional_int(self.start));
+        record.push("end", optional_int(self.end));
+        record.push("label", optional_string(self.label));
+        record.push(
+            "notes",
+            Value::list(
+                self.notes
+                    .into_iter()
+                    .map(|note| Value::string(note, span))
+                    .collect(),
+                span,
+            ),
+        );
+        record.push(
+            "suggestion",
+            self.suggestion.map_or(Value::nothing(span), |suggestion| {
+                suggestion.into_value(span)
+            }),
+        );
+        Value::record(record, span)
+    }
+}
+
+/// Convert a Nickel error into diagnostics, one per diagnostic Nickel reports for it
+///
+/// Diagnostics pointing at a typo'd identifier or field, or at a word that looks like an unquoted
+/// string, carry a suggested fix.
+pub fn diagnostics(files: &mut Files, error: impl Into<Error>) -> Vec<NickelDiagnostic> {
+    let error = error.into();
+    let error_code = error_code(&error);
+
+    error
+        .into_diagnostics(files)
+        .into_iter()
+        .map(|diagnostic| {
+            let primary = diagnostic
+                .labels
+                .iter()
+                .find(|label| label.style == LabelStyle::Primary);
+            let location = primary
+                .and_then(|label| files.location(label.file_id, label.range.start as u32).ok());
+            let suggestion = primary.and_then(|label| {
+                suggest(
+                    error_code,
+                    files.source(label.file_id),
+                    label.range.clone(),
+                    &diagnostic.notes,
+                )
+            });
+
+            NickelDiagnostic {
+                severity: format!("{:?}", diagnostic.severity).to_lowercase(),
+                code: error_code.code(),
+                message: diagnostic.message,
+                file: primary.map(|label| files.name(label.file_id).to_string_lossy().into()),
+                line: location.as_ref().map(|l| l.line.to_usize() + 1),
+                column: location.as_ref().map(|l| l.column.to_usize() + 1),
+                start: primary.map(|label| label.range.start),
+                end: primary.map(|label| label.range.end),
+                label: primary
+                    .filter(|label| !label.message.is_empty())
+                    .map(|label| label.message.clone()),
+                notes: diagnostic.notes,
+                suggestion,
+            }
+        })
+        .collect()
+}
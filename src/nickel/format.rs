@@ -0,0 +1,264 @@
+#[cfg(feature = "dataframe")]
+use crate::nickel::arrow;
+use crate::nickel::convert::json_to_value;
+use nickel_lang_core::{cache::InputFormat, serialize::ExportFormat};
+use nu_protocol::{LabeledError, PipelineMetadata, Span, Spanned, Value, engine::EngineState};
+use nuon::ToStyle;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Text formats that results can be written as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutputFormat {
+    Json,
+    Yaml,
+    Toml,
+    /// Nushell's own format, read back by `from nuon`
+    Nuon,
+}
+
+impl OutputFormat {
+    /// Every output format, in the order they are listed to users
+    pub const ALL: [OutputFormat; 4] = [
+        OutputFormat::Json,
+        OutputFormat::Yaml,
+        OutputFormat::Toml,
+        OutputFormat::Nuon,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Yaml => "yaml",
+            OutputFormat::Toml => "toml",
+            OutputFormat::Nuon => "nuon",
+        }
+    }
+
+    /// The format with this name or file extension, ignoring case
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        match name.as_str() {
+            "yml" => Some(OutputFormat::Yaml),
+            name => Self::ALL.into_iter().find(|format| format.name() == name),
+        }
+    }
+
+    /// The format named by the value of a `--format` flag, or a `format` field
+    pub fn parse(name: &Spanned<String>) -> Result<Self, LabeledError> {
+        Self::from_name(&name.item).ok_or_else(|| {
+            LabeledError::new("Unknown output format")
+                .with_label(format!("`{}` is not a known format", name.item), name.span)
+                .with_help(format!(
+                    "use one of {}",
+                    Self::ALL.map(OutputFormat::name).join(", ")
+                ))
+        })
+    }
+
+    /// The exporter Nickel uses for this format, if it has one
+    pub fn export_format(self) -> Option<ExportFormat> {
+        match self {
+            OutputFormat::Json => Some(ExportFormat::Json),
+            OutputFormat::Yaml => Some(ExportFormat::Yaml),
+            OutputFormat::Toml => Some(ExportFormat::Toml),
+            OutputFormat::Nuon => None,
+        }
+    }
+
+    /// The media type of the format, set as the `content_type` of output written in it
+    pub fn content_type(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "application/json",
+            OutputFormat::Yaml => "application/yaml",
+            OutputFormat::Toml => "application/toml",
+            OutputFormat::Nuon => "application/x-nuon",
+        }
+    }
+
+    /// Write data that needs no evaluation, such as a parsed data file
+    pub fn serialize(self, json: &serde_json::Value) -> Result<String, String> {
+        match self {
+            OutputFormat::Json => serde_json::to_string_pretty(json).map_err(|e| e.to_string()),
+            OutputFormat::Yaml => serde_yaml::to_string(json).map_err(|e| e.to_string()),
+            OutputFormat::Toml => toml::to_string(json).map_err(|e| e.to_string()),
+            OutputFormat::Nuon => {
+                to_nuon(&json_to_value(json, Span::unknown()).map_err(|e| e.msg)?)
+            }
+        }
+    }
+}
+
+/// Binary formats that tables of flat records can be written as, for data tooling
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TableFormat {
+    /// An Arrow IPC file, read by `polars open`
+    Arrow,
+    Parquet,
+}
+
+impl TableFormat {
+    /// Every table format, in the order they are listed to users
+    pub const ALL: [TableFormat; 2] = [TableFormat::Arrow, TableFormat::Parquet];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TableFormat::Arrow => "arrow",
+            TableFormat::Parquet => "parquet",
+        }
+    }
+
+    /// The format with this name or file extension, ignoring case
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        Self::ALL.into_iter().find(|format| format.name() == name)
+    }
+
+    /// The media type of the format, set as the `content_type` of output written in it
+    pub fn content_type(self) -> &'static str {
+        match self {
+            TableFormat::Arrow => "application/vnd.apache.arrow.file",
+            TableFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    /// The cargo feature the plugin needs to be built with to write this format
+    pub fn feature(self) -> &'static str {
+        match self {
+            TableFormat::Arrow => "dataframe",
+            TableFormat::Parquet => "parquet",
+        }
+    }
+
+    /// Whether the plugin was built with the feature writing this format
+    pub fn is_available(self) -> bool {
+        match self {
+            TableFormat::Arrow => cfg!(feature = "dataframe"),
+            TableFormat::Parquet => cfg!(feature = "parquet"),
+        }
+    }
+
+    /// Write a table of flat records in this format
+    #[cfg_attr(not(feature = "dataframe"), allow(unused_variables))]
+    pub fn encode(self, value: &Value, span: Span) -> Result<Vec<u8>, LabeledError> {
+        match self {
+            #[cfg(feature = "dataframe")]
+            TableFormat::Arrow => arrow::to_arrow_ipc(value, span),
+            #[cfg(feature = "parquet")]
+            TableFormat::Parquet => arrow::to_parquet(value, span),
+            #[allow(unreachable_patterns)]
+            _ => Err(unavailable(self.name(), self.feature(), span)),
+        }
+    }
+}
+
+/// Pipeline metadata telling commands like `save` and `http post` the media type of an output
+pub fn content_metadata(content_type: &str) -> PipelineMetadata {
+    PipelineMetadata {
+        content_type: Some(content_type.to_string()),
+        ..Default::default()
+    }
+}
+
+/// The error for an output the plugin was built without the cargo feature of
+pub fn unavailable(output: &str, feature: &str, span: Span) -> LabeledError {
+    LabeledError::new(format!("{output} output is not available"))
+        .with_label(
+            format!("The plugin was built without the `{feature}` feature"),
+            span,
+        )
+        .with_help(format!(
+            "reinstall it with `cargo install nu_plugin_nickel --features {feature}`"
+        ))
+}
+
+/// Write a Nushell value as NUON, on a single line like `to nuon` does
+pub fn to_nuon(value: &Value) -> Result<String, String> {
+    nuon::to_nuon(&EngineState::new(), value, ToStyle::Default, None, false)
+        .map_err(|e| e.to_string())
+}
+
+/// Detect the format of an input from its path extension, falling back to sniffing the content
+pub fn detect_format(path: Option<&Path>, content: &str) -> InputFormat {
+    path.and_then(InputFormat::from_path)
+        .unwrap_or_else(|| sniff_format(content))
+}
+
+/// Guess the format of some content by looking at its first significant line
+///
+/// Anything that cannot be confidently identified as JSON, TOML or YAML is treated as Nickel.
+pub fn sniff_format(content: &str) -> InputFormat {
+    let first_line = content
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .unwrap_or_default();
+
+    if first_line.starts_with('{') || first_line.starts_with('[') {
+        // `{ foo = 1 }` is a Nickel record and `[1, 2]` is a Nickel array, only strict JSON
+        // documents are treated as JSON
+        if serde_json::from_str::<serde_json::Value>(content).is_ok() {
+            return InputFormat::Json;
+        }
+        // A TOML table header such as `[package]`
+        if first_line.starts_with('[') && toml::from_str::<toml::Table>(content).is_ok() {
+            return InputFormat::Toml;
+        }
+        return InputFormat::Nickel;
+    }
+
+    if first_line.starts_with("---") || first_line.starts_with("- ") || is_yaml_key(first_line) {
+        if serde_yaml::from_str::<serde_yaml::Value>(content).is_ok() {
+            return InputFormat::Yaml;
+        }
+    } else if is_toml_key(first_line) && toml::from_str::<toml::Table>(content).is_ok() {
+        return InputFormat::Toml;
+    }
+
+    InputFormat::Nickel
+}
+
+/// Parse a data document into JSON, returning `None` for formats that need evaluation
+pub fn parse_data(content: &str, format: InputFormat) -> Option<Result<serde_json::Value, String>> {
+    let result = match format {
+        InputFormat::Json => serde_json::from_str(content).map_err(|e| e.to_string()),
+        InputFormat::Yaml => serde_yaml::from_str(content).map_err(|e| e.to_string()),
+        InputFormat::Toml => toml::from_str(content).map_err(|e| e.to_string()),
+        InputFormat::Text => Ok(serde_json::Value::String(content.to_string())),
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// `key: value` or `key:` where the key is a plain identifier
+fn is_yaml_key(line: &str) -> bool {
+    match line.split_once(':') {
+        Some((key, rest)) => {
+            is_bare_key(key.trim_matches(['"', '\''])) && (rest.is_empty() || rest.starts_with(' '))
+        }
+        None => false,
+    }
+}
+
+/// `key = value` where the key is a plain or dotted identifier, but not a Nickel `let`
+fn is_toml_key(line: &str) -> bool {
+    match line.split_once('=') {
+        Some((key, rest)) => {
+            let key = key.trim();
+            !key.starts_with("let ")
+                && key
+                    .split('.')
+                    .all(|part| is_bare_key(part.trim_matches('"')))
+                && !rest.starts_with('=')
+                && !rest.starts_with('>')
+        }
+        None => false,
+    }
+}
+
+fn is_bare_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
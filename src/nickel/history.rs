@@ -0,0 +1,264 @@
+use crate::nickel::{input::plugin_settings, write::WriteSet};
+use chrono::{DateTime, Utc};
+use nu_plugin::{EngineInterface, EvaluatedCall};
+use nu_protocol::{LabeledError, Record, Span, Spanned, Value};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::OpenOptions;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// The plugin setting naming the history directory when `--history` is not given
+pub const HISTORY_SETTING: &str = "history";
+
+/// File of the history listing its entries, one JSON object per line, oldest first
+const LOG_FILE: &str = "log.jsonl";
+
+/// Directory of the history holding the contents of the entries, named by their hash
+const OBJECTS_DIR: &str = "objects";
+
+/// The shortest prefix a hash can be looked up by, like an abbreviated git commit
+const MIN_PREFIX: usize = 4;
+
+/// A local record of the files written with `nickel eval --output`
+///
+/// Each write appends an entry to the log, and its contents are stored once per hash, so that
+/// rendering the same file again only costs a line of the log.
+#[derive(Debug, Clone)]
+pub struct History {
+    dir: PathBuf,
+}
+
+/// The flags an output was rendered with
+///
+/// Environment bindings are left out, as they are as likely to hold secrets as settings.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Parameters {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assign: Vec<String>,
+    #[serde(default, rename = "override", skip_serializing_if = "Vec::is_empty")]
+    pub overrides: Vec<String>,
+    #[serde(default, rename = "arg", skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+}
+
+impl Parameters {
+    pub fn from_call(call: &EvaluatedCall) -> Result<Self, LabeledError> {
+        Ok(Self {
+            assign: call.get_flag("assign")?.unwrap_or_default(),
+            overrides: call.get_flag("override")?.unwrap_or_default(),
+            args: call.get_flag("arg")?.unwrap_or_default(),
+            field: call.get_flag("field")?,
+        })
+    }
+
+    fn into_value(self, span: Span) -> Value {
+        let list = |items: Vec<String>| {
+            Value::list(
+                items.into_iter().map(|s| Value::string(s, span)).collect(),
+                span,
+            )
+        };
+        let mut record = Record::new();
+        record.push("assign", list(self.assign));
+        record.push("override", list(self.overrides));
+        record.push("arg", list(self.args));
+        record.push(
+            "field",
+            self.field
+                .map_or(Value::nothing(span), |field| Value::string(field, span)),
+        );
+        Value::record(record, span)
+    }
+}
+
+/// A recorded output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    /// SHA-256 of the contents, in hex
+    pub hash: String,
+    pub timestamp: DateTime<Utc>,
+    /// The file the output was written to
+    pub path: String,
+    pub format: String,
+    pub size: u64,
+    /// The program the output was rendered from, if it was a file
+    pub source: Option<String>,
+    pub parameters: Parameters,
+}
+
+impl Entry {
+    pub fn into_value(self, span: Span) -> Value {
+        let mut record = Record::new();
+        record.push("hash", Value::string(self.hash, span));
+        record.push(
+            "timestamp",
+            Value::date(self.timestamp.fixed_offset(), span),
+        );
+        record.push("path", Value::string(self.path, span));
+        record.push("format", Value::string(self.format, span));
+        record.push("size", Value::filesize(self.size as i64, span));
+        record.push(
+            "source",
+            self.source
+                .map_or(Value::nothing(span), |source| Value::string(source, span)),
+        );
+        record.push("parameters", self.parameters.into_value(span));
+        Value::record(record, span)
+    }
+}
+
+impl History {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// The history named by the `--history` flag or the `history` plugin setting, if any
+    ///
+    /// A relative directory is resolved against `base_dir`.
+    pub fn from_call(
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        base_dir: &Path,
+    ) -> Result<Option<Self>, LabeledError> {
+        let dir = match call.get_flag::<String>("history")? {
+            Some(dir) => Some(dir),
+            None => plugin_settings(engine, call)?
+                .and_then(|config| config.get_data_by_key(HISTORY_SETTING))
+                .map(Value::coerce_into_string)
+                .transpose()?,
+        };
+        Ok(dir.map(|dir| Self::new(base_dir.join(dir))))
+    }
+
+    /// Like [`History::from_call`], for commands that have nothing to do without a history
+    pub fn required(
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        base_dir: &Path,
+    ) -> Result<Self, LabeledError> {
+        Self::from_call(engine, call, base_dir)?.ok_or_else(|| {
+            LabeledError::new("No output history")
+                .with_label("No history directory was given", call.head)
+                .with_help(format!(
+                    "pass `--history <dir>`, or set the `{HISTORY_SETTING}` plugin setting"
+                ))
+        })
+    }
+
+    /// Store the contents of an output written to `path`, and append its entry to the log
+    pub fn record(
+        &self,
+        contents: &[u8],
+        path: &Path,
+        format: &str,
+        source: Option<&Path>,
+        parameters: Parameters,
+    ) -> io::Result<Entry> {
+        let hash = hash(contents);
+        let object = self.object_path(&hash);
+        if !object.is_file() {
+            let mut writes = WriteSet::new();
+            writes.add(object, contents);
+            writes.commit()?;
+        }
+
+        let entry = Entry {
+            hash,
+            timestamp: Utc::now(),
+            path: path.display().to_string(),
+            format: format.to_string(),
+            size: contents.len() as u64,
+            source: source.map(|source| source.display().to_string()),
+            parameters,
+        };
+        let mut line = serde_json::to_string(&entry).map_err(io::Error::other)?;
+        line.push('\n');
+        // A single write, so that concurrent renders do not interleave their lines
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(LOG_FILE))?
+            .write_all(line.as_bytes())?;
+        Ok(entry)
+    }
+
+    /// Every entry of the log, oldest first, an empty history having none
+    pub fn entries(&self) -> io::Result<Vec<Entry>> {
+        let file = match std::fs::File::open(self.dir.join(LOG_FILE)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        BufReader::new(file)
+            .lines()
+            .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+            .map(|line| serde_json::from_str(&line?).map_err(io::Error::other))
+            .collect()
+    }
+
+    /// The latest entry whose hash starts with `prefix`
+    ///
+    /// The same contents rendered twice share their hash, so only a prefix matching different
+    /// hashes is ambiguous.
+    pub fn find(&self, prefix: &Spanned<String>) -> Result<Entry, LabeledError> {
+        let read_error = |e: io::Error| {
+            LabeledError::new("Failed to read the output history")
+                .with_label(e.to_string(), prefix.span)
+        };
+        let hash = prefix.item.to_ascii_lowercase();
+        if hash.len() < MIN_PREFIX {
+            return Err(LabeledError::new("Hash too short").with_label(
+                format!("At least {MIN_PREFIX} characters of a hash are needed"),
+                prefix.span,
+            ));
+        }
+
+        let matches: Vec<Entry> = self
+            .entries()
+            .map_err(read_error)?
+            .into_iter()
+            .rev()
+            .filter(|entry| entry.hash.starts_with(&hash))
+            .collect();
+        let Some(latest) = matches.first() else {
+            return Err(LabeledError::new("Output not found")
+                .with_label(
+                    format!("No output of the history has hash `{hash}`"),
+                    prefix.span,
+                )
+                .with_help("list the recorded outputs with `nickel outputs log`"));
+        };
+        if let Some(other) = matches.iter().find(|entry| entry.hash != latest.hash) {
+            return Err(LabeledError::new("Ambiguous hash")
+                .with_label(
+                    format!("`{hash}` matches {} and {}", latest.hash, other.hash),
+                    prefix.span,
+                )
+                .with_help("give more characters of the hash"));
+        }
+        Ok(latest.clone())
+    }
+
+    /// The stored contents of an entry
+    pub fn contents(&self, entry: &Entry) -> io::Result<Vec<u8>> {
+        std::fs::read(self.object_path(&entry.hash))
+    }
+
+    /// Where [`History::record`] would store `contents`, or `None` when they are stored already
+    pub fn unstored(&self, contents: &[u8]) -> Option<PathBuf> {
+        let object = self.object_path(&hash(contents));
+        (!object.is_file()).then_some(object)
+    }
+
+    fn object_path(&self, hash: &str) -> PathBuf {
+        self.dir.join(OBJECTS_DIR).join(hash)
+    }
+}
+
+/// The name an output is stored under, the hash of its contents
+fn hash(contents: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(contents))
+}
@@ -0,0 +1,170 @@
+use crate::nickel::{
+    lex::lex,
+    symbols::{
+        Usage, UsageKind, imports, let_bindings, nickel_files, resolve_import, symbol_table,
+    },
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Symbols and imports of a Nickel file
+#[derive(Debug, Clone)]
+pub struct IndexedFile {
+    pub modified: Option<SystemTime>,
+    pub source: String,
+    /// Occurrences of every identifier, by name
+    pub symbols: HashMap<String, Vec<Usage>>,
+    /// Names bound by `let`, with their definitions
+    pub bindings: Vec<(String, Usage)>,
+    /// Canonical paths of the imported files that exist
+    pub imports: Vec<PathBuf>,
+    /// Whether the file failed to lex, in which case it has no symbols nor imports
+    pub lex_error: bool,
+}
+
+impl IndexedFile {
+    fn read(path: &Path, modified: Option<SystemTime>) -> std::io::Result<Self> {
+        let source = std::fs::read_to_string(path)?;
+        let (symbols, bindings, imports, lex_error) = match lex(&source) {
+            Ok(tokens) => (
+                symbol_table(&tokens),
+                let_bindings(&tokens),
+                imports(&tokens)
+                    .iter()
+                    .filter_map(|import| resolve_import(path, import).canonicalize().ok())
+                    .collect(),
+                false,
+            ),
+            Err(_) => (HashMap::new(), Vec::new(), Vec::new(), true),
+        };
+        Ok(Self {
+            modified,
+            source,
+            symbols,
+            bindings,
+            imports,
+            lex_error,
+        })
+    }
+
+    /// The occurrences of a name in this file
+    pub fn usages(&self, name: &str) -> &[Usage] {
+        self.symbols.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// The `let` bindings of this file that are never used in it
+    pub fn unused_bindings(&self) -> impl Iterator<Item = &(String, Usage)> {
+        self.bindings.iter().filter(|(name, _)| {
+            self.usages(name)
+                .iter()
+                .all(|usage| usage.kind == UsageKind::Definition)
+        })
+    }
+
+    /// The names this file defines
+    pub fn definitions(&self) -> impl Iterator<Item = &str> {
+        self.symbols
+            .iter()
+            .filter(|(_, usages)| usages.iter().any(|u| u.kind == UsageKind::Definition))
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+/// Symbol and import index of the Nickel files of a directory tree
+#[derive(Debug, Clone, Default)]
+pub struct ProjectIndex {
+    /// Indexed files, by canonical path
+    pub files: BTreeMap<PathBuf, IndexedFile>,
+}
+
+impl ProjectIndex {
+    /// Bring the index up to date with the files under `root`, returning how many were (re)indexed
+    ///
+    /// Files whose modification time did not change since they were indexed are kept as is.
+    pub fn update(&mut self, root: &Path) -> std::io::Result<usize> {
+        let mut files = BTreeMap::new();
+        let mut indexed = 0;
+
+        for path in nickel_files(root)? {
+            let path = path.canonicalize()?;
+            let modified = std::fs::metadata(&path)?.modified().ok();
+            let file = match self.files.remove(&path) {
+                Some(file) if modified.is_some() && file.modified == modified => file,
+                _ => {
+                    indexed += 1;
+                    IndexedFile::read(&path, modified)?
+                }
+            };
+            files.insert(path, file);
+        }
+
+        self.files = files;
+        Ok(indexed)
+    }
+
+    /// The files no other file imports, which are the deliverables of a project
+    pub fn entrypoints(&self) -> Vec<&PathBuf> {
+        let imported: BTreeSet<_> = self
+            .files
+            .iter()
+            .flat_map(|(path, file)| file.imports.iter().filter(move |import| *import != path))
+            .collect();
+        self.files
+            .keys()
+            .filter(|path| !imported.contains(path))
+            .collect()
+    }
+
+    /// The files reachable from `roots` by following imports, including the roots
+    pub fn reachable<'a>(&self, roots: impl IntoIterator<Item = &'a PathBuf>) -> BTreeSet<PathBuf> {
+        let mut reachable = BTreeSet::new();
+        let mut pending: Vec<PathBuf> = roots.into_iter().cloned().collect();
+        while let Some(path) = pending.pop() {
+            if let Some(file) = self.files.get(&path) {
+                pending.extend(
+                    file.imports
+                        .iter()
+                        .filter(|i| !reachable.contains(*i))
+                        .cloned(),
+                );
+            }
+            reachable.insert(path);
+        }
+        reachable
+    }
+
+    /// The indexed files importing `path`
+    pub fn importers<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> impl Iterator<Item = (&'a PathBuf, &'a IndexedFile)> {
+        self.files
+            .iter()
+            .filter(move |(_, file)| file.imports.iter().any(|import| import == path))
+    }
+}
+
+/// Project indexes kept for the lifetime of the plugin, by canonical root directory
+#[derive(Debug, Clone, Default)]
+pub struct IndexCache {
+    inner: Arc<Mutex<HashMap<PathBuf, ProjectIndex>>>,
+}
+
+impl IndexCache {
+    /// Update the index of the project rooted at `root`, building it on first use, and query it
+    ///
+    /// Returns the canonical root and how many files were (re)indexed along with the result of `f`.
+    pub fn with_index<R>(
+        &self,
+        root: &Path,
+        f: impl FnOnce(&ProjectIndex) -> R,
+    ) -> std::io::Result<(PathBuf, usize, R)> {
+        let root = root.canonicalize()?;
+        let mut indexes = self.inner.lock().unwrap();
+        let index = indexes.entry(root.clone()).or_default();
+        let indexed = index.update(&root)?;
+        Ok((root, indexed, f(index)))
+    }
+}
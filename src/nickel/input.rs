@@ -0,0 +1,339 @@
+use crate::{
+    cache::{CachedNickelValue, NickelPluginObject},
+    nickel::{format::detect_format, program::INPUT_SOURCE_NAME},
+};
+use nickel_lang_core::cache::InputFormat;
+use nu_glob::{MatchOptions, Uninterruptible};
+use nu_plugin::{EngineInterface, EvaluatedCall};
+use nu_protocol::{LabeledError, PipelineData, Span, Spanned, Value};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Directory relative paths are resolved against
+///
+/// This is the `--cwd` flag when the command has one, relative to the caller's current directory
+/// as reported by the engine. The plugin process has its own working directory, which does not
+/// follow `cd` in the calling script.
+pub fn working_dir(
+    engine: &EngineInterface,
+    call: &EvaluatedCall,
+) -> Result<PathBuf, LabeledError> {
+    let cwd = PathBuf::from(engine.get_current_dir()?);
+    Ok(match call.get_flag::<String>("cwd")? {
+        Some(dir) => cwd.join(dir),
+        None => cwd,
+    })
+}
+
+/// The plugin settings of the `nickel` entry of `$env.config.plugins`, if any
+///
+/// Commands with a `--no-config` switch ignore them when it is given, to behave as if nothing
+/// was configured.
+pub fn plugin_settings(
+    engine: &EngineInterface,
+    call: &EvaluatedCall,
+) -> Result<Option<Value>, LabeledError> {
+    if call.has_flag("no-config")? {
+        return Ok(None);
+    }
+    Ok(engine.get_plugin_config()?)
+}
+
+/// The name of the environment variable listing the import paths, as in the Nickel CLI
+pub const IMPORT_PATH_VAR: &str = "NICKEL_IMPORT_PATH";
+
+/// Directories imports are looked up in, from the `--import-path` flag and `NICKEL_IMPORT_PATH`
+///
+/// The directories of the flag come first and are relative to the working directory, while the
+/// variable is a list of paths, or a string of them separated like `PATH`.
+pub fn import_paths(
+    engine: &EngineInterface,
+    call: &EvaluatedCall,
+) -> Result<Vec<PathBuf>, LabeledError> {
+    let base_dir = working_dir(engine, call)?;
+    let mut paths: Vec<PathBuf> = call
+        .get_flag::<Vec<String>>("import-path")?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|path| base_dir.join(path))
+        .collect();
+    paths.extend(env_import_paths(engine)?);
+    Ok(paths)
+}
+
+/// Whether imports are forbidden, by the `--no-imports` switch of the command or the
+/// `no_imports` plugin setting
+pub fn no_imports(engine: &EngineInterface, call: &EvaluatedCall) -> Result<bool, LabeledError> {
+    Ok(call.has_flag("no-imports")? || forbids_imports(plugin_settings(engine, call)?))
+}
+
+/// Whether imports are forbidden by the `no_imports` plugin setting alone, for code that runs
+/// outside of a command, like the operators on Nickel values
+pub fn env_no_imports(engine: &EngineInterface) -> Result<bool, LabeledError> {
+    Ok(forbids_imports(engine.get_plugin_config()?))
+}
+
+fn forbids_imports(settings: Option<Value>) -> bool {
+    matches!(
+        settings.and_then(|settings| settings.get_data_by_key("no_imports")),
+        Some(Value::Bool { val: true, .. })
+    )
+}
+
+/// Directories imports are looked up in from `NICKEL_IMPORT_PATH` alone, for code that runs
+/// outside of a command, like the operators on Nickel values
+pub fn env_import_paths(engine: &EngineInterface) -> Result<Vec<PathBuf>, LabeledError> {
+    let mut paths = Vec::new();
+    match engine.get_env_var(IMPORT_PATH_VAR)? {
+        Some(Value::String { val, .. }) => paths.extend(std::env::split_paths(&val)),
+        Some(Value::List { vals, .. }) => {
+            for val in vals {
+                paths.push(val.coerce_into_string()?.into());
+            }
+        }
+        _ => {}
+    }
+    Ok(paths)
+}
+
+/// Resolve file arguments against `base_dir`, expanding the glob patterns among them
+///
+/// An argument naming an existing file, or without any of `*`, `?` and `[`, is kept as it is. The
+/// files matching a pattern are sorted, so that their order does not depend on the file system,
+/// and a pattern matching no file is an error rather than nothing.
+pub fn expand_paths(
+    paths: Vec<Spanned<String>>,
+    base_dir: &Path,
+) -> Result<Vec<PathBuf>, LabeledError> {
+    let mut expanded = Vec::new();
+    for path in paths {
+        if !is_pattern(&path.item, base_dir) {
+            expanded.push(base_dir.join(&path.item));
+            continue;
+        }
+
+        let matches = nu_glob::glob_with_parent(
+            &path.item,
+            MatchOptions::default(),
+            base_dir,
+            Uninterruptible,
+        )
+        .map_err(|e| {
+            LabeledError::new("Invalid glob pattern").with_label(e.to_string(), path.span)
+        })?;
+        let mut matches = matches
+            .filter_map(Result::ok)
+            .filter(|path| path.is_file())
+            .collect::<Vec<_>>();
+        if matches.is_empty() {
+            return Err(LabeledError::new("No files found")
+                .with_label(format!("No file matches `{}`", path.item), path.span));
+        }
+        matches.sort();
+        expanded.extend(matches);
+    }
+    Ok(expanded)
+}
+
+/// Whether a file argument is a glob pattern, having any of `*`, `?` and `[` without naming an
+/// existing file
+pub fn is_pattern(path: &str, base_dir: &Path) -> bool {
+    path.contains(['*', '?', '[']) && !base_dir.join(path).exists()
+}
+
+/// Decode piped bytes as source code, which Nickel reads as UTF-8
+fn utf8_source(bytes: Vec<u8>, span: Span) -> Result<String, LabeledError> {
+    String::from_utf8(bytes).map_err(|e| {
+        LabeledError::new("Invalid input encoding")
+            .with_label(format!("The input is not UTF-8 text: {e}"), span)
+    })
+}
+
+/// Source text read either from a file argument or from the pipeline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NickelInput {
+    pub source: String,
+    pub path: Option<PathBuf>,
+    #[serde(with = "format_name")]
+    pub format: InputFormat,
+    /// Directory relative paths and imports of piped code are resolved against
+    pub base_dir: Option<PathBuf>,
+    /// Directories imports are looked up in when they are not found relative to the importer
+    pub import_paths: Vec<PathBuf>,
+    /// Whether the program may not import anything, which [`new_program`] checks
+    ///
+    /// [`new_program`]: crate::nickel::program::new_program
+    pub no_imports: bool,
+    /// Name piped code is reported under in errors, from `--source-name`, `<input>` by default
+    pub source_name: Option<String>,
+}
+
+impl NickelInput {
+    /// Read the source from the optional path argument at `pos`, or from the pipeline input
+    ///
+    /// A relative path argument is resolved against `base_dir` when one is given.
+    pub fn from_call(
+        call: &EvaluatedCall,
+        input: PipelineData,
+        pos: usize,
+        base_dir: Option<PathBuf>,
+    ) -> Result<Self, LabeledError> {
+        let span = call.head;
+
+        if let Some(path) = call.opt::<Spanned<String>>(pos)? {
+            let dir = base_dir.clone().unwrap_or_default();
+            let path_span = path.span;
+            return match expand_paths(vec![path], &dir)?.as_slice() {
+                [path] => Self::from_path(path.clone(), base_dir, span),
+                paths => Err(LabeledError::new("Ambiguous file argument")
+                    .with_label(
+                        format!("This pattern matches {} files", paths.len()),
+                        path_span,
+                    )
+                    .with_help("pass a single file, or evaluate each of them with `nickel eval`")),
+            };
+        }
+
+        // Read from input
+        let source = match input {
+            PipelineData::Value(Value::String { val, .. }, _) => val,
+            // Raw files and the output of external commands come as bytes
+            PipelineData::ByteStream(stream, _) => {
+                let stream_span = stream.span();
+                utf8_source(stream.into_bytes()?, stream_span)?
+            }
+            PipelineData::Value(Value::Binary { val, internal_span }, _) => {
+                utf8_source(val, internal_span)?
+            }
+            PipelineData::Empty => {
+                return Err(LabeledError::new("No input provided")
+                    .with_label("Provide Nickel code as input or specify a file path", span));
+            }
+            _ => {
+                return Err(LabeledError::new("Invalid input type")
+                    .with_label("Expected string or binary input", span));
+            }
+        };
+        Ok(Self {
+            source_name: call.get_flag("source-name")?,
+            ..Self::from_source(source, base_dir)
+        })
+    }
+
+    /// Source code that is not read from a file, detecting whether it is data
+    pub fn from_source(source: String, base_dir: Option<PathBuf>) -> Self {
+        let format = detect_format(None, &source);
+        Self {
+            source,
+            path: None,
+            format,
+            base_dir,
+            import_paths: Vec::new(),
+            no_imports: false,
+            source_name: None,
+        }
+    }
+
+    /// Read the source of a file, resolving a relative path against `base_dir` when one is given
+    pub fn from_path(
+        path: PathBuf,
+        base_dir: Option<PathBuf>,
+        span: Span,
+    ) -> Result<Self, LabeledError> {
+        let path = match &base_dir {
+            Some(base_dir) => base_dir.join(path),
+            None => path,
+        };
+        let source = std::fs::read_to_string(&path).map_err(|e| {
+            LabeledError::new(format!("Failed to read file: {}", e))
+                .with_label(format!("Cannot read file '{}'", path.display()), span)
+        })?;
+        let format = detect_format(Some(&path), &source);
+        Ok(Self {
+            source,
+            path: Some(path),
+            format,
+            base_dir,
+            import_paths: Vec::new(),
+            no_imports: false,
+            source_name: None,
+        })
+    }
+
+    /// The source code of a cached Nickel value, with the path of the file it was read from
+    ///
+    /// Keeping the path resolves the relative imports of the code next to its file, rather than
+    /// against `base_dir`.
+    pub fn from_cached(cached: &CachedNickelValue, base_dir: Option<PathBuf>) -> Option<Self> {
+        // Parsed terms record the format of their source, anything else is Nickel code
+        let format = match &cached.value {
+            NickelPluginObject::SerializedNickelTerm { type_info, .. } => {
+                format_from_name(type_info).unwrap_or(InputFormat::Nickel)
+            }
+            _ => InputFormat::Nickel,
+        };
+        Some(Self {
+            source: cached.as_source_code()?.clone(),
+            path: cached.source_path().map(Path::to_path_buf),
+            format,
+            base_dir,
+            import_paths: Vec::new(),
+            no_imports: false,
+            source_name: None,
+        })
+    }
+
+    /// The name the source is reported under in errors, whose directory its imports resolve from
+    ///
+    /// Piped code is named after `--source-name`, or `<input>`, in the input's base directory.
+    pub fn name(&self) -> PathBuf {
+        if let Some(path) = &self.path {
+            return path.clone();
+        }
+        let name = self.source_name.as_deref().unwrap_or(INPUT_SOURCE_NAME);
+        match &self.base_dir {
+            Some(base_dir) => base_dir.join(name),
+            None => name.into(),
+        }
+    }
+
+    /// Whether the input is plain data rather than Nickel code
+    pub fn is_data(&self) -> bool {
+        self.format != InputFormat::Nickel
+    }
+}
+
+/// The [`InputFormat`] named `name`, as written by [`InputFormat::to_str`]
+fn format_from_name(name: &str) -> Option<InputFormat> {
+    [
+        InputFormat::Nickel,
+        InputFormat::Json,
+        InputFormat::Yaml,
+        InputFormat::Toml,
+        InputFormat::Text,
+    ]
+    .into_iter()
+    .find(|format| format.to_str() == name)
+}
+
+/// An [`InputFormat`] written as its name, as Nickel does not serialize it
+mod format_name {
+    use super::format_from_name;
+    use nickel_lang_core::cache::InputFormat;
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(
+        format: &InputFormat,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(format.to_str())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<InputFormat, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        format_from_name(&name)
+            .ok_or_else(|| D::Error::custom(format!("unknown input format `{name}`")))
+    }
+}
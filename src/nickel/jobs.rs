@@ -0,0 +1,197 @@
+use crate::nickel::program::EVAL_STACK_SIZE;
+use nu_plugin::EvaluatedCall;
+use nu_protocol::{LabeledError, ShellError, Signals, Span, Spanned};
+use std::collections::BTreeMap;
+use std::num::NonZero;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Nice value of the worker threads with `--nice`, the default of the `nice` command
+#[cfg(target_os = "linux")]
+const NICENESS: i32 = 10;
+
+/// How a batch command spreads its items over threads, from its `--jobs` and `--nice` flags
+#[derive(Debug, Clone, Copy)]
+pub struct Jobs {
+    /// Number of items processed at the same time, the number of CPUs by default
+    pub limit: usize,
+    /// Whether the worker threads lower their scheduling priority
+    pub nice: bool,
+}
+
+impl Jobs {
+    pub fn from_call(call: &EvaluatedCall) -> Result<Self, LabeledError> {
+        let limit = match call.get_flag::<Spanned<usize>>("jobs")? {
+            Some(jobs) if jobs.item == 0 => {
+                return Err(LabeledError::new("Invalid number of jobs")
+                    .with_label("At least one job is needed to do anything", jobs.span));
+            }
+            Some(jobs) => jobs.item,
+            None => thread::available_parallelism().map_or(1, NonZero::get),
+        };
+        Ok(Self {
+            limit,
+            nice: call.has_flag("nice")?,
+        })
+    }
+
+    /// Apply `f` to every item on up to `limit` threads, returning the results in item order
+    ///
+    /// Items are handed out one at a time, so that a slow one does not hold back the others.
+    /// Once Ctrl-C is pressed no item is started, and the batch fails when the running ones end.
+    pub fn run<T: Sync, R: Send>(
+        self,
+        items: &[T],
+        signals: &Signals,
+        span: Span,
+        f: impl Fn(&T) -> R + Sync,
+    ) -> Result<Vec<R>, LabeledError> {
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(
+            std::iter::repeat_with(|| None)
+                .take(items.len())
+                .collect::<Vec<_>>(),
+        );
+
+        let worker = || {
+            if self.nice {
+                lower_priority();
+            }
+            while !signals.interrupted() {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+                let result = f(item);
+                results.lock().unwrap_or_else(|e| e.into_inner())[index] = Some(result);
+            }
+        };
+        thread::scope(|scope| {
+            let workers = (0..self.limit.min(items.len()))
+                .map(|_| {
+                    thread::Builder::new()
+                        .name("nickel job".into())
+                        .stack_size(EVAL_STACK_SIZE)
+                        .spawn_scoped(scope, worker)
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| {
+                    LabeledError::new("Failed to start evaluation").with_label(e.to_string(), span)
+                })?;
+            for worker in workers {
+                worker.join().map_err(|_| {
+                    LabeledError::new("Nickel evaluation failed")
+                        .with_label("An evaluation thread panicked", span)
+                })?;
+            }
+            Ok::<_, LabeledError>(())
+        })?;
+
+        if signals.interrupted() {
+            return Err(ShellError::Interrupted { span }.into());
+        }
+        Ok(results
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner())
+            .into_iter()
+            .map(|result| result.expect("every item is processed unless interrupted"))
+            .collect())
+    }
+
+    /// Apply `f` to items as they are read, on up to `limit` threads, yielding the results in
+    /// item order as soon as they and the ones before them are done
+    ///
+    /// A free thread reads the next item, so that the items are never all held at once. Once
+    /// Ctrl-C is pressed, or the results are no longer read, no item is started.
+    pub fn stream<T, R>(
+        self,
+        items: impl Iterator<Item = T> + Send + 'static,
+        signals: &Signals,
+        span: Span,
+        f: impl Fn(T) -> R + Send + Sync + 'static,
+    ) -> Result<impl Iterator<Item = R> + Send + 'static, LabeledError>
+    where
+        R: Send + 'static,
+    {
+        let items = Arc::new(Mutex::new(items.enumerate()));
+        let f = Arc::new(f);
+        // Bounded, so that the threads wait for the results to be read rather than pile them up
+        let (sender, receiver) = mpsc::sync_channel(self.limit);
+        for _ in 0..self.limit {
+            let (items, f, sender, signals) =
+                (items.clone(), f.clone(), sender.clone(), signals.clone());
+            thread::Builder::new()
+                .name("nickel job".into())
+                .stack_size(EVAL_STACK_SIZE)
+                .spawn(move || {
+                    if self.nice {
+                        lower_priority();
+                    }
+                    while !signals.interrupted() {
+                        let next = items.lock().unwrap_or_else(|e| e.into_inner()).next();
+                        let Some((index, item)) = next else {
+                            break;
+                        };
+                        if sender.send((index, f(item))).is_err() {
+                            break;
+                        }
+                    }
+                })
+                .map_err(|e| {
+                    LabeledError::new("Failed to start evaluation").with_label(e.to_string(), span)
+                })?;
+        }
+        Ok(InOrder {
+            receiver,
+            pending: BTreeMap::new(),
+            next: 0,
+        })
+    }
+}
+
+/// The results of [`Jobs::stream`], put back in the order of their items
+struct InOrder<R> {
+    receiver: Receiver<(usize, R)>,
+    /// Results done before the ones of earlier items
+    pending: BTreeMap<usize, R>,
+    next: usize,
+}
+
+impl<R> Iterator for InOrder<R> {
+    type Item = R;
+
+    fn next(&mut self) -> Option<R> {
+        loop {
+            if let Some(result) = self.pending.remove(&self.next) {
+                self.next += 1;
+                return Some(result);
+            }
+            match self.receiver.recv() {
+                Ok((index, result)) => {
+                    self.pending.insert(index, result);
+                }
+                // Every thread is done, and an item whose thread panicked has no result to wait for
+                Err(_) => {
+                    let (index, result) = self.pending.pop_first()?;
+                    self.next = index + 1;
+                    return Some(result);
+                }
+            }
+        }
+    }
+}
+
+/// Lower the scheduling priority of the current thread, like `nice` does for a process
+///
+/// Linux gives every thread a nice value of its own. Elsewhere it belongs to the whole plugin,
+/// which would stay slowed down after the batch, so nothing is done.
+#[cfg(target_os = "linux")]
+fn lower_priority() {
+    // SAFETY: setpriority only reads its arguments, and 0 names the calling thread on Linux
+    unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, NICENESS) };
+}
+
+#[cfg(not(target_os = "linux"))]
+fn lower_priority() {}
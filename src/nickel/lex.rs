@@ -0,0 +1,125 @@
+use nickel_lang_core::parser::{
+    error::{LexicalError, ParseError},
+    lexer::{Lexer, Token},
+};
+
+/// A token of Nickel source, located by its byte range and by the line and column it starts at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexedToken {
+    /// Name of the token, e.g. `Identifier`, `Let` or `LBrace`
+    pub kind: String,
+    /// Lexer mode the token was produced in: `normal`, `string` or `multiline_string`
+    pub mode: &'static str,
+    pub text: String,
+    pub start: usize,
+    pub end: usize,
+    /// 1-based line of the first character
+    pub line: usize,
+    /// 1-based column of the first character, in characters
+    pub column: usize,
+}
+
+/// A lexical error, with the byte offset it occurred at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexError {
+    pub message: String,
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Lex Nickel source into its token stream
+///
+/// Comments and whitespace are skipped by the Nickel lexer, so they have no tokens.
+pub fn lex(source: &str) -> Result<Vec<LexedToken>, LexError> {
+    let lines = LineIndex::new(source);
+
+    Lexer::new(source)
+        .map(|token| {
+            let (start, token, end) = token.map_err(|e| lex_error(e, &lines))?;
+            let (mode, name) = match &token {
+                Token::Normal(t) => ("normal", format!("{t:?}")),
+                Token::Str(t) => ("string", format!("{t:?}")),
+                Token::MultiStr(t) => ("multiline_string", format!("{t:?}")),
+            };
+            let (line, column) = lines.location(start);
+            Ok(LexedToken {
+                kind: variant_name(&name).to_string(),
+                mode,
+                text: source.get(start..end).unwrap_or_default().to_string(),
+                start,
+                end,
+                line,
+                column,
+            })
+        })
+        .collect()
+}
+
+/// Whether some text is a single identifier, and not e.g. a keyword
+pub fn is_identifier(text: &str) -> bool {
+    lex(text).is_ok_and(|tokens| {
+        matches!(tokens.as_slice(), [token] if token.kind == "Identifier" && token.text == text)
+    })
+}
+
+/// The variant name of a token from its debug representation, e.g. `Identifier("foo")`
+fn variant_name(debug: &str) -> &str {
+    debug
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .next()
+        .unwrap_or(debug)
+}
+
+fn lex_error(error: ParseError, lines: &LineIndex) -> LexError {
+    let (message, offset) = match error {
+        ParseError::Lexical(LexicalError::UnmatchedCloseBrace(offset)) => {
+            ("unmatched closing brace", offset)
+        }
+        ParseError::Lexical(LexicalError::InvalidEscapeSequence(offset)) => {
+            ("invalid escape sequence", offset)
+        }
+        ParseError::Lexical(LexicalError::InvalidAsciiEscapeCode(offset)) => {
+            ("invalid ASCII escape code", offset)
+        }
+        ParseError::Lexical(LexicalError::StringDelimiterMismatch {
+            closing_delimiter, ..
+        }) => (
+            "multiline string closed by a delimiter with more `%` than the opening one",
+            closing_delimiter.start,
+        ),
+        ParseError::Lexical(LexicalError::Generic(range)) => ("unexpected character", range.start),
+        _ => ("invalid token", 0),
+    };
+    let (line, column) = lines.location(offset);
+    LexError {
+        message: message.to_string(),
+        offset,
+        line,
+        column,
+    }
+}
+
+/// Byte offsets of the start of each line, to turn offsets into 1-based lines and columns
+struct LineIndex<'a> {
+    source: &'a str,
+    starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    fn new(source: &'a str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { source, starts }
+    }
+
+    fn location(&self, offset: usize) -> (usize, usize) {
+        let line = self.starts.partition_point(|&start| start <= offset) - 1;
+        let column = self
+            .source
+            .get(self.starts[line]..offset)
+            .map_or(0, |prefix| prefix.chars().count());
+        (line + 1, column + 1)
+    }
+}
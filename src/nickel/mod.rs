@@ -1,4 +1,27 @@
+#[cfg(feature = "dataframe")]
+pub mod arrow;
+pub mod codegen;
 pub mod command;
+pub mod convert;
+pub mod error;
+pub mod format;
+pub mod history;
+pub mod index;
+pub mod input;
+pub mod jobs;
+pub mod lex;
+pub mod package;
+pub mod program;
+pub mod provenance;
+pub mod schema;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod stamp;
+pub mod stdlib;
+pub mod suggest;
+pub mod symbols;
 pub mod values;
+pub mod worker;
+pub mod write;
 
-pub use values::*;
\ No newline at end of file
+pub use values::*;
@@ -0,0 +1,253 @@
+use crate::nickel::error::render_nickel_error;
+use nickel_lang_core::{
+    error::NullReporter,
+    eval::cache::CacheImpl,
+    identifier::LocIdent,
+    program::Program,
+    term::{RichTerm, Term, record::RecordData},
+};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// File name of a package manifest
+pub const MANIFEST_FILE: &str = "Nickel-pkg.ncl";
+/// Minimal Nickel version written in new manifests
+pub const MINIMAL_NICKEL_VERSION: &str = "1.14";
+
+/// The parts of a package manifest needed to resolve dependencies
+#[derive(Debug, Clone)]
+pub struct Manifest {
+    pub name: String,
+    pub version: String,
+    pub dependencies: BTreeMap<String, Dependency>,
+}
+
+/// A dependency as declared in a manifest
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Dependency {
+    Path(PathBuf),
+    Git { url: String },
+    Index { package: String, version: String },
+}
+
+impl Dependency {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Dependency::Path(_) => "path",
+            Dependency::Git { .. } => "git",
+            Dependency::Index { .. } => "index",
+        }
+    }
+
+    /// Where the dependency is fetched from: its path, git URL or index package
+    pub fn source(&self) -> String {
+        match self {
+            Dependency::Path(path) => path.display().to_string(),
+            Dependency::Git { url } => url.clone(),
+            Dependency::Index { package, .. } => package.clone(),
+        }
+    }
+}
+
+/// Resolved dependency graph of a package
+///
+/// It is only reported: the lockfile of a package is the `Nickel-pkg.lock` of the Nickel CLI,
+/// which also fetches the git and index dependencies.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Resolution {
+    /// Direct dependencies of the root package, by local name
+    pub dependencies: BTreeMap<String, PathBuf>,
+    /// Every package of the graph, by its path relative to the root package
+    pub packages: BTreeMap<PathBuf, ResolvedPackage>,
+    /// Git and index dependencies of the graph, which only the Nickel CLI can fetch
+    pub external: BTreeSet<Dependency>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedPackage {
+    pub name: String,
+    pub version: String,
+    /// Dependencies of this package, by local name
+    pub dependencies: BTreeMap<String, PathBuf>,
+}
+
+/// Render the manifest written by `nickel package init`
+pub fn manifest_template(name: &str) -> String {
+    format!(
+        r#"{{
+  name = "{name}",
+  description = "",
+  version = "0.1.0",
+  authors = [],
+  minimal_nickel_version = "{MINIMAL_NICKEL_VERSION}",
+  dependencies = {{}},
+}} | std.package.Manifest
+"#
+    )
+}
+
+/// Evaluate the manifest of the package rooted at `dir`
+pub fn read_manifest(dir: &Path) -> Result<Manifest, String> {
+    let path = dir.join(MANIFEST_FILE);
+    if !path.is_file() {
+        return Err(format!("no {MANIFEST_FILE} found in {}", dir.display()));
+    }
+
+    let mut program: Program<CacheImpl> =
+        Program::new_from_file(path.as_os_str(), std::io::sink(), NullReporter {})
+            .map_err(|e| format!("cannot read {}: {e}", path.display()))?;
+    let term = program
+        .eval_full_for_export()
+        .map_err(|e| render_nickel_error(&mut program.files(), e))?;
+
+    let Term::Record(data) = term.as_ref() else {
+        return Err(format!("{} does not evaluate to a record", path.display()));
+    };
+
+    let dependencies = match field(data, "dependencies").map(AsRef::as_ref) {
+        Some(Term::Record(deps)) => deps
+            .fields
+            .iter()
+            .filter_map(|(name, dep)| Some((name.label().to_string(), dep.value.as_ref()?)))
+            .map(|(name, dep)| Ok((name.clone(), parse_dependency(&name, dep)?)))
+            .collect::<Result<_, String>>()?,
+        _ => BTreeMap::new(),
+    };
+
+    Ok(Manifest {
+        name: string_field(data, "name").unwrap_or_default(),
+        version: version_field(data).unwrap_or_default(),
+        dependencies,
+    })
+}
+
+/// Resolve the dependency graph of the package rooted at `root`
+///
+/// Only path dependencies can be resolved by the plugin. Git and index dependencies need to be
+/// fetched with the Nickel CLI, they are listed as they are declared.
+pub fn resolve(root: &Path) -> Result<Resolution, String> {
+    let manifest = read_manifest(root)?;
+    let mut resolution = Resolution::default();
+    let mut visiting = BTreeSet::new();
+
+    resolution.dependencies =
+        resolve_dependencies(root, root, &manifest, &mut resolution, &mut visiting)?;
+    Ok(resolution)
+}
+
+fn resolve_dependencies(
+    root: &Path,
+    dir: &Path,
+    manifest: &Manifest,
+    resolution: &mut Resolution,
+    visiting: &mut BTreeSet<PathBuf>,
+) -> Result<BTreeMap<String, PathBuf>, String> {
+    let mut resolved = BTreeMap::new();
+
+    for (name, dependency) in &manifest.dependencies {
+        let Dependency::Path(dep_path) = dependency else {
+            resolution.external.insert(dependency.clone());
+            continue;
+        };
+
+        let dep_dir = dir.join(dep_path);
+        let key = relative_key(root, &dep_dir)?;
+        resolved.insert(name.clone(), key.clone());
+
+        if resolution.packages.contains_key(&key) {
+            continue;
+        }
+        if !visiting.insert(key.clone()) {
+            return Err(format!("dependency cycle through `{}`", key.display()));
+        }
+
+        let dep_manifest = read_manifest(&dep_dir)?;
+        let dependencies =
+            resolve_dependencies(root, &dep_dir, &dep_manifest, resolution, visiting)?;
+        visiting.remove(&key);
+
+        resolution.packages.insert(
+            key,
+            ResolvedPackage {
+                name: dep_manifest.name,
+                version: dep_manifest.version,
+                dependencies,
+            },
+        );
+    }
+
+    Ok(resolved)
+}
+
+/// Path of a dependency relative to the root package, so that the resolution does not depend on
+/// where the package is
+fn relative_key(root: &Path, dep_dir: &Path) -> Result<PathBuf, String> {
+    let root = root
+        .canonicalize()
+        .map_err(|e| format!("cannot resolve {}: {e}", root.display()))?;
+    let dep = dep_dir
+        .canonicalize()
+        .map_err(|e| format!("cannot resolve {}: {e}", dep_dir.display()))?;
+
+    let common = root
+        .components()
+        .zip(dep.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut relative = PathBuf::new();
+    for _ in root.components().skip(common) {
+        relative.push("..");
+    }
+    relative.extend(dep.components().skip(common));
+    Ok(relative)
+}
+
+fn field<'a>(data: &'a RecordData, name: &str) -> Option<&'a RichTerm> {
+    data.fields.get(&LocIdent::new(name))?.value.as_ref()
+}
+
+fn string_field(data: &RecordData, name: &str) -> Option<String> {
+    match field(data, name)?.as_ref() {
+        Term::Str(s) => Some(s.to_string()),
+        _ => None,
+    }
+}
+
+/// The `Semver` contract normalizes versions into records, render them back as strings
+fn version_field(data: &RecordData) -> Option<String> {
+    match field(data, "version")?.as_ref() {
+        Term::Str(s) => Some(s.to_string()),
+        Term::Record(version) => {
+            let part = |name| match field(version, name).map(AsRef::as_ref) {
+                Some(Term::Num(n)) => Some(n.to_string()),
+                Some(Term::Str(s)) => Some(s.to_string()),
+                _ => None,
+            };
+            let mut rendered = format!("{}.{}.{}", part("major")?, part("minor")?, part("patch")?);
+            if let Some(pre) = part("pre") {
+                rendered = format!("{rendered}-{pre}");
+            }
+            Some(rendered)
+        }
+        _ => None,
+    }
+}
+
+fn parse_dependency(name: &str, term: &RichTerm) -> Result<Dependency, String> {
+    let invalid = || format!("invalid definition for dependency `{name}`");
+
+    let Term::EnumVariant { tag, arg, .. } = term.as_ref() else {
+        return Err(invalid());
+    };
+    match (tag.label(), arg.as_ref()) {
+        ("Path", Term::Str(path)) => Ok(Dependency::Path(PathBuf::from(path.as_str()))),
+        ("Git", Term::Record(git)) => Ok(Dependency::Git {
+            url: string_field(git, "url").ok_or_else(invalid)?,
+        }),
+        ("Index", Term::Record(index)) => Ok(Dependency::Index {
+            package: string_field(index, "package").unwrap_or_default(),
+            version: string_field(index, "version").unwrap_or_default(),
+        }),
+        _ => Err(invalid()),
+    }
+}
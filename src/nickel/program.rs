@@ -0,0 +1,828 @@
+use crate::nickel::{
+    convert::{nickel_to_nu_value, value_to_nickel},
+    error::nickel_error,
+    format::{OutputFormat, parse_data, to_nuon},
+    input::NickelInput,
+    stdlib::StdlibFilter,
+};
+use nickel_lang_core::{
+    cache::{CacheHub, InputFormat, SourcePath},
+    error::{EvalError, NullReporter},
+    eval::{Closure, cache::CacheImpl},
+    program::Program,
+    serialize::{self, ExportFormat},
+    term::{
+        Import, LabeledType, MergePriority, RichTerm, SharedTerm, Term, TypeAnnotation, UnaryOp,
+        record::RecordData,
+    },
+    traverse::{Traverse, TraverseControl, TraverseOrder},
+    typ::{Type, TypeF},
+    typecheck::TypecheckMode,
+};
+use nu_protocol::{
+    ByteStream, ByteStreamType, LabeledError, ListStream, PipelineData, ShellError, Signals, Span,
+    Value,
+};
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{
+    Arc,
+    atomic::{AtomicU8, AtomicUsize, Ordering},
+    mpsc::{self, RecvTimeoutError, SyncSender},
+};
+use std::thread;
+use std::time::Duration;
+
+/// How often a running evaluation checks whether it was interrupted
+pub const INTERRUPT_POLL: Duration = Duration::from_millis(50);
+
+/// Stack size of evaluation threads, as deeply nested programs recurse deeply in Nickel
+pub const EVAL_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Number of interrupted evaluations left running in the background past which no evaluation is
+/// started on a thread anymore, as each of them may hold on to CPU and memory
+pub const MAX_DETACHED: usize = 8;
+
+/// Interrupted evaluations still running in the background, see [`detached_evaluations`]
+static DETACHED: AtomicUsize = AtomicUsize::new(0);
+
+/// States of an evaluation thread, which either finishes or is abandoned when interrupted
+const RUNNING: u8 = 0;
+const FINISHED: u8 = 1;
+const ABANDONED: u8 = 2;
+
+/// Number of converted elements, or serialized chunks, of a streamed result waiting to be read
+const STREAM_BUFFER: usize = 64;
+
+/// Size of the chunks a streamed serialization is sent in
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The name given to Nickel code piped in as a string
+pub const INPUT_SOURCE_NAME: &str = "<input>";
+
+/// The name the record of a prelude file is bound to in programs
+pub const PRELUDE_NAME: &str = "prelude";
+
+/// Build a Nickel program from some input
+///
+/// Programs read from a file keep its path, so the import resolver looks up relative imports
+/// (Nickel, JSON, YAML, TOML or text files) next to it. Piped code resolves its imports from the
+/// input's base directory, or from the current directory when it has none. Imports not found
+/// there are then looked up in the input's import paths, in order. An input that may not import
+/// anything fails here when it does, before anything is evaluated.
+pub fn new_program(input: &NickelInput, span: Span) -> Result<Program<CacheImpl>, LabeledError> {
+    let program = match &input.path {
+        Some(path) if input.is_data() => {
+            Program::new_from_file(path.as_os_str(), std::io::sink(), NullReporter {})
+        }
+        // The source is used rather than the file, as it may have been edited since it was read
+        Some(path) => Program::new_from_source(
+            input.source.as_bytes(),
+            path.as_os_str(),
+            std::io::sink(),
+            NullReporter {},
+        ),
+        None => Program::new_from_source(
+            input.source.as_bytes(),
+            // Imports are resolved relative to the parent of the source name
+            input.name(),
+            std::io::sink(),
+            NullReporter {},
+        ),
+    };
+
+    let mut program = program.map_err(|e| {
+        LabeledError::new("Failed to load Nickel program").with_label(e.to_string(), span)
+    })?;
+    program.add_import_paths(input.import_paths.iter().cloned());
+    if input.no_imports {
+        forbid_imports(&mut program, None, span)?;
+    }
+    Ok(program)
+}
+
+/// Merge `path.to.field=value` assignments into a program, like Nickel's customize mode
+///
+/// Assignments use the default merge priority and can only set fields left undefined (or with a
+/// default value), while overrides use the highest priority and replace any definition.
+pub fn add_assignments(
+    program: &mut Program<CacheImpl>,
+    assignments: Vec<String>,
+    priority: MergePriority,
+    span: Span,
+) -> Result<(), LabeledError> {
+    let overrides = assignments
+        .into_iter()
+        .map(|assignment| {
+            program
+                .parse_override(assignment, priority.clone())
+                .map_err(|e| {
+                    nickel_error(&mut program.files(), e, "Invalid field assignment", span)
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    program.add_overrides(overrides);
+    Ok(())
+}
+
+/// Bind variables in scope of the code of a program
+///
+/// The bindings are written before the code, on its first line unless a value spans several.
+pub fn bind_values(
+    input: &mut NickelInput,
+    bindings: Vec<(String, Value)>,
+    span: Span,
+) -> Result<(), LabeledError> {
+    let mut prelude = String::new();
+    for (name, value) in bindings {
+        prelude.push_str(&format!(
+            "let {name} = {} in ",
+            value_to_nickel(&value, span)?
+        ));
+    }
+
+    input.source.insert_str(0, &prelude);
+    Ok(())
+}
+
+/// Bind the record of a prelude file in scope of the code of a program, as [`PRELUDE_NAME`]
+///
+/// The prelude is imported rather than pasted in, so that its errors point into its own file.
+pub fn bind_prelude(input: &mut NickelInput, path: &Path, span: Span) -> Result<(), LabeledError> {
+    let path = value_to_nickel(&Value::string(path.to_string_lossy(), span), span)?;
+    input
+        .source
+        .insert_str(0, &format!("let {PRELUDE_NAME} = import {path} in "));
+    Ok(())
+}
+
+/// Source code applying the function a program evaluates to, to values converted to Nickel
+///
+/// The program is wrapped in parentheses from its first line, so that its errors keep their line
+/// numbers, and closed on a line of its own, after any comment ending it.
+pub fn apply_source(source: &str, args: &[Value], span: Span) -> Result<String, LabeledError> {
+    let mut application = format!("({source}\n)");
+    for arg in args {
+        application.push_str(&format!(" ({})", value_to_nickel(arg, span)?));
+    }
+    Ok(application)
+}
+
+/// Whether an evaluated term is a function, which has no data to convert or export
+pub fn is_function(term: &RichTerm) -> bool {
+    matches!(
+        term.as_ref(),
+        Term::Fun(..) | Term::FunPattern(..) | Term::Match(..)
+    )
+}
+
+/// Parse `name=value` arguments into bindings for [`bind_values`]
+///
+/// Values are read as NUON, so `port=8080` binds a number and `tags=[a b]` a list, and values
+/// that are not NUON, like `env=prod`, are bound as strings.
+pub fn parse_args(args: Vec<String>, span: Span) -> Result<Vec<(String, Value)>, LabeledError> {
+    let invalid = |label: String| {
+        LabeledError::new("Invalid argument")
+            .with_label(label, span)
+            .with_help("arguments are written `name=value`")
+    };
+
+    args.into_iter()
+        .map(|arg| {
+            let Some((name, value)) = arg.split_once('=') else {
+                return Err(invalid(format!("`{arg}` has no value")));
+            };
+            if !is_identifier(name) {
+                return Err(invalid(format!("`{name}` is not a Nickel identifier")));
+            }
+            let value =
+                nuon::from_nuon(value, Some(span)).unwrap_or_else(|_| Value::string(value, span));
+            Ok((name.to_string(), value))
+        })
+        .collect()
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '\''))
+}
+
+/// Fail if a program imports any file or package, before anything is evaluated
+///
+/// Imports are written with literal paths, so parsing the program is enough to find them all.
+/// The import of the prelude bound by [`bind_prelude`], if any, is the one allowed.
+pub fn forbid_imports(
+    program: &mut Program<CacheImpl>,
+    prelude: Option<&Path>,
+    span: Span,
+) -> Result<(), LabeledError> {
+    let term = program
+        .parse()
+        .map_err(|e| nickel_error(&mut program.files(), e, "Nickel parsing failed", span))?;
+    let import = term.traverse_ref(
+        &mut |term: &RichTerm, _: &()| match term.as_ref() {
+            Term::Import(Import::Path { path, .. })
+                if prelude.is_some_and(|prelude| prelude.as_os_str() == path) =>
+            {
+                TraverseControl::Continue
+            }
+            Term::Import(import) => TraverseControl::Return(import.clone()),
+            _ => TraverseControl::Continue,
+        },
+        &(),
+    );
+
+    let imported = match import {
+        Some(Import::Path { path, .. }) => format!("\"{}\"", path.to_string_lossy()),
+        Some(Import::Package { id }) => format!("the package `{id}`"),
+        None => return Ok(()),
+    };
+    Err(LabeledError::new("Imports are disabled")
+        .with_label(format!("The program imports {imported}"), span)
+        .with_help("imports are forbidden by `--no-imports` or the `no_imports` plugin setting"))
+}
+
+/// Fail if the Nickel file at `path` imports any file or package, before anything is evaluated
+///
+/// Commands importing a file they are given, like a template or a policy, check the file itself
+/// rather than the program importing it, whose import of the file is allowed. Data files cannot
+/// import anything.
+pub fn forbid_file_imports(path: &Path, span: Span) -> Result<(), LabeledError> {
+    let input = NickelInput::from_path(path.to_path_buf(), None, span)?;
+    if input.is_data() {
+        return Ok(());
+    }
+    forbid_imports(&mut new_program(&input, span)?, None, span)
+}
+
+/// Fail if a program uses a part of the standard library that `filter` hides, before anything
+/// is evaluated
+///
+/// Like imports, uses of the standard library are written as literal paths, e.g.
+/// `std.string.length`. Using `std` in any other way, like binding it to another name, could reach
+/// any of it, so it counts as using all of it. Imported files are not checked, which
+/// `--no-imports` takes care of.
+pub fn restrict_stdlib(
+    program: &mut Program<CacheImpl>,
+    filter: &StdlibFilter,
+    span: Span,
+) -> Result<(), LabeledError> {
+    if filter.is_empty() {
+        return Ok(());
+    }
+    let term = program
+        .parse()
+        .map_err(|e| nickel_error(&mut program.files(), e, "Nickel parsing failed", span))?;
+    let refusal = term.traverse_ref(
+        &mut |term: &RichTerm, _: &()| {
+            // The fields of a chain of accesses, outermost last, and the term they are read from
+            let mut fields = Vec::new();
+            let mut root = term;
+            while let Term::Op1(UnaryOp::RecordAccess(id), record) = root.as_ref() {
+                fields.push(id.label());
+                root = record;
+            }
+            match root.as_ref() {
+                Term::Var(id) if id.label() == "std" => {
+                    let name = std::iter::once("std")
+                        .chain(fields.into_iter().rev())
+                        .collect::<Vec<_>>()
+                        .join(".");
+                    match filter.refusal(&name) {
+                        Some(refusal) => TraverseControl::Return(refusal),
+                        // The inner accesses of the chain only use less of it
+                        None => TraverseControl::SkipBranch,
+                    }
+                }
+                _ => TraverseControl::Continue,
+            }
+        },
+        &(),
+    );
+
+    match refusal {
+        Some(refusal) => Err(LabeledError::new("Standard library restricted")
+            .with_label(refusal, span)
+            .with_help(
+                "the standard library is restricted by `--stdlib-allow` and `--stdlib-deny`, or \
+                 the `stdlib_allow` and `stdlib_deny` plugin settings",
+            )),
+        None => Ok(()),
+    }
+}
+
+/// Remove the type and contract annotations of a program and of its imports, once typechecked
+///
+/// Statically typed blocks are still typechecked, but no contract is checked at runtime. Contracts
+/// applied explicitly, e.g. with `std.contract.apply`, and annotations inside patterns are kept.
+///
+/// Record contracts also give default values, so those written in a file, as a record or a
+/// variable bound to one by a `let`, are kept for their defaults: they are made open and lose the
+/// contracts of their fields, so that they check nothing. Record contracts from another file, or
+/// computed by a function, are removed with the defaults they would give.
+pub fn disable_contracts(program: &mut Program<CacheImpl>, span: Span) -> Result<(), LabeledError> {
+    // Typechecking converts the files again, so it must come before the annotations are removed
+    program
+        .typecheck(TypecheckMode::Walk)
+        .map_err(|e| nickel_error(&mut program.files(), e, "Nickel typechecking failed", span))?;
+
+    // Stripping annotations cannot fail, the program is only missing if it was not parsed
+    program
+        .custom_transform(|_, term| strip_annotations(term))
+        .map_err(|_| {
+            LabeledError::new("Failed to disable contracts")
+                .with_label("The Nickel program was not parsed", span)
+        })
+}
+
+fn strip_annotations(term: RichTerm) -> Result<RichTerm, std::convert::Infallible> {
+    let is_record =
+        |term: &RichTerm| matches!(term.as_ref(), Term::Record(_) | Term::RecRecord(..));
+    // The variables bound to records, which may be record contracts giving defaults
+    let mut records = HashSet::new();
+    term.traverse_ref(
+        &mut |term: &RichTerm, _: &()| {
+            if let Term::Let(bindings, _, _) = term.as_ref() {
+                for (id, value) in bindings.iter().filter(|(_, value)| is_record(value)) {
+                    records.insert(id.label().to_string());
+                }
+            }
+            TraverseControl::<(), ()>::Continue
+        },
+        &(),
+    );
+
+    let gives_defaults = |labeled: &LabeledType| match &labeled.typ.typ {
+        TypeF::Contract(contract) => match contract.as_ref() {
+            Term::Var(id) => records.contains(id.label()),
+            _ => is_record(contract),
+        },
+        _ => false,
+    };
+    let strip = |annotation: TypeAnnotation| TypeAnnotation {
+        typ: None,
+        contracts: annotation
+            .contracts
+            .into_iter()
+            .filter(|labeled| gives_defaults(labeled))
+            .collect(),
+    };
+    let strip_fields = |data: &mut RecordData| {
+        data.attrs.open = true;
+        for field in data.fields.values_mut() {
+            field.metadata.annotation = strip(std::mem::take(&mut field.metadata.annotation));
+        }
+    };
+
+    term.traverse(
+        &mut |term: RichTerm| {
+            let pos = term.pos;
+            Ok(match SharedTerm::into_owned(term.term) {
+                Term::Annotated(annotation, inner) => {
+                    let annotation = strip(annotation);
+                    if annotation.contracts.is_empty() {
+                        inner
+                    } else {
+                        RichTerm::new(Term::Annotated(annotation, inner), pos)
+                    }
+                }
+                Term::Record(mut data) => {
+                    strip_fields(&mut data);
+                    RichTerm::new(Term::Record(data), pos)
+                }
+                Term::RecRecord(mut data, includes, dyn_fields, deps) => {
+                    strip_fields(&mut data);
+                    RichTerm::new(Term::RecRecord(data, includes, dyn_fields, deps), pos)
+                }
+                term => RichTerm::new(term, pos),
+            })
+        },
+        TraverseOrder::BottomUp,
+    )
+}
+
+/// Fully evaluate a program, skipping fields that are not exported
+pub fn eval_for_export(
+    program: &mut Program<CacheImpl>,
+    span: Span,
+) -> Result<RichTerm, LabeledError> {
+    check_cycles(program, span)?;
+    program
+        .eval_full_for_export()
+        .map_err(|e| nickel_error(&mut program.files(), e, "Nickel evaluation failed", span))
+}
+
+/// Run an evaluation on a worker thread, giving up on it when the user presses Ctrl-C
+///
+/// Nickel has no way to stop an evaluation from outside, so an interrupted worker is left to
+/// finish in the background and its result is discarded. Once [`MAX_DETACHED`] of them are still
+/// running, evaluations fail rather than start another thread. Evaluations whose resources are
+/// limited run in a process of their own instead, which is killed when interrupted, see
+/// [`crate::nickel::worker`].
+pub fn interruptible<T: Send + 'static>(
+    signals: &Signals,
+    span: Span,
+    f: impl FnOnce() -> T + Send + 'static,
+) -> Result<T, LabeledError> {
+    interruptible_then(signals, span, move || (f(), None))
+}
+
+/// The result of an evaluation, whose array elements may be left to convert as they are read
+pub enum Evaluated {
+    Value(Value),
+    /// The elements of an array, converted on the evaluation thread as the stream is read
+    Elements(Box<dyn Iterator<Item = Result<Value, LabeledError>>>),
+    /// The serialization of the result, written on the evaluation thread as the stream is read
+    Serialized(Box<dyn FnOnce(&mut dyn Write) -> Result<(), LabeledError>>),
+}
+
+impl Evaluated {
+    /// The result as a single value, collecting the elements of an array into a list
+    pub fn into_value(self, span: Span) -> Result<Value, LabeledError> {
+        match self {
+            Evaluated::Value(value) => Ok(value),
+            Evaluated::Elements(elements) => {
+                Ok(Value::list(elements.collect::<Result<_, _>>()?, span))
+            }
+            Evaluated::Serialized(serialize) => {
+                let mut bytes = Vec::new();
+                serialize(&mut bytes)?;
+                Ok(Value::string(String::from_utf8_lossy(&bytes), span))
+            }
+        }
+    }
+}
+
+/// What an evaluation streaming its result sends back before it streams it
+enum Streamed {
+    Value(Value),
+    Elements,
+    Serialized,
+}
+
+/// Run an evaluation like [`interruptible`], streaming an array or serialized result
+///
+/// The elements of an array are converted a few at a time, and a serialization is written in
+/// chunks, as the stream is read, so that a large result is never held in memory twice. An
+/// element that fails to convert gives an error value in its place.
+pub fn interruptible_stream(
+    signals: &Signals,
+    span: Span,
+    f: impl FnOnce() -> Result<Evaluated, LabeledError> + Send + 'static,
+) -> Result<PipelineData, LabeledError> {
+    let (element_sender, elements) = mpsc::sync_channel(STREAM_BUFFER);
+    let (chunk_sender, chunks) = mpsc::sync_channel(STREAM_BUFFER);
+    let result = interruptible_then(signals, span, move || match f() {
+        Ok(Evaluated::Value(value)) => (Ok(Streamed::Value(value)), None),
+        Ok(Evaluated::Elements(elements)) => {
+            let then: Box<dyn FnOnce()> = Box::new(move || {
+                for element in elements {
+                    // Nothing reads the elements once the stream is dropped
+                    if element_sender.send(element).is_err() {
+                        break;
+                    }
+                }
+            });
+            (Ok(Streamed::Elements), Some(then))
+        }
+        Ok(Evaluated::Serialized(serialize)) => {
+            let then: Box<dyn FnOnce()> = Box::new(move || {
+                let mut writer = ChunkWriter {
+                    sender: chunk_sender,
+                    chunk: Vec::with_capacity(CHUNK_SIZE),
+                };
+                let result = serialize(&mut writer);
+                let _ = writer.flush();
+                if let Err(e) = result {
+                    let _ = writer.sender.send(Err(e.into()));
+                }
+            });
+            (Ok(Streamed::Serialized), Some(then))
+        }
+        Err(e) => (Err(e), None),
+    })?;
+
+    Ok(match result? {
+        Streamed::Value(value) => PipelineData::Value(value, None),
+        Streamed::Elements => {
+            let elements = elements
+                .into_iter()
+                .map(move |element| element.unwrap_or_else(|e| Value::error(e.into(), span)));
+            PipelineData::ListStream(ListStream::new(elements, span, signals.clone()), None)
+        }
+        Streamed::Serialized => PipelineData::ByteStream(
+            ByteStream::from_result_iter(chunks, span, signals.clone(), ByteStreamType::String),
+            None,
+        ),
+    })
+}
+
+/// Bytes written by an evaluation thread, sent in chunks to the stream reading them
+struct ChunkWriter {
+    sender: SyncSender<Result<Vec<u8>, ShellError>>,
+    chunk: Vec<u8>,
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.chunk.extend_from_slice(buf);
+        if self.chunk.len() >= CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.chunk.is_empty() {
+            return Ok(());
+        }
+        let chunk = std::mem::replace(&mut self.chunk, Vec::with_capacity(CHUNK_SIZE));
+        // Nothing reads the chunks once the stream is dropped, which stops the serialization
+        self.sender
+            .send(Ok(chunk))
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+    }
+}
+
+/// Number of interrupted evaluations still running in the background
+pub fn detached_evaluations() -> usize {
+    DETACHED.load(Ordering::Relaxed)
+}
+
+/// Run an evaluation like [`interruptible`], then the work it returns along with its result
+///
+/// The work runs on the evaluation thread once the result is returned.
+fn interruptible_then<T: Send + 'static>(
+    signals: &Signals,
+    span: Span,
+    f: impl FnOnce() -> (T, Option<Box<dyn FnOnce()>>) + Send + 'static,
+) -> Result<T, LabeledError> {
+    let detached = detached_evaluations();
+    if detached >= MAX_DETACHED {
+        return Err(LabeledError::new("Too many interrupted evaluations")
+            .with_label(
+                format!("{detached} interrupted evaluations are still running in the background"),
+                span,
+            )
+            .with_help(
+                "wait for them to finish, or restart the plugin with `plugin stop nickel`; \
+                 evaluations limited with `--max-memory` or `--max-stack` run in a process that \
+                 is stopped when interrupted",
+            ));
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let state = Arc::new(AtomicU8::new(RUNNING));
+    let thread_state = state.clone();
+    thread::Builder::new()
+        .name("nickel eval".into())
+        .stack_size(EVAL_STACK_SIZE)
+        .spawn(move || {
+            let (result, then) = f();
+            let _ = sender.send(result);
+            if let Some(then) = then {
+                then();
+            }
+            let finished = thread_state.compare_exchange(
+                RUNNING,
+                FINISHED,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            );
+            if finished == Err(ABANDONED) {
+                DETACHED.fetch_sub(1, Ordering::Relaxed);
+            }
+        })
+        .map_err(|e| {
+            LabeledError::new("Failed to start evaluation").with_label(e.to_string(), span)
+        })?;
+
+    loop {
+        match receiver.recv_timeout(INTERRUPT_POLL) {
+            Ok(result) => return Ok(result),
+            Err(RecvTimeoutError::Timeout) if signals.interrupted() => {
+                // Counted until the thread finishes, unless it finished in the meantime
+                if state
+                    .compare_exchange(RUNNING, ABANDONED, Ordering::AcqRel, Ordering::Acquire)
+                    .is_ok()
+                {
+                    DETACHED.fetch_add(1, Ordering::Relaxed);
+                }
+                return Err(ShellError::Interrupted { span }.into());
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(LabeledError::new("Nickel evaluation failed")
+                    .with_label("The evaluation thread panicked", span));
+            }
+        }
+    }
+}
+
+/// Fail if the records of a program contain themselves, which full evaluation would unfold forever
+///
+/// Evaluating the record spine locks each field while its children are evaluated, so a field
+/// referring back to a record it is in is left unevaluated, as are fields missing a definition.
+/// Nickel cannot evaluate a program again once it failed, so errors are reported from here.
+fn check_cycles(program: &mut Program<CacheImpl>, span: Span) -> Result<(), LabeledError> {
+    let spine = program
+        .eval_record_spine()
+        .map_err(|e| nickel_error(&mut program.files(), e, "Nickel evaluation failed", span))?;
+
+    let mut path = Vec::new();
+    match find_cycle(program, &spine, &mut path) {
+        Ok(Some(path)) => Err(LabeledError::new("Cyclic value")
+            .with_label(format!("`{path}` contains a record it is in"), span)
+            .with_help("mark recursive fields `| not_exported` to leave them out of the output")),
+        Ok(None) => Ok(()),
+        Err(e) => Err(nickel_error(
+            &mut program.files(),
+            *e,
+            "Nickel evaluation failed",
+            span,
+        )),
+    }
+}
+
+/// The dotted path to the first field of an evaluated record spine that refers back to a parent
+fn find_cycle(
+    program: &mut Program<CacheImpl>,
+    term: &RichTerm,
+    path: &mut Vec<String>,
+) -> Result<Option<String>, Box<EvalError>> {
+    let Term::Record(data) = term.as_ref() else {
+        return Ok(None);
+    };
+
+    let mut fields: Vec<_> = data
+        .fields
+        .iter()
+        .filter(|(_, field)| !field.metadata.not_exported)
+        .filter_map(|(id, field)| Some((id.label(), field.value.as_ref()?)))
+        .collect();
+    fields.sort_by_key(|(label, _)| *label);
+
+    for (label, value) in fields {
+        path.push(label.to_owned());
+        if let Term::Closure(_) = value.as_ref() {
+            let value = program
+                .eval_closure(Closure::atomic_closure(value.clone()))
+                .map_err(Box::new)?;
+            if let Term::Record(_) = value.as_ref() {
+                return Ok(Some(path.join(".")));
+            }
+        } else if let Some(cycle) = find_cycle(program, value, path)? {
+            return Ok(Some(cycle));
+        }
+        path.pop();
+    }
+    Ok(None)
+}
+
+/// Serialize an evaluated term with one of Nickel's exporters
+pub fn export_to_string(
+    program: &Program<CacheImpl>,
+    term: &RichTerm,
+    format: ExportFormat,
+    span: Span,
+) -> Result<String, LabeledError> {
+    serialize::validate(format, term)
+        .and_then(|_| serialize::to_string(format, term))
+        .map_err(|e| {
+            nickel_error(
+                &mut program.files(),
+                e,
+                &format!("Failed to export as {format}"),
+                span,
+            )
+        })
+}
+
+/// Evaluate an input into JSON data, data files being parsed rather than evaluated
+pub fn eval_to_json(input: &NickelInput, span: Span) -> Result<serde_json::Value, LabeledError> {
+    if let Some(data) = parse_data(&input.source, input.format) {
+        return data.map_err(|e| {
+            LabeledError::new(format!("Failed to parse {} input", input.format.to_str()))
+                .with_label(e, span)
+        });
+    }
+
+    let mut program = new_program(input, span)?;
+    let term = eval_for_export(&mut program, span)?;
+    let json = export_to_string(&program, &term, ExportFormat::Json, span)?;
+    serde_json::from_str(&json).map_err(|e| {
+        LabeledError::new("Failed to read the evaluated value").with_label(e.to_string(), span)
+    })
+}
+
+/// The shape of the value of an input: `record`, `array`, `function`, `contract` or `scalar`
+///
+/// Programs are only evaluated to their outermost constructor, so the fields of a record or the
+/// elements of an array are not evaluated, and may still fail.
+pub fn top_level_shape(input: &NickelInput, span: Span) -> Result<&'static str, LabeledError> {
+    if let Some(data) = parse_data(&input.source, input.format) {
+        let data = data.map_err(|e| {
+            LabeledError::new(format!("Failed to parse {} input", input.format.to_str()))
+                .with_label(e, span)
+        })?;
+        return Ok(match data {
+            serde_json::Value::Object(_) => "record",
+            serde_json::Value::Array(_) => "array",
+            _ => "scalar",
+        });
+    }
+
+    let mut program = new_program(input, span)?;
+    let term = program
+        .eval()
+        .map_err(|e| nickel_error(&mut program.files(), e, "Nickel evaluation failed", span))?;
+    Ok(match term.as_ref() {
+        Term::Record(_) | Term::RecRecord(..) => "record",
+        Term::Array(..) => "array",
+        Term::Fun(..) | Term::FunPattern(..) | Term::Match(_) => "function",
+        Term::Type { .. } | Term::CustomContract(_) => "contract",
+        _ => "scalar",
+    })
+}
+
+/// Serialize an evaluated term with one of Nickel's exporters, leaving the writing for later
+///
+/// The term is validated first, so that a value that cannot be exported fails right away rather
+/// than in the middle of its output.
+pub fn export_later(
+    program: Program<CacheImpl>,
+    term: RichTerm,
+    format: ExportFormat,
+    span: Span,
+) -> Result<Evaluated, LabeledError> {
+    let export_error = move |program: &Program<CacheImpl>, e| {
+        nickel_error(
+            &mut program.files(),
+            e,
+            &format!("Failed to export as {format}"),
+            span,
+        )
+    };
+    serialize::validate(format, &term).map_err(|e| export_error(&program, e))?;
+    Ok(Evaluated::Serialized(Box::new(
+        move |out: &mut dyn Write| {
+            serialize::to_writer(out, format, &term).map_err(|e| export_error(&program, e))
+        },
+    )))
+}
+
+/// Write an evaluated term in an output format, with Nickel's exporter when it has one
+pub fn export_term(
+    program: &Program<CacheImpl>,
+    term: &RichTerm,
+    format: OutputFormat,
+    span: Span,
+) -> Result<String, LabeledError> {
+    match format.export_format() {
+        Some(export_format) => export_to_string(program, term, export_format, span),
+        None => to_nuon(&nickel_to_nu_value(term, span)?).map_err(|e| {
+            LabeledError::new(format!("Failed to export as {}", format.name())).with_label(e, span)
+        }),
+    }
+}
+
+/// Infer the type of a Nickel program with the typechecker
+///
+/// The program is typechecked as `(program) : _`, so that the typechecker infers the type of the
+/// whole program for the wildcard, which requires the program to be well typed as a statically
+/// typed block.
+pub fn infer_type(input: &NickelInput, span: Span) -> Result<Type, LabeledError> {
+    let name = input.name();
+    // The newline keeps a trailing comment from swallowing the annotation
+    let source = format!("({}\n) : _", input.source);
+
+    let mut cache = CacheHub::new();
+    let file_id = cache
+        .sources
+        .add_string(SourcePath::Path(name, InputFormat::Nickel), source);
+
+    let msg = "Nickel typechecking failed";
+    cache
+        .load_stdlib()
+        .map_err(|e| nickel_error(&mut cache.sources.files().clone(), e, msg, span))?;
+    cache
+        .parse(file_id, InputFormat::Nickel)
+        .map_err(|e| nickel_error(&mut cache.sources.files().clone(), e, msg, span))?;
+    cache.typecheck(file_id, TypecheckMode::Walk).map_err(|e| {
+        let e = e.unwrap_error("infer_type(): expected source to be parsed");
+        nickel_error(&mut cache.sources.files().clone(), e, msg, span)
+    })?;
+
+    // Wildcards are numbered in parsing order, which ends with the annotation of the program
+    Ok(cache
+        .wildcards
+        .get(file_id)
+        .and_then(|wildcards| wildcards.last())
+        .cloned()
+        .unwrap_or_else(|| Type::from(TypeF::Dyn)))
+}
@@ -0,0 +1,372 @@
+use crate::nickel::{
+    error::nickel_error, input::NickelInput, program::new_program, symbols::resolve_import,
+};
+use malachite::base::{num::conversion::traits::RoundingFrom, rounding_modes::RoundingMode};
+use nickel_lang_core::{
+    term::{BinaryOp, Import, MergePriority, RichTerm, Term, record::Field},
+    typ::TypeF,
+};
+use nu_protocol::{LabeledError, Record, Span, Value};
+use std::{collections::HashMap, path::Path};
+
+/// How deep variables and imports are followed, which also stops on cycles between them
+const MAX_DEPTH: usize = 32;
+
+/// How much of the code of a definition is kept, on a single line
+const MAX_CODE: usize = 60;
+
+/// A place in the source that gives a field a value
+#[derive(Debug, Clone)]
+pub struct Definition {
+    /// The file of the definition, or the name of the input it was read from
+    pub file: String,
+    /// The 1-based position of the value, absent for generated code
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub priority: MergePriority,
+    /// The code of the value on one line, shortened when it is long
+    pub code: String,
+}
+
+/// What the source of a configuration says about one of its fields
+#[derive(Debug, Clone, Default)]
+pub struct Provenance {
+    /// The definitions of the field, in the order they are merged
+    pub definitions: Vec<Definition>,
+    /// The type and contracts of every definition and record contract, as written, once each
+    pub contracts: Vec<String>,
+    /// The first documentation found for the field
+    pub doc: Option<String>,
+}
+
+impl Provenance {
+    /// The definitions whose priority is the highest, which are merged into the value while the
+    /// others are discarded
+    pub fn winners(&self) -> Vec<&Definition> {
+        let highest = self
+            .definitions
+            .iter()
+            .map(|definition| rank(&definition.priority))
+            .fold(f64::NEG_INFINITY, f64::max);
+        self.definitions
+            .iter()
+            .filter(|definition| rank(&definition.priority) == highest)
+            .collect()
+    }
+}
+
+/// Find the definitions of the field at `path` in the source of `input`
+///
+/// The source is read rather than evaluated: records, merges, annotations and `let` bodies are
+/// followed, along with the variables bound by `let` and relative imports, and record contracts
+/// count as definitions of the defaults they give. A field computed by a function, or coming from
+/// an import looked up in the import path, is not found.
+pub fn provenance(
+    input: &NickelInput,
+    path: &[String],
+    span: Span,
+) -> Result<Provenance, LabeledError> {
+    let mut found = Provenance::default();
+    walk_source(input, path, &mut found, 0, span)?;
+    let dir = input.path.as_deref().and_then(Path::parent);
+    for definition in &mut found.definitions {
+        definition.file = relative_to(&definition.file, dir);
+    }
+    Ok(found)
+}
+
+/// A source being walked, to locate the definitions found in it
+struct Source<'a> {
+    name: String,
+    path: Option<&'a Path>,
+    text: &'a str,
+}
+
+type Env = HashMap<String, RichTerm>;
+
+fn walk_source(
+    input: &NickelInput,
+    path: &[String],
+    found: &mut Provenance,
+    depth: usize,
+    span: Span,
+) -> Result<(), LabeledError> {
+    let mut program = new_program(input, span)?;
+    let term = program
+        .parse()
+        .map_err(|e| nickel_error(&mut program.files(), e, "Nickel parsing failed", span))?;
+    let source = Source {
+        name: input.name().display().to_string(),
+        path: input.path.as_deref(),
+        text: &input.source,
+    };
+    walk(&term, path, &Env::new(), &source, found, depth, span)
+}
+
+fn walk(
+    term: &RichTerm,
+    path: &[String],
+    env: &Env,
+    source: &Source,
+    found: &mut Provenance,
+    depth: usize,
+    span: Span,
+) -> Result<(), LabeledError> {
+    if depth > MAX_DEPTH {
+        return Ok(());
+    }
+    match term.as_ref() {
+        Term::Record(data) | Term::RecRecord(data, ..) => {
+            let Some((label, rest)) = path.split_first() else {
+                return Ok(());
+            };
+            let Some(field) = data
+                .fields
+                .iter()
+                .find(|(id, _)| id.label() == label)
+                .map(|(_, field)| field)
+            else {
+                return Ok(());
+            };
+            if rest.is_empty() {
+                record_field(field, source, found);
+            } else {
+                if let Some(value) = &field.value {
+                    walk(value, rest, env, source, found, depth, span)?;
+                }
+                walk_contracts(field, rest, env, source, found, depth, span)?;
+            }
+        }
+        Term::Op2(BinaryOp::Merge(_), left, right) => {
+            walk(left, path, env, source, found, depth, span)?;
+            walk(right, path, env, source, found, depth, span)?;
+        }
+        Term::Annotated(annotation, inner) => {
+            walk(inner, path, env, source, found, depth, span)?;
+            for labeled in annotation.typ.iter().chain(&annotation.contracts) {
+                if let TypeF::Contract(contract) = &labeled.typ.typ {
+                    walk(contract, path, env, source, found, depth + 1, span)?;
+                }
+            }
+        }
+        Term::Let(bindings, body, _) => {
+            let mut env = env.clone();
+            for (id, value) in bindings.iter() {
+                env.insert(id.label().to_string(), value.clone());
+            }
+            walk(body, path, &env, source, found, depth, span)?;
+        }
+        Term::Var(id) => {
+            if let Some(bound) = env.get(id.label()) {
+                walk(bound, path, env, source, found, depth + 1, span)?;
+            }
+        }
+        Term::Import(Import::Path { path: import, .. }) => {
+            let Some(importer) = source.path else {
+                return Ok(());
+            };
+            let imported = resolve_import(importer, &import.to_string_lossy());
+            // A missing import fails the evaluation of the field, which reports it better
+            if let Ok(input) = NickelInput::from_path(imported, None, span) {
+                walk_source(&input, path, found, depth + 1, span)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Follow the rest of a path into the record contracts of a field, for the defaults they give
+fn walk_contracts(
+    field: &Field,
+    rest: &[String],
+    env: &Env,
+    source: &Source,
+    found: &mut Provenance,
+    depth: usize,
+    span: Span,
+) -> Result<(), LabeledError> {
+    let annotation = &field.metadata.annotation;
+    for labeled in annotation.typ.iter().chain(&annotation.contracts) {
+        if let TypeF::Contract(contract) = &labeled.typ.typ {
+            walk(contract, rest, env, source, found, depth + 1, span)?;
+        }
+    }
+    Ok(())
+}
+
+/// Record the metadata of a field at the end of the path, and its value as a definition
+fn record_field(field: &Field, source: &Source, found: &mut Provenance) {
+    let annotation = &field.metadata.annotation;
+    for labeled in annotation.typ.iter().chain(&annotation.contracts) {
+        let is_record = match &labeled.typ.typ {
+            TypeF::Record(_) => true,
+            TypeF::Contract(contract) => {
+                matches!(contract.as_ref(), Term::Record(_) | Term::RecRecord(..))
+            }
+            _ => false,
+        };
+        let contract = labeled.typ.to_string();
+        if !is_record && !found.contracts.contains(&contract) {
+            found.contracts.push(contract);
+        }
+    }
+    if found.doc.is_none() {
+        found.doc = field.metadata.doc.clone();
+    }
+
+    let Some(value) = &field.value else {
+        return;
+    };
+    let (line, column, code) = match value.pos.into_opt() {
+        Some(pos) => {
+            let start = pos.start.to_usize().min(source.text.len());
+            let end = pos.end.to_usize().clamp(start, source.text.len());
+            let before = &source.text[..start];
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            (
+                Some(before.matches('\n').count() + 1),
+                Some(before[line_start..].chars().count() + 1),
+                one_line(&source.text[start..end]),
+            )
+        }
+        None => (None, None, "<generated>".to_string()),
+    };
+    found.definitions.push(Definition {
+        file: source.name.clone(),
+        line,
+        column,
+        priority: field.metadata.priority.clone(),
+        code,
+    });
+}
+
+/// Code on a single line, with its runs of whitespace collapsed, shortened to `MAX_CODE`
+fn one_line(code: &str) -> String {
+    let code = code.split_whitespace().collect::<Vec<_>>().join(" ");
+    match code.char_indices().nth(MAX_CODE) {
+        Some((i, _)) => format!("{}…", &code[..i]),
+        None => code,
+    }
+}
+
+/// The priority of a definition as a number, to compare them
+fn rank(priority: &MergePriority) -> f64 {
+    match priority {
+        MergePriority::Bottom => f64::NEG_INFINITY,
+        MergePriority::Neutral => 0.0,
+        MergePriority::Numeral(n) => f64::rounding_from(n, RoundingMode::Nearest).0,
+        MergePriority::Top => f64::INFINITY,
+    }
+}
+
+/// The priority of a definition as its annotation reads
+pub fn priority_name(priority: &MergePriority) -> String {
+    match priority {
+        MergePriority::Bottom => "default".to_string(),
+        MergePriority::Neutral => "normal".to_string(),
+        MergePriority::Numeral(n) => format!("priority {n}"),
+        MergePriority::Top => "force".to_string(),
+    }
+}
+
+impl Definition {
+    /// Where the definition is, as `file:line:column`
+    pub fn location(&self) -> String {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => format!("{}:{line}:{column}", self.file),
+            _ => self.file.clone(),
+        }
+    }
+
+    pub fn to_value(&self, wins: bool, span: Span) -> Value {
+        let position = |n: Option<usize>| match n {
+            Some(n) => Value::int(n as i64, span),
+            None => Value::nothing(span),
+        };
+        let mut record = Record::new();
+        record.push("file", Value::string(&self.file, span));
+        record.push("line", position(self.line));
+        record.push("column", position(self.column));
+        record.push(
+            "priority",
+            Value::string(priority_name(&self.priority), span),
+        );
+        record.push("code", Value::string(&self.code, span));
+        record.push("wins", Value::bool(wins, span));
+        Value::record(record, span)
+    }
+}
+
+/// Tell the story of a field in a few sentences: its value, its definitions, which of them wins
+/// and why, and what checks it
+pub fn narrative(path: &str, value: Result<&str, &str>, found: &Provenance) -> String {
+    let mut sentences = vec![match value {
+        Ok(value) => format!("`{path}` is {value}."),
+        Err(error) => format!("`{path}` fails to evaluate: {error}."),
+    }];
+
+    let describe = |definition: &Definition| {
+        format!(
+            "`{}` at {} ({})",
+            definition.code,
+            definition.location(),
+            priority_name(&definition.priority)
+        )
+    };
+    let winners = found.winners();
+    match found.definitions.as_slice() {
+        [] => sentences.push(
+            "No definition of it was found in the source, so it is computed, or comes from a \
+             file that is not imported by a relative path."
+                .to_string(),
+        ),
+        [definition] => sentences.push(format!("It is defined once, {}.", describe(definition))),
+        definitions => {
+            let list: Vec<_> = definitions.iter().map(describe).collect();
+            sentences.push(format!(
+                "It is defined {} times: {}.",
+                definitions.len(),
+                list.join(", ")
+            ));
+            let priority = priority_name(&winners[0].priority);
+            let losers = definitions.len() - winners.len();
+            sentences.push(match (winners.as_slice(), losers) {
+                (_, 0) => {
+                    format!("They all have {priority} priority, so they are merged together.")
+                }
+                ([winner], _) => format!(
+                    "The {priority} definition at {} wins over the {losers} of lower priority.",
+                    winner.location()
+                ),
+                (winners, _) => format!(
+                    "The {} definitions of {priority} priority are merged, and win over the \
+                     {losers} of lower priority.",
+                    winners.len()
+                ),
+            });
+        }
+    }
+    if !winners.is_empty()
+        && winners
+            .iter()
+            .all(|winner| matches!(winner.priority, MergePriority::Bottom))
+    {
+        sentences.push("Nothing overrides it, so its default applies.".to_string());
+    }
+
+    if !found.contracts.is_empty() {
+        sentences.push(format!("It is checked by {}.", found.contracts.join(", ")));
+    }
+    sentences.join(" ")
+}
+
+/// The path of a definition's file relative to the directory of the configuration, to keep the
+/// narrative short
+fn relative_to(file: &str, dir: Option<&Path>) -> String {
+    let file = Path::new(file);
+    match dir.and_then(|dir| file.strip_prefix(dir).ok()) {
+        Some(relative) => relative.display().to_string(),
+        None => file.display().to_string(),
+    }
+}
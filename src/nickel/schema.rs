@@ -0,0 +1,351 @@
+use nickel_lang_core::{
+    term::{RichTerm, Term, record::Field},
+    typ::{EnumRowsIteratorItem, RecordRowsIteratorItem, Type, TypeF},
+};
+use nu_protocol::{Record, Span, Value};
+use std::collections::BTreeSet;
+
+/// Contracts accepting every value of another one, as `(narrower, wider)` pairs
+const WIDENINGS: [(&str, &str); 7] = [
+    ("std.number.PosNat", "std.number.Nat"),
+    ("std.number.PosNat", "std.number.Integer"),
+    ("std.number.PosNat", "Number"),
+    ("std.number.Nat", "std.number.Integer"),
+    ("std.number.Nat", "Number"),
+    ("std.number.Integer", "Number"),
+    ("std.string.NonEmpty", "String"),
+];
+
+/// A field of a record contract, found at any depth
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaField {
+    /// The dotted path of the field
+    pub path: String,
+    /// The type and contracts of the field as written, joined with `|`, empty when it has none
+    ///
+    /// Record contracts are left out, their fields being schema fields of their own.
+    pub contract: String,
+    /// The tags of an enum contract, to tell added variants from removed ones
+    pub tags: Option<BTreeSet<String>>,
+    /// Whether a configuration has to define the field: it is not optional and has no value
+    pub required: bool,
+}
+
+impl SchemaField {
+    fn parent(&self) -> &str {
+        self.path.rsplit_once('.').map_or("", |(parent, _)| parent)
+    }
+}
+
+/// The fields of an evaluated record spine, parents before their children, sorted by path
+pub fn schema_fields(schema: &RichTerm) -> Option<Vec<SchemaField>> {
+    let mut fields = Vec::new();
+    match schema.as_ref() {
+        Term::Record(data) | Term::RecRecord(data, ..) => {
+            for (id, field) in &data.fields {
+                record_field(id.label(), field, &mut fields);
+            }
+        }
+        _ => return None,
+    }
+    fields.sort_by(|a, b| a.path.cmp(&b.path));
+    Some(fields)
+}
+
+fn record_field(path: &str, field: &Field, fields: &mut Vec<SchemaField>) {
+    if field.metadata.not_exported {
+        return;
+    }
+    let annotation = &field.metadata.annotation;
+    let mut contracts = Vec::new();
+    let mut tags = None;
+    for labeled in annotation.typ.iter().chain(&annotation.contracts) {
+        if !nested_type(path, &labeled.typ, fields) {
+            contracts.push(labeled.typ.to_string());
+            tags = tags.or_else(|| enum_tags(&labeled.typ));
+        }
+    }
+    if let Some(Term::Record(data) | Term::RecRecord(data, ..)) =
+        field.value.as_ref().map(AsRef::as_ref)
+    {
+        for (id, field) in &data.fields {
+            record_field(&format!("{path}.{}", id.label()), field, fields);
+        }
+    }
+    fields.push(SchemaField {
+        path: path.to_string(),
+        contract: contracts.join(" | "),
+        tags,
+        required: !field.metadata.opt && field.value.is_none(),
+    });
+}
+
+/// Add the fields of a record type or record contract, returning whether `typ` is one
+fn nested_type(path: &str, typ: &Type, fields: &mut Vec<SchemaField>) -> bool {
+    match &typ.typ {
+        TypeF::Record(rows) => {
+            for row in rows.iter() {
+                if let RecordRowsIteratorItem::Row(row) = row {
+                    let path = format!("{path}.{}", row.id.label());
+                    if !nested_type(&path, row.typ, fields) {
+                        fields.push(SchemaField {
+                            path,
+                            contract: row.typ.to_string(),
+                            tags: enum_tags(row.typ),
+                            required: true,
+                        });
+                    }
+                }
+            }
+            true
+        }
+        TypeF::Contract(term) => match term.as_ref() {
+            Term::Record(data) | Term::RecRecord(data, ..) => {
+                for (id, field) in &data.fields {
+                    record_field(&format!("{path}.{}", id.label()), field, fields);
+                }
+                true
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn enum_tags(typ: &Type) -> Option<BTreeSet<String>> {
+    let TypeF::Enum(rows) = &typ.typ else {
+        return None;
+    };
+    Some(
+        rows.iter()
+            .filter_map(|row| match row {
+                EnumRowsIteratorItem::Row(row) => Some(row.id.label().to_string()),
+                EnumRowsIteratorItem::TailVar(_) => None,
+            })
+            .collect(),
+    )
+}
+
+/// Whether a change can break configurations that satisfied the old schema
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Breaking,
+    Compatible,
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Breaking => "breaking",
+            Severity::Compatible => "compatible",
+        }
+    }
+}
+
+/// A difference between two versions of a schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub path: String,
+    /// `added`, `removed`, `renamed`, `required` or `contract`
+    pub kind: &'static str,
+    pub severity: Severity,
+    /// The contract of the field in the old schema, or its old path when it was renamed
+    pub old: Option<String>,
+    /// The contract of the field in the new schema, or its new path when it was renamed
+    pub new: Option<String>,
+    pub message: String,
+}
+
+impl Change {
+    pub fn into_value(self, span: Span) -> Value {
+        let optional = |text: Option<String>| {
+            text.map_or(Value::nothing(span), |text| Value::string(text, span))
+        };
+        let mut record = Record::new();
+        record.push("path", Value::string(self.path, span));
+        record.push("change", Value::string(self.kind, span));
+        record.push("severity", Value::string(self.severity.name(), span));
+        record.push("old", optional(self.old));
+        record.push("new", optional(self.new));
+        record.push("message", Value::string(self.message, span));
+        Value::record(record, span)
+    }
+
+    /// The line of a changelog describing the change, with the contracts it is about
+    fn entry(&self) -> String {
+        let contract = |contract: &Option<String>| match contract.as_deref() {
+            None | Some("") => "no contract".to_string(),
+            Some(contract) => format!("`{contract}`"),
+        };
+        let mut chars = self.message.chars();
+        let mut entry: String = chars
+            .next()
+            .map(|first| first.to_uppercase().chain(chars).collect())
+            .unwrap_or_default();
+        match self.kind {
+            "contract" => entry.push_str(&format!(
+                " ({} to {})",
+                contract(&self.old),
+                contract(&self.new)
+            )),
+            "added" => entry.push_str(&format!(" ({})", contract(&self.new))),
+            _ => {}
+        }
+        entry
+    }
+}
+
+/// A Markdown changelog section of schema changes, the breaking ones first
+///
+/// Its headings are of the third level, to go under the heading of a release.
+pub fn changelog(changes: &[Change]) -> String {
+    if changes.is_empty() {
+        return "No changes to the configuration schema.\n".to_string();
+    }
+    let mut text = String::new();
+    for (severity, heading) in [
+        (Severity::Breaking, "Breaking changes"),
+        (Severity::Compatible, "Compatible changes"),
+    ] {
+        let mut entries = changes
+            .iter()
+            .filter(|change| change.severity == severity)
+            .peekable();
+        if entries.peek().is_none() {
+            continue;
+        }
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(&format!("### {heading}\n\n"));
+        for change in entries {
+            text.push_str(&format!("- {}\n", change.entry()));
+        }
+    }
+    text
+}
+
+/// The changes from one version of a schema to the next, sorted by path
+///
+/// A field removed from a record while another one with the same contract is added to it is
+/// taken to be renamed.
+pub fn compare(old: &[SchemaField], new: &[SchemaField]) -> Vec<Change> {
+    let find =
+        |fields: &'_ [SchemaField], path: &str| fields.iter().position(|field| field.path == path);
+    let mut changes = Vec::new();
+    let mut added: Vec<&SchemaField> = new
+        .iter()
+        .filter(|field| find(old, &field.path).is_none())
+        .collect();
+
+    for before in old {
+        let Some(after) = find(new, &before.path).map(|i| &new[i]) else {
+            // Children of a removed or renamed record are reported with it
+            if find(old, before.parent()).is_some() && find(new, before.parent()).is_none() {
+                continue;
+            }
+            let renamed = added.iter().position(|field| {
+                field.parent() == before.parent() && field.contract == before.contract
+            });
+            changes.push(match renamed {
+                Some(i) => {
+                    let after = added.remove(i);
+                    Change {
+                        path: before.path.clone(),
+                        kind: "renamed",
+                        severity: Severity::Breaking,
+                        old: Some(before.path.clone()),
+                        new: Some(after.path.clone()),
+                        message: format!("`{}` seems renamed to `{}`", before.path, after.path),
+                    }
+                }
+                None => Change {
+                    path: before.path.clone(),
+                    kind: "removed",
+                    severity: Severity::Breaking,
+                    old: Some(before.contract.clone()),
+                    new: None,
+                    message: format!("`{}` was removed", before.path),
+                },
+            });
+            continue;
+        };
+
+        if before.required != after.required {
+            changes.push(Change {
+                path: before.path.clone(),
+                kind: "required",
+                severity: if after.required {
+                    Severity::Breaking
+                } else {
+                    Severity::Compatible
+                },
+                old: None,
+                new: None,
+                message: if after.required {
+                    format!("`{}` became required", before.path)
+                } else {
+                    format!("`{}` is no longer required", before.path)
+                },
+            });
+        }
+        if before.contract != after.contract {
+            let widened = widens(before, after);
+            changes.push(Change {
+                path: before.path.clone(),
+                kind: "contract",
+                severity: if widened {
+                    Severity::Compatible
+                } else {
+                    Severity::Breaking
+                },
+                old: Some(before.contract.clone()),
+                new: Some(after.contract.clone()),
+                message: format!(
+                    "the contract of `{}` was {}",
+                    before.path,
+                    if widened {
+                        "widened"
+                    } else {
+                        "narrowed or changed"
+                    }
+                ),
+            });
+        }
+    }
+
+    for after in added {
+        if find(new, after.parent()).is_some() && find(old, after.parent()).is_none() {
+            continue;
+        }
+        changes.push(Change {
+            path: after.path.clone(),
+            kind: "added",
+            severity: if after.required {
+                Severity::Breaking
+            } else {
+                Severity::Compatible
+            },
+            old: None,
+            new: Some(after.contract.clone()),
+            message: if after.required {
+                format!("`{}` was added and is required", after.path)
+            } else {
+                format!("`{}` was added", after.path)
+            },
+        });
+    }
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    changes
+}
+
+/// Whether every value satisfying the old contract of a field satisfies the new one
+fn widens(before: &SchemaField, after: &SchemaField) -> bool {
+    if after.contract.is_empty() || after.contract == "Dyn" {
+        return true;
+    }
+    if let (Some(old), Some(new)) = (&before.tags, &after.tags) {
+        return new.is_superset(old);
+    }
+    WIDENINGS.contains(&(before.contract.as_str(), after.contract.as_str()))
+}
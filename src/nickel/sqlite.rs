@@ -0,0 +1,141 @@
+use nu_protocol::{LabeledError, Span, Value};
+use rusqlite::{Connection, types::Value as SqlValue};
+use std::path::Path;
+
+/// Write a table of flat records into a table of a SQLite database, returning the rows written
+///
+/// The table is replaced in a single transaction, so exporting again leaves only the new rows.
+/// Columns are ordered by their first appearance, and are declared with the type of their cells
+/// when they all agree, integers and floats making a `REAL` column.
+pub fn write_table(
+    value: &Value,
+    database: &Path,
+    table: &str,
+    span: Span,
+) -> Result<usize, LabeledError> {
+    let not_a_table = |found: String| {
+        LabeledError::new("Not a table")
+            .with_label(format!("Expected a list of records, found {found}"), span)
+    };
+    let Value::List { vals: rows, .. } = value else {
+        return Err(not_a_table(value.get_type().to_string()));
+    };
+    let records = rows
+        .iter()
+        .map(|row| match row {
+            Value::Record { val, .. } => Ok(&**val),
+            other => Err(not_a_table(format!("a list holding {}", other.get_type()))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut names: Vec<&str> = Vec::new();
+    for record in &records {
+        for name in record.columns() {
+            if !names.contains(&name.as_str()) {
+                names.push(name);
+            }
+        }
+    }
+
+    if names.is_empty() {
+        return Err(LabeledError::new("Empty table")
+            .with_label("A SQLite table needs at least one column", span));
+    }
+
+    let mut columns = Vec::new();
+    let mut cells = vec![Vec::with_capacity(names.len()); records.len()];
+    for name in &names {
+        let column: Vec<_> = records
+            .iter()
+            .filter_map(|record| record.get(name))
+            .collect();
+        if let Some(nested) = column
+            .iter()
+            .find(|cell| matches!(cell, Value::Record { .. } | Value::List { .. }))
+        {
+            return Err(LabeledError::new("Not a flat table")
+                .with_label(
+                    format!("Column `{name}` holds a {}", nested.get_type()),
+                    span,
+                )
+                .with_help("select or flatten nested fields before exporting the table"));
+        }
+        columns.push(match declared_type(&column) {
+            Some(declared) => format!("{} {declared}", quote(name)),
+            None => quote(name),
+        });
+        for (record, row) in records.iter().zip(&mut cells) {
+            row.push(match record.get(name) {
+                Some(Value::Int { val, .. }) => SqlValue::Integer(*val),
+                Some(Value::Float { val, .. }) => SqlValue::Real(*val),
+                Some(Value::Bool { val, .. }) => SqlValue::Integer(*val as i64),
+                Some(Value::String { val, .. }) => SqlValue::Text(val.clone()),
+                Some(other) if !other.is_nothing() => SqlValue::Text(other.coerce_string()?),
+                _ => SqlValue::Null,
+            });
+        }
+    }
+
+    let sql_error = |e: rusqlite::Error| {
+        LabeledError::new("Failed to write the SQLite table").with_label(e.to_string(), span)
+    };
+    let mut connection = Connection::open(database).map_err(|e| {
+        LabeledError::new("Failed to open the SQLite database").with_label(
+            format!("'{}' cannot be opened: {e}", database.display()),
+            span,
+        )
+    })?;
+    let transaction = connection.transaction().map_err(sql_error)?;
+    transaction
+        .execute(&format!("DROP TABLE IF EXISTS {}", quote(table)), [])
+        .map_err(sql_error)?;
+    transaction
+        .execute(
+            &format!("CREATE TABLE {} ({})", quote(table), columns.join(", ")),
+            [],
+        )
+        .map_err(sql_error)?;
+    {
+        let placeholders = vec!["?"; names.len()].join(", ");
+        let mut insert = transaction
+            .prepare(&format!(
+                "INSERT INTO {} VALUES ({placeholders})",
+                quote(table)
+            ))
+            .map_err(sql_error)?;
+        for row in &cells {
+            insert
+                .execute(rusqlite::params_from_iter(row))
+                .map_err(sql_error)?;
+        }
+    }
+    transaction.commit().map_err(sql_error)?;
+    Ok(records.len())
+}
+
+/// The type a column is declared with, if all its cells agree on one
+fn declared_type(cells: &[&Value]) -> Option<&'static str> {
+    let mut declared = None;
+    for cell in cells {
+        let cell_type = match cell {
+            Value::Int { .. } => "INTEGER",
+            Value::Float { .. } => "REAL",
+            Value::Bool { .. } => "BOOLEAN",
+            Value::String { .. } => "TEXT",
+            _ => continue,
+        };
+        declared = match declared {
+            None => Some(cell_type),
+            Some(declared) if declared == cell_type => Some(declared),
+            Some("INTEGER" | "REAL") if matches!(cell_type, "INTEGER" | "REAL") => Some("REAL"),
+            // SQLite columns may hold any type, so a mixed column is left undeclared
+            Some(_) => return None,
+        };
+    }
+    declared
+}
+
+/// Quote an identifier, so that table and column names may hold any character
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
@@ -0,0 +1,49 @@
+use chrono::{SecondsFormat, Utc};
+use nu_protocol::{Record, Span, Value};
+use std::path::Path;
+use std::process::Command;
+
+/// The provenance of a configuration rendered now from the files in `dir`
+///
+/// The `commit`, `describe` and `dirty` fields come from the git repository `dir` is in, and are
+/// `null` when it is in none or git is not installed. A repository is dirty when tracked files
+/// have changes, as for `git describe --dirty`.
+pub fn stamp(dir: &Path, span: Span) -> Value {
+    let optional =
+        |text: Option<String>| text.map_or(Value::nothing(span), |text| Value::string(text, span));
+    let dirty = git(dir, &["status", "--porcelain", "--untracked-files=no"])
+        .map_or(Value::nothing(span), |status| {
+            Value::bool(!status.is_empty(), span)
+        });
+
+    let mut record = Record::new();
+    record.push("commit", optional(git(dir, &["rev-parse", "HEAD"])));
+    record.push(
+        "describe",
+        optional(git(dir, &["describe", "--always", "--tags", "--dirty"])),
+    );
+    record.push("dirty", dirty);
+    record.push(
+        "rendered_at",
+        Value::string(Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true), span),
+    );
+    record.push(
+        "plugin_version",
+        Value::string(env!("CARGO_PKG_VERSION"), span),
+    );
+    Value::record(record, span)
+}
+
+/// The trimmed output of a git command run in `dir`, if it succeeded
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -0,0 +1,227 @@
+use crate::nickel::error::render_nickel_error;
+use nickel_lang_core::{error::NullReporter, eval::cache::CacheImpl, program::Program};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Documentation of a single standard library symbol
+#[derive(Debug, Clone)]
+pub struct StdlibEntry {
+    /// Fully qualified name, e.g. `std.array.fold_left`
+    pub name: String,
+    pub kind: StdlibKind,
+    /// Rendered type annotation, if any
+    pub signature: Option<String>,
+    /// Rendered contract annotations
+    pub contracts: Vec<String>,
+    pub doc: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StdlibKind {
+    Module,
+    Function,
+    Contract,
+    Value,
+}
+
+impl StdlibKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StdlibKind::Module => "module",
+            StdlibKind::Function => "function",
+            StdlibKind::Contract => "contract",
+            StdlibKind::Value => "value",
+        }
+    }
+}
+
+impl StdlibEntry {
+    /// The first paragraph of the documentation, joined on a single line
+    pub fn summary(&self) -> Option<String> {
+        let doc = self.doc.as_deref()?;
+        let paragraph = doc
+            .trim()
+            .split("\n\n")
+            .next()?
+            .lines()
+            .map(str::trim)
+            .collect::<Vec<_>>()
+            .join(" ");
+        Some(paragraph)
+    }
+
+    /// The fenced `nickel` code blocks of the documentation
+    pub fn examples(&self) -> Vec<String> {
+        let Some(doc) = self.doc.as_deref() else {
+            return Vec::new();
+        };
+
+        let mut examples = Vec::new();
+        let mut current: Option<Vec<&str>> = None;
+        for line in doc.lines() {
+            let trimmed = line.trim();
+            match current.as_mut() {
+                Some(block) if trimmed.starts_with("```") => {
+                    examples.push(dedent(block));
+                    current = None;
+                }
+                Some(block) => block.push(line),
+                None if trimmed.starts_with("```nickel") => current = Some(Vec::new()),
+                None => (),
+            }
+        }
+        examples
+    }
+}
+
+/// Join lines, removing the indentation they all have in common
+fn dedent(lines: &[&str]) -> String {
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    lines
+        .iter()
+        .map(|line| line.get(indent..).unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Shape of the documentation exported by `ExtractedDocumentation::write_json`
+#[derive(Deserialize)]
+struct DocField {
+    fields: Option<HashMap<String, DocField>>,
+    #[serde(rename = "type")]
+    typ: Option<String>,
+    contracts: Vec<String>,
+    documentation: Option<String>,
+}
+
+/// All symbols of the embedded standard library, sorted by name
+///
+/// The documentation is extracted once and reused for the lifetime of the plugin.
+pub fn stdlib_entries() -> Result<&'static [StdlibEntry], String> {
+    static ENTRIES: OnceLock<Result<Vec<StdlibEntry>, String>> = OnceLock::new();
+
+    ENTRIES
+        .get_or_init(extract_stdlib_entries)
+        .as_deref()
+        .map_err(Clone::clone)
+}
+
+/// Look up a symbol by its qualified name, the leading `std.` being optional
+pub fn find_stdlib_entry(name: &str) -> Result<Option<&'static StdlibEntry>, String> {
+    let name = qualified_name(name);
+    Ok(stdlib_entries()?.iter().find(|entry| entry.name == name))
+}
+
+/// Prefix `name` with `std.` unless it is already qualified
+pub fn qualified_name(name: &str) -> String {
+    if name == "std" || name.starts_with("std.") {
+        name.to_string()
+    } else {
+        format!("std.{name}")
+    }
+}
+
+/// The parts of the standard library a program may use, by qualified name
+///
+/// A name covers the symbols under it, so `std.string` covers `std.string.length`. Using a
+/// module that holds a denied symbol is denied too, as the symbol could be reached from it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StdlibFilter {
+    /// The names every used symbol must be under, any symbol being allowed when `None`
+    pub allow: Option<Vec<String>>,
+    /// The names no used symbol may be under or above, even when allowed
+    pub deny: Vec<String>,
+}
+
+impl StdlibFilter {
+    pub fn new(allow: Option<Vec<String>>, deny: Vec<String>) -> Self {
+        let qualify = |names: Vec<String>| names.iter().map(|name| qualified_name(name)).collect();
+        Self {
+            allow: allow.map(qualify),
+            deny: qualify(deny),
+        }
+    }
+
+    /// Whether the filter lets programs use the whole standard library
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_none() && self.deny.is_empty()
+    }
+
+    /// Why a program may not use the symbol with this qualified name, if it may not
+    pub fn refusal(&self, name: &str) -> Option<String> {
+        // Whether `name` is `parent` or one of the symbols under it
+        let covers = |parent: &str, name: &str| {
+            name == parent
+                || name
+                    .strip_prefix(parent)
+                    .is_some_and(|rest| rest.starts_with('.'))
+        };
+        if let Some(denied) = self.deny.iter().find(|denied| covers(denied, name)) {
+            return Some(format!("`{name}` is under the denied `{denied}`"));
+        }
+        if let Some(denied) = self.deny.iter().find(|denied| covers(name, denied)) {
+            return Some(format!("`{name}` holds the denied `{denied}`"));
+        }
+        match &self.allow {
+            Some(allow) if !allow.iter().any(|allowed| covers(allowed, name)) => {
+                Some(format!("`{name}` is not under an allowed name"))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn extract_stdlib_entries() -> Result<Vec<StdlibEntry>, String> {
+    let mut program: Program<CacheImpl> =
+        Program::new_from_source("std".as_bytes(), "<std>", std::io::sink(), NullReporter {})
+            .map_err(|e| e.to_string())?;
+
+    let doc = program
+        .extract_doc()
+        .map_err(|e| render_nickel_error(&mut program.files(), e))?;
+    let mut json = Vec::new();
+    doc.write_json(&mut json)
+        .map_err(|e| render_nickel_error(&mut program.files(), e))?;
+    let fields: HashMap<String, DocField> =
+        serde_json::from_slice(&json).map_err(|e| e.to_string())?;
+
+    let mut entries = Vec::new();
+    collect_entries("std", fields, &mut entries);
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
+
+fn collect_entries(prefix: &str, fields: HashMap<String, DocField>, acc: &mut Vec<StdlibEntry>) {
+    for (name, field) in fields {
+        let qualified = format!("{prefix}.{name}");
+
+        let kind = if field.fields.as_ref().is_some_and(|f| !f.is_empty()) {
+            StdlibKind::Module
+        } else if field.typ.as_deref().is_some_and(|t| t.contains("->")) {
+            StdlibKind::Function
+        } else if name.starts_with(char::is_uppercase) {
+            StdlibKind::Contract
+        } else {
+            StdlibKind::Value
+        };
+
+        if let Some(subfields) = field.fields {
+            collect_entries(&qualified, subfields, acc);
+        }
+
+        acc.push(StdlibEntry {
+            name: qualified,
+            kind,
+            signature: field.typ,
+            contracts: field.contracts,
+            doc: field.documentation,
+        });
+    }
+}
@@ -0,0 +1,220 @@
+use crate::nickel::{
+    error::ErrorCode,
+    input::NickelInput,
+    lex::{is_identifier, lex},
+    program::new_program,
+};
+use nickel_lang_core::{
+    error::suggest::{MIN_SIMILARITY, find_best_match},
+    term::{RichTerm, Term},
+    traverse::{Traverse, TraverseControl},
+};
+use nu_protocol::{Record, Span, Value};
+use std::ops::Range;
+
+/// A fix for a diagnostic: replace the `start..end` byte range of the source by `replacement`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    pub message: String,
+    pub replacement: String,
+    pub start: usize,
+    pub end: usize,
+    /// Whether the fix can be applied without review, see [`is_trivial_rename`]
+    pub safe: bool,
+}
+
+impl Suggestion {
+    pub fn into_value(self, span: Span) -> Value {
+        let mut record = Record::new();
+        record.push("message", Value::string(self.message, span));
+        record.push("replacement", Value::string(self.replacement, span));
+        record.push("start", Value::int(self.start as i64, span));
+        record.push("end", Value::int(self.end as i64, span));
+        record.push("safe", Value::bool(self.safe, span));
+        Value::record(record, span)
+    }
+}
+
+/// Suggest a fix for a diagnostic whose primary label covers `range` of `source`
+///
+/// Nickel's own "did you mean" notes are turned into a replacement of the misspelled name. For
+/// unbound identifiers, the closest name bound where the identifier is used is suggested, or
+/// quoting the identifier when nothing comes close, as it is often a string missing its quotes.
+/// When the bound names cannot be found, the closest identifier used elsewhere in the source is
+/// suggested instead, and never as a safe fix.
+pub fn suggest(
+    code: &ErrorCode,
+    source: &str,
+    range: Range<usize>,
+    notes: &[String],
+) -> Option<Suggestion> {
+    let text = source.get(range.clone())?;
+
+    if let Some(candidate) = notes.iter().find_map(|note| did_you_mean(note)) {
+        let name = trailing_identifier(text)?;
+        return Some(rename(name, candidate, range.end - name.len(), range.end));
+    }
+
+    if code.name != "unbound-identifier" || !is_identifier(text) {
+        return None;
+    }
+
+    let in_scope = names_in_scope(source, range.start);
+    let mut identifiers: Vec<String> = match &in_scope {
+        Some(names) => names.iter().filter(|name| *name != text).cloned().collect(),
+        None => lex(source)
+            .ok()?
+            .into_iter()
+            .filter(|token| token.kind == "Identifier" && token.text != text)
+            .map(|token| token.text)
+            .collect(),
+    };
+    identifiers.sort();
+    identifiers.dedup();
+
+    Some(match find_best_match(&identifiers, &text) {
+        Some(candidate) => {
+            let close = identifiers
+                .iter()
+                .filter(|id| is_trivial_rename(text, id))
+                .count();
+            let mut suggestion = rename(text, candidate, range.start, range.end);
+            // A name that may not be bound where it is used would trade one error for another
+            suggestion.safe &= close == 1 && in_scope.is_some();
+            suggestion
+        }
+        None => Suggestion {
+            message: format!("Quote `{text}` if it is meant as a string"),
+            replacement: format!("\"{text}\""),
+            start: range.start,
+            end: range.end,
+            safe: false,
+        },
+    })
+}
+
+/// The names bound where the identifier starting at `start` is used: by the `let`s and functions
+/// around it, by the fields of the recursive records it is in, and `std`
+///
+/// Names bound by patterns are left out. This is `None` when the source does not parse, or when
+/// no identifier starts at `start`.
+fn names_in_scope(source: &str, start: usize) -> Option<Vec<String>> {
+    let input = NickelInput::from_source(source.to_string(), None);
+    let term = new_program(&input, Span::unknown()).ok()?.parse().ok()?;
+    let contains = |term: &RichTerm| {
+        term.pos
+            .into_opt()
+            .is_some_and(|pos| (pos.start.to_usize()..=pos.end.to_usize()).contains(&start))
+    };
+
+    term.traverse_ref(
+        &mut |term: &RichTerm, scope: &Vec<String>| -> TraverseControl<Vec<String>, Vec<String>> {
+            if !contains(term) {
+                return TraverseControl::SkipBranch;
+            }
+            let mut scope = scope.clone();
+            match term.as_ref() {
+                Term::Var(_)
+                    if term.pos.into_opt().map(|pos| pos.start.to_usize()) == Some(start) =>
+                {
+                    return TraverseControl::Return(scope);
+                }
+                // The bound values of a `let` only see its names when it is recursive
+                Term::Let(bindings, body, attrs) if attrs.rec || contains(body) => {
+                    scope.extend(bindings.iter().map(|(id, _)| id.label().to_string()));
+                }
+                Term::Fun(id, _) => scope.push(id.label().to_string()),
+                Term::RecRecord(data, ..) => {
+                    scope.extend(data.fields.keys().map(|id| id.label().to_string()));
+                }
+                _ => {}
+            }
+            TraverseControl::ContinueWithScope(scope)
+        },
+        &vec!["std".to_string()],
+    )
+}
+
+/// Apply fixes to a source, skipping fixes overlapping a previous one
+///
+/// Along with the fixed source, this tells which of the `suggestions` were applied, in their
+/// order.
+pub fn apply_suggestions(source: &str, suggestions: &[&Suggestion]) -> (String, Vec<bool>) {
+    let mut order: Vec<_> = (0..suggestions.len()).collect();
+    order.sort_by_key(|&i| (suggestions[i].start, suggestions[i].end));
+
+    let mut applied = vec![false; suggestions.len()];
+    let mut fixed = String::with_capacity(source.len());
+    let mut pos = 0;
+    for i in order {
+        let suggestion = suggestions[i];
+        if suggestion.start < pos || suggestion.end > source.len() {
+            continue;
+        }
+        fixed.push_str(&source[pos..suggestion.start]);
+        fixed.push_str(&suggestion.replacement);
+        pos = suggestion.end;
+        applied[i] = true;
+    }
+    fixed.push_str(&source[pos..]);
+    (fixed, applied)
+}
+
+/// Maximum number of names suggested for a misspelled one
+pub const MAX_CANDIDATES: usize = 3;
+
+/// The existing names closest to a misspelled one, most similar first
+///
+/// This uses the same similarity measure and threshold as the suggestions of Nickel's own
+/// reports, which only show the closest name.
+pub fn closest_names<'a>(name: &str, names: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let mut scored: Vec<_> = names
+        .into_iter()
+        .map(|candidate| {
+            let similarity = if candidate.to_lowercase() == name.to_lowercase() {
+                1.0
+            } else {
+                strsim::normalized_damerau_levenshtein(name, candidate)
+            };
+            (similarity, candidate)
+        })
+        .filter(|(similarity, _)| *similarity >= MIN_SIMILARITY)
+        .collect();
+    scored.sort_by(|(a, x), (b, y)| b.total_cmp(a).then_with(|| x.cmp(y)));
+    scored
+        .into_iter()
+        .map(|(_, candidate)| candidate)
+        .take(MAX_CANDIDATES)
+        .collect()
+}
+
+/// A rename is trivial when the names only differ by case or by a single edit
+pub fn is_trivial_rename(from: &str, to: &str) -> bool {
+    from.to_lowercase() == to.to_lowercase() || strsim::damerau_levenshtein(from, to) == 1
+}
+
+fn rename(from: &str, to: &str, start: usize, end: usize) -> Suggestion {
+    Suggestion {
+        message: format!("Did you mean `{to}`?"),
+        replacement: to.to_string(),
+        start,
+        end,
+        safe: is_trivial_rename(from, to),
+    }
+}
+
+/// The candidate of a note of the form ``Did you mean `candidate`?``
+fn did_you_mean(note: &str) -> Option<&str> {
+    note.strip_prefix("Did you mean `")?.strip_suffix("`?")
+}
+
+/// The identifier a label ends with, e.g. the field of a `record.field` access
+fn trailing_identifier(text: &str) -> Option<&str> {
+    let start = text
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_alphanumeric() || matches!(c, '_' | '-' | '\''))
+        .last()?
+        .0;
+    Some(&text[start..])
+}
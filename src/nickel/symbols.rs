@@ -0,0 +1,155 @@
+use crate::nickel::lex::LexedToken;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// How a name is used at one of its occurrences
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageKind {
+    /// `let name = ...`, or a record field `name = ...` or `name | Contract`
+    Definition,
+    /// `record.name`
+    FieldAccess,
+    /// Any other use of the name as a variable
+    Reference,
+}
+
+impl UsageKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UsageKind::Definition => "definition",
+            UsageKind::FieldAccess => "field_access",
+            UsageKind::Reference => "reference",
+        }
+    }
+}
+
+/// An occurrence of a name in some Nickel source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Usage {
+    pub kind: UsageKind,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Find the occurrences of `name` as an identifier in a token stream
+///
+/// Occurrences are matched by name, not by scope: a local variable shadowing a top-level binding
+/// is reported as well.
+pub fn usages(tokens: &[LexedToken], name: &str) -> Vec<Usage> {
+    identifiers(tokens)
+        .filter(|(_, token)| token.text == name)
+        .map(|(i, token)| usage(tokens, i, token))
+        .collect()
+}
+
+/// Index the occurrences of every identifier of a token stream by name
+pub fn symbol_table(tokens: &[LexedToken]) -> HashMap<String, Vec<Usage>> {
+    let mut table: HashMap<String, Vec<Usage>> = HashMap::new();
+    for (i, token) in identifiers(tokens) {
+        table
+            .entry(token.text.clone())
+            .or_default()
+            .push(usage(tokens, i, token));
+    }
+    table
+}
+
+/// The names bound by `let` (or `let rec`) in a token stream, with their definitions
+pub fn let_bindings(tokens: &[LexedToken]) -> Vec<(String, Usage)> {
+    identifiers(tokens)
+        .filter(|(i, _)| {
+            i.checked_sub(1)
+                .and_then(|i| tokens.get(i))
+                .is_some_and(|prev| {
+                    prev.mode == "normal" && matches!(prev.kind.as_str(), "Let" | "Rec")
+                })
+        })
+        .map(|(i, token)| (token.text.clone(), usage(tokens, i, token)))
+        .collect()
+}
+
+fn identifiers(tokens: &[LexedToken]) -> impl Iterator<Item = (usize, &LexedToken)> {
+    tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, token)| token.mode == "normal" && token.kind == "Identifier")
+}
+
+/// Classify the identifier at `i` from the tokens around it
+fn usage(tokens: &[LexedToken], i: usize, token: &LexedToken) -> Usage {
+    let kind_at = |i: Option<usize>| {
+        i.and_then(|i| tokens.get(i))
+            .filter(|token| token.mode == "normal")
+            .map(|token| token.kind.as_str())
+    };
+
+    // An annotation follows a definition only at the start of a record field, elsewhere the
+    // name is used, as in `cfg | Schema`
+    let kind = match (kind_at(i.checked_sub(1)), kind_at(Some(i + 1))) {
+        (Some("Dot"), _) => UsageKind::FieldAccess,
+        (Some("Let" | "Rec"), _)
+        | (_, Some("Equals"))
+        | (Some("LBrace" | "Comma" | "Semicolon"), Some("Pipe" | "Colon")) => UsageKind::Definition,
+        _ => UsageKind::Reference,
+    };
+    Usage {
+        kind,
+        start: token.start,
+        end: token.end,
+        line: token.line,
+        column: token.column,
+    }
+}
+
+/// The paths imported by a token stream, as written in the `import "path"` expressions
+pub fn imports(tokens: &[LexedToken]) -> Vec<String> {
+    tokens
+        .windows(3)
+        .filter_map(|window| match window {
+            [import, quote, path]
+                if import.kind == "Import"
+                    && quote.kind == "DoubleQuote"
+                    && path.mode == "string"
+                    && path.kind == "Literal" =>
+            {
+                Some(path.text.clone())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Resolve an import of the file at `importer` the way Nickel does, relative to its directory
+pub fn resolve_import(importer: &Path, import: &str) -> PathBuf {
+    importer
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(import)
+}
+
+/// The Nickel files of a directory tree, sorted, skipping hidden directories
+pub fn nickel_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let hidden = path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            if path.is_dir() {
+                if !hidden {
+                    dirs.push(path);
+                }
+            } else if path.extension().is_some_and(|ext| ext == "ncl") {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
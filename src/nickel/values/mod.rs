@@ -1,3 +1,3 @@
 pub mod nu_nickel_value;
 
-pub use nu_nickel_value::{NuNickelValue, NuNickelValueCustomValue};
\ No newline at end of file
+pub use nu_nickel_value::{NuNickelValue, NuNickelValueCustomValue};
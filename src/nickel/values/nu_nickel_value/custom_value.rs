@@ -1,8 +1,6 @@
+use crate::cache::{CachedNickelValue, NickelPluginObject};
 use crate::nickel::values::NuNickelValue;
-use crate::cache::CachedNickelValue;
-use nu_protocol::{
-    CustomValue, Record, ShellError, Span, Value,
-};
+use nu_protocol::{CustomValue, LabeledError, Record, ShellError, Span, Value};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -13,6 +11,11 @@ pub struct NuNickelValueCustomValue {
     /// Optional cached data - skipped during serialization for thread safety
     #[serde(skip)]
     pub cached_value: Option<CachedNickelValue>,
+    /// The data of the value itself, when it was made self-contained by `nickel keep`
+    ///
+    /// A kept value does not refer to the cache, so it survives evictions and plugin restarts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kept: Option<NickelPluginObject>,
 }
 
 impl NuNickelValueCustomValue {
@@ -21,15 +24,30 @@ impl NuNickelValueCustomValue {
             id: value.id,
             type_name: value.type_name,
             cached_value: None,
+            kept: None,
+        }
+    }
+
+    /// Create a self-contained custom value carrying `object` instead of a cache entry
+    pub fn kept(value: NuNickelValue, object: NickelPluginObject) -> Self {
+        Self {
+            id: value.id,
+            type_name: value.type_name,
+            cached_value: None,
+            kept: Some(object),
         }
     }
 
     /// Create a new custom value with optional cached data
-    pub fn with_cached_value(value: NuNickelValue, cached_value: Option<CachedNickelValue>) -> Self {
+    pub fn with_cached_value(
+        value: NuNickelValue,
+        cached_value: Option<CachedNickelValue>,
+    ) -> Self {
         Self {
             id: value.id,
             type_name: value.type_name,
             cached_value,
+            kept: None,
         }
     }
 
@@ -53,16 +71,34 @@ impl CustomValue for NuNickelValueCustomValue {
         let mut record = Record::new();
         record.push("id", Value::string(self.id.to_string(), span));
         record.push("type", Value::string(&self.type_name, span));
-        
+
         // Add cached value info if available
         if let Some(cached_value) = &self.cached_value {
-            record.push("object_type", Value::string(cached_value.object_type(), span));
-            record.push("reference_count", Value::int(cached_value.reference_count as i64, span));
-            record.push("created", Value::string(cached_value.created.to_rfc3339(), span));
+            record.push(
+                "object_type",
+                Value::string(cached_value.object_type(), span),
+            );
+            record.push(
+                "reference_count",
+                Value::int(cached_value.reference_count as i64, span),
+            );
+            record.push(
+                "created",
+                Value::string(cached_value.created.to_rfc3339(), span),
+            );
+        } else if let Some(kept) = &self.kept {
+            // As text, so that `nickel keep` can read the record back after a round trip to disk
+            let kept = serde_json::to_string(kept).map_err(|e| {
+                ShellError::from(
+                    LabeledError::new("Failed to serialize Nickel value")
+                        .with_label(e.to_string(), span),
+                )
+            })?;
+            record.push("kept", Value::string(kept, span));
         } else {
             record.push("cached", Value::bool(false, span));
         }
-        
+
         Ok(Value::record(record, span))
     }
 
@@ -75,7 +111,7 @@ impl CustomValue for NuNickelValueCustomValue {
     }
 
     fn notify_plugin_on_drop(&self) -> bool {
-        true
+        // A kept value has no cache entry to release
+        self.kept.is_none()
     }
-
-}
\ No newline at end of file
+}
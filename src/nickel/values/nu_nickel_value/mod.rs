@@ -1,8 +1,14 @@
 pub mod custom_value;
+mod operation;
 
-use crate::{NickelPlugin, cache::CachedNickelValue};
+use crate::{
+    NickelPlugin,
+    cache::{CachedNickelValue, NickelCache},
+    nickel::input::NickelInput,
+};
 use nu_protocol::{LabeledError, Span, Value};
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use uuid::Uuid;
 
 pub use custom_value::NuNickelValueCustomValue;
@@ -23,6 +29,12 @@ impl NuNickelValue {
         Value::custom(Box::new(NuNickelValueCustomValue::new(self)), span)
     }
 
+    /// Create a value holding the cache entry `id`, counted as a reference to it until it is dropped
+    fn handle(cache: &NickelCache, id: Uuid, type_name: &str, span: Span) -> Value {
+        cache.increment_ref(&id);
+        NuNickelValue::new(id, type_name.to_string()).into_value(span)
+    }
+
     /// Cache a JSON value and create a NuNickelValue
     pub fn cache_json_value(
         plugin: &NickelPlugin,
@@ -30,8 +42,7 @@ impl NuNickelValue {
         span: Span,
     ) -> Result<Value, LabeledError> {
         let id = plugin.cache.insert_json(json_value, span);
-        let nu_value = NuNickelValue::new(id, "JsonValue".to_string());
-        Ok(nu_value.into_value(span))
+        Ok(Self::handle(&plugin.cache, id, "JsonValue", span))
     }
 
     /// Cache a Nickel term representation and create a NuNickelValue
@@ -40,11 +51,36 @@ impl NuNickelValue {
         source_code: String,
         json_representation: Option<serde_json::Value>,
         type_info: String,
+        source_path: Option<PathBuf>,
         span: Span,
     ) -> Result<Value, LabeledError> {
-        let id = plugin.cache.insert_nickel_term(source_code, json_representation, type_info, span);
-        let nu_value = NuNickelValue::new(id, "NickelTerm".to_string());
-        Ok(nu_value.into_value(span))
+        let id = plugin.cache.insert_nickel_term(
+            source_code,
+            json_representation,
+            type_info,
+            source_path,
+            span,
+        );
+        Ok(Self::handle(&plugin.cache, id, "NickelTerm", span))
+    }
+
+    /// Cache the parse of some Nickel input and create a NuNickelValue keeping its source
+    ///
+    /// Nothing is evaluated, so the value has no data yet: the commands reading it evaluate the
+    /// source when they need to.
+    pub fn cache_parsed(
+        plugin: &NickelPlugin,
+        input: NickelInput,
+        span: Span,
+    ) -> Result<Value, LabeledError> {
+        Self::cache_nickel_term(
+            plugin,
+            input.source,
+            None,
+            input.format.to_str().to_string(),
+            input.path,
+            span,
+        )
     }
 
     /// Cache an evaluated value and create a NuNickelValue
@@ -55,8 +91,21 @@ impl NuNickelValue {
         span: Span,
     ) -> Result<Value, LabeledError> {
         let id = plugin.cache.insert_evaluated(json, source_code, span);
-        let nu_value = NuNickelValue::new(id, "EvaluatedValue".to_string());
-        Ok(nu_value.into_value(span))
+        Ok(Self::handle(&plugin.cache, id, "EvaluatedValue", span))
+    }
+
+    /// Cache a function, as the source of a program evaluating to it, and create a NuNickelValue
+    ///
+    /// This takes the cache rather than the plugin, as functions are found by evaluations running
+    /// on threads of their own.
+    pub fn cache_function(
+        cache: &NickelCache,
+        source_code: String,
+        source_path: Option<PathBuf>,
+        span: Span,
+    ) -> Value {
+        let id = cache.insert_function(source_code, source_path, span);
+        Self::handle(cache, id, "NickelFunction", span)
     }
 
     /// Try to get the cached JSON value from a NuNickelValue
@@ -64,30 +113,13 @@ impl NuNickelValue {
         plugin: &NickelPlugin,
         value: &Value,
     ) -> Result<Option<serde_json::Value>, LabeledError> {
-        let custom_value = match value.as_custom_value() {
-            Ok(custom_value) => custom_value,
-            Err(_) => return Ok(None),
-        };
-
-        let nickel_custom_value = match custom_value
-            .as_any()
-            .downcast_ref::<NuNickelValueCustomValue>()
-        {
-            Some(value) => value,
-            None => return Ok(None),
-        };
-
-        match plugin.cache.get(&nickel_custom_value.id) {
-            Some(cached_value) => {
-                if let Some(json) = cached_value.as_json() {
-                    Ok(Some(json.clone()))
-                } else {
-                    Err(LabeledError::new("Type mismatch")
-                        .with_label("Expected JSON value, found different type", value.span()))
-                }
-            }
-            None => Err(LabeledError::new("Cached Nickel value not found")
-                .with_label("This Nickel value is no longer available", value.span())),
+        match Self::try_get_cached_value(plugin, value)? {
+            Some(cached_value) => match cached_value.as_json() {
+                Some(json) => Ok(Some(json.clone())),
+                None => Err(LabeledError::new("Type mismatch")
+                    .with_label("Expected JSON value, found different type", value.span())),
+            },
+            None => Ok(None),
         }
     }
 
@@ -96,34 +128,21 @@ impl NuNickelValue {
         plugin: &NickelPlugin,
         value: &Value,
     ) -> Result<Option<String>, LabeledError> {
-        let custom_value = match value.as_custom_value() {
-            Ok(custom_value) => custom_value,
-            Err(_) => return Ok(None),
-        };
-
-        let nickel_custom_value = match custom_value
-            .as_any()
-            .downcast_ref::<NuNickelValueCustomValue>()
-        {
-            Some(value) => value,
-            None => return Ok(None),
-        };
-
-        match plugin.cache.get(&nickel_custom_value.id) {
-            Some(cached_value) => {
-                if let Some(source) = cached_value.as_source_code() {
-                    Ok(Some(source.clone()))
-                } else {
-                    Err(LabeledError::new("Type mismatch")
-                        .with_label("Expected Nickel term with source code, found different type", value.span()))
-                }
-            }
-            None => Err(LabeledError::new("Cached Nickel value not found")
-                .with_label("This Nickel value is no longer available", value.span())),
+        match Self::try_get_cached_value(plugin, value)? {
+            Some(cached_value) => match cached_value.as_source_code() {
+                Some(source) => Ok(Some(source.clone())),
+                None => Err(LabeledError::new("Type mismatch").with_label(
+                    "Expected Nickel term with source code, found different type",
+                    value.span(),
+                )),
+            },
+            None => Ok(None),
         }
     }
 
     /// Try to get any cached value from a NuNickelValue
+    ///
+    /// A value made self-contained by `nickel keep` gives its own data, without the cache.
     pub fn try_get_cached_value(
         plugin: &NickelPlugin,
         value: &Value,
@@ -141,10 +160,19 @@ impl NuNickelValue {
             None => return Ok(None),
         };
 
+        if let Some(kept) = &nickel_custom_value.kept {
+            return Ok(Some(CachedNickelValue::detached(
+                nickel_custom_value.id,
+                kept.clone(),
+                plugin.cache.now(),
+                value.span(),
+            )));
+        }
         match plugin.cache.get(&nickel_custom_value.id) {
             Some(cached_value) => Ok(Some(cached_value)),
             None => Err(LabeledError::new("Cached Nickel value not found")
-                .with_label("This Nickel value is no longer available", value.span())),
+                .with_label("This Nickel value is no longer available", value.span())
+                .with_help("keep values that outlive the cache with `nickel keep`")),
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,237 @@
+use crate::{
+    NickelPlugin,
+    nickel::{
+        convert::{json_to_value, nickel_to_nu_value, value_to_nickel},
+        input::{NickelInput, env_import_paths, env_no_imports},
+        program::{eval_for_export, eval_to_json, new_program},
+        values::NuNickelValue,
+    },
+};
+use nickel_lang_core::{cache::InputFormat, identifier::LocIdent, program::FieldPath};
+use nu_plugin::EngineInterface;
+use nu_protocol::{
+    LabeledError, Span, Spanned, Value,
+    ast::{Comparison, Math, Operator, PathMember},
+    casing::Casing,
+};
+use std::{borrow::Cow, cmp::Ordering, path::PathBuf};
+
+impl NuNickelValue {
+    /// Apply an operator to a Nickel value on its left
+    ///
+    /// `+` merges the two values with Nickel's `&`, keeping the priorities of their fields, and
+    /// gives a Nickel value of the merge. The right one is a Nickel value or Nushell data. The
+    /// comparisons compare the data of both sides as Nushell does, so a Nickel value of a number
+    /// compares with a number, and values that Nushell cannot order are an error.
+    pub fn operation(
+        plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        left: &Value,
+        operator: Spanned<Operator>,
+        right: &Value,
+    ) -> Result<Value, LabeledError> {
+        let span = left.span();
+        let imports = Imports::from_engine(engine)?;
+        let operands = || -> Result<(Value, Value), LabeledError> {
+            Ok((
+                operand_data(plugin, left, &imports, span)?,
+                operand_data(plugin, right, &imports, right.span())?,
+            ))
+        };
+
+        let op = operator.span;
+        let result = match operator.item {
+            Operator::Math(Math::Add) => {
+                return merge(plugin, left, right, &imports, op);
+            }
+            Operator::Comparison(Comparison::Equal) => {
+                let (left, right) = operands()?;
+                left.eq(op, &right, span)
+            }
+            Operator::Comparison(Comparison::NotEqual) => {
+                let (left, right) = operands()?;
+                left.ne(op, &right, span)
+            }
+            Operator::Comparison(Comparison::LessThan) => {
+                let (left, right) = operands()?;
+                left.lt(op, &right, span)
+            }
+            Operator::Comparison(Comparison::LessThanOrEqual) => {
+                let (left, right) = operands()?;
+                left.lte(op, &right, span)
+            }
+            Operator::Comparison(Comparison::GreaterThan) => {
+                let (left, right) = operands()?;
+                left.gt(op, &right, span)
+            }
+            Operator::Comparison(Comparison::GreaterThanOrEqual) => {
+                let (left, right) = operands()?;
+                left.gte(op, &right, span)
+            }
+            operator => {
+                return Err(LabeledError::new("Unsupported operator")
+                    .with_label(format!("`{operator}` does not apply to Nickel values"), op)
+                    .with_help(
+                        "`+` merges Nickel values, and comparisons compare their data; convert \
+                         the value to Nushell data with `nickel into record` for other operators",
+                    ));
+            }
+        };
+        Ok(result?)
+    }
+
+    /// Order a Nickel value against another value by their data, for sorting
+    pub fn compare(
+        plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        value: &Value,
+        other: &Value,
+    ) -> Result<Option<Ordering>, LabeledError> {
+        let imports = Imports::from_engine(engine)?;
+        let value = operand_data(plugin, value, &imports, value.span())?;
+        let other = operand_data(plugin, other, &imports, other.span())?;
+        Ok(value.partial_cmp(&other))
+    }
+
+    /// Follow a cell path member into a Nickel value, as in `$config.server`
+    ///
+    /// A field of a value with source code is evaluated alone, as `nickel get` does, along with
+    /// the records on the way to it, and the rest of the cell path follows its data. Optional
+    /// and case-insensitive members, and list indices, follow the data of the whole value.
+    pub fn follow_path(
+        plugin: &NickelPlugin,
+        engine: &EngineInterface,
+        value: &Value,
+        member: PathMember,
+    ) -> Result<Value, LabeledError> {
+        let imports = Imports::from_engine(engine)?;
+        if let PathMember::String {
+            val,
+            span,
+            optional: false,
+            casing: Casing::Sensitive,
+        } = &member
+            && let Some(input) = NuNickelValue::try_get_cached_value(plugin, value)?
+                .filter(|cached| !cached.is_function())
+                .and_then(|cached| {
+                    NickelInput::from_cached(&cached, Some(imports.base_dir.clone()))
+                })
+                .filter(|input| !input.is_data())
+        {
+            let input = NickelInput {
+                import_paths: imports.paths,
+                no_imports: imports.forbidden,
+                ..input
+            };
+            let mut program = new_program(&input, *span)?;
+            program.field = FieldPath(vec![LocIdent::new(val)]);
+            let term = eval_for_export(&mut program, *span)?;
+            return nickel_to_nu_value(&term, *span);
+        }
+
+        let data = operand_data(plugin, value, &imports, value.span())?;
+        Ok(data.follow_cell_path(&[member]).map(Cow::into_owned)?)
+    }
+}
+
+/// How the code of the operands resolves its imports, as commands do without flags
+struct Imports {
+    base_dir: PathBuf,
+    paths: Vec<PathBuf>,
+    /// Whether the code may not import anything, by the `no_imports` plugin setting
+    forbidden: bool,
+}
+
+impl Imports {
+    fn from_engine(engine: &EngineInterface) -> Result<Self, LabeledError> {
+        Ok(Self {
+            base_dir: PathBuf::from(engine.get_current_dir()?),
+            paths: env_import_paths(engine)?,
+            forbidden: env_no_imports(engine)?,
+        })
+    }
+}
+
+/// Merge two values in Nickel, evaluating the merge to report its errors right away
+///
+/// Relative imports of the merge are resolved next to the file of the left value.
+fn merge(
+    plugin: &NickelPlugin,
+    left: &Value,
+    right: &Value,
+    imports: &Imports,
+    span: Span,
+) -> Result<Value, LabeledError> {
+    let (left_code, path) = operand_code(plugin, left, imports)?;
+    let (right_code, _) = operand_code(plugin, right, imports)?;
+    let input = NickelInput {
+        // On lines of their own, so that a trailing comment does not swallow the rest
+        source: format!("({left_code}\n)\n& ({right_code}\n)"),
+        path,
+        format: InputFormat::Nickel,
+        base_dir: Some(imports.base_dir.clone()),
+        import_paths: imports.paths.clone(),
+        no_imports: imports.forbidden,
+        source_name: None,
+    };
+    let json = eval_to_json(&input, span)?;
+    NuNickelValue::cache_nickel_term(
+        plugin,
+        input.source,
+        Some(json),
+        InputFormat::Nickel.to_str().to_string(),
+        input.path,
+        span,
+    )
+}
+
+/// The Nickel code of an operand, with the file it was read from
+///
+/// The source of a Nickel value is kept as it is, so that its default values can still be
+/// overridden, while data is written as Nickel code.
+fn operand_code(
+    plugin: &NickelPlugin,
+    value: &Value,
+    imports: &Imports,
+) -> Result<(String, Option<PathBuf>), LabeledError> {
+    let source = NuNickelValue::try_get_cached_value(plugin, value)?
+        .filter(|cached| !cached.is_function())
+        .and_then(|cached| NickelInput::from_cached(&cached, None))
+        .filter(|input| !input.is_data());
+    match source {
+        Some(input) => Ok((input.source, input.path)),
+        None => {
+            let data = operand_data(plugin, value, imports, value.span())?;
+            Ok((value_to_nickel(&data, value.span())?, None))
+        }
+    }
+}
+
+/// The data of an operand, evaluating a Nickel value that only has source code
+fn operand_data(
+    plugin: &NickelPlugin,
+    value: &Value,
+    imports: &Imports,
+    span: Span,
+) -> Result<Value, LabeledError> {
+    let Some(cached) = NuNickelValue::try_get_cached_value(plugin, value)? else {
+        return Ok(value.clone());
+    };
+    if cached.is_function() {
+        return Err(LabeledError::new("Cannot operate on a function")
+            .with_label("This Nickel value is a function, which has no data", span)
+            .with_help("apply it to its arguments with `nickel call` first"));
+    }
+    let json = match NickelInput::from_cached(&cached, Some(imports.base_dir.clone())) {
+        Some(input) => eval_to_json(
+            &NickelInput {
+                import_paths: imports.paths.clone(),
+                no_imports: imports.forbidden,
+                ..input
+            },
+            span,
+        )?,
+        None => cached.as_json().cloned().unwrap_or(serde_json::Value::Null),
+    };
+    json_to_value(&json, span)
+}
@@ -0,0 +1,237 @@
+use crate::measure::{EXIT_MEMORY, lift_memory_limit, limit_memory};
+use crate::nickel::program::{EVAL_STACK_SIZE, INTERRUPT_POLL};
+use nu_protocol::{Filesize, LabeledError, ShellError, Signals, Span};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
+use uuid::Uuid;
+
+/// The environment variable a worker process is started with, naming the file of its job
+pub const WORKER_VAR: &str = "NU_PLUGIN_NICKEL_WORKER";
+
+/// How worker processes are started
+///
+/// By default this is the plugin executable itself, whose entry point serves the job named by
+/// [`WORKER_VAR`] with [`serve`]. Another program, like a test binary running a test that calls
+/// [`serve`], can be started instead.
+#[derive(Debug, Clone, Default)]
+pub struct WorkerCommand {
+    program: Option<PathBuf>,
+    args: Vec<OsString>,
+}
+
+impl WorkerCommand {
+    /// Start `program` with `args` as the worker
+    pub fn new(
+        program: impl Into<PathBuf>,
+        args: impl IntoIterator<Item = impl Into<OsString>>,
+    ) -> Self {
+        Self {
+            program: Some(program.into()),
+            args: args.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    fn command(&self) -> io::Result<Command> {
+        let program = match &self.program {
+            Some(program) => program.clone(),
+            None => std::env::current_exe()?,
+        };
+        let mut command = Command::new(program);
+        command.args(&self.args);
+        Ok(command)
+    }
+}
+
+/// Resources a job may use in its worker process, where unset ones are not limited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Limits {
+    /// Bytes the job may allocate
+    pub memory: Option<usize>,
+    /// Size of the stack of the thread running the job
+    pub stack: Option<usize>,
+}
+
+impl Limits {
+    /// Whether any limit is set, so that the job needs a worker process
+    pub fn is_set(&self) -> bool {
+        self.memory.is_some() || self.stack.is_some()
+    }
+}
+
+/// A job as written for a worker process, with the resources it may use
+#[derive(Serialize, Deserialize)]
+struct Envelope<J> {
+    limits: Limits,
+    job: J,
+}
+
+/// Run a job in a worker process within `limits`, giving up on it when the user presses Ctrl-C
+///
+/// A thread cannot be stopped from outside, an allocator may not fail an allocation without
+/// aborting, and a thread overflowing its stack aborts the whole process, so a limited evaluation
+/// runs in a process of its own: started by `worker`, which serves the job with [`serve`] instead
+/// of the plugin protocol. A worker past its memory limit exits with [`EXIT_MEMORY`], and an
+/// interrupted one is killed, leaving the plugin as it was. The job and its result go through
+/// files only the user can read, as the plugin's stdout is its protocol.
+pub fn isolated<J: Serialize, T: DeserializeOwned>(
+    worker: &WorkerCommand,
+    signals: &Signals,
+    limits: Limits,
+    span: Span,
+    job: &J,
+) -> Result<T, LabeledError> {
+    let failed = |e: &dyn Error| {
+        LabeledError::new("Failed to start evaluation").with_label(e.to_string(), span)
+    };
+    let files = JobFiles::new();
+    let envelope = serde_json::to_vec(&Envelope { limits, job }).map_err(|e| failed(&e))?;
+    write_private(&files.job, &envelope).map_err(|e| failed(&e))?;
+
+    let mut child = worker
+        .command()
+        .map_err(|e| failed(&e))?
+        .env(WORKER_VAR, &files.job)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| failed(&e))?;
+
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(|e| failed(&e))? {
+            break status;
+        }
+        if signals.interrupted() {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(ShellError::Interrupted { span }.into());
+        }
+        thread::sleep(INTERRUPT_POLL);
+    };
+
+    match status.code() {
+        Some(0) => fs::read(&files.result)
+            .map_err(|e| e.to_string())
+            .and_then(|result| serde_json::from_slice(&result).map_err(|e| e.to_string()))
+            .map_err(|e| {
+                LabeledError::new("Nickel evaluation failed").with_label(
+                    format!("The result of the evaluation is unreadable: {e}"),
+                    span,
+                )
+            }),
+        Some(EXIT_MEMORY) => Err(LabeledError::new("Memory limit exceeded")
+            .with_label(
+                format!(
+                    "The evaluation allocated more than {}",
+                    Filesize::new(limits.memory.unwrap_or_default() as i64)
+                ),
+                span,
+            )
+            .with_help("raise the limit with `--max-memory`")),
+        _ => match limits.stack {
+            Some(stack) if aborted(status) => Err(LabeledError::new("Stack limit exceeded")
+                .with_label(
+                    format!(
+                        "The evaluation recursed past its stack of {}",
+                        Filesize::new(stack as i64)
+                    ),
+                    span,
+                )
+                .with_help("raise the limit with `--max-stack`")),
+            _ => Err(LabeledError::new("Nickel evaluation failed")
+                .with_label(format!("The evaluation process ended with {status}"), span)
+                .with_help("a program recursing too deeply overflows the stack of the evaluation")),
+        },
+    }
+}
+
+/// Whether a process was ended by a signal, as a thread overflowing its stack aborts its process
+fn aborted(status: ExitStatus) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        status.signal().is_some()
+    }
+    #[cfg(not(unix))]
+    {
+        status.code().is_none()
+    }
+}
+
+/// Serve the job of a worker process started by [`isolated`], and exit
+///
+/// The memory limit is lifted as soon as the job returns, so that writing its result is not
+/// counted against it.
+pub fn serve<J, T>(path: &Path, run: impl FnOnce(J) -> T + Send + 'static) -> !
+where
+    J: DeserializeOwned + Send + 'static,
+    T: Serialize + Send + 'static,
+{
+    let served = || -> Result<(), Box<dyn Error>> {
+        let Envelope { limits, job } = serde_json::from_slice::<Envelope<J>>(&fs::read(path)?)?;
+        let result = thread::Builder::new()
+            .name("nickel eval".into())
+            .stack_size(limits.stack.unwrap_or(EVAL_STACK_SIZE))
+            .spawn(move || {
+                if let Some(max_memory) = limits.memory {
+                    limit_memory(max_memory);
+                }
+                let result = run(job);
+                lift_memory_limit();
+                result
+            })?
+            .join()
+            .map_err(|_| "the evaluation thread panicked")?;
+        write_private(&JobFiles::result_of(path), &serde_json::to_vec(&result)?)?;
+        Ok(())
+    };
+    match served() {
+        Ok(()) => std::process::exit(0),
+        Err(e) => {
+            eprintln!("nu_plugin_nickel worker: {e}");
+            std::process::exit(1)
+        }
+    }
+}
+
+/// Write a new file only its owner can read, as jobs hold the code and data of the user
+fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents)
+}
+
+/// The files a job and its result are written to, removed once the worker is done
+struct JobFiles {
+    job: PathBuf,
+    result: PathBuf,
+}
+
+impl JobFiles {
+    fn new() -> Self {
+        let job =
+            std::env::temp_dir().join(format!("nu_plugin_nickel-job-{}.json", Uuid::new_v4()));
+        Self {
+            result: Self::result_of(&job),
+            job,
+        }
+    }
+
+    fn result_of(job: &Path) -> PathBuf {
+        job.with_extension("result.json")
+    }
+}
+
+impl Drop for JobFiles {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.job);
+        let _ = fs::remove_file(&self.result);
+    }
+}
@@ -0,0 +1,232 @@
+use nu_protocol::{Record, Span, Value};
+use similar::TextDiff;
+use std::io;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Suffix of the copies of files edited in place, when no other one is given
+pub const DEFAULT_BACKUP_SUFFIX: &str = ".orig";
+
+/// Files written all at once: either every file is written, or none is
+///
+/// The contents are first written to temporary files next to their targets, with the permissions
+/// of the files they replace, and then renamed over the targets, so that a target is never
+/// missing. When a step fails, the files already replaced are restored and the directories
+/// created for the outputs are removed.
+#[derive(Debug, Default)]
+pub struct WriteSet {
+    files: Vec<(PathBuf, Vec<u8>)>,
+}
+
+/// A file written by [`WriteSet::commit`], with what it replaced
+struct Replaced {
+    target: PathBuf,
+    backup: Option<PathBuf>,
+}
+
+impl WriteSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a file to write, a later file with the same path replacing an earlier one
+    pub fn add(&mut self, path: PathBuf, contents: impl Into<Vec<u8>>) {
+        self.files.push((path, contents.into()));
+    }
+
+    /// The paths of the files to write, in the order they were added
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|(path, _)| path.as_path())
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// What committing would do, as a table of the files with their `action` (`create`, `modify`
+    /// or `unchanged`), new `size` and the unified `diff` of their contents
+    pub fn preview(&self, span: Span) -> Value {
+        let rows = self
+            .files
+            .iter()
+            .map(|(path, contents)| {
+                let old = std::fs::read(path).ok();
+                let action = match &old {
+                    None => "create",
+                    Some(old) if old == contents => "unchanged",
+                    Some(_) => "modify",
+                };
+                let old = String::from_utf8_lossy(old.as_deref().unwrap_or_default());
+                let new = String::from_utf8_lossy(contents);
+                let name = path.display().to_string();
+                let diff = TextDiff::from_lines(old.as_ref(), new.as_ref())
+                    .unified_diff()
+                    .header(&name, &name)
+                    .to_string();
+
+                let mut record = Record::new();
+                record.push("path", Value::string(name, span));
+                record.push("action", Value::string(action, span));
+                record.push("size", Value::filesize(contents.len() as i64, span));
+                record.push("diff", Value::string(diff, span));
+                Value::record(record, span)
+            })
+            .collect();
+        Value::list(rows, span)
+    }
+
+    /// Write every file, or none of them if one cannot be written
+    pub fn commit(self) -> io::Result<()> {
+        let mut created_dirs = Vec::new();
+        let mut staged = Vec::new();
+
+        for (target, contents) in self.files {
+            let temp = sibling(&target, "tmp");
+            let result = create_parent(&target, &mut created_dirs)
+                .and_then(|()| std::fs::write(&temp, contents))
+                .and_then(|()| keep_permissions(&target, &temp));
+            if let Err(e) = result {
+                let _ = std::fs::remove_file(&temp);
+                rollback(Vec::new(), staged, created_dirs);
+                return Err(with_path(e, &target));
+            }
+            staged.push((temp, target));
+        }
+
+        let mut replaced = Vec::new();
+        let mut pending = staged.into_iter();
+        while let Some((temp, target)) = pending.next() {
+            match replace(&temp, &target) {
+                Ok(backup) => replaced.push(Replaced { target, backup }),
+                Err(e) => {
+                    let _ = std::fs::remove_file(&temp);
+                    rollback(replaced, pending.collect(), created_dirs);
+                    return Err(with_path(e, &target));
+                }
+            }
+        }
+
+        for backup in replaced.into_iter().filter_map(|r| r.backup) {
+            let _ = std::fs::remove_file(backup);
+        }
+        Ok(())
+    }
+}
+
+/// The path of the backup of `path`, whose file name is followed by `suffix`
+fn backup_path(path: &Path, suffix: &str) -> io::Result<PathBuf> {
+    // An empty suffix would make the file its own backup
+    if suffix.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the backup suffix is empty",
+        ));
+    }
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    Ok(path.with_file_name(name))
+}
+
+/// Copy a file to its backup before editing it, replacing any older backup
+pub fn backup(path: &Path, suffix: &str) -> io::Result<PathBuf> {
+    let backup = backup_path(path, suffix)?;
+    std::fs::copy(path, &backup).map_err(|e| with_path(e, path))?;
+    Ok(backup)
+}
+
+/// Move the backup of a file back over it
+pub fn restore_backup(path: &Path, suffix: &str) -> io::Result<PathBuf> {
+    let backup = backup_path(path, suffix)?;
+    std::fs::rename(&backup, path).map_err(|e| with_path(e, &backup))?;
+    Ok(backup)
+}
+
+/// The contents of the backup of a file, which restoring it would bring back
+pub fn read_backup(path: &Path, suffix: &str) -> io::Result<Vec<u8>> {
+    let backup = backup_path(path, suffix)?;
+    std::fs::read(&backup).map_err(|e| with_path(e, &backup))
+}
+
+/// A hidden file next to `path`, unique to this write
+fn sibling(path: &Path, extension: &str) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{name}.{}.{extension}", Uuid::new_v4()))
+}
+
+/// Create the missing parent directories of `path`, recording them deepest first
+fn create_parent(path: &Path, created: &mut Vec<PathBuf>) -> io::Result<()> {
+    let Some(parent) = path.parent() else {
+        return Ok(());
+    };
+    let missing: Vec<_> = parent
+        .ancestors()
+        .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+        .map(Path::to_path_buf)
+        .collect();
+    std::fs::create_dir_all(parent)?;
+    // Deeper directories go first, so that removing them in order empties their parents
+    created.splice(0..0, missing);
+    Ok(())
+}
+
+/// Give `temp` the permissions of `target`, if it exists
+fn keep_permissions(target: &Path, temp: &Path) -> io::Result<()> {
+    match std::fs::metadata(target) {
+        Ok(metadata) => std::fs::set_permissions(temp, metadata.permissions()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Rename `temp` over `target`, keeping what `target` was as a backup
+///
+/// The backup is a hard link to the target, or a copy where links are not supported, so that
+/// the rename replaces the target in a single step.
+fn replace(temp: &Path, target: &Path) -> io::Result<Option<PathBuf>> {
+    let backup = if target.exists() {
+        let backup = sibling(target, "bak");
+        if std::fs::hard_link(target, &backup).is_err()
+            && let Err(e) = std::fs::copy(target, &backup)
+        {
+            let _ = std::fs::remove_file(&backup);
+            return Err(e);
+        }
+        Some(backup)
+    } else {
+        None
+    };
+
+    match std::fs::rename(temp, target) {
+        Ok(()) => Ok(backup),
+        Err(e) => {
+            if let Some(backup) = &backup {
+                let _ = std::fs::remove_file(backup);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Undo a partial commit, on a best effort basis as the write already failed
+fn rollback(replaced: Vec<Replaced>, staged: Vec<(PathBuf, PathBuf)>, created_dirs: Vec<PathBuf>) {
+    for (temp, _) in staged {
+        let _ = std::fs::remove_file(temp);
+    }
+    for Replaced { target, backup } in replaced.into_iter().rev() {
+        let _ = match backup {
+            Some(backup) => std::fs::rename(backup, &target),
+            None => std::fs::remove_file(&target),
+        };
+    }
+    for dir in created_dirs {
+        let _ = std::fs::remove_dir(dir);
+    }
+}
+
+fn with_path(error: io::Error, path: &Path) -> io::Error {
+    io::Error::new(error.kind(), format!("{}: {error}", path.display()))
+}